@@ -369,6 +369,8 @@ void CL_ParseServerInfo (void)
 
 	// copy the naked name of the map file to the cl structure -- O.S
 	COM_StripExtension (COM_SkipPath(model_precache[1]), cl.mapname, sizeof(cl.mapname));
+	CL_StatsNewLevel (current_skill);
+	CL_ScoreboardNewLevel ();
 
 	for (i = 1; i < nummodels; i++)
 	{
@@ -1084,6 +1086,7 @@ void CL_ParseServerMessage (void)
 			if (i >= cl.maxclients)
 				Host_Error ("CL_ParseServerMessage: svc_updatename > MAX_SCOREBOARD");
 			q_strlcpy (cl.scores[i].name, MSG_ReadString(), MAX_SCOREBOARDNAME);
+			CL_ScoreboardUpdateName (i, cl.scores[i].name);
 			break;
 
 		case svc_updatefrags:
@@ -1092,6 +1095,7 @@ void CL_ParseServerMessage (void)
 			if (i >= cl.maxclients)
 				Host_Error ("CL_ParseServerMessage: svc_updatefrags > MAX_SCOREBOARD");
 			cl.scores[i].frags = MSG_ReadShort ();
+			CL_ScoreboardUpdateFrags (i, cl.scores[i].frags);
 			break;
 
 		case svc_updatecolors:
@@ -1100,6 +1104,7 @@ void CL_ParseServerMessage (void)
 			if (i >= cl.maxclients)
 				Host_Error ("CL_ParseServerMessage: svc_updatecolors > MAX_SCOREBOARD");
 			cl.scores[i].colors = MSG_ReadByte ();
+			CL_ScoreboardUpdateColors (i, cl.scores[i].colors);
 			CL_NewTranslation (i);
 			break;
 
@@ -1153,10 +1158,12 @@ void CL_ParseServerMessage (void)
 
 		case svc_killedmonster:
 			cl.stats[STAT_MONSTERS]++;
+			CL_StatsKilledMonster ();
 			break;
 
 		case svc_foundsecret:
 			cl.stats[STAT_SECRETS]++;
+			CL_StatsFoundSecret ();
 			break;
 
 		case svc_updatestat:
@@ -1164,6 +1171,7 @@ void CL_ParseServerMessage (void)
 			if (i < 0 || i >= MAX_CL_STATS)
 				Sys_Error ("svc_updatestat: %i is invalid", i);
 			cl.stats[i] = MSG_ReadLong ();;
+			CL_StatsUpdateStat (i, cl.stats[i]);
 			break;
 
 		case svc_spawnstaticsound:
@@ -1183,12 +1191,18 @@ void CL_ParseServerMessage (void)
 			cl.intermission = 1;
 			cl.completed_time = cl.time;
 			vid.recalc_refdef = true;	// go to full screen
+			if (!cls.demoplayback && sv.active)	// a remote server's skill isn't known
+				CL_StatsIntermission ();
+			CL_ScoreboardIntermission ();
 			break;
 
 		case svc_finale:
 			cl.intermission = 2;
 			cl.completed_time = cl.time;
 			vid.recalc_refdef = true;	// go to full screen
+			if (!cls.demoplayback && sv.active)	// a remote server's skill isn't known
+				CL_StatsIntermission ();
+			CL_ScoreboardIntermission ();
 			//johnfitz -- log centerprints to console
 			str = MSG_ReadString ();
 			SCR_CenterPrint (str);
@@ -60,7 +60,7 @@ typedef struct
 
 #define	SIGNONS		4			// signon messages to receive before connected
 
-#define	MAX_DLIGHTS		64 //johnfitz -- was 32
+#define	MAX_DLIGHTS		128 //johnfitz -- was 32
 typedef struct
 {
 	vec3_t	origin;
@@ -334,6 +334,24 @@ void CL_TimeDemo_f (void);
 void CL_ParseServerMessage (void);
 void CL_NewTranslation (int slot);
 
+//
+// cl_stats.rs
+//
+void CL_StatsNewLevel (int skill);
+void CL_StatsUpdateStat (int stat, int value);
+void CL_StatsKilledMonster (void);
+void CL_StatsFoundSecret (void);
+void CL_StatsIntermission (void);
+
+//
+// sbar.rs
+//
+void CL_ScoreboardNewLevel (void);
+void CL_ScoreboardUpdateName (int slot, const char *name);
+void CL_ScoreboardUpdateFrags (int slot, int frags);
+void CL_ScoreboardUpdateColors (int slot, int colors);
+void CL_ScoreboardIntermission (void);
+
 //
 // view
 //
@@ -386,6 +386,9 @@ void Con_Printf (const char *fmt, ...)
 	if (con_debuglog)
 		Con_DebugLog(msg);
 
+// keep it for rcon
+	Con_Redirect(msg);
+
 	if (!con_initialized)
 		return;
 
@@ -95,6 +95,10 @@ void	Cvar_RegisterVariable (cvar_t *variable);
 void Cvar_SetCallback (cvar_t *var, cvarcallback_t func);
 // set a callback function to the var
 
+void	Cvar_NotifyChanges (cvarcallback_t func);
+// calls func on the CVAR_NOTIFY vars without a callback that changed
+// since the last call
+
 void	Cvar_Set (const char *var_name, const char *value);
 // equivelant to "<name> <variable>" typed at the console
 
@@ -709,6 +709,9 @@ void _Host_Frame (float time)
 // check for commands typed to the host
 	Host_GetConsoleCommands ();
 
+// tell the clients about rule changes that had no callback to do it
+	Cvar_NotifyChanges (Host_Callback_Notify);
+
 	if (sv.active)
 		Host_ServerFrame ();
 
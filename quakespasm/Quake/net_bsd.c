@@ -27,6 +27,7 @@ Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
 
 #include "net_dgrm.h"
 #include "net_loop.h"
+#include "net_replay.h"
 
 net_driver_t net_drivers[] =
 {
@@ -46,6 +47,22 @@ net_driver_t net_drivers[] =
 		Loop_Shutdown
 	},
 
+	{	"Replay",
+		false,
+		Replay_Init,
+		Replay_Listen,
+		Replay_SearchForHosts,
+		Replay_Connect,
+		Replay_CheckNewConnections,
+		Replay_GetMessage,
+		Replay_SendMessage,
+		Replay_SendUnreliableMessage,
+		Replay_CanSendMessage,
+		Replay_CanSendUnreliableMessage,
+		Replay_Close,
+		Replay_Shutdown
+	},
+
 	{	"Datagram",
 		false,
 		Datagram_Init,
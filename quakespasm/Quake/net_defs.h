@@ -78,6 +78,11 @@ CCREQ_PLAYER_INFO
 CCREQ_RULE_INFO
 		string	rule
 
+CCREQ_RCON
+		long	challenge		0 to ask for one
+		string	digest			hex HMAC-SHA256 of challenge and command, keyed with rcon_password
+		string	command
+
 CCREP_ACCEPT
 		long	port
 
@@ -104,6 +109,10 @@ CCREP_RULE_INFO
 		string	rule
 		string	value
 
+CCREP_RCON
+		long	challenge		the one to sign the command with, 0 with the output
+		string	output			the command's console output, or why it wasn't run
+
 	note:
 		There are two address forms used above.  The short form is just a
 		port number.  The address that goes along with the port is defined as
@@ -120,12 +129,34 @@ CCREP_RULE_INFO
 #define CCREQ_SERVER_INFO	0x02
 #define CCREQ_PLAYER_INFO	0x03
 #define CCREQ_RULE_INFO		0x04
+#define CCREQ_RCON		0x05
 
 #define CCREP_ACCEPT		0x81
 #define CCREP_REJECT		0x82
 #define CCREP_SERVER_INFO	0x83
 #define CCREP_PLAYER_INFO	0x84
 #define CCREP_RULE_INFO		0x85
+#define CCREP_RCON		0x86
+
+/* the statistic counters of a socket, see net_stats */
+typedef struct
+{
+	int		messagesSent;
+	int		messagesReceived;
+	int		unreliableMessagesSent;
+	int		unreliableMessagesReceived;
+	int		packetsSent;
+	int		packetsReSent;
+	int		packetsReceived;
+	int		receivedDuplicateCount;
+	int		shortPacketCount;
+	int		droppedDatagrams;
+	uint64_t	bytesSent;		// every datagram, headers and acks included
+	uint64_t	bytesReceived;
+	double		sendRate;		// bytes per second; the engine's sockets leave these 0
+	double		receiveRate;
+	double		rtt;			// smoothed seconds from a reliable fragment to its ack
+} datagramstats_t;
 
 typedef struct qsocket_s
 {
@@ -157,6 +188,9 @@ typedef struct qsocket_s
 	struct qsockaddr	addr;
 	char		address[NET_NAMELEN];
 
+	double		fragmentTime;	// when the unacknowledged fragment went out, 0 once resent
+	datagramstats_t	stats;
+
 } qsocket_t;
 
 extern qsocket_t	*net_activeSockets;
@@ -226,6 +260,10 @@ extern int		unreliableMessagesReceived;
 qsocket_t *NET_NewQSocket (void);
 void NET_FreeQSocket(qsocket_t *);
 double SetNetTime(void);
+void NET_Stats_f (void);
+void NET_RetireStats (const datagramstats_t *stats);
+void NET_Capture_f (void);
+void NET_CaptureDatagram (sys_socket_t socketid, qboolean sent, const char *addr, const byte *data, int len);
 
 
 #define HOSTCACHESIZE	8
@@ -35,14 +35,6 @@ Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
 
 static int net_landriverlevel;
 
-/* statistic counters */
-static int packetsSent = 0;
-static int packetsReSent = 0;
-static int packetsReceived = 0;
-static int receivedDuplicateCount = 0;
-static int shortPacketCount = 0;
-static int droppedDatagrams;
-
 static struct
 {
 	unsigned int	length;
@@ -167,11 +159,13 @@ int Datagram_SendMessage (qsocket_t *sock, sizebuf_t *data)
 
 	sock->canSend = false;
 
-	if (sfunc.Write (sock->socket, (byte *)&packetBuffer, packetLen, &sock->addr) == -1)
+	if (NET_SimWrite (sock, sfunc.Write, (byte *)&packetBuffer, packetLen, &sock->addr) == -1)
 		return -1;
 
 	sock->lastSendTime = net_time;
-	packetsSent++;
+	sock->fragmentTime = net_time;
+	sock->stats.packetsSent++;
+	sock->stats.bytesSent += packetLen;
 	return 1;
 }
 
@@ -200,11 +194,13 @@ static int SendMessageNext (qsocket_t *sock)
 
 	sock->sendNext = false;
 
-	if (sfunc.Write (sock->socket, (byte *)&packetBuffer, packetLen, &sock->addr) == -1)
+	if (NET_SimWrite (sock, sfunc.Write, (byte *)&packetBuffer, packetLen, &sock->addr) == -1)
 		return -1;
 
 	sock->lastSendTime = net_time;
-	packetsSent++;
+	sock->fragmentTime = net_time;
+	sock->stats.packetsSent++;
+	sock->stats.bytesSent += packetLen;
 	return 1;
 }
 
@@ -233,11 +229,13 @@ static int ReSendMessage (qsocket_t *sock)
 
 	sock->sendNext = false;
 
-	if (sfunc.Write (sock->socket, (byte *)&packetBuffer, packetLen, &sock->addr) == -1)
+	if (NET_SimWrite (sock, sfunc.Write, (byte *)&packetBuffer, packetLen, &sock->addr) == -1)
 		return -1;
 
 	sock->lastSendTime = net_time;
-	packetsReSent++;
+	sock->fragmentTime = 0;
+	sock->stats.packetsReSent++;
+	sock->stats.bytesSent += packetLen;
 	return 1;
 }
 
@@ -275,10 +273,11 @@ int Datagram_SendUnreliableMessage (qsocket_t *sock, sizebuf_t *data)
 	packetBuffer.sequence = BigLong(sock->unreliableSendSequence++);
 	Q_memcpy (packetBuffer.data, data->data, data->cursize);
 
-	if (sfunc.Write (sock->socket, (byte *)&packetBuffer, packetLen, &sock->addr) == -1)
+	if (NET_SimWrite (sock, sfunc.Write, (byte *)&packetBuffer, packetLen, &sock->addr) == -1)
 		return -1;
 
-	packetsSent++;
+	sock->stats.packetsSent++;
+	sock->stats.bytesSent += packetLen;
 	return 1;
 }
 
@@ -298,8 +297,8 @@ int	Datagram_GetMessage (qsocket_t *sock)
 
 	while (1)
 	{
-		length = (unsigned int) sfunc.Read(sock->socket, (byte *)&packetBuffer,
-							NET_DATAGRAMSIZE, &readaddr);
+		length = (unsigned int) NET_SimRead(sock, sfunc.Read, sfunc.Write,
+							(byte *)&packetBuffer, NET_DATAGRAMSIZE, &readaddr);
 
 	//	if ((rand() & 255) > 220)
 	//		continue;
@@ -321,9 +320,11 @@ int	Datagram_GetMessage (qsocket_t *sock)
 			continue;
 		}
 
+		sock->stats.bytesReceived += length;
+
 		if (length < NET_HEADERSIZE)
 		{
-			shortPacketCount++;
+			sock->stats.shortPacketCount++;
 			continue;
 		}
 
@@ -335,7 +336,7 @@ int	Datagram_GetMessage (qsocket_t *sock)
 			continue;
 
 		sequence = BigLong(packetBuffer.sequence);
-		packetsReceived++;
+		sock->stats.packetsReceived++;
 
 		if (flags & NETFLAG_UNRELIABLE)
 		{
@@ -348,7 +349,7 @@ int	Datagram_GetMessage (qsocket_t *sock)
 			if (sequence != sock->unreliableReceiveSequence)
 			{
 				count = sequence - sock->unreliableReceiveSequence;
-				droppedDatagrams += count;
+				sock->stats.droppedDatagrams += count;
 				Con_DPrintf("Dropped %u datagram(s)\n", count);
 			}
 			sock->unreliableReceiveSequence = sequence + 1;
@@ -371,6 +372,16 @@ int	Datagram_GetMessage (qsocket_t *sock)
 			}
 			if (sequence == sock->ackSequence)
 			{
+				// fragments that were resent aren't measured
+				if (sock->fragmentTime)
+				{
+					double	sample = net_time - sock->fragmentTime;
+
+					if (sock->stats.rtt == 0)
+						sock->stats.rtt = sample;
+					else
+						sock->stats.rtt += (sample - sock->stats.rtt) * 0.125;
+				}
 				sock->ackSequence++;
 				if (sock->ackSequence != sock->sendSequence)
 					Con_DPrintf("ack sequencing error\n");
@@ -398,11 +409,12 @@ int	Datagram_GetMessage (qsocket_t *sock)
 		{
 			packetBuffer.length = BigLong(NET_HEADERSIZE | NETFLAG_ACK);
 			packetBuffer.sequence = BigLong(sequence);
-			sfunc.Write (sock->socket, (byte *)&packetBuffer, NET_HEADERSIZE, &readaddr);
+			NET_SimWrite (sock, sfunc.Write, (byte *)&packetBuffer, NET_HEADERSIZE, &readaddr);
+			sock->stats.bytesSent += NET_HEADERSIZE;
 
 			if (sequence != sock->receiveSequence)
 			{
-				receivedDuplicateCount++;
+				sock->stats.receivedDuplicateCount++;
 				continue;
 			}
 			sock->receiveSequence++;
@@ -433,63 +445,6 @@ int	Datagram_GetMessage (qsocket_t *sock)
 }
 
 
-static void PrintStats(qsocket_t *s)
-{
-	Con_Printf("canSend = %4u   \n", s->canSend);
-	Con_Printf("sendSeq = %4u   ", s->sendSequence);
-	Con_Printf("recvSeq = %4u   \n", s->receiveSequence);
-	Con_Printf("\n");
-}
-
-static void NET_Stats_f (void)
-{
-	qsocket_t	*s;
-
-	if (Cmd_Argc () == 1)
-	{
-		Con_Printf("unreliable messages sent   = %i\n", unreliableMessagesSent);
-		Con_Printf("unreliable messages recv   = %i\n", unreliableMessagesReceived);
-		Con_Printf("reliable messages sent     = %i\n", messagesSent);
-		Con_Printf("reliable messages received = %i\n", messagesReceived);
-		Con_Printf("packetsSent                = %i\n", packetsSent);
-		Con_Printf("packetsReSent              = %i\n", packetsReSent);
-		Con_Printf("packetsReceived            = %i\n", packetsReceived);
-		Con_Printf("receivedDuplicateCount     = %i\n", receivedDuplicateCount);
-		Con_Printf("shortPacketCount           = %i\n", shortPacketCount);
-		Con_Printf("droppedDatagrams           = %i\n", droppedDatagrams);
-	}
-	else if (Q_strcmp(Cmd_Argv(1), "*") == 0)
-	{
-		for (s = net_activeSockets; s; s = s->next)
-			PrintStats(s);
-		for (s = net_freeSockets; s; s = s->next)
-			PrintStats(s);
-	}
-	else
-	{
-		for (s = net_activeSockets; s; s = s->next)
-		{
-			if (q_strcasecmp(Cmd_Argv(1), s->address) == 0)
-				break;
-		}
-
-		if (s == NULL)
-		{
-			for (s = net_freeSockets; s; s = s->next)
-			{
-				if (q_strcasecmp(Cmd_Argv(1), s->address) == 0)
-					break;
-			}
-		}
-
-		if (s == NULL)
-			return;
-
-		PrintStats(s);
-	}
-}
-
-
 // recognize ip:port (based on ProQuake)
 static const char *Strip_Port (const char *host)
 {
@@ -790,6 +745,8 @@ int Datagram_Init (void)
 	myDriverLevel = net_driverlevel;
 
 	Cmd_AddCommand ("net_stats", NET_Stats_f);
+	Cmd_AddCommand ("net_capture", NET_Capture_f);
+	Cmd_AddCommand ("rcon", Rcon_f);
 
 	if (safemode || COM_CheckParm("-nolan"))
 		return -1;
@@ -838,6 +795,7 @@ void Datagram_Shutdown (void)
 
 void Datagram_Close (qsocket_t *sock)
 {
+	NET_SimClose (sock);
 	sfunc.Close_Socket(sock->socket);
 }
 
@@ -953,25 +911,20 @@ static qsocket_t *_Datagram_CheckNewConnections (void)
 
 	if (command == CCREQ_RULE_INFO)
 	{
-		const char	*prevCvarName;
-		cvar_t			*var;
+		// the rule after the one asked for, from the ServerInfo cvars
+		Datagram_RuleInfo (MSG_ReadString(), &net_message);
+		dfunc.Write (acceptsock, net_message.data, net_message.cursize, &clientaddr);
+		SZ_Clear(&net_message);
 
-		// find the search start location
-		prevCvarName = MSG_ReadString();
-		var = Cvar_FindVarAfter (prevCvarName, CVAR_SERVERINFO);
+		return NULL;
+	}
 
-		// send the response
-		SZ_Clear(&net_message);
-		// save space for the header, filled in later
-		MSG_WriteLong(&net_message, 0);
-		MSG_WriteByte(&net_message, CCREP_RULE_INFO);
-		if (var)
-		{
-			MSG_WriteString(&net_message, var->name);
-			MSG_WriteString(&net_message, var->string);
-		}
-		*((int *)net_message.data) = BigLong(NETFLAG_CTL | (net_message.cursize & NETFLAG_LENGTH_MASK));
-		dfunc.Write (acceptsock, net_message.data, net_message.cursize, &clientaddr);
+	if (command == CCREQ_RCON)
+	{
+		// answered in place, or dropped for an address that is being ignored
+		Datagram_Rcon (dfunc.AddrToString(&clientaddr), &net_message);
+		if (net_message.cursize)
+			dfunc.Write (acceptsock, net_message.data, net_message.cursize, &clientaddr);
 		SZ_Clear(&net_message);
 
 		return NULL;
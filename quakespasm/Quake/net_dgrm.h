@@ -35,5 +35,20 @@ qboolean	Datagram_CanSendUnreliableMessage (qsocket_t *sock);
 void		Datagram_Close (qsocket_t *sock);
 void		Datagram_Shutdown (void);
 
+/* net_sim.rs: the datagrams of connected sockets go through the network
+   simulator while one of the net_fake cvars is set */
+typedef int	(*net_lanio_t) (sys_socket_t socketid, byte *buf, int len, struct qsockaddr *addr);
+
+int			NET_SimWrite (qsocket_t *sock, net_lanio_t write, byte *buf, int len, struct qsockaddr *addr);
+int			NET_SimRead (qsocket_t *sock, net_lanio_t read, net_lanio_t write, byte *buf, int len, struct qsockaddr *addr);
+void		NET_SimClose (qsocket_t *sock);
+
+/* net_dgrm.rs */
+void		Datagram_RuleInfo (const char *prev, sizebuf_t *reply);
+
+/* net_rcon.rs */
+void		Datagram_Rcon (const char *from, sizebuf_t *packet);
+void		Rcon_f (void);
+
 #endif	/* __NET_DATAGRAM_H */
 
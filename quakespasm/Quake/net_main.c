@@ -36,18 +36,19 @@ static PollProcedure	slistSendProcedure = {NULL, 0.0, Slist_Send};
 static PollProcedure	slistPollProcedure = {NULL, 0.0, Slist_Poll};
 
 extern cvar_t net_messagetimeout;
+extern cvar_t net_fakelag;
+extern cvar_t net_fakejitter;
+extern cvar_t net_fakeloss;
+extern cvar_t net_fakedup;
+extern cvar_t net_fakereorder;
+extern cvar_t net_fakeseed;
+extern cvar_t rcon_password;
+extern cvar_t rcon_address;
 
 // these two macros are to make the code more readable
 #define sfunc	net_drivers[sock->driver]
 #define dfunc	net_drivers[net_driverlevel]
 
-double SetNetTime (void)
-{
-	net_time = Sys_DoubleTime();
-	return net_time;
-}
-
-
 /*
 ===================
 NET_NewQSocket
@@ -90,6 +91,9 @@ qsocket_t *NET_NewQSocket (void)
 	sock->receiveSequence = 0;
 	sock->unreliableReceiveSequence = 0;
 	sock->receiveMessageLength = 0;
+	sock->fragmentTime = 0;
+	NET_RetireStats (&sock->stats);
+	memset (&sock->stats, 0, sizeof(sock->stats));
 
 	return sock;
 }
@@ -401,6 +405,10 @@ qsocket_t *NET_Connect (const char *host)
 			goto JustDoIt;
 		}
 
+		// a capture file for the replay driver, no need to look for servers
+		if (q_strncasecmp (host, "replay:", 7) == 0)
+			goto JustDoIt;
+
 		if (hostCacheCount)
 		{
 			for (n = 0; n < hostCacheCount; n++)
@@ -555,9 +563,15 @@ int	NET_GetMessage (qsocket_t *sock)
 		{
 			sock->lastMessageTime = net_time;
 			if (ret == 1)
+			{
 				messagesReceived++;
+				sock->stats.messagesReceived++;
+			}
 			else if (ret == 2)
+			{
 				unreliableMessagesReceived++;
+				sock->stats.unreliableMessagesReceived++;
+			}
 		}
 	}
 
@@ -592,7 +606,10 @@ int NET_SendMessage (qsocket_t *sock, sizebuf_t *data)
 	SetNetTime();
 	r = sfunc.QSendMessage(sock, data);
 	if (r == 1 && !IS_LOOP_DRIVER(sock->driver))
+	{
 		messagesSent++;
+		sock->stats.messagesSent++;
+	}
 
 	return r;
 }
@@ -614,7 +631,10 @@ int NET_SendUnreliableMessage (qsocket_t *sock, sizebuf_t *data)
 	SetNetTime();
 	r = sfunc.SendUnreliableMessage(sock, data);
 	if (r == 1 && !IS_LOOP_DRIVER(sock->driver))
+	{
 		unreliableMessagesSent++;
+		sock->stats.unreliableMessagesSent++;
+	}
 
 	return r;
 }
@@ -768,6 +788,14 @@ void NET_Init (void)
 
 	Cvar_RegisterVariable (&net_messagetimeout);
 	Cvar_RegisterVariable (&hostname);
+	Cvar_RegisterVariable (&net_fakelag);
+	Cvar_RegisterVariable (&net_fakejitter);
+	Cvar_RegisterVariable (&net_fakeloss);
+	Cvar_RegisterVariable (&net_fakedup);
+	Cvar_RegisterVariable (&net_fakereorder);
+	Cvar_RegisterVariable (&net_fakeseed);
+	Cvar_RegisterVariable (&rcon_password);
+	Cvar_RegisterVariable (&rcon_address);
 
 	Cmd_AddCommand ("slist", NET_Slist_f);
 	Cmd_AddCommand ("listen", NET_Listen_f);
@@ -279,6 +279,8 @@ int UDP_Read (sys_socket_t socketid, byte *buf, int len, struct qsockaddr *addr)
 			return 0;
 		Con_SafePrintf ("UDP_Read, recvfrom: %s\n", socketerror(err));
 	}
+	else if (ret > 0)
+		NET_CaptureDatagram (socketid, false, UDP_AddrToString (addr), buf, ret);
 	return ret;
 }
 
@@ -337,6 +339,8 @@ int UDP_Write (sys_socket_t socketid, byte *buf, int len, struct qsockaddr *addr
 			return 0;
 		Con_SafePrintf ("UDP_Write, sendto: %s\n", socketerror(err));
 	}
+	else if (ret > 0)
+		NET_CaptureDatagram (socketid, true, UDP_AddrToString (addr), buf, ret);
 	return ret;
 }
 
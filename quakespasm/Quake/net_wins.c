@@ -361,6 +361,8 @@ int WINS_Read (sys_socket_t socketid, byte *buf, int len, struct qsockaddr *addr
 			return 0;
 		Con_SafePrintf ("WINS_Read, recvfrom: %s\n", socketerror(err));
 	}
+	else if (ret > 0)
+		NET_CaptureDatagram (socketid, false, WINS_AddrToString (addr), buf, ret);
 	return ret;
 }
 
@@ -420,6 +422,8 @@ int WINS_Write (sys_socket_t socketid, byte *buf, int len, struct qsockaddr *add
 			return 0;
 		Con_SafePrintf ("WINS_Write, sendto: %s\n", socketerror(err));
 	}
+	else if (ret > 0)
+		NET_CaptureDatagram (socketid, true, WINS_AddrToString (addr), buf, ret);
 	return ret;
 }
 
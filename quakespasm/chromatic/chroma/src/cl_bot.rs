@@ -0,0 +1,881 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2002-2009 John Fitzgibbons and others
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// cl_bot.rs -- a client without video, sound or input: signon, entity and stat tracking,
+// moves sent by the program driving it
+
+use cl_input::{send_move, InputT};
+use cl_main::{lerp_entity, lerp_player, lerp_point, RelinkSettingsT};
+use cl_parse;
+use cl_parse::{
+    entity_num, parse_baseline, parse_clientdata, parse_server_info, parse_start_sound,
+    parse_static_sound, parse_update, ParseError, StartSoundT,
+};
+use cl_tent::{parse_temp_entity, TempEntityT};
+use client::{ClientStateT, ScoreboardT, MAX_STATIC_ENTITIES, SIGNONS};
+use common::{MsgReaderT, MsgWriterT};
+use mathlib::RandT;
+use net::{NetConnection, NetError, NetMessageT};
+use protocol::{
+    ClientToServerMessage, ServerToClientMessage, UpdateFlags, UserCmdT, PROTOCOL_FITZQUAKE,
+    PROTOCOL_NETQUAKE, PROTOCOL_RMQ,
+};
+use r_part::{parse_particle_effect, ParticleEffectT};
+use render::EntityT;
+use std::mem;
+use std::os::raw::{c_double, c_float, c_int, c_uint};
+use std::ptr::null_mut;
+use strl::strlcpy_slice;
+use {Byte, Vec3T, MAX_CL_STATS, MAX_LIGHTSTYLES, MAX_STYLESTRING, STAT_MONSTERS, STAT_SECRETS};
+
+/// something the server said that a client with a console, screen or speakers would have
+/// shown; the state it changes is already in the bot when these come out of read_messages
+pub enum BotEventT {
+    /// svc_serverinfo: a new level, everything from the last one has been cleared
+    ServerInfo,
+    /// the signon stage was reached and its reply queued; SIGNONS means the bot is in the game
+    Signon(c_int),
+    Print(Vec<Byte>),
+    CenterPrint(Vec<Byte>),
+    /// console commands from the server; the bot only acts on "reconnect"
+    StuffText(Vec<Byte>),
+    UpdateName {
+        slot: c_int,
+        name: Vec<Byte>,
+    },
+    UpdateFrags {
+        slot: c_int,
+        frags: c_int,
+    },
+    UpdateColors {
+        slot: c_int,
+        colors: c_int,
+    },
+    UpdateStat {
+        stat: c_int,
+        value: c_int,
+    },
+    KilledMonster,
+    FoundSecret,
+    /// svc_intermission, the end of level scoreboard
+    Intermission,
+    /// svc_finale, the end of an episode
+    Finale(Vec<Byte>),
+    Cutscene(Vec<Byte>),
+    Damage {
+        armor: c_int,
+        blood: c_int,
+        from: Vec3T,
+    },
+    Sound(StartSoundT),
+    Particle(ParticleEffectT),
+    TempEntity(TempEntityT),
+}
+
+/// why the bot stopped
+#[derive(Debug, PartialEq)]
+pub enum BotError {
+    Net(NetError),
+    /// a message the bot couldn't make sense of; Host_Error in the original
+    Parse(ParseError),
+    /// svc_disconnect; Host_EndGame in the original
+    ServerDisconnected,
+}
+
+impl From<NetError> for BotError {
+    fn from(e: NetError) -> Self {
+        return BotError::Net(e);
+    }
+}
+
+impl From<ParseError> for BotError {
+    fn from(e: ParseError) -> Self {
+        return BotError::Parse(e);
+    }
+}
+
+/// cvars and connection state the bot depends on
+#[derive(Clone)]
+pub struct BotSettingsT {
+    /// _cl_name
+    pub name: String,
+    /// _cl_color, top color in the high 4 bits, bottom color in the low 4
+    pub color: c_int,
+    /// cls.spawnparms, empty for a new player
+    pub spawnparms: String,
+    /// max_edicts, the number of cl_entities
+    pub max_edicts: usize,
+    /// standard_quake, false for the hipnotic and rogue mission packs
+    pub standard_quake: bool,
+    pub relink: RelinkSettingsT,
+}
+
+impl Default for BotSettingsT {
+    fn default() -> Self {
+        Self {
+            name: "player".to_string(),
+            color: 0,
+            spawnparms: String::new(),
+            max_edicts: 8192,
+            standard_quake: true,
+            relink: RelinkSettingsT::default(),
+        }
+    }
+}
+
+/// the bits of CL_ParseServerMessage the bot handles
+fn server_message(cmd: c_int) -> Option<ServerToClientMessage> {
+    return Some(match cmd {
+        1 => ServerToClientMessage::Nop,
+        2 => ServerToClientMessage::Disconnect,
+        3 => ServerToClientMessage::Updatestat,
+        4 => ServerToClientMessage::Version,
+        5 => ServerToClientMessage::Setview,
+        6 => ServerToClientMessage::Sound,
+        7 => ServerToClientMessage::Time,
+        8 => ServerToClientMessage::Print,
+        9 => ServerToClientMessage::Stufftext,
+        10 => ServerToClientMessage::SetAngle,
+        11 => ServerToClientMessage::ServerInfo,
+        12 => ServerToClientMessage::LightStyle,
+        13 => ServerToClientMessage::UpdateName,
+        14 => ServerToClientMessage::UpdateFrags,
+        15 => ServerToClientMessage::ClientData,
+        16 => ServerToClientMessage::StopSound,
+        17 => ServerToClientMessage::UpdateColors,
+        18 => ServerToClientMessage::Particle,
+        19 => ServerToClientMessage::Damage,
+        20 => ServerToClientMessage::SpawnStatic,
+        22 => ServerToClientMessage::SpawnBaseline,
+        23 => ServerToClientMessage::TempEntity,
+        24 => ServerToClientMessage::SetPause,
+        25 => ServerToClientMessage::SignOnNum,
+        26 => ServerToClientMessage::CenterPrint,
+        27 => ServerToClientMessage::KilledMonster,
+        28 => ServerToClientMessage::FoundSecret,
+        29 => ServerToClientMessage::SpawnStaticSound,
+        30 => ServerToClientMessage::Intermission,
+        31 => ServerToClientMessage::Finale,
+        32 => ServerToClientMessage::CdTrack,
+        33 => ServerToClientMessage::SellScreen,
+        34 => ServerToClientMessage::CutScene,
+        37 => ServerToClientMessage::Skybox,
+        40 => ServerToClientMessage::Bf,
+        41 => ServerToClientMessage::Fog,
+        42 => ServerToClientMessage::SpawnBaseline2,
+        43 => ServerToClientMessage::SpawnStatic2,
+        44 => ServerToClientMessage::SpawnStaticSound2,
+        _ => return None,
+    });
+}
+
+/// a client on the other end of conn that needs no SDL, GL or sound.  Call read_messages and
+/// send_cmd once a frame; the bot answers the signon on its own, and once signon() reaches
+/// SIGNONS its moves go out
+pub struct BotClientT<C: NetConnection> {
+    pub conn: C,
+    pub settings: BotSettingsT,
+    /// cl; set cl.viewangles to aim the next move
+    pub cl: Box<ClientStateT>,
+    /// the buttons and impulse of the next move
+    pub input: InputT,
+    /// cls.signon
+    signon: c_int,
+    /// cls.message, reliable commands waiting for the connection to take them
+    message: MsgWriterT,
+    /// cl_entities
+    entities: Vec<EntityT>,
+    /// the model_precache index of every entity, as there are no models to point to
+    models: Vec<c_int>,
+    /// cl_static_entities
+    statics: Vec<EntityT>,
+    /// what cl.scores points to
+    scores: Vec<ScoreboardT>,
+    model_precache: Vec<Vec<Byte>>,
+    sound_precache: Vec<Vec<Byte>>,
+    /// cl_lightstyle maps
+    lightstyles: Vec<Vec<Byte>>,
+    events: Vec<BotEventT>,
+    /// for the randomized animation of entities
+    rand: RandT,
+}
+
+impl<C: NetConnection> BotClientT<C> {
+    pub fn new(conn: C, settings: BotSettingsT) -> Self {
+        let max_edicts = settings.max_edicts;
+        return Self {
+            conn,
+            settings,
+            cl: Box::new(ClientStateT::default()),
+            input: InputT::default(),
+            signon: 0,
+            message: MsgWriterT::new(),
+            entities: vec![EntityT::default(); max_edicts],
+            models: vec![0; max_edicts],
+            statics: Vec::new(),
+            scores: Vec::new(),
+            model_precache: vec![Vec::new()],
+            sound_precache: vec![Vec::new()],
+            lightstyles: vec![Vec::new(); MAX_LIGHTSTYLES],
+            events: Vec::new(),
+            rand: RandT::new(0),
+        };
+    }
+
+    /// Formerly: CL_ClearState
+    fn clear_state(&mut self) {
+        // wipe the entire cl structure
+        *self.cl = ClientStateT::default();
+        self.message.data.clear();
+
+        // clear other arrays
+        for ent in self.entities.iter_mut() {
+            *ent = EntityT::default();
+        }
+        for model in self.models.iter_mut() {
+            *model = 0;
+        }
+        self.statics.clear();
+        self.scores.clear();
+        self.model_precache.truncate(1);
+        self.sound_precache.truncate(1);
+        for style in self.lightstyles.iter_mut() {
+            style.clear();
+        }
+    }
+
+    /// cls.signon
+    pub fn signon(&self) -> c_int {
+        return self.signon;
+    }
+
+    /// every cl_entities slot in use, including ones that weren't in the last message
+    pub fn entities(&self) -> &[EntityT] {
+        let num = (self.cl.num_entities.max(0) as usize).min(self.entities.len());
+        return &self.entities[..num];
+    }
+
+    /// entity num if it has a model and was in the last message
+    pub fn entity(&self, num: c_int) -> Option<&EntityT> {
+        if num < 0 || num >= self.cl.num_entities || self.models[num as usize] == 0 {
+            return None;
+        }
+        return Some(&self.entities[num as usize]);
+    }
+
+    /// the name of the model entity num uses, see entity
+    pub fn entity_model(&self, num: c_int) -> Option<&[Byte]> {
+        self.entity(num)?;
+        return self.model_name(self.models[num as usize]);
+    }
+
+    /// cl_entities[cl.viewentity]
+    pub fn player(&self) -> Option<&EntityT> {
+        return self.entity(self.cl.viewentity);
+    }
+
+    pub fn statics(&self) -> &[EntityT] {
+        return &self.statics;
+    }
+
+    /// cl.scores, cl.maxclients long
+    pub fn scores(&self) -> &[ScoreboardT] {
+        return &self.scores;
+    }
+
+    /// cl.stats[stat], STAT_HEALTH and so on
+    pub fn stat(&self, stat: c_uint) -> c_int {
+        return self.cl.stats.get(stat as usize).cloned().unwrap_or(0);
+    }
+
+    /// the name of cl.model_precache[index]
+    pub fn model_name(&self, index: c_int) -> Option<&[Byte]> {
+        if index <= 0 {
+            return None;
+        }
+        return self.model_precache.get(index as usize).map(|n| &n[..]);
+    }
+
+    /// the name of cl.sound_precache[index]
+    pub fn sound_name(&self, index: c_int) -> Option<&[Byte]> {
+        if index <= 0 {
+            return None;
+        }
+        return self.sound_precache.get(index as usize).map(|n| &n[..]);
+    }
+
+    /// the map of light style i
+    pub fn lightstyle(&self, i: usize) -> Option<&[Byte]> {
+        return self.lightstyles.get(i).map(|s| &s[..]);
+    }
+
+    /// Formerly: Cmd_ForwardToServer
+    /// queue a console command for the server, like "say hello" or "kill"; it goes out with
+    /// the next send_cmd
+    pub fn string_cmd(&mut self, text: &str) {
+        self.message
+            .write_byte(ClientToServerMessage::StringCmd as c_int);
+        self.message.write_string(text.as_bytes());
+    }
+
+    /// Formerly: CL_SignonReply
+    /// An svc_signonnum has been received, perform a client side setup
+    fn signon_reply(&mut self) {
+        match self.signon {
+            1 => self.string_cmd("prespawn"),
+            2 => {
+                let name = format!("name \"{}\"\n", self.settings.name);
+                self.string_cmd(&name);
+
+                let color = self.settings.color;
+                self.string_cmd(&format!("color {} {}\n", color >> 4, color & 15));
+
+                let spawn = format!("spawn {}", self.settings.spawnparms);
+                self.string_cmd(&spawn);
+            }
+            3 => self.string_cmd("begin"),
+            _ => {}
+        }
+        self.events.push(BotEventT::Signon(self.signon));
+    }
+
+    /// svc_stufftext; the bot has no console, but a level change asks it to start the signon
+    /// over
+    fn stuff_text(&mut self, text: Vec<Byte>) {
+        for line in text.split(|&c| c == b'\n' || c == b';') {
+            let line = String::from_utf8_lossy(line);
+            if line.trim() == "reconnect" {
+                // Formerly: CL_Reconnect_f
+                self.signon = 0;
+            }
+        }
+        self.events.push(BotEventT::StuffText(text));
+    }
+
+    /// Formerly: CL_ParseStatic
+    fn spawn_static(&mut self, msg: &mut MsgReaderT, version: c_int) -> Result<(), ParseError> {
+        if self.statics.len() >= MAX_STATIC_ENTITIES {
+            return Err(ParseError::TooManyStatics);
+        }
+        let mut ent = EntityT::default();
+        cl_parse::parse_static(msg, &self.cl, &mut ent, version, null_mut())?;
+        self.cl.num_statics += 1;
+        self.statics.push(ent);
+        return Ok(());
+    }
+
+    /// svc_updatename, svc_updatefrags and svc_updatecolors slot
+    fn score_slot(&self, slot: c_int) -> Result<usize, ParseError> {
+        if slot < 0 || slot >= self.cl.maxclients || slot as usize >= self.scores.len() {
+            return Err(ParseError::BadScoreboardSlot(slot));
+        }
+        return Ok(slot as usize);
+    }
+
+    /// Formerly: CL_ParseServerMessage
+    /// apply one message from the server, queueing events and signon replies
+    pub fn parse_server_message(&mut self, data: &[Byte]) -> Result<(), BotError> {
+        // unless the server says otherwise
+        self.cl.onground = false.into();
+
+        let mut msg = MsgReaderT::new(data);
+        loop {
+            let cmd = msg.read_byte();
+            if cmd == -1 {
+                // end of message
+                return Ok(());
+            }
+
+            // if the high bit of the command byte is set, it is a fast update
+            if cmd & UpdateFlags::Signal.bits() as c_int != 0 {
+                let update = parse_update(
+                    &mut msg,
+                    &mut self.cl,
+                    &mut self.entities,
+                    cmd & 127,
+                    &mut self.signon,
+                    null_mut(),
+                    &mut self.rand,
+                )?;
+                self.models[update.num as usize] = update.model;
+                if update.signon {
+                    self.signon_reply();
+                }
+                continue;
+            }
+
+            let protocolflags = self.cl.protocolflags;
+            match server_message(cmd) {
+                None => return Err(ParseError::Illegible(cmd).into()),
+                Some(ServerToClientMessage::Nop) => {}
+                Some(ServerToClientMessage::Time) => {
+                    self.cl.mtime[1] = self.cl.mtime[0];
+                    self.cl.mtime[0] = msg.read_float() as c_double;
+                }
+                Some(ServerToClientMessage::ClientData) => {
+                    parse_clientdata(&mut msg, &mut self.cl, self.settings.standard_quake)?;
+                }
+                Some(ServerToClientMessage::Version) => {
+                    let i = msg.read_long();
+                    if i != PROTOCOL_NETQUAKE as c_int
+                        && i != PROTOCOL_FITZQUAKE as c_int
+                        && i != PROTOCOL_RMQ as c_int
+                    {
+                        return Err(ParseError::BadProtocol(i).into());
+                    }
+                    self.cl.protocol = i as c_uint;
+                }
+                Some(ServerToClientMessage::Disconnect) => {
+                    self.signon = 0;
+                    return Err(BotError::ServerDisconnected);
+                }
+                Some(ServerToClientMessage::Print) => {
+                    self.events.push(BotEventT::Print(msg.read_string()));
+                }
+                Some(ServerToClientMessage::CenterPrint) => {
+                    self.events.push(BotEventT::CenterPrint(msg.read_string()));
+                }
+                Some(ServerToClientMessage::Stufftext) => {
+                    let text = msg.read_string();
+                    self.stuff_text(text);
+                }
+                Some(ServerToClientMessage::Damage) => {
+                    let armor = msg.read_byte();
+                    let blood = msg.read_byte();
+                    let mut from = Vec3T::new(0.0, 0.0, 0.0);
+                    for i in 0..3 {
+                        from[i] = msg.read_coord(protocolflags);
+                    }
+                    self.events.push(BotEventT::Damage { armor, blood, from });
+                }
+                Some(ServerToClientMessage::ServerInfo) => {
+                    // wipe the client_state_t struct
+                    self.clear_state();
+                    let info = parse_server_info(&mut msg, &mut self.cl)?;
+                    self.scores = vec![ScoreboardT::default(); self.cl.maxclients as usize];
+                    self.cl.scores = self.scores.as_mut_ptr();
+                    self.model_precache.extend(info.models);
+                    self.sound_precache.extend(info.sounds);
+                    // local state
+                    self.models[0] = 1;
+                    self.events.push(BotEventT::ServerInfo);
+                }
+                Some(ServerToClientMessage::SetAngle) => {
+                    for i in 0..3 {
+                        self.cl.viewangles[i] = msg.read_angle(protocolflags);
+                    }
+                }
+                Some(ServerToClientMessage::Setview) => {
+                    self.cl.viewentity = msg.read_short();
+                }
+                Some(ServerToClientMessage::LightStyle) => {
+                    let i = msg.read_byte();
+                    if i < 0 || i as usize >= MAX_LIGHTSTYLES {
+                        return Err(ParseError::BadLightStyle(i).into());
+                    }
+                    let mut map = msg.read_string();
+                    map.truncate(MAX_STYLESTRING - 1);
+                    self.lightstyles[i as usize] = map;
+                }
+                Some(ServerToClientMessage::Sound) => {
+                    let max_edicts = self.entities.len() as c_int;
+                    let sound = parse_start_sound(&mut msg, protocolflags, max_edicts)?;
+                    self.events.push(BotEventT::Sound(sound));
+                }
+                Some(ServerToClientMessage::StopSound) => {
+                    msg.read_short();
+                }
+                Some(ServerToClientMessage::UpdateName) => {
+                    let slot = msg.read_byte();
+                    let i = self.score_slot(slot)?;
+                    let name = msg.read_string();
+                    strlcpy_slice(&mut self.scores[i].name, &name);
+                    self.events.push(BotEventT::UpdateName { slot, name });
+                }
+                Some(ServerToClientMessage::UpdateFrags) => {
+                    let slot = msg.read_byte();
+                    let i = self.score_slot(slot)?;
+                    let frags = msg.read_short();
+                    self.scores[i].frags = frags;
+                    self.events.push(BotEventT::UpdateFrags { slot, frags });
+                }
+                Some(ServerToClientMessage::UpdateColors) => {
+                    let slot = msg.read_byte();
+                    let i = self.score_slot(slot)?;
+                    let colors = msg.read_byte();
+                    self.scores[i].colors = colors;
+                    self.events.push(BotEventT::UpdateColors { slot, colors });
+                }
+                Some(ServerToClientMessage::Particle) => {
+                    let effect = parse_particle_effect(&mut msg, protocolflags)?;
+                    self.events.push(BotEventT::Particle(effect));
+                }
+                Some(ServerToClientMessage::SpawnBaseline) => {
+                    let i = msg.read_short();
+                    // must use CL_EntityNum() to force cl.num_entities up
+                    let ent = entity_num(&mut self.cl, &mut self.entities, i, null_mut())?;
+                    parse_baseline(&mut msg, ent, 1, protocolflags);
+                }
+                Some(ServerToClientMessage::SpawnStatic) => {
+                    self.spawn_static(&mut msg, 1)?;
+                }
+                Some(ServerToClientMessage::TempEntity) => {
+                    let tent = parse_temp_entity(&mut msg, protocolflags, &mut ())?;
+                    self.events.push(BotEventT::TempEntity(tent));
+                }
+                Some(ServerToClientMessage::SetPause) => {
+                    self.cl.paused = (msg.read_byte() != 0).into();
+                }
+                Some(ServerToClientMessage::SignOnNum) => {
+                    let i = msg.read_byte();
+                    if i <= self.signon {
+                        return Err(ParseError::BadSignon(i).into());
+                    }
+                    self.signon = i;
+                    self.signon_reply();
+                }
+                Some(ServerToClientMessage::KilledMonster) => {
+                    self.cl.stats[STAT_MONSTERS as usize] += 1;
+                    self.events.push(BotEventT::KilledMonster);
+                }
+                Some(ServerToClientMessage::FoundSecret) => {
+                    self.cl.stats[STAT_SECRETS as usize] += 1;
+                    self.events.push(BotEventT::FoundSecret);
+                }
+                Some(ServerToClientMessage::Updatestat) => {
+                    let stat = msg.read_byte();
+                    if stat < 0 || stat as usize >= MAX_CL_STATS {
+                        return Err(ParseError::BadStat(stat).into());
+                    }
+                    let value = msg.read_long();
+                    self.cl.stats[stat as usize] = value;
+                    self.events.push(BotEventT::UpdateStat { stat, value });
+                }
+                Some(ServerToClientMessage::SpawnStaticSound) => {
+                    parse_static_sound(&mut msg, 1, protocolflags)?;
+                }
+                Some(ServerToClientMessage::CdTrack) => {
+                    self.cl.cdtrack = msg.read_byte();
+                    self.cl.looptrack = msg.read_byte();
+                }
+                Some(ServerToClientMessage::Intermission) => {
+                    self.cl.intermission = 1;
+                    self.cl.completed_time = self.cl.time as c_int;
+                    self.events.push(BotEventT::Intermission);
+                }
+                Some(ServerToClientMessage::Finale) => {
+                    self.cl.intermission = 2;
+                    self.cl.completed_time = self.cl.time as c_int;
+                    self.events.push(BotEventT::Finale(msg.read_string()));
+                }
+                Some(ServerToClientMessage::CutScene) => {
+                    self.cl.intermission = 3;
+                    self.cl.completed_time = self.cl.time as c_int;
+                    self.events.push(BotEventT::Cutscene(msg.read_string()));
+                }
+                Some(ServerToClientMessage::SellScreen) | Some(ServerToClientMessage::Bf) => {}
+                Some(ServerToClientMessage::Skybox) => {
+                    msg.read_string();
+                }
+                Some(ServerToClientMessage::Fog) => {
+                    // density, red, green, blue, time
+                    for _ in 0..4 {
+                        msg.read_byte();
+                    }
+                    msg.read_short();
+                }
+                Some(ServerToClientMessage::SpawnBaseline2) => {
+                    let i = msg.read_short();
+                    // must use CL_EntityNum() to force cl.num_entities up
+                    let ent = entity_num(&mut self.cl, &mut self.entities, i, null_mut())?;
+                    parse_baseline(&mut msg, ent, 2, protocolflags);
+                }
+                Some(ServerToClientMessage::SpawnStatic2) => {
+                    self.spawn_static(&mut msg, 2)?;
+                }
+                Some(ServerToClientMessage::SpawnStaticSound2) => {
+                    parse_static_sound(&mut msg, 2, protocolflags)?;
+                }
+                Some(_) => return Err(ParseError::Illegible(cmd).into()),
+            }
+
+            if msg.badread {
+                return Err(ParseError::BadRead.into());
+            }
+        }
+    }
+
+    /// Formerly: part of CL_RelinkEntities
+    /// lerp the entities between the last two messages, without the effects
+    /// relink_entities adds; it skips every entity of a client without models
+    fn relink(&mut self) {
+        // determine partial update time
+        let frac = lerp_point(&mut self.cl, &self.settings.relink);
+
+        // interpolate player info
+        lerp_player(&mut self.cl, &self.settings.relink, frac);
+
+        let cl = &self.cl;
+        let num_entities = (cl.num_entities.max(0) as usize).min(self.entities.len());
+        // start on the entity after the world
+        for (ent, model) in self
+            .entities
+            .iter_mut()
+            .zip(self.models.iter_mut())
+            .take(num_entities)
+            .skip(1)
+        {
+            if *model == 0 {
+                // empty slot
+                continue;
+            }
+
+            if !lerp_entity(cl, ent, frac, &self.settings.relink) {
+                *model = 0;
+            }
+        }
+    }
+
+    /// Formerly: CL_ReadFromServer
+    /// read and apply everything the server sent, frametime seconds after the last call
+    pub fn read_messages(&mut self, frametime: c_double) -> Result<Vec<BotEventT>, BotError> {
+        self.cl.oldtime = self.cl.time;
+        self.cl.time += frametime;
+
+        loop {
+            let data = match self.conn.get_message()? {
+                None => break,
+                Some(NetMessageT::Reliable(data)) | Some(NetMessageT::Unreliable(data)) => data,
+            };
+
+            // discard nop keepalive message
+            if data.len() == 1 && data[0] == ServerToClientMessage::Nop as Byte {
+                continue;
+            }
+
+            self.cl.last_received_message = self.cl.time as c_float;
+            self.parse_server_message(&data)?;
+        }
+
+        self.relink();
+        return Ok(mem::take(&mut self.events));
+    }
+
+    /// Formerly: CL_SendCmd
+    /// send cmd once the signon is done, and the queued reliable commands when the
+    /// connection can take them
+    pub fn send_cmd(&mut self, cmd: &UserCmdT) -> Result<(), BotError> {
+        if self.signon == SIGNONS as c_int {
+            // send the unreliable message
+            if let Some(data) = send_move(&mut self.cl, &mut self.input, cmd, false) {
+                self.conn.send_unreliable_message(&data)?;
+            }
+        }
+
+        // send the reliable message
+        if self.message.data.is_empty() {
+            // no message at all
+            return Ok(());
+        }
+
+        if !self.conn.can_send_message() {
+            return Ok(());
+        }
+
+        self.conn.send_message(&self.message.data)?;
+        self.message.data.clear();
+        return Ok(());
+    }
+
+    /// Formerly: CL_Disconnect
+    /// tell the server the bot is leaving and close the connection
+    pub fn disconnect(&mut self) {
+        // send a clear message
+        let msg = [ClientToServerMessage::Disconnect as Byte];
+        for _ in 0..3 {
+            let _ = self.conn.send_unreliable_message(&msg);
+        }
+        self.conn.close();
+
+        self.message.data.clear();
+        self.signon = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use net_loop::{LoopDriverT, LoopSocketT, LOOP_HOST};
+
+    /// the server end of the loop, scripted by hand
+    struct ServerT {
+        conn: LoopSocketT,
+    }
+
+    impl ServerT {
+        fn send(&mut self, build: impl FnOnce(&mut MsgWriterT)) {
+            let mut msg = MsgWriterT::new();
+            build(&mut msg);
+            self.conn.send_message(&msg.data).unwrap();
+        }
+
+        fn send_unreliable(&mut self, build: impl FnOnce(&mut MsgWriterT)) {
+            let mut msg = MsgWriterT::new();
+            build(&mut msg);
+            self.conn.send_unreliable_message(&msg.data).unwrap();
+        }
+
+        /// every string command waiting, and the first byte of anything else
+        fn commands(&mut self) -> Vec<String> {
+            let mut commands = Vec::new();
+            while let Some(message) = self.conn.get_message().unwrap() {
+                let data = match message {
+                    NetMessageT::Reliable(data) | NetMessageT::Unreliable(data) => data,
+                };
+                let mut msg = MsgReaderT::new(&data);
+                loop {
+                    let cmd = msg.read_byte();
+                    if cmd == ClientToServerMessage::StringCmd as c_int {
+                        let s = msg.read_string();
+                        commands.push(String::from_utf8_lossy(&s).trim().to_string());
+                    } else {
+                        if cmd != -1 {
+                            commands.push(format!("clc {}", cmd));
+                        }
+                        break;
+                    }
+                }
+            }
+            return commands;
+        }
+
+        /// svc_time then a fast update of entity 1's x
+        fn update(&mut self, time: c_float, x: c_float) {
+            self.send_unreliable(|msg| {
+                msg.write_byte(ServerToClientMessage::Time as c_int);
+                msg.write_float(time);
+                msg.write_byte((UpdateFlags::Signal | UpdateFlags::Origin1).bits() as c_int);
+                msg.write_byte(1);
+                msg.write_coord(x, 0);
+            });
+        }
+    }
+
+    fn frame(bot: &mut BotClientT<LoopSocketT>, frametime: c_double) -> Vec<BotEventT> {
+        let events = bot.read_messages(frametime).unwrap();
+        bot.send_cmd(&UserCmdT::default()).unwrap();
+        return events;
+    }
+
+    fn signons(events: &[BotEventT]) -> Vec<c_int> {
+        return events
+            .iter()
+            .filter_map(|e| match *e {
+                BotEventT::Signon(i) => Some(i),
+                _ => None,
+            })
+            .collect();
+    }
+
+    #[test]
+    fn signs_on_and_follows_an_entity() {
+        let mut driver = LoopDriverT::new();
+        let conn = driver.connect_local(LOOP_HOST).unwrap();
+        let mut server = ServerT {
+            conn: driver.check_new_local_connections().unwrap(),
+        };
+        let mut settings = BotSettingsT::default();
+        settings.name = "bot".to_string();
+        settings.color = 0x4d;
+        let mut bot = BotClientT::new(conn, settings);
+
+        server.send(|msg| {
+            msg.write_byte(ServerToClientMessage::ServerInfo as c_int);
+            msg.write_long(PROTOCOL_NETQUAKE as c_int);
+            msg.write_byte(1); // maxclients
+            msg.write_byte(0); // coop
+            msg.write_string(b"The Test");
+            msg.write_string(b"maps/test.bsp");
+            msg.write_string(b"progs/player.mdl");
+            msg.write_string(b"");
+            msg.write_string(b"");
+            msg.write_byte(ServerToClientMessage::SignOnNum as c_int);
+            msg.write_byte(1);
+        });
+        let events = frame(&mut bot, 0.0);
+        assert!(matches!(events[0], BotEventT::ServerInfo));
+        assert_eq!(signons(&events), [1]);
+        assert_eq!(server.commands(), ["prespawn"]);
+
+        server.send(|msg| {
+            msg.write_byte(ServerToClientMessage::SpawnBaseline as c_int);
+            msg.write_short(1);
+            for &b in [2, 0, 1, 0].iter() {
+                msg.write_byte(b); // model, frame, colormap, skin
+            }
+            for _ in 0..3 {
+                msg.write_coord(0.0, 0);
+                msg.write_angle(0.0, 0);
+            }
+            msg.write_byte(ServerToClientMessage::SignOnNum as c_int);
+            msg.write_byte(2);
+        });
+        assert_eq!(signons(&frame(&mut bot, 0.0)), [2]);
+        assert_eq!(server.commands(), ["name \"bot\"", "color 4 13", "spawn"]);
+
+        server.send(|msg| {
+            msg.write_byte(ServerToClientMessage::Setview as c_int);
+            msg.write_short(1);
+            msg.write_byte(ServerToClientMessage::SignOnNum as c_int);
+            msg.write_byte(3);
+        });
+        assert_eq!(signons(&frame(&mut bot, 0.0)), [3]);
+        assert_eq!(server.commands(), ["begin"]);
+        assert!(bot.player().is_none());
+
+        // the first update finishes the signon, and moves follow
+        server.update(1.0, 0.0);
+        server.update(1.1, 40.0);
+        assert_eq!(signons(&frame(&mut bot, 1.05)), [SIGNONS as c_int]);
+        assert_eq!(bot.signon(), SIGNONS as c_int);
+        // the first two moves are held back, as CL_SendMove does
+        assert!(server.commands().is_empty());
+
+        // relinked halfway between the two updates
+        let player = bot.player().unwrap();
+        assert!((player.origin[0] - 20.0).abs() < 1e-3);
+        assert_eq!(bot.entity_model(1), Some(&b"progs/player.mdl"[..]));
+
+        // and dropped once it's left out of a message
+        server.send_unreliable(|msg| {
+            msg.write_byte(ServerToClientMessage::Time as c_int);
+            msg.write_float(1.2);
+        });
+        frame(&mut bot, 0.1);
+        assert!(bot.entity(1).is_none());
+        assert!(server.commands().is_empty());
+
+        frame(&mut bot, 0.1);
+        let move_cmd = format!("clc {}", ClientToServerMessage::Move as c_int);
+        assert_eq!(server.commands(), [move_cmd]);
+    }
+}
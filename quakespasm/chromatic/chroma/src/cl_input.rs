@@ -0,0 +1,543 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2002-2009 John Fitzgibbons and others
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// cl_input.rs -- builds an intended movement command to send to the server
+
+/*
+===============================================================================
+
+KEY BUTTONS
+
+Continuous button event tracking is complicated by the fact that two different
+input sources (say, mouse button 1 and the control key) can both press the
+same button, but the button should only be released when both of the
+pressing key have been released.
+
+When a key event issues a button command (+forward, +attack, etc), it appends
+its key number as a parameter to the command so it can be matched up with
+the release.
+
+state bit 0 is the current state of the key
+state bit 1 is edge triggered on the up to down transition
+state bit 2 is edge triggered on the down to up transition
+
+===============================================================================
+*/
+
+use cl_parse::ParseError;
+use client::{ClientStateT, KButtonT, SIGNONS};
+use common::{MsgReaderT, MsgWriterT};
+use mathlib::anglemod;
+use protocol::{ClientToServerMessage, UserCmdT, PROTOCOL_NETQUAKE};
+use std::os::raw::{c_double, c_float, c_int, c_uint};
+use view::stop_pitch_drift;
+use Byte;
+
+const PITCH: usize = 0;
+const YAW: usize = 1;
+const ROLL: usize = 2;
+
+/// button bit for +attack in clc_move
+pub const BUTTON_ATTACK: c_int = 1;
+/// button bit for +jump in clc_move
+pub const BUTTON_JUMP: c_int = 2;
+
+impl KButtonT {
+    /// Formerly: KeyDown
+    /// key is the key number the binding appended, or -1 when typed manually at the console
+    /// for continuous down.  false if two other keys already hold the button
+    pub fn key_down(&mut self, key: c_int) -> bool {
+        if key == self.down[0] || key == self.down[1] {
+            // repeating key
+            return true;
+        }
+
+        if self.down[0] == 0 {
+            self.down[0] = key;
+        } else if self.down[1] == 0 {
+            self.down[1] = key;
+        } else {
+            return false;
+        }
+
+        if self.state & 1 != 0 {
+            // still down
+            return true;
+        }
+        // down + impulse down
+        self.state |= 1 + 2;
+        return true;
+    }
+
+    /// Formerly: KeyUp
+    /// key is the key number the binding appended, or -1 when typed manually at the console,
+    /// which is assumed to be for unsticking, so every key is let go
+    pub fn key_up(&mut self, key: c_int) {
+        if key == -1 {
+            self.down = [0, 0];
+            // impulse up
+            self.state = 4;
+            return;
+        }
+
+        if self.down[0] == key {
+            self.down[0] = 0;
+        } else if self.down[1] == key {
+            self.down[1] = 0;
+        } else {
+            // key up without coresponding down (menu pass through)
+            return;
+        }
+        if self.down[0] != 0 || self.down[1] != 0 {
+            // some other key is still holding it down
+            return;
+        }
+
+        if self.state & 1 == 0 {
+            // still up (this should not happen)
+            return;
+        }
+        // now up, impulse up
+        self.state &= !1;
+        self.state |= 4;
+    }
+
+    pub fn is_down(&self) -> bool {
+        return self.state & 1 != 0;
+    }
+
+    /// Formerly: CL_KeyState
+    /// Returns 0.25 if a key was pressed and released during the frame,
+    /// 0.5 if it was pressed and held
+    /// 0 if held then released, and
+    /// 1.0 if held for the entire time
+    pub fn key_state(&mut self) -> c_float {
+        let impulsedown = self.state & 2 != 0;
+        let impulseup = self.state & 4 != 0;
+        let down = self.state & 1 != 0;
+
+        let val = match (impulsedown, impulseup, down) {
+            // pressed and held this frame
+            (true, false, true) => 0.5,
+            // held the entire frame
+            (false, false, true) => 1.0,
+            // released and re-pressed this frame
+            (true, true, true) => 0.75,
+            // pressed and released this frame
+            (true, true, false) => 0.25,
+            // released this frame, up the entire frame, or impossible
+            _ => 0.0,
+        };
+
+        // clear impulses
+        self.state &= 1;
+
+        return val;
+    }
+}
+
+/// what an input command did, for the caller to follow up on
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InputEventT {
+    /// not a button or impulse command
+    Unknown,
+    Handled,
+    /// "Three keys down for a button!"
+    TooManyKeys,
+    /// -mlook let go of mouse look; V_StartPitchDrift is due if lookspring is set
+    MLookReleased,
+}
+
+/// the button states and pending impulse the +/- commands drive
+#[derive(Clone, Copy)]
+pub struct InputT {
+    pub mlook: KButtonT,
+    pub klook: KButtonT,
+    pub left: KButtonT,
+    pub right: KButtonT,
+    pub forward: KButtonT,
+    pub back: KButtonT,
+    pub lookup: KButtonT,
+    pub lookdown: KButtonT,
+    pub moveleft: KButtonT,
+    pub moveright: KButtonT,
+    pub strafe: KButtonT,
+    pub speed: KButtonT,
+    pub r#use: KButtonT,
+    pub jump: KButtonT,
+    pub attack: KButtonT,
+    pub up: KButtonT,
+    pub down: KButtonT,
+    pub impulse: c_int,
+}
+
+impl InputT {
+    pub const fn default() -> Self {
+        Self {
+            mlook: KButtonT::default(),
+            klook: KButtonT::default(),
+            left: KButtonT::default(),
+            right: KButtonT::default(),
+            forward: KButtonT::default(),
+            back: KButtonT::default(),
+            lookup: KButtonT::default(),
+            lookdown: KButtonT::default(),
+            moveleft: KButtonT::default(),
+            moveright: KButtonT::default(),
+            strafe: KButtonT::default(),
+            speed: KButtonT::default(),
+            r#use: KButtonT::default(),
+            jump: KButtonT::default(),
+            attack: KButtonT::default(),
+            up: KButtonT::default(),
+            down: KButtonT::default(),
+            impulse: 0,
+        }
+    }
+
+    /// the button a +name/-name command pair drives
+    pub fn button_mut(&mut self, name: &str) -> Option<&mut KButtonT> {
+        return Some(match name {
+            "moveup" => &mut self.up,
+            "movedown" => &mut self.down,
+            "left" => &mut self.left,
+            "right" => &mut self.right,
+            "forward" => &mut self.forward,
+            "back" => &mut self.back,
+            "lookup" => &mut self.lookup,
+            "lookdown" => &mut self.lookdown,
+            "strafe" => &mut self.strafe,
+            "moveleft" => &mut self.moveleft,
+            "moveright" => &mut self.moveright,
+            "speed" => &mut self.speed,
+            "attack" => &mut self.attack,
+            "use" => &mut self.r#use,
+            "jump" => &mut self.jump,
+            "klook" => &mut self.klook,
+            "mlook" => &mut self.mlook,
+            _ => return None,
+        });
+    }
+
+    /// Formerly: the commands CL_InitInput adds
+    /// run a button command line like "+forward 17", "-forward" or "impulse 10"
+    pub fn command(&mut self, line: &str) -> InputEventT {
+        let mut args = line.split_whitespace();
+        let cmd = match args.next() {
+            Some(cmd) => cmd,
+            None => return InputEventT::Unknown,
+        };
+        // atoi, missing means typed manually at the console
+        let arg = args.next().map(|a| a.parse::<c_int>().unwrap_or(0));
+
+        if cmd == "impulse" {
+            self.impulse = arg.unwrap_or(0);
+            return InputEventT::Handled;
+        }
+
+        let down = cmd.starts_with('+');
+        if !down && !cmd.starts_with('-') {
+            return InputEventT::Unknown;
+        }
+        let name = &cmd[1..];
+        let b = match self.button_mut(name) {
+            Some(b) => b,
+            None => return InputEventT::Unknown,
+        };
+        let key = arg.unwrap_or(-1);
+        if down {
+            if !b.key_down(key) {
+                return InputEventT::TooManyKeys;
+            }
+        } else {
+            b.key_up(key);
+            if name == "mlook" && !b.is_down() {
+                return InputEventT::MLookReleased;
+            }
+        }
+        return InputEventT::Handled;
+    }
+}
+
+impl Default for InputT {
+    fn default() -> Self {
+        Self::default()
+    }
+}
+
+/// the cvars movement depends on, with their stock defaults
+#[derive(Clone, Copy)]
+pub struct MoveSettingsT {
+    /// cl_upspeed
+    pub upspeed: c_float,
+    /// cl_forwardspeed
+    pub forwardspeed: c_float,
+    /// cl_backspeed
+    pub backspeed: c_float,
+    /// cl_sidespeed
+    pub sidespeed: c_float,
+    /// cl_movespeedkey
+    pub movespeedkey: c_float,
+    /// cl_yawspeed
+    pub yawspeed: c_float,
+    /// cl_pitchspeed
+    pub pitchspeed: c_float,
+    /// cl_anglespeedkey
+    pub anglespeedkey: c_float,
+    /// cl_alwaysrun
+    pub alwaysrun: bool,
+    /// cl_maxpitch
+    pub maxpitch: c_float,
+    /// cl_minpitch
+    pub minpitch: c_float,
+}
+
+impl MoveSettingsT {
+    pub const fn default() -> Self {
+        Self {
+            upspeed: 200.0,
+            forwardspeed: 200.0,
+            backspeed: 200.0,
+            sidespeed: 350.0,
+            movespeedkey: 2.0,
+            yawspeed: 140.0,
+            pitchspeed: 150.0,
+            anglespeedkey: 1.5,
+            alwaysrun: false,
+            maxpitch: 90.0,
+            minpitch: -90.0,
+        }
+    }
+}
+
+impl Default for MoveSettingsT {
+    fn default() -> Self {
+        Self::default()
+    }
+}
+
+/// Formerly: CL_AdjustAngles
+/// Moves the local angle positions
+pub fn adjust_angles(
+    cl: &mut ClientStateT,
+    input: &mut InputT,
+    settings: &MoveSettingsT,
+    frametime: c_double,
+) {
+    let speed = if input.speed.is_down() ^ settings.alwaysrun {
+        frametime as c_float * settings.anglespeedkey
+    } else {
+        frametime as c_float
+    };
+
+    if !input.strafe.is_down() {
+        cl.viewangles[YAW] -= speed * settings.yawspeed * input.right.key_state();
+        cl.viewangles[YAW] += speed * settings.yawspeed * input.left.key_state();
+        cl.viewangles[YAW] = anglemod(cl.viewangles[YAW]);
+    }
+    if input.klook.is_down() {
+        stop_pitch_drift(cl);
+        cl.viewangles[PITCH] -= speed * settings.pitchspeed * input.forward.key_state();
+        cl.viewangles[PITCH] += speed * settings.pitchspeed * input.back.key_state();
+    }
+
+    let up = input.lookup.key_state();
+    let down = input.lookdown.key_state();
+
+    cl.viewangles[PITCH] -= speed * settings.pitchspeed * up;
+    cl.viewangles[PITCH] += speed * settings.pitchspeed * down;
+
+    if up != 0.0 || down != 0.0 {
+        stop_pitch_drift(cl);
+    }
+
+    // variable pitch clamping
+    if cl.viewangles[PITCH] > settings.maxpitch {
+        cl.viewangles[PITCH] = settings.maxpitch;
+    }
+    if cl.viewangles[PITCH] < settings.minpitch {
+        cl.viewangles[PITCH] = settings.minpitch;
+    }
+
+    cl.viewangles[ROLL] = cl.viewangles[ROLL].clamp(-50.0, 50.0);
+}
+
+/// Formerly: CL_BaseMove
+/// the intended movement from the buttons.  signon is cls.signon; nothing moves until the
+/// connection is fully signed on
+pub fn base_move(
+    cl: &mut ClientStateT,
+    input: &mut InputT,
+    settings: &MoveSettingsT,
+    frametime: c_double,
+    signon: c_int,
+) -> UserCmdT {
+    let mut cmd = UserCmdT::default();
+    if signon != SIGNONS as c_int {
+        return cmd;
+    }
+
+    adjust_angles(cl, input, settings, frametime);
+
+    if input.strafe.is_down() {
+        cmd.sidemove += settings.sidespeed * input.right.key_state();
+        cmd.sidemove -= settings.sidespeed * input.left.key_state();
+    }
+
+    cmd.sidemove += settings.sidespeed * input.moveright.key_state();
+    cmd.sidemove -= settings.sidespeed * input.moveleft.key_state();
+
+    cmd.upmove += settings.upspeed * input.up.key_state();
+    cmd.upmove -= settings.upspeed * input.down.key_state();
+
+    if !input.klook.is_down() {
+        cmd.forwardmove += settings.forwardspeed * input.forward.key_state();
+        cmd.forwardmove -= settings.backspeed * input.back.key_state();
+    }
+
+    // adjust for speed key
+    if input.speed.is_down() ^ settings.alwaysrun {
+        cmd.forwardmove *= settings.movespeedkey;
+        cmd.sidemove *= settings.movespeedkey;
+        cmd.upmove *= settings.movespeedkey;
+    }
+    return cmd;
+}
+
+/// the contents of a clc_move
+#[derive(Clone, Copy)]
+pub struct MoveT {
+    /// cl.mtime[0] of the client, so server can get ping times
+    pub time: c_float,
+    /// viewangles and movement; moves go out as whole numbers
+    pub cmd: UserCmdT,
+    /// BUTTON_ATTACK, BUTTON_JUMP
+    pub buttons: c_int,
+    pub impulse: c_int,
+}
+
+/// Formerly: part of CL_SendMove
+/// write a clc_move.  angles are 8 bit for PROTOCOL_NETQUAKE and 16 bit otherwise, unless
+/// protocolflags asks for ShortAngle or FloatAngle
+pub fn write_move(msg: &mut MsgWriterT, mv: &MoveT, protocol: c_uint, protocolflags: c_uint) {
+    msg.write_byte(ClientToServerMessage::Move as c_int);
+
+    msg.write_float(mv.time);
+
+    for i in 0..3 {
+        if protocol == PROTOCOL_NETQUAKE {
+            msg.write_angle(mv.cmd.viewangles[i], protocolflags);
+        } else {
+            msg.write_angle16(mv.cmd.viewangles[i], protocolflags);
+        }
+    }
+
+    msg.write_short(mv.cmd.forwardmove as c_int);
+    msg.write_short(mv.cmd.sidemove as c_int);
+    msg.write_short(mv.cmd.upmove as c_int);
+
+    msg.write_byte(mv.buttons);
+    msg.write_byte(mv.impulse);
+}
+
+/// Formerly: SV_ReadClientMove
+/// read a clc_move after its command byte, see write_move
+pub fn read_move(
+    msg: &mut MsgReaderT,
+    protocol: c_uint,
+    protocolflags: c_uint,
+) -> Result<MoveT, ParseError> {
+    let time = msg.read_float();
+
+    let mut cmd = UserCmdT::default();
+    for i in 0..3 {
+        cmd.viewangles[i] = if protocol == PROTOCOL_NETQUAKE {
+            msg.read_angle(protocolflags)
+        } else {
+            msg.read_angle16(protocolflags)
+        };
+    }
+
+    cmd.forwardmove = msg.read_short() as c_float;
+    cmd.sidemove = msg.read_short() as c_float;
+    cmd.upmove = msg.read_short() as c_float;
+
+    let buttons = msg.read_byte();
+    let impulse = msg.read_byte();
+    if msg.badread {
+        return Err(ParseError::BadRead);
+    }
+    return Ok(MoveT {
+        time,
+        cmd,
+        buttons,
+        impulse,
+    });
+}
+
+/// Formerly: CL_SendMove
+/// the clc_move for cmd, consuming the attack and jump presses and the impulse.  None when
+/// nothing should go out: during demo playback, and for the first two moves of a level,
+/// which may contain leftover inputs from the last level
+pub fn send_move(
+    cl: &mut ClientStateT,
+    input: &mut InputT,
+    cmd: &UserCmdT,
+    demoplayback: bool,
+) -> Option<Vec<Byte>> {
+    cl.cmd = *cmd;
+
+    let mut mv = MoveT {
+        time: cl.mtime[0] as c_float,
+        cmd: *cmd,
+        buttons: 0,
+        impulse: input.impulse,
+    };
+    mv.cmd.viewangles = cl.viewangles;
+
+    // send button bits
+    if input.attack.state & 3 != 0 {
+        mv.buttons |= BUTTON_ATTACK;
+    }
+    input.attack.state &= !2;
+
+    if input.jump.state & 3 != 0 {
+        mv.buttons |= BUTTON_JUMP;
+    }
+    input.jump.state &= !2;
+
+    input.impulse = 0;
+
+    let mut msg = MsgWriterT::new();
+    write_move(&mut msg, &mv, cl.protocol, cl.protocolflags);
+
+    // deliver the message
+    if demoplayback {
+        return None;
+    }
+
+    cl.movemessages += 1;
+    if cl.movemessages <= 2 {
+        return None;
+    }
+    return Some(msg.data);
+}
@@ -21,6 +21,333 @@ Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
 */
 // cl_main.rs  -- client main loop
 
+use client::{ClientStateT, DlightT, MAX_VISEDICTS};
+use gl_model::{EntityEffects, ModelFlags};
+use mathlib::{angle_vectors, anglemod, vector_ma, RandT};
+use r_alias::angle_delta;
+use render::{EntityT, LerpFlags};
+use std::os::raw::{c_double, c_float, c_int};
+use std::ptr::null_mut;
+use Vec3T;
+
+/// effects left behind by relinked entities; the particle system implements this, a client
+/// without a renderer can use ()
+pub trait ClientEffects {
+    /// Formerly: R_EntityParticles
+    fn entity_particles(&mut self, ent: &EntityT);
+    /// Formerly: R_RocketTrail
+    fn rocket_trail(&mut self, start: Vec3T, end: Vec3T, r#type: c_int);
+}
+
+impl ClientEffects for () {
+    fn entity_particles(&mut self, _ent: &EntityT) {}
+    fn rocket_trail(&mut self, _start: Vec3T, _end: Vec3T, _type: c_int) {}
+}
+
+/// cvars and connection state relink_entities depends on
+#[derive(Clone, Copy)]
+pub struct RelinkSettingsT {
+    /// cl_nolerp
+    pub nolerp: bool,
+    /// r_lerpmove
+    pub lerpmove: bool,
+    /// r_lerpmodels
+    pub lerpmodels: c_int,
+    /// chase_active, draw the player's own entity
+    pub chase_active: bool,
+    /// cls.timedemo or sv.active, snap to the latest message instead of lerping between two
+    pub snap: bool,
+    /// cls.demoplayback, view angles come from the messages
+    pub demoplayback: bool,
+}
+
+impl RelinkSettingsT {
+    pub const fn default() -> Self {
+        Self {
+            nolerp: false,
+            lerpmove: true,
+            lerpmodels: 1,
+            chase_active: false,
+            snap: false,
+            demoplayback: false,
+        }
+    }
+}
+
+impl Default for RelinkSettingsT {
+    fn default() -> Self {
+        Self::default()
+    }
+}
+
+/// Formerly: CL_AllocDlight
+/// a cleared dlight for key, reusing the one key already has, else one that has died, else
+/// the first.  key 0 never matches.  None only if there are no dlights at all
+pub fn alloc_dlight(dlights: &mut [DlightT], key: c_int, time: c_double) -> Option<&mut DlightT> {
+    if dlights.is_empty() {
+        return None;
+    }
+    let i = dlights
+        .iter()
+        .position(|dl| key != 0 && dl.key == key)
+        .or_else(|| dlights.iter().position(|dl| (dl.die as c_double) < time))
+        .unwrap_or(0);
+
+    let dl = &mut dlights[i];
+    *dl = DlightT::default();
+    dl.key = key;
+    // lit support via lordhavoc
+    dl.color = Vec3T::new(1.0, 1.0, 1.0);
+    return Some(dl);
+}
+
+/// Formerly: CL_DecayLights
+pub fn decay_lights(dlights: &mut [DlightT], time: c_double, oldtime: c_double) {
+    let frametime = (time - oldtime) as c_float;
+    for dl in dlights.iter_mut() {
+        if (dl.die as c_double) < time || dl.radius == 0.0 {
+            continue;
+        }
+
+        dl.radius = (dl.radius - frametime * dl.decay).max(0.0);
+    }
+}
+
+/// Formerly: CL_LerpPoint
+/// Determines the fraction between the last two messages that the objects should be put at.
+pub fn lerp_point(cl: &mut ClientStateT, settings: &RelinkSettingsT) -> c_float {
+    let mut f = cl.mtime[0] - cl.mtime[1];
+
+    if f == 0.0 || settings.snap {
+        cl.time = cl.mtime[0];
+        return 1.0;
+    }
+
+    if f > 0.1 {
+        // dropped packet, or start of demo
+        cl.mtime[1] = cl.mtime[0] - 0.1;
+        f = 0.1;
+    }
+
+    let mut frac = (cl.time - cl.mtime[1]) / f;
+
+    if frac < 0.0 {
+        if frac < -0.01 {
+            cl.time = cl.mtime[1];
+        }
+        frac = 0.0;
+    } else if frac > 1.0 {
+        if frac > 1.01 {
+            cl.time = cl.mtime[0];
+        }
+        frac = 1.0;
+    }
+
+    // better nolerp behavior
+    if settings.nolerp {
+        return 1.0;
+    }
+
+    return frac as c_float;
+}
+
+fn add_lerp_flags(e: &mut EntityT, flags: LerpFlags) {
+    e.lerpflags = (LerpFlags::from_bits_truncate(e.lerpflags as _) | flags).bits() as _;
+}
+
+/// Formerly: the player part of CL_RelinkEntities
+/// interpolate the player's velocity, and the view angles when they come from a demo
+pub fn lerp_player(cl: &mut ClientStateT, settings: &RelinkSettingsT, frac: c_float) {
+    for i in 0..3 {
+        cl.velocity[i] = cl.mvelocity[1][i] + frac * (cl.mvelocity[0][i] - cl.mvelocity[1][i]);
+    }
+
+    if settings.demoplayback {
+        // interpolate the angles
+        let d = angle_delta(cl.mviewangles[1], cl.mviewangles[0]);
+        for j in 0..3 {
+            cl.viewangles[j] = cl.mviewangles[1][j] + frac * d[j];
+        }
+    }
+}
+
+/// Formerly: the lerp part of CL_RelinkEntities
+/// move ent to its lerped position for frac; false if it wasn't in the last message, its
+/// model is then cleared
+pub fn lerp_entity(
+    cl: &ClientStateT,
+    ent: &mut EntityT,
+    frac: c_float,
+    settings: &RelinkSettingsT,
+) -> bool {
+    // if the object wasn't included in the last packet, remove it
+    if ent.msgtime != cl.mtime[0] {
+        ent.model = null_mut();
+        // next time this entity slot is reused, the lerp will need to be reset
+        add_lerp_flags(ent, LerpFlags::ResetMove | LerpFlags::ResetAnim);
+        return false;
+    }
+
+    if ent.forcelink == true {
+        // the entity was not updated in the last message so move to the final spot
+        ent.origin = ent.msg_origins[0];
+        ent.angles = ent.msg_angles[0];
+    } else {
+        // if the delta is large, assume a teleport and don't lerp
+        let mut f = frac;
+        let mut delta = Vec3T::default();
+        for j in 0..3 {
+            delta[j] = ent.msg_origins[0][j] - ent.msg_origins[1][j];
+            if delta[j] > 100.0 || delta[j] < -100.0 {
+                // assume a teleportation, not a motion
+                f = 1.0;
+                // don't lerp teleports
+                add_lerp_flags(ent, LerpFlags::ResetMove);
+            }
+        }
+
+        // don't cl_lerp entities that will be r_lerped
+        let flags = LerpFlags::from_bits_truncate(ent.lerpflags as _);
+        if settings.lerpmove && flags.contains(LerpFlags::MoveStep) {
+            f = 1.0;
+        }
+
+        // interpolate the origin and angles
+        let d = angle_delta(ent.msg_angles[1], ent.msg_angles[0]);
+        for j in 0..3 {
+            ent.origin[j] = ent.msg_origins[1][j] + f * delta[j];
+            ent.angles[j] = ent.msg_angles[1][j] + f * d[j];
+        }
+    }
+
+    ent.forcelink = false.into();
+    return true;
+}
+
+/// Formerly: CL_RelinkEntities
+/// move the entities to their lerped positions for cl.time and collect the ones to draw in
+/// visedicts.  entities is cl_entities, dlights cl_dlights and visedicts cl_visedicts;
+/// returns cl_numvisedicts
+pub fn relink_entities<E: ClientEffects>(
+    cl: &mut ClientStateT,
+    entities: &mut [EntityT],
+    dlights: &mut [DlightT],
+    settings: &RelinkSettingsT,
+    effects: &mut E,
+    rand: &mut RandT,
+    visedicts: &mut [*mut EntityT],
+) -> c_int {
+    // determine partial update time
+    let frac = lerp_point(cl, settings);
+
+    let mut numvisedicts = 0;
+
+    //
+    // interpolate player info
+    //
+    lerp_player(cl, settings, frac);
+
+    let bobjrotate = anglemod(100.0 * cl.time as c_float);
+
+    // start on the entity after the world
+    let num_entities = (cl.num_entities.max(0) as usize).min(entities.len());
+    for (i, ent) in entities.iter_mut().enumerate().take(num_entities).skip(1) {
+        if ent.model.is_null() {
+            // empty slot
+            continue;
+        }
+
+        let oldorg = ent.origin;
+        if !lerp_entity(cl, ent, frac, settings) {
+            continue;
+        }
+
+        let modelflags = match unsafe { ent.model.as_ref() } {
+            Some(model) => ModelFlags::from_bits_truncate(model.flags as _),
+            None => ModelFlags::empty(),
+        };
+        let entflags = EntityEffects::from_bits_truncate(ent.effects as _);
+
+        // rotate binary objects locally
+        if modelflags.contains(ModelFlags::Rotate) {
+            ent.angles[1] = bobjrotate;
+        }
+
+        if entflags.contains(EntityEffects::BrightField) {
+            effects.entity_particles(ent);
+        }
+
+        if entflags.contains(EntityEffects::MuzzleFlash) {
+            if let Some(dl) = alloc_dlight(dlights, i as c_int, cl.time) {
+                dl.origin = ent.origin;
+                dl.origin[2] += 16.0;
+                let (fv, _, _) = angle_vectors(ent.angles);
+                dl.origin = vector_ma(dl.origin, 18.0, fv);
+                dl.radius = 200.0 + (rand.rand() & 31) as c_float;
+                dl.minlight = 32.0;
+                dl.die = (cl.time + 0.1) as c_float;
+            }
+
+            // assume muzzle flash accompanied by muzzle flare, which looks bad when lerped
+            if settings.lerpmodels != 2 {
+                // no lerping for two frames
+                let reset = LerpFlags::ResetAnim | LerpFlags::ResetAnim2;
+                if i as c_int == cl.viewentity {
+                    add_lerp_flags(&mut cl.viewent, reset);
+                } else {
+                    add_lerp_flags(ent, reset);
+                }
+            }
+        }
+        if entflags.contains(EntityEffects::BrightLight) {
+            if let Some(dl) = alloc_dlight(dlights, i as c_int, cl.time) {
+                dl.origin = ent.origin;
+                dl.origin[2] += 16.0;
+                dl.radius = 400.0 + (rand.rand() & 31) as c_float;
+                dl.die = (cl.time + 0.001) as c_float;
+            }
+        }
+        if entflags.contains(EntityEffects::DimLight) {
+            if let Some(dl) = alloc_dlight(dlights, i as c_int, cl.time) {
+                dl.origin = ent.origin;
+                dl.radius = 200.0 + (rand.rand() & 31) as c_float;
+                dl.die = (cl.time + 0.001) as c_float;
+            }
+        }
+
+        if modelflags.contains(ModelFlags::Gib) {
+            effects.rocket_trail(oldorg, ent.origin, 2);
+        } else if modelflags.contains(ModelFlags::ZomGib) {
+            effects.rocket_trail(oldorg, ent.origin, 4);
+        } else if modelflags.contains(ModelFlags::Tracer) {
+            effects.rocket_trail(oldorg, ent.origin, 3);
+        } else if modelflags.contains(ModelFlags::Tracer2) {
+            effects.rocket_trail(oldorg, ent.origin, 5);
+        } else if modelflags.contains(ModelFlags::Rocket) {
+            effects.rocket_trail(oldorg, ent.origin, 0);
+            if let Some(dl) = alloc_dlight(dlights, i as c_int, cl.time) {
+                dl.origin = ent.origin;
+                dl.radius = 200.0;
+                dl.die = (cl.time + 0.01) as c_float;
+            }
+        } else if modelflags.contains(ModelFlags::Grenade) {
+            effects.rocket_trail(oldorg, ent.origin, 1);
+        } else if modelflags.contains(ModelFlags::Tracer3) {
+            effects.rocket_trail(oldorg, ent.origin, 6);
+        }
+
+        if i as c_int == cl.viewentity && !settings.chase_active {
+            continue;
+        }
+
+        if numvisedicts < visedicts.len().min(MAX_VISEDICTS) {
+            visedicts[numvisedicts] = ent;
+            numvisedicts += 1;
+        }
+    }
+    return numvisedicts as c_int;
+}
+
 #[allow(bad_style)]
 pub mod capi {
     use crate::MAX_LIGHTSTYLES;
@@ -62,3 +389,137 @@ pub mod capi {
     #[no_mangle]
     pub static mut cl_visedicts: [*mut EntityT; MAX_VISEDICTS] = [null_mut(); MAX_VISEDICTS];
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::MAX_DLIGHTS;
+    use gl_model::QModelT;
+    use Byte;
+
+    fn lerped_entity(
+        model: &mut QModelT,
+        from: c_float,
+        to: c_float,
+        msgtime: c_double,
+    ) -> EntityT {
+        let mut ent = EntityT::default();
+        ent.model = model;
+        ent.msgtime = msgtime;
+        ent.msg_origins[1][0] = from;
+        ent.msg_origins[0][0] = to;
+        return ent;
+    }
+
+    #[test]
+    fn relink_fills_visedicts() {
+        let mut cl = Box::new(ClientStateT::default());
+        let mut model = QModelT::default();
+        model.flags = ModelFlags::Rotate.bits() as _;
+        let mut entities = vec![EntityT::default(); 4];
+        entities[1] = lerped_entity(&mut model, 16.0, 24.0, 1.1);
+        entities[2] = lerped_entity(&mut model, 0.0, 200.0, 1.1);
+        entities[2].effects = EntityEffects::DimLight.bits() as _;
+        entities[3] = lerped_entity(&mut model, 0.0, 8.0, 1.0);
+        cl.num_entities = 4;
+        cl.mtime = [1.1, 1.0];
+        cl.time = 1.05;
+
+        let mut dlights = [DlightT::default(); MAX_DLIGHTS];
+        let mut rand = RandT::new(0);
+        let mut visedicts = [null_mut(); MAX_VISEDICTS];
+        let settings = RelinkSettingsT::default();
+        let num = relink_entities(
+            &mut cl,
+            &mut entities,
+            &mut dlights,
+            &settings,
+            &mut (),
+            &mut rand,
+            &mut visedicts,
+        );
+
+        // halfway between the messages, teleports snap, and stale entities are removed
+        assert_eq!(num, 2);
+        assert_eq!(visedicts[0], &mut entities[1] as *mut EntityT);
+        assert_eq!(visedicts[1], &mut entities[2] as *mut EntityT);
+        assert!((entities[1].origin[0] - 20.0).abs() < 1e-3);
+        assert_eq!(entities[1].angles[1], anglemod(100.0 * 1.05));
+        assert_eq!(entities[2].origin[0], 200.0);
+        assert!(entities[3].model.is_null());
+
+        let mut expect = RandT::new(0);
+        assert_eq!(dlights[0].key, 2);
+        assert_eq!(dlights[0].radius, 200.0 + (expect.rand() & 31) as c_float);
+    }
+
+    #[test]
+    fn relink_caps_visedicts() {
+        let mut cl = Box::new(ClientStateT::default());
+        let mut model = QModelT::default();
+        let mut entities = vec![lerped_entity(&mut model, 0.0, 0.0, 1.0); 4];
+        cl.num_entities = 4;
+        cl.mtime = [1.0, 1.0];
+
+        let mut visedicts = [null_mut(); 2];
+        let num = relink_entities(
+            &mut cl,
+            &mut entities,
+            &mut [],
+            &RelinkSettingsT::default(),
+            &mut (),
+            &mut RandT::new(0),
+            &mut visedicts,
+        );
+        assert_eq!(num, 2);
+    }
+
+    #[test]
+    fn dlights_reuse_the_key_then_the_dead() {
+        let mut dlights = [DlightT::default(); 3];
+        dlights[0].die = 5.0;
+        dlights[1].key = 7;
+        dlights[1].die = 5.0;
+        let dl = alloc_dlight(&mut dlights, 7, 1.0).unwrap();
+        dl.radius = 100.0;
+        dl.die = 5.0;
+        assert_eq!(dlights[1].radius, 100.0);
+        alloc_dlight(&mut dlights, 8, 1.0).unwrap().radius = 50.0;
+        assert_eq!((dlights[2].key, dlights[2].radius), (8, 50.0));
+
+        // with none free, the first is taken
+        dlights[2].die = 5.0;
+        alloc_dlight(&mut dlights, 0, 1.0).unwrap();
+        assert_eq!(dlights[0].die, 0.0);
+
+        assert!(alloc_dlight(&mut [], 1, 1.0).is_none());
+    }
+
+    #[test]
+    fn relink_without_dlights() {
+        let mut cl = Box::new(ClientStateT::default());
+        let mut model = QModelT::default();
+        model.flags = ModelFlags::Rocket.bits() as _;
+        let mut entities = vec![lerped_entity(&mut model, 0.0, 0.0, 1.0); 2];
+        entities[1].effects = (EntityEffects::MuzzleFlash | EntityEffects::DimLight).bits() as _;
+        cl.num_entities = 2;
+        cl.mtime = [1.0, 1.0];
+
+        let mut visedicts = [null_mut(); MAX_VISEDICTS];
+        let num = relink_entities(
+            &mut cl,
+            &mut entities,
+            &mut [],
+            &RelinkSettingsT::default(),
+            &mut (),
+            &mut RandT::new(0),
+            &mut visedicts,
+        );
+        assert_eq!(num, 1);
+        // the flare still isn't lerped
+        assert_ne!(
+            entities[1].lerpflags & LerpFlags::ResetAnim2.bits() as Byte,
+            0
+        );
+    }
+}
@@ -0,0 +1,813 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2002-2009 John Fitzgibbons and others
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// cl_parse.rs -- parse entity updates and baselines from the server
+
+use client::{ClientStateT, SIGNONS};
+use common::MsgReaderT;
+use mathlib::RandT;
+use modelgen::SyncTypeT;
+use protocol::{
+    ent_alpha_encode, BaselineFlags, ServerUpdateFlags, SoundUpdateFlags, UpdateFlags,
+    DEFAULT_SOUND_PACKET_ATTENUATION, DEFAULT_SOUND_PACKET_VOLUME, DEFAULT_VIEWHEIGHT,
+    ENTALPHA_DEFAULT, PROTOCOL_FITZQUAKE, PROTOCOL_NETQUAKE, PROTOCOL_RMQ,
+};
+use render::{EntityT, LerpFlags};
+use std::os::raw::{c_float, c_int, c_uint};
+use strl::strlcpy_slice;
+use {
+    Byte, Vec3T, MAX_MODELS, MAX_SCOREBOARD, MAX_SOUNDS, STAT_ACTIVEWEAPON, STAT_AMMO, STAT_ARMOR,
+    STAT_CELLS, STAT_HEALTH, STAT_NAILS, STAT_ROCKETS, STAT_SHELLS, STAT_WEAPON, STAT_WEAPONFRAME,
+};
+
+/// a server message that can't be applied; Host_Error in the original
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    /// entity number below 0 or past cl_max_edicts
+    BadEntityNum(c_int),
+    /// model index past MAX_MODELS
+    BadModelNum(c_int),
+    /// colormap of a player slot past cl.maxclients
+    BadColormap(c_int),
+    /// svc_temp_entity of an unknown type; Sys_Error in the original
+    BadTempEntity(c_int),
+    /// a protocol other than PROTOCOL_NETQUAKE, PROTOCOL_FITZQUAKE or PROTOCOL_RMQ
+    BadProtocol(c_int),
+    /// svc_serverinfo maxclients outside 1..MAX_SCOREBOARD
+    BadMaxClients(c_int),
+    /// svc_serverinfo listed MAX_MODELS models or MAX_SOUNDS sounds
+    TooManyPrecaches,
+    /// svc_signonnum not past the current signon
+    BadSignon(c_int),
+    /// svc_updatename, svc_updatefrags or svc_updatecolors past cl.maxclients
+    BadScoreboardSlot(c_int),
+    /// svc_updatestat past MAX_CL_STATS; Sys_Error in the original
+    BadStat(c_int),
+    /// svc_lightstyle past MAX_LIGHTSTYLES; Sys_Error in the original
+    BadLightStyle(c_int),
+    /// sound index past MAX_SOUNDS
+    BadSoundNum(c_int),
+    /// svc_spawnstatic past MAX_STATIC_ENTITIES
+    TooManyStatics,
+    /// an unknown svc command byte
+    Illegible(c_int),
+    /// the message ended in the middle of an update
+    BadRead,
+}
+
+/// follow-up work for the caller of parse_update
+pub struct EntityUpdateT {
+    pub num: c_int,
+    /// the cl.model_precache index the entity now uses
+    pub model: c_int,
+    /// this was the first update, which is the final signon stage; CL_SignonReply is due
+    pub signon: bool,
+    /// a player's skin or model changed; R_TranslateNewPlayerSkin (num - 1) is due
+    pub translate_skin: bool,
+    /// the update used the nonstandard Nehahra transparency bit
+    pub nehahra: bool,
+}
+
+fn lerp_flags(e: &EntityT) -> LerpFlags {
+    return LerpFlags::from_bits_truncate(e.lerpflags as _);
+}
+
+fn set_lerp_flags(e: &mut EntityT, flags: LerpFlags) {
+    e.lerpflags = flags.bits() as _;
+}
+
+/// Formerly: CL_EntityNum
+/// entity num, growing cl.num_entities to cover it.  entities is cl_entities, cl_max_edicts
+/// long; slots coming into use get colormap, which is vid.colormap
+pub fn entity_num<'a>(
+    cl: &mut ClientStateT,
+    entities: &'a mut [EntityT],
+    num: c_int,
+    colormap: *mut Byte,
+) -> Result<&'a mut EntityT, ParseError> {
+    if num < 0 || num as usize >= entities.len() {
+        return Err(ParseError::BadEntityNum(num));
+    }
+
+    while cl.num_entities <= num {
+        let e = &mut entities[cl.num_entities as usize];
+        e.colormap = colormap;
+        let flags = lerp_flags(e) | LerpFlags::ResetMove | LerpFlags::ResetAnim;
+        set_lerp_flags(e, flags);
+        cl.num_entities += 1;
+    }
+
+    return Ok(&mut entities[num as usize]);
+}
+
+/// Formerly: CL_ParseBaseline
+/// version is 1 for svc_spawnbaseline and svc_spawnstatic, 2 for their PROTOCOL_FITZQUAKE
+/// versions, which start with BaselineFlags
+pub fn parse_baseline(
+    msg: &mut MsgReaderT,
+    ent: &mut EntityT,
+    version: c_int,
+    protocolflags: c_uint,
+) {
+    let bits = if version == 2 {
+        BaselineFlags::from_bits_truncate(msg.read_byte() as c_uint)
+    } else {
+        BaselineFlags::None
+    };
+    let baseline = &mut ent.baseline;
+    baseline.modelindex = if bits.contains(BaselineFlags::LargeModel) {
+        msg.read_short()
+    } else {
+        msg.read_byte()
+    } as _;
+    baseline.frame = if bits.contains(BaselineFlags::LargeFrame) {
+        msg.read_short()
+    } else {
+        msg.read_byte()
+    } as _;
+
+    baseline.colormap = msg.read_byte() as _;
+    baseline.skin = msg.read_byte() as _;
+    for i in 0..3 {
+        baseline.origin[i] = msg.read_coord(protocolflags);
+        baseline.angles[i] = msg.read_angle(protocolflags);
+    }
+
+    baseline.alpha = if bits.contains(BaselineFlags::Alpha) {
+        msg.read_byte() as _
+    } else {
+        ENTALPHA_DEFAULT as _
+    };
+}
+
+/// Formerly: CL_ParseUpdate
+/// Parse an entity update message from the server; bits is the command byte without the
+/// signal bit.  If an entities model or origin changes from frame to frame, it must be
+/// relinked.  Other attributes can change without relinking.  signon is cls.signon, colormap
+/// vid.colormap and rand the client's generator, for randomized animation
+pub fn parse_update(
+    msg: &mut MsgReaderT,
+    cl: &mut ClientStateT,
+    entities: &mut [EntityT],
+    bits: c_int,
+    signon: &mut c_int,
+    colormap: *mut Byte,
+    rand: &mut RandT,
+) -> Result<EntityUpdateT, ParseError> {
+    let mut update = EntityUpdateT {
+        num: 0,
+        model: 0,
+        signon: false,
+        translate_skin: false,
+        nehahra: false,
+    };
+
+    if *signon == SIGNONS as c_int - 1 {
+        // first update is the final signon stage
+        *signon = SIGNONS as c_int;
+        update.signon = true;
+    }
+
+    let mut bits = bits as c_uint;
+    if bits & UpdateFlags::MoreBits.bits() != 0 {
+        bits |= (msg.read_byte() as c_uint) << 8;
+    }
+
+    let fitz = cl.protocol == PROTOCOL_FITZQUAKE || cl.protocol == PROTOCOL_RMQ;
+    if fitz {
+        if bits & UpdateFlags::Extend1.bits() != 0 {
+            bits |= (msg.read_byte() as c_uint) << 16;
+        }
+        if bits & UpdateFlags::Extend2.bits() != 0 {
+            bits |= (msg.read_byte() as c_uint) << 24;
+        }
+    }
+    let bits = UpdateFlags::from_bits_truncate(bits);
+
+    let num = if bits.contains(UpdateFlags::LongEntity) {
+        msg.read_short()
+    } else {
+        msg.read_byte()
+    };
+    update.num = num;
+
+    let ent = entity_num(cl, entities, num, colormap)?;
+    let mtime = cl.mtime;
+
+    // no previous frame to lerp from
+    let mut forcelink = ent.msgtime != mtime[1];
+
+    // more than 0.2 seconds since the last message (most entities think every 0.1 sec)
+    if ent.msgtime + 0.2 < mtime[0] {
+        // if we missed a think, we'd be lerping from the wrong frame
+        let flags = lerp_flags(ent) | LerpFlags::ResetAnim;
+        set_lerp_flags(ent, flags);
+    }
+
+    ent.msgtime = mtime[0];
+
+    let mut modnum = if bits.contains(UpdateFlags::Model) {
+        msg.read_byte()
+    } else {
+        ent.baseline.modelindex as c_int
+    };
+
+    ent.frame = if bits.contains(UpdateFlags::Frame) {
+        msg.read_byte()
+    } else {
+        ent.baseline.frame as c_int
+    };
+
+    let i = if bits.contains(UpdateFlags::ColorMap) {
+        msg.read_byte()
+    } else {
+        ent.baseline.colormap as c_int
+    };
+    if i <= 0 {
+        ent.colormap = colormap;
+    } else if i > cl.maxclients {
+        return Err(ParseError::BadColormap(i));
+    } else if !cl.scores.is_null() {
+        ent.colormap = unsafe { (*cl.scores.add(i as usize - 1)).translations.as_mut_ptr() };
+    }
+
+    let skin = if bits.contains(UpdateFlags::Skin) {
+        msg.read_byte()
+    } else {
+        ent.baseline.skin as c_int
+    };
+    if skin != ent.skinnum {
+        ent.skinnum = skin;
+        if num > 0 && num <= cl.maxclients {
+            update.translate_skin = true;
+        }
+    }
+    ent.effects = if bits.contains(UpdateFlags::Effects) {
+        msg.read_byte()
+    } else {
+        ent.baseline.effects
+    };
+
+    // shift the known values for interpolation
+    ent.msg_origins[1] = ent.msg_origins[0];
+    ent.msg_angles[1] = ent.msg_angles[0];
+
+    let origin_bits = [
+        UpdateFlags::Origin1,
+        UpdateFlags::Origin2,
+        UpdateFlags::Origin3,
+    ];
+    let angle_bits = [
+        UpdateFlags::Angle1,
+        UpdateFlags::Angle2,
+        UpdateFlags::Angle3,
+    ];
+    for j in 0..3 {
+        ent.msg_origins[0][j] = if bits.contains(origin_bits[j]) {
+            msg.read_coord(cl.protocolflags)
+        } else {
+            ent.baseline.origin[j]
+        };
+        ent.msg_angles[0][j] = if bits.contains(angle_bits[j]) {
+            msg.read_angle(cl.protocolflags)
+        } else {
+            ent.baseline.angles[j]
+        };
+    }
+
+    // lerping for movetype_step entities
+    let mut flags = lerp_flags(ent);
+    if bits.contains(UpdateFlags::Step) {
+        flags |= LerpFlags::MoveStep;
+        ent.forcelink = true.into();
+    } else {
+        flags -= LerpFlags::MoveStep;
+    }
+    set_lerp_flags(ent, flags);
+
+    if fitz {
+        ent.alpha = if bits.contains(UpdateFlags::Alpha) {
+            msg.read_byte() as Byte
+        } else {
+            ent.baseline.alpha
+        };
+        if bits.contains(UpdateFlags::Scale) {
+            // PROTOCOL_RMQ: currently ignored
+            msg.read_byte();
+        }
+        if bits.contains(UpdateFlags::Frame2) {
+            ent.frame = (ent.frame & 0x00FF) | (msg.read_byte() << 8);
+        }
+        if bits.contains(UpdateFlags::Model2) {
+            modnum = (modnum & 0x00FF) | (msg.read_byte() << 8);
+        }
+        let mut flags = lerp_flags(ent);
+        if bits.contains(UpdateFlags::LerpFinish) {
+            ent.lerpfinish = (ent.msgtime + msg.read_byte() as f64 / 255.0) as c_float;
+            flags |= LerpFlags::Finish;
+        } else {
+            flags -= LerpFlags::Finish;
+        }
+        set_lerp_flags(ent, flags);
+    } else if cl.protocol == PROTOCOL_NETQUAKE {
+        // HACK: if this bit is set, assume this is PROTOCOL_NEHAHRA
+        if bits.contains(UpdateFlags::Trans) {
+            update.nehahra = true;
+            let a = msg.read_float();
+            // alpha
+            let b = msg.read_float();
+            if a == 2.0 {
+                // fullbright (not using this yet)
+                msg.read_float();
+            }
+            ent.alpha = ent_alpha_encode(b) as Byte;
+        } else {
+            ent.alpha = ent.baseline.alpha;
+        }
+    }
+
+    if modnum < 0 || modnum as usize >= MAX_MODELS {
+        return Err(ParseError::BadModelNum(modnum));
+    }
+    update.model = modnum;
+    let model = cl.model_precache[modnum as usize];
+    if model != ent.model {
+        ent.model = model;
+        // automatic animation (torches, etc) can be either all together or randomized
+        if !model.is_null() {
+            ent.syncbase = if unsafe { (*model).synctype } == SyncTypeT::Rand {
+                (rand.rand() & 0x7fff) as c_float / 0x7fff as c_float
+            } else {
+                0.0
+            };
+        } else {
+            // hack to make null model players work
+            forcelink = true;
+        }
+        if num > 0 && num <= cl.maxclients {
+            update.translate_skin = true;
+        }
+
+        // don't lerp animation across model changes
+        let flags = lerp_flags(ent) | LerpFlags::ResetAnim;
+        set_lerp_flags(ent, flags);
+    }
+
+    if forcelink {
+        // didn't have an update last message
+        ent.msg_origins[1] = ent.msg_origins[0];
+        ent.origin = ent.msg_origins[0];
+        ent.msg_angles[1] = ent.msg_angles[0];
+        ent.angles = ent.msg_angles[0];
+        ent.forcelink = true.into();
+    }
+
+    if msg.badread {
+        return Err(ParseError::BadRead);
+    }
+    return Ok(update);
+}
+
+/// svc_serverinfo, see parse_server_info
+pub struct ServerInfoT {
+    /// the signon message, also copied to cl.levelname
+    pub levelname: Vec<Byte>,
+    /// model_precache names, starting at index 1 with the world
+    pub models: Vec<Vec<Byte>>,
+    /// sound_precache names, starting at index 1
+    pub sounds: Vec<Vec<Byte>>,
+}
+
+/// the names of an svc_serverinfo precache list, up to the empty string
+fn read_precache(msg: &mut MsgReaderT, max: usize) -> Result<Vec<Vec<Byte>>, ParseError> {
+    let mut names = Vec::new();
+    loop {
+        let name = msg.read_string();
+        if name.is_empty() {
+            break;
+        }
+        if names.len() + 1 == max {
+            return Err(ParseError::TooManyPrecaches);
+        }
+        names.push(name);
+    }
+    return Ok(names);
+}
+
+/// Formerly: CL_ParseServerInfo
+/// read svc_serverinfo into a cleared cl: protocol, protocolflags, maxclients, gametype,
+/// levelname and mapname.  Loading the precaches and allocating cl.scores is up to the caller
+pub fn parse_server_info(
+    msg: &mut MsgReaderT,
+    cl: &mut ClientStateT,
+) -> Result<ServerInfoT, ParseError> {
+    // parse protocol version number
+    let protocol = msg.read_long();
+    if protocol != PROTOCOL_NETQUAKE as c_int
+        && protocol != PROTOCOL_FITZQUAKE as c_int
+        && protocol != PROTOCOL_RMQ as c_int
+    {
+        return Err(ParseError::BadProtocol(protocol));
+    }
+    cl.protocol = protocol as c_uint;
+
+    // mh - read protocol flags from server so that we know what protocol features to expect
+    cl.protocolflags = if cl.protocol == PROTOCOL_RMQ {
+        msg.read_long() as c_uint
+    } else {
+        0
+    };
+
+    // parse maxclients
+    cl.maxclients = msg.read_byte();
+    if cl.maxclients < 1 || cl.maxclients as usize > MAX_SCOREBOARD {
+        return Err(ParseError::BadMaxClients(cl.maxclients));
+    }
+
+    // parse gametype
+    cl.gametype = msg.read_byte();
+
+    // parse signon message
+    let levelname = msg.read_string();
+    strlcpy_slice(&mut cl.levelname, &levelname);
+
+    let models = read_precache(msg, MAX_MODELS)?;
+    let sounds = read_precache(msg, MAX_SOUNDS)?;
+
+    // copy the naked name of the map file to the cl structure -- O.S
+    if let Some(world) = models.first() {
+        let base = match world.iter().rposition(|&c| c == b'/') {
+            Some(slash) => &world[slash + 1..],
+            None => &world[..],
+        };
+        let name = match base.iter().rposition(|&c| c == b'.') {
+            Some(dot) => &base[..dot],
+            None => base,
+        };
+        strlcpy_slice(&mut cl.mapname, name);
+    }
+
+    if msg.badread {
+        return Err(ParseError::BadRead);
+    }
+    return Ok(ServerInfoT {
+        levelname,
+        models,
+        sounds,
+    });
+}
+
+/// Formerly: CL_ParseClientdata
+/// svc_clientdata: the player's view, items and stats.  standard_quake is false for the
+/// hipnotic and rogue mission packs, which send the active weapon as a bit number.  The
+/// caller passes the new cl.punchangle on to ViewStateT::set_punchangle
+pub fn parse_clientdata(
+    msg: &mut MsgReaderT,
+    cl: &mut ClientStateT,
+    standard_quake: bool,
+) -> Result<(), ParseError> {
+    let mut bits = msg.read_short() as u16 as c_uint;
+
+    // PROTOCOL_FITZQUAKE
+    if bits & ServerUpdateFlags::Extend1.bits() != 0 {
+        bits |= (msg.read_byte() as c_uint) << 16;
+    }
+    if bits & ServerUpdateFlags::Extend2.bits() != 0 {
+        bits |= (msg.read_byte() as c_uint) << 24;
+    }
+    let bits = ServerUpdateFlags::from_bits_truncate(bits);
+
+    cl.viewheight = if bits.contains(ServerUpdateFlags::ViewHeight) {
+        msg.read_char() as c_float
+    } else {
+        DEFAULT_VIEWHEIGHT as c_float
+    };
+
+    cl.idealpitch = if bits.contains(ServerUpdateFlags::IdealPitch) {
+        msg.read_char() as c_float
+    } else {
+        0.0
+    };
+
+    let punch_bits = [
+        ServerUpdateFlags::Punch1,
+        ServerUpdateFlags::Punch2,
+        ServerUpdateFlags::Punch3,
+    ];
+    let velocity_bits = [
+        ServerUpdateFlags::Velocity1,
+        ServerUpdateFlags::Velocity2,
+        ServerUpdateFlags::Velocity3,
+    ];
+    cl.mvelocity[1] = cl.mvelocity[0];
+    for i in 0..3 {
+        cl.punchangle[i] = if bits.contains(punch_bits[i]) {
+            msg.read_char() as c_float
+        } else {
+            0.0
+        };
+        cl.mvelocity[0][i] = if bits.contains(velocity_bits[i]) {
+            (msg.read_char() * 16) as c_float
+        } else {
+            0.0
+        };
+    }
+
+    // [always sent]	if (bits & SU_ITEMS)
+    let items = msg.read_long();
+    if cl.items != items {
+        // set flash times
+        for j in 0..32 {
+            if items & (1 << j) != 0 && cl.items & (1 << j) == 0 {
+                cl.item_gettime[j] = cl.time as c_float;
+            }
+        }
+        cl.items = items;
+    }
+
+    cl.onground = bits.contains(ServerUpdateFlags::OnGround).into();
+    cl.inwater = bits.contains(ServerUpdateFlags::InWater).into();
+
+    let mut read_if = |flag: ServerUpdateFlags| {
+        if bits.contains(flag) {
+            msg.read_byte()
+        } else {
+            0
+        }
+    };
+    cl.stats[STAT_WEAPONFRAME as usize] = read_if(ServerUpdateFlags::WeaponFrame);
+    cl.stats[STAT_ARMOR as usize] = read_if(ServerUpdateFlags::Armor);
+    cl.stats[STAT_WEAPON as usize] = read_if(ServerUpdateFlags::Weapon);
+
+    cl.stats[STAT_HEALTH as usize] = msg.read_short();
+    cl.stats[STAT_AMMO as usize] = msg.read_byte();
+    for i in 0..4 {
+        cl.stats[STAT_SHELLS as usize + i] = msg.read_byte();
+    }
+
+    let active = msg.read_byte();
+    cl.stats[STAT_ACTIVEWEAPON as usize] = if standard_quake { active } else { 1 << active };
+
+    // PROTOCOL_FITZQUAKE
+    let high_bits = [
+        (ServerUpdateFlags::Weapon2, STAT_WEAPON),
+        (ServerUpdateFlags::Armor2, STAT_ARMOR),
+        (ServerUpdateFlags::Ammo2, STAT_AMMO),
+        (ServerUpdateFlags::Shells2, STAT_SHELLS),
+        (ServerUpdateFlags::Nails2, STAT_NAILS),
+        (ServerUpdateFlags::Rockets2, STAT_ROCKETS),
+        (ServerUpdateFlags::Cells2, STAT_CELLS),
+        (ServerUpdateFlags::WeaponFrame2, STAT_WEAPONFRAME),
+    ];
+    for &(flag, stat) in high_bits.iter() {
+        if bits.contains(flag) {
+            cl.stats[stat as usize] |= msg.read_byte() << 8;
+        }
+    }
+    cl.viewent.alpha = if bits.contains(ServerUpdateFlags::WeaponAlpha) {
+        msg.read_byte() as Byte
+    } else {
+        ENTALPHA_DEFAULT as Byte
+    };
+
+    if msg.badread {
+        return Err(ParseError::BadRead);
+    }
+
+    // ericw -- this was done before the upper 8 bits of cl.stats[STAT_WEAPON] were filled in,
+    // breaking on large maps like zendar.bsp
+    let weapon = cl.stats[STAT_WEAPON as usize];
+    if weapon < 0 || weapon as usize >= MAX_MODELS {
+        return Err(ParseError::BadModelNum(weapon));
+    }
+    if cl.viewent.model != cl.model_precache[weapon as usize] {
+        // don't lerp animation across model changes
+        let flags = lerp_flags(&cl.viewent) | LerpFlags::ResetAnim;
+        set_lerp_flags(&mut cl.viewent, flags);
+    }
+    return Ok(());
+}
+
+/// svc_sound, see parse_start_sound
+#[derive(Clone, Copy)]
+pub struct StartSoundT {
+    pub entity: c_int,
+    pub channel: c_int,
+    /// cl.sound_precache index
+    pub sound: c_int,
+    pub origin: Vec3T,
+    /// 0.0 - 1.0
+    pub volume: c_float,
+    pub attenuation: c_float,
+}
+
+/// Formerly: CL_ParseStartSoundPacket
+/// svc_sound; max_edicts is cl_max_edicts
+pub fn parse_start_sound(
+    msg: &mut MsgReaderT,
+    protocolflags: c_uint,
+    max_edicts: c_int,
+) -> Result<StartSoundT, ParseError> {
+    let field_mask = SoundUpdateFlags::from_bits_truncate(msg.read_byte() as c_uint);
+
+    let volume = if field_mask.contains(SoundUpdateFlags::Volume) {
+        msg.read_byte()
+    } else {
+        DEFAULT_SOUND_PACKET_VOLUME as c_int
+    };
+
+    let attenuation = if field_mask.contains(SoundUpdateFlags::Attenuation) {
+        msg.read_byte() as c_float / 64.0
+    } else {
+        DEFAULT_SOUND_PACKET_ATTENUATION
+    };
+
+    // PROTOCOL_FITZQUAKE
+    let (entity, channel) = if field_mask.contains(SoundUpdateFlags::LargeEntity) {
+        let entity = msg.read_short() as u16 as c_int;
+        (entity, msg.read_byte())
+    } else {
+        let channel = msg.read_short() as u16 as c_int;
+        (channel >> 3, channel & 7)
+    };
+
+    let sound = if field_mask.contains(SoundUpdateFlags::LargeSound) {
+        msg.read_short() as u16 as c_int
+    } else {
+        msg.read_byte()
+    };
+
+    if sound as usize >= MAX_SOUNDS {
+        return Err(ParseError::BadSoundNum(sound));
+    }
+    if entity > max_edicts {
+        return Err(ParseError::BadEntityNum(entity));
+    }
+
+    let mut origin = Vec3T::new(0.0, 0.0, 0.0);
+    for i in 0..3 {
+        origin[i] = msg.read_coord(protocolflags);
+    }
+
+    if msg.badread {
+        return Err(ParseError::BadRead);
+    }
+    return Ok(StartSoundT {
+        entity,
+        channel,
+        sound,
+        origin,
+        volume: volume as c_float / 255.0,
+        attenuation,
+    });
+}
+
+/// svc_spawnstaticsound, see parse_static_sound
+#[derive(Clone, Copy)]
+pub struct StaticSoundT {
+    /// cl.sound_precache index
+    pub sound: c_int,
+    pub origin: Vec3T,
+    /// 0 - 255
+    pub volume: c_int,
+    /// 64ths
+    pub attenuation: c_int,
+}
+
+/// Formerly: CL_ParseStaticSound
+/// version is 1 for svc_spawnstaticsound and 2 for svc_spawnstaticsound2, which has a short
+/// sound index
+pub fn parse_static_sound(
+    msg: &mut MsgReaderT,
+    version: c_int,
+    protocolflags: c_uint,
+) -> Result<StaticSoundT, ParseError> {
+    let mut origin = Vec3T::new(0.0, 0.0, 0.0);
+    for i in 0..3 {
+        origin[i] = msg.read_coord(protocolflags);
+    }
+
+    // PROTOCOL_FITZQUAKE
+    let sound = if version == 2 {
+        msg.read_short()
+    } else {
+        msg.read_byte()
+    };
+
+    let volume = msg.read_byte();
+    let attenuation = msg.read_byte();
+
+    if msg.badread {
+        return Err(ParseError::BadRead);
+    }
+    if sound < 0 || sound as usize >= MAX_SOUNDS {
+        return Err(ParseError::BadSoundNum(sound));
+    }
+    return Ok(StaticSoundT {
+        sound,
+        origin,
+        volume,
+        attenuation,
+    });
+}
+
+/// Formerly: CL_ParseStatic
+/// svc_spawnstatic, version as for parse_baseline.  ent is the next free cl_static_entities
+/// slot; it gets its baseline as the current state.  colormap is vid.colormap
+pub fn parse_static(
+    msg: &mut MsgReaderT,
+    cl: &ClientStateT,
+    ent: &mut EntityT,
+    version: c_int,
+    colormap: *mut Byte,
+) -> Result<(), ParseError> {
+    parse_baseline(msg, ent, version, cl.protocolflags);
+    if msg.badread {
+        return Err(ParseError::BadRead);
+    }
+
+    let modnum = ent.baseline.modelindex as c_int;
+    if modnum < 0 || modnum as usize >= MAX_MODELS {
+        return Err(ParseError::BadModelNum(modnum));
+    }
+
+    // copy it to the current state
+    ent.model = cl.model_precache[modnum as usize];
+    let flags = lerp_flags(ent) | LerpFlags::ResetAnim;
+    set_lerp_flags(ent, flags);
+    ent.frame = ent.baseline.frame as c_int;
+
+    ent.colormap = colormap;
+    ent.skinnum = ent.baseline.skin as c_int;
+    ent.effects = ent.baseline.effects;
+    ent.alpha = ent.baseline.alpha;
+
+    ent.origin = ent.baseline.origin;
+    ent.angles = ent.baseline.angles;
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gl_model::QModelT;
+    use std::ptr::null_mut;
+
+    #[test]
+    fn randomized_animation_follows_the_generator() {
+        let mut cl = Box::new(ClientStateT::default());
+        cl.protocol = PROTOCOL_NETQUAKE;
+        let (mut torch, mut door) = (QModelT::default(), QModelT::default());
+        torch.synctype = SyncTypeT::Rand;
+        cl.model_precache[1] = &mut torch;
+        cl.model_precache[2] = &mut door;
+        let mut entities = vec![EntityT::default(); 4];
+        let mut signon = 0;
+        let mut rand = RandT::new(3);
+
+        // U_MODEL is in the second byte
+        let bits = UpdateFlags::MoreBits.bits() as c_int;
+        let high = (UpdateFlags::Model.bits() >> 8) as Byte;
+        for &(num, modnum) in [(1, 1), (2, 1), (3, 2)].iter() {
+            let data = [high, num, modnum];
+            let mut msg = MsgReaderT::new(&data);
+            let update = parse_update(
+                &mut msg,
+                &mut cl,
+                &mut entities,
+                bits,
+                &mut signon,
+                null_mut(),
+                &mut rand,
+            )
+            .unwrap();
+            assert_eq!(update.model, modnum as c_int);
+        }
+
+        let mut expect = RandT::new(3);
+        for ent in &entities[1..3] {
+            let r = expect.rand() as c_float / 0x7fff as c_float;
+            assert_eq!(ent.syncbase, r);
+        }
+        assert_eq!(entities[3].syncbase, 0.0);
+    }
+}
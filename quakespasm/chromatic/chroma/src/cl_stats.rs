@@ -0,0 +1,483 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2002-2009 John Fitzgibbons and others
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// cl_stats.rs -- level statistics: the intermission numbers, and the best times kept for
+// every map and skill
+
+use cl_bot::BotEventT;
+use client::ClientStateT;
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::os::raw::{c_double, c_int};
+use std::path::Path;
+use {STAT_MONSTERS, STAT_SECRETS, STAT_TOTALMONSTERS, STAT_TOTALSECRETS};
+
+/// the history file in host_parms->userdir
+pub const BEST_TIMES_FILE: &str = "besttimes.txt";
+
+/// what Sbar_IntermissionOverlay shows for a level
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelStatsT {
+    /// cl.mapname
+    pub mapname: String,
+    /// the skill the server's level was spawned at; only a local server's is known
+    pub skill: c_int,
+    /// cl.completed_time, before it is truncated to whole seconds
+    pub time: c_double,
+    pub monsters: c_int,
+    pub total_monsters: c_int,
+    pub secrets: c_int,
+    pub total_secrets: c_int,
+}
+
+impl LevelStatsT {
+    pub fn new(mapname: &str, skill: c_int) -> Self {
+        Self {
+            mapname: mapname.to_string(),
+            ..Self::unnamed(skill)
+        }
+    }
+
+    /// a level with no map name yet
+    const fn unnamed(skill: c_int) -> Self {
+        Self {
+            mapname: String::new(),
+            skill,
+            time: 0.0,
+            monsters: 0,
+            total_monsters: 0,
+            secrets: 0,
+            total_secrets: 0,
+        }
+    }
+}
+
+/// follows svc_updatestat, svc_killedmonster, svc_foundsecret and the end of level messages
+/// for the level being played
+pub struct LevelTrackerT {
+    /// skill to file the levels under
+    pub skill: c_int,
+    stats: LevelStatsT,
+    /// the level has reached its intermission
+    completed: bool,
+}
+
+impl LevelTrackerT {
+    pub const fn new(skill: c_int) -> Self {
+        Self {
+            skill,
+            stats: LevelStatsT::unnamed(skill),
+            completed: false,
+        }
+    }
+
+    /// the level so far, or as it finished
+    pub fn stats(&self) -> &LevelStatsT {
+        return &self.stats;
+    }
+
+    pub fn completed(&self) -> bool {
+        return self.completed;
+    }
+
+    /// start over on mapname
+    pub fn new_level(&mut self, mapname: &str) {
+        self.stats = LevelStatsT::new(mapname, self.skill);
+        self.completed = false;
+    }
+
+    /// svc_updatestat
+    pub fn update_stat(&mut self, stat: c_int, value: c_int) {
+        match stat as u32 {
+            STAT_MONSTERS => self.stats.monsters = value,
+            STAT_TOTALMONSTERS => self.stats.total_monsters = value,
+            STAT_SECRETS => self.stats.secrets = value,
+            STAT_TOTALSECRETS => self.stats.total_secrets = value,
+            _ => {}
+        }
+    }
+
+    /// svc_killedmonster
+    pub fn killed_monster(&mut self) {
+        self.stats.monsters += 1;
+    }
+
+    /// svc_foundsecret
+    pub fn found_secret(&mut self) {
+        self.stats.secrets += 1;
+    }
+
+    /// svc_intermission or svc_finale at time; the stats of the level the first time it
+    /// ends, None after that
+    pub fn intermission(&mut self, time: c_double) -> Option<LevelStatsT> {
+        if self.completed {
+            return None;
+        }
+        self.completed = true;
+        self.stats.time = time;
+        return Some(self.stats.clone());
+    }
+
+    /// keep up with a bot or demo; the level's stats when it ends
+    pub fn apply(&mut self, event: &BotEventT, cl: &ClientStateT) -> Option<LevelStatsT> {
+        match *event {
+            BotEventT::ServerInfo => self.new_level(&mapname(cl)),
+            BotEventT::UpdateStat { stat, value } => self.update_stat(stat, value),
+            BotEventT::KilledMonster => self.killed_monster(),
+            BotEventT::FoundSecret => self.found_secret(),
+            BotEventT::Intermission | BotEventT::Finale(_) => return self.intermission(cl.time),
+            _ => {}
+        }
+        return None;
+    }
+}
+
+/// cl.mapname as a string
+fn mapname(cl: &ClientStateT) -> String {
+    let len = cl.mapname.iter().position(|&c| c == 0).unwrap_or(0);
+    let mapname: Vec<u8> = cl.mapname[..len].iter().map(|&c| c as u8).collect();
+    return String::from_utf8_lossy(&mapname).into_owned();
+}
+
+/// the fastest completion of every map and skill, kept in a text file with a line for each:
+/// map skill time monsters total_monsters secrets total_secrets
+pub struct BestTimesT {
+    best: BTreeMap<(String, c_int), LevelStatsT>,
+}
+
+impl BestTimesT {
+    pub fn new() -> Self {
+        Self {
+            best: BTreeMap::new(),
+        }
+    }
+
+    /// the best run of mapname at skill
+    pub fn get(&self, mapname: &str, skill: c_int) -> Option<&LevelStatsT> {
+        return self.best.get(&(mapname.to_string(), skill));
+    }
+
+    /// every best run, by map and then skill
+    pub fn iter(&self) -> impl Iterator<Item = &LevelStatsT> {
+        return self.best.values();
+    }
+
+    /// remember stats if it beats the best time of its map and skill; true if it did
+    pub fn record(&mut self, stats: &LevelStatsT) -> bool {
+        let key = (stats.mapname.clone(), stats.skill);
+        if let Some(best) = self.best.get(&key) {
+            if best.time <= stats.time {
+                return false;
+            }
+        }
+        self.best.insert(key, stats.clone());
+        return true;
+    }
+
+    /// parse the history; lines that don't make sense are skipped
+    pub fn read_from<R: BufRead>(input: R) -> io::Result<Self> {
+        let mut times = Self::new();
+        for line in input.lines() {
+            let line = line?;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 7 {
+                continue;
+            }
+            let numbers: Vec<c_int> = fields[3..].iter().filter_map(|f| f.parse().ok()).collect();
+            let (skill, time) = match (fields[1].parse(), fields[2].parse()) {
+                (Ok(skill), Ok(time)) => (skill, time),
+                _ => continue,
+            };
+            if numbers.len() != 4 {
+                continue;
+            }
+            times.record(&LevelStatsT {
+                mapname: fields[0].to_string(),
+                skill,
+                time,
+                monsters: numbers[0],
+                total_monsters: numbers[1],
+                secrets: numbers[2],
+                total_secrets: numbers[3],
+            });
+        }
+        return Ok(times);
+    }
+
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for stats in self.best.values() {
+            writeln!(
+                out,
+                "{} {} {} {} {} {} {}",
+                stats.mapname,
+                stats.skill,
+                stats.time,
+                stats.monsters,
+                stats.total_monsters,
+                stats.secrets,
+                stats.total_secrets
+            )?;
+        }
+        return Ok(());
+    }
+
+    /// the history at path; a missing file is an empty history
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(e) => return Err(e),
+        };
+        return Self::read_from(BufReader::new(file));
+    }
+
+    /// write the history beside path and rename it over, so a crash while saving can't
+    /// lose the old one
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp = path.with_extension("tmp");
+        {
+            let mut file = File::create(&tmp)?;
+            self.write_to(&mut file)?;
+            file.sync_all()?;
+        }
+        return fs::rename(&tmp, path);
+    }
+}
+
+impl Default for BestTimesT {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[allow(bad_style)]
+pub mod capi {
+    use super::{mapname, BestTimesT, LevelTrackerT, BEST_TIMES_FILE};
+    use cl_main::capi::cl;
+    use std::ffi::{CStr, CString};
+    use std::os::raw::{c_char, c_double, c_int};
+    use std::path::PathBuf;
+    use std::ptr::{addr_of, addr_of_mut};
+    use QuakeParmsT;
+
+    extern "C" {
+        static host_parms: *mut QuakeParmsT;
+        fn Con_Printf(fmt: *const c_char, ...);
+    }
+
+    /// the level the client is in
+    static mut TRACKER: LevelTrackerT = LevelTrackerT::new(0);
+    /// read from the user's directory the first time a level is completed
+    static mut BEST_TIMES: Option<BestTimesT> = None;
+
+    unsafe fn tracker() -> &'static mut LevelTrackerT {
+        return &mut *addr_of_mut!(TRACKER);
+    }
+
+    unsafe fn print(msg: &str) {
+        let msg = CString::new(msg).unwrap_or_default();
+        Con_Printf(b"%s\0".as_ptr() as *const c_char, msg.as_ptr());
+    }
+
+    unsafe fn best_times_path() -> PathBuf {
+        let userdir = CStr::from_ptr((*host_parms).userdir)
+            .to_string_lossy()
+            .into_owned();
+        return PathBuf::from(userdir).join(BEST_TIMES_FILE);
+    }
+
+    /// CL_ParseServerInfo, once cl.mapname is set; skill is the local server's
+    ///
+    /// # Safety
+    ///
+    /// reads cl; only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn CL_StatsNewLevel(skill: c_int) {
+        let mapname = mapname(&*addr_of!(cl));
+        let tracker = tracker();
+        tracker.skill = skill;
+        tracker.new_level(&mapname);
+    }
+
+    /// svc_updatestat
+    ///
+    /// # Safety
+    ///
+    /// only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn CL_StatsUpdateStat(stat: c_int, value: c_int) {
+        tracker().update_stat(stat, value);
+    }
+
+    /// svc_killedmonster
+    ///
+    /// # Safety
+    ///
+    /// only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn CL_StatsKilledMonster() {
+        tracker().killed_monster();
+    }
+
+    /// svc_foundsecret
+    ///
+    /// # Safety
+    ///
+    /// only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn CL_StatsFoundSecret() {
+        tracker().found_secret();
+    }
+
+    /// svc_intermission and svc_finale of a level played on the local server: file the level's
+    /// time in the history if it's the best
+    ///
+    /// # Safety
+    ///
+    /// reads cl and host_parms; only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn CL_StatsIntermission() {
+        let time = cl.time as c_double;
+        let stats = match tracker().intermission(time) {
+            Some(stats) => stats,
+            None => return,
+        };
+
+        let path = best_times_path();
+        let best = &mut *addr_of_mut!(BEST_TIMES);
+        if best.is_none() {
+            match BestTimesT::load(&path) {
+                Ok(times) => *best = Some(times),
+                Err(e) => {
+                    // don't write over a history that couldn't be read
+                    print(&format!("couldn't read {}: {}\n", path.display(), e));
+                    return;
+                }
+            }
+        }
+        let times = best.as_mut().unwrap();
+        if !times.record(&stats) {
+            return;
+        }
+        print(&format!(
+            "new best time on {} at skill {}: {}:{:05.2}\n",
+            stats.mapname,
+            stats.skill,
+            (stats.time / 60.0) as c_int,
+            stats.time % 60.0
+        ));
+        if let Err(e) = times.save(&path) {
+            print(&format!("couldn't save {}: {}\n", path.display(), e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn level() -> LevelStatsT {
+        let mut t = LevelTrackerT::new(2);
+        t.new_level("e1m1");
+        t.update_stat(STAT_TOTALMONSTERS as c_int, 20);
+        t.update_stat(STAT_TOTALSECRETS as c_int, 3);
+        t.killed_monster();
+        t.killed_monster();
+        t.found_secret();
+        return t.intermission(95.25).unwrap();
+    }
+
+    #[test]
+    fn tracker_counts_the_level() {
+        let stats = level();
+        assert_eq!(stats.mapname, "e1m1");
+        assert_eq!(stats.skill, 2);
+        assert_eq!(stats.time, 95.25);
+        assert_eq!((stats.monsters, stats.total_monsters), (2, 20));
+        assert_eq!((stats.secrets, stats.total_secrets), (1, 3));
+
+        let mut t = LevelTrackerT::new(2);
+        assert!(t.intermission(1.0).is_some());
+        assert!(t.intermission(2.0).is_none());
+        t.new_level("e1m2");
+        assert!(!t.completed());
+    }
+
+    #[test]
+    fn only_faster_runs_are_recorded() {
+        let stats = level();
+        let mut best = BestTimesT::new();
+        assert!(best.record(&stats));
+        let mut slow = stats.clone();
+        slow.time = 100.0;
+        assert!(!best.record(&slow));
+        let mut other_skill = slow.clone();
+        other_skill.skill = 3;
+        assert!(best.record(&other_skill));
+        assert_eq!(best.get("e1m1", 2), Some(&stats));
+    }
+
+    #[test]
+    fn history_round_trips() {
+        let mut best = BestTimesT::new();
+        best.record(&level());
+        let mut out = Vec::new();
+        best.write_to(&mut out).unwrap();
+        assert_eq!(out, b"e1m1 2 95.25 2 20 1 3\n");
+
+        let back = BestTimesT::read_from(&out[..]).unwrap();
+        assert_eq!(back.get("e1m1", 2), Some(&level()));
+        let garbage = BestTimesT::read_from(&b"garbage\ne1m1 x 1 2 3 4 5\n"[..]).unwrap();
+        assert!(garbage.iter().next().is_none());
+    }
+
+    #[test]
+    fn save_replaces_the_history() {
+        let dir = env::temp_dir().join(format!("cl_stats_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(BEST_TIMES_FILE);
+        assert!(BestTimesT::load(&path).unwrap().iter().next().is_none());
+
+        let mut best = BestTimesT::new();
+        best.record(&level());
+        best.save(&path).unwrap();
+        let mut faster = level();
+        faster.time = 90.0;
+        best.record(&faster);
+        best.save(&path).unwrap();
+
+        assert_eq!(
+            BestTimesT::load(&path)
+                .unwrap()
+                .get("e1m1", 2)
+                .unwrap()
+                .time,
+            90.0
+        );
+        assert!(!path.with_extension("tmp").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,675 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2002-2009 John Fitzgibbons and others
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// cl_tent.rs -- client side temporary entities
+
+use cl_main::alloc_dlight;
+use cl_parse::ParseError;
+use client::{BeamT, DlightT, MAX_BEAMS, MAX_TEMP_ENTITIES, MAX_VISEDICTS};
+use common::MsgReaderT;
+use gl_model::QModelT;
+use mathlib::{vector_normalize, vector_subtract, RandT};
+use protocol::TempEntityEvent;
+use render::EntityT;
+use std::os::raw::{c_double, c_float, c_int, c_uint};
+use std::ptr::null_mut;
+use {Byte, Vec3T};
+
+/// how long a beam lasts without being refreshed
+pub const BEAM_LIFETIME: c_double = 0.2;
+/// length of each segment a beam is drawn with
+pub const BEAM_SEGMENT: c_float = 30.0;
+
+/// a parsed svc_temp_entity
+#[derive(Clone, Copy)]
+pub enum TempEntityT {
+    /// an effect at a point: spikes, gunshots, explosions, splashes
+    Point(TempEntityEvent, Vec3T),
+    /// color mapped explosion
+    Explosion2 {
+        pos: Vec3T,
+        color_start: c_int,
+        color_length: c_int,
+    },
+    /// lightning bolts and the grappling hook beam
+    Beam {
+        r#type: TempEntityEvent,
+        entity: c_int,
+        start: Vec3T,
+        end: Vec3T,
+    },
+    /// a type the stock client doesn't know, already read by TempEntityHandler::parse_custom
+    Custom(c_int),
+}
+
+/// what temp entities spawn besides dlights and beams.  the particle system and sound code
+/// implement this; mods can replace stock effects in spawn and add new types in parse_custom
+pub trait TempEntityHandler {
+    /// Formerly: R_RunParticleEffect
+    fn run_particle_effect(&mut self, org: Vec3T, dir: Vec3T, color: c_int, count: c_int);
+    /// Formerly: R_ParticleExplosion
+    fn particle_explosion(&mut self, org: Vec3T);
+    /// Formerly: R_ParticleExplosion2
+    fn particle_explosion2(&mut self, org: Vec3T, color_start: c_int, color_length: c_int);
+    /// Formerly: R_BlobExplosion
+    fn blob_explosion(&mut self, org: Vec3T);
+    /// Formerly: R_LavaSplash
+    fn lava_splash(&mut self, org: Vec3T);
+    /// Formerly: R_TeleportSplash
+    fn teleport_splash(&mut self, org: Vec3T);
+    /// play one of the sounds CL_InitTEnts precaches, e.g. "weapons/r_exp3.wav", at org
+    fn start_sound(&mut self, name: &str, org: Vec3T);
+    /// the model for a beam, Mod_ForName of one of the progs/*.mdl beam models
+    fn beam_model(&mut self, name: &str) -> *mut QModelT;
+
+    /// read the payload of an unknown temp entity type; false if the type is unknown here too
+    fn parse_custom(
+        &mut self,
+        _type: c_int,
+        _msg: &mut MsgReaderT,
+        _protocolflags: c_uint,
+    ) -> bool {
+        return false;
+    }
+
+    /// called before the stock handling of every event; true if the event was handled and the
+    /// stock effects should be skipped
+    fn spawn(&mut self, _event: &TempEntityT) -> bool {
+        return false;
+    }
+}
+
+fn temp_entity_event(t: c_int) -> Option<TempEntityEvent> {
+    return Some(match t {
+        0 => TempEntityEvent::Spike,
+        1 => TempEntityEvent::SuperSpike,
+        2 => TempEntityEvent::Gunshot,
+        3 => TempEntityEvent::Explosion,
+        4 => TempEntityEvent::TarExplosion,
+        5 => TempEntityEvent::Lightning1,
+        6 => TempEntityEvent::Lightning2,
+        7 => TempEntityEvent::WizSpike,
+        8 => TempEntityEvent::KnightSpike,
+        9 => TempEntityEvent::Lightning3,
+        10 => TempEntityEvent::LavaSplash,
+        11 => TempEntityEvent::Teleport,
+        12 => TempEntityEvent::Explosion2,
+        13 => TempEntityEvent::Beam,
+        _ => return None,
+    });
+}
+
+/// model a beam type is drawn with
+pub fn beam_model_name(r#type: TempEntityEvent) -> &'static str {
+    return match r#type {
+        TempEntityEvent::Lightning2 => "progs/bolt2.mdl",
+        TempEntityEvent::Lightning3 => "progs/bolt3.mdl",
+        // PGM 01/21/97
+        TempEntityEvent::Beam => "progs/beam.mdl",
+        _ => "progs/bolt.mdl",
+    };
+}
+
+fn read_pos(msg: &mut MsgReaderT, protocolflags: c_uint) -> Vec3T {
+    let mut pos = Vec3T::default();
+    for i in 0..3 {
+        pos[i] = msg.read_coord(protocolflags);
+    }
+    return pos;
+}
+
+/// Formerly: part of CL_ParseTEnt and CL_ParseBeam
+/// read the svc_temp_entity payload following the message type byte
+pub fn parse_temp_entity<H: TempEntityHandler>(
+    msg: &mut MsgReaderT,
+    protocolflags: c_uint,
+    handler: &mut H,
+) -> Result<TempEntityT, ParseError> {
+    let t = msg.read_byte();
+    let event = match temp_entity_event(t) {
+        Some(event) => event,
+        None => {
+            if handler.parse_custom(t, msg, protocolflags) {
+                return Ok(TempEntityT::Custom(t));
+            }
+            return Err(ParseError::BadTempEntity(t));
+        }
+    };
+
+    let tent = match event {
+        TempEntityEvent::Lightning1
+        | TempEntityEvent::Lightning2
+        | TempEntityEvent::Lightning3
+        | TempEntityEvent::Beam => {
+            let entity = msg.read_short();
+            let start = read_pos(msg, protocolflags);
+            let end = read_pos(msg, protocolflags);
+            TempEntityT::Beam {
+                r#type: event,
+                entity,
+                start,
+                end,
+            }
+        }
+        TempEntityEvent::Explosion2 => {
+            let pos = read_pos(msg, protocolflags);
+            let color_start = msg.read_byte();
+            let color_length = msg.read_byte();
+            TempEntityT::Explosion2 {
+                pos,
+                color_start,
+                color_length,
+            }
+        }
+        _ => TempEntityT::Point(event, read_pos(msg, protocolflags)),
+    };
+
+    if msg.badread {
+        return Err(ParseError::BadRead);
+    }
+    return Ok(tent);
+}
+
+/// Formerly: cl_beams, cl_temp_entities and num_temp_entities
+/// the beams in flight and the entities they're drawn with this frame
+pub struct TempEntsT {
+    pub beams: [BeamT; MAX_BEAMS],
+    /// never grows past MAX_TEMP_ENTITIES, so pointers handed out by update stay valid
+    entities: Vec<EntityT>,
+    /// reseeded from the time every update, so beams freeze when paused
+    rand: RandT,
+}
+
+impl TempEntsT {
+    pub fn new() -> Self {
+        Self {
+            beams: [BeamT::default(); MAX_BEAMS],
+            entities: Vec::with_capacity(MAX_TEMP_ENTITIES),
+            rand: RandT::new(0),
+        }
+    }
+
+    /// forget all beams, at a new level
+    pub fn clear(&mut self) {
+        self.beams = [BeamT::default(); MAX_BEAMS];
+        self.entities.clear();
+    }
+
+    /// the temp entities added by the last update
+    pub fn entities(&self) -> &[EntityT] {
+        return &self.entities;
+    }
+
+    /// Formerly: part of CL_ParseBeam
+    /// start or refresh the beam from entity; false if the beam list is full.  a beam without
+    /// a model is kept all the same, it just isn't drawn
+    pub fn add_beam(
+        &mut self,
+        entity: c_int,
+        model: *mut QModelT,
+        start: Vec3T,
+        end: Vec3T,
+        time: c_double,
+    ) -> bool {
+        // override any beam with the same entity, else find a free beam
+        let slot = self
+            .beams
+            .iter()
+            .position(|b| b.entity == entity)
+            .or_else(|| {
+                self.beams
+                    .iter()
+                    .position(|b| (b.endtime as c_double) < time)
+            });
+
+        return match slot {
+            Some(i) => {
+                self.beams[i] = BeamT {
+                    entity,
+                    model,
+                    endtime: (time + BEAM_LIFETIME) as c_float,
+                    start,
+                    end,
+                };
+                true
+            }
+            None => false,
+        };
+    }
+
+    /// Formerly: CL_NewTempEntity
+    fn new_temp_entity(
+        &mut self,
+        visedicts: &mut [*mut EntityT],
+        numvisedicts: &mut c_int,
+        colormap: *mut Byte,
+    ) -> Option<&mut EntityT> {
+        let num = *numvisedicts as usize;
+        if num >= visedicts.len().min(MAX_VISEDICTS) || self.entities.len() >= MAX_TEMP_ENTITIES {
+            return None;
+        }
+        let mut ent = EntityT::default();
+        ent.colormap = colormap;
+        self.entities.push(ent);
+        let ent = self.entities.last_mut().unwrap();
+        visedicts[num] = ent;
+        *numvisedicts += 1;
+        return Some(ent);
+    }
+
+    /// Formerly: CL_UpdateTEnts
+    /// lay out the live beams as chains of temp entities and add them after the
+    /// numvisedicts already in visedicts (cl_visedicts).  beams from viewentity start at
+    /// vieworg, the player's current origin; colormap is vid.colormap
+    pub fn update(
+        &mut self,
+        time: c_double,
+        viewentity: c_int,
+        vieworg: Vec3T,
+        colormap: *mut Byte,
+        visedicts: &mut [*mut EntityT],
+        numvisedicts: &mut c_int,
+    ) {
+        self.entities.clear();
+
+        // freeze beams when paused
+        self.rand.seed((time * 1000.0) as c_int as u32);
+
+        // update lightning
+        for i in 0..MAX_BEAMS {
+            let b = &mut self.beams[i];
+            if (b.endtime as c_double) < time {
+                continue;
+            }
+
+            // if coming from the player, update the start position
+            if b.entity == viewentity {
+                b.start = vieworg;
+            }
+            let b = *b;
+            if b.model.is_null() {
+                continue; // nothing to draw it with
+            }
+
+            // calculate pitch and yaw
+            let mut dist = vector_subtract(b.end, b.start);
+
+            let (yaw, pitch) = if dist[1] == 0.0 && dist[0] == 0.0 {
+                (0.0, if dist[2] > 0.0 { 90.0 } else { 270.0 })
+            } else {
+                let mut yaw = ((dist[1] as c_double)
+                    .atan2(dist[0] as c_double)
+                    .to_degrees() as c_int) as c_float;
+                if yaw < 0.0 {
+                    yaw += 360.0;
+                }
+
+                let forward = ((dist[0] * dist[0] + dist[1] * dist[1]) as c_double).sqrt();
+                let mut pitch =
+                    ((dist[2] as c_double).atan2(forward).to_degrees() as c_int) as c_float;
+                if pitch < 0.0 {
+                    pitch += 360.0;
+                }
+                (yaw, pitch)
+            };
+
+            // add new entities for the lightning
+            let mut org = b.start;
+            let mut d = vector_normalize(&mut dist);
+            while d > 0.0 {
+                let roll = (self.rand.rand() % 360) as c_float;
+                let ent = match self.new_temp_entity(visedicts, numvisedicts, colormap) {
+                    Some(ent) => ent,
+                    None => return,
+                };
+                ent.origin = org;
+                ent.model = b.model;
+                ent.angles = Vec3T::new(pitch, yaw, roll);
+
+                for j in 0..3 {
+                    org[j] += dist[j] * BEAM_SEGMENT;
+                }
+                d -= BEAM_SEGMENT;
+            }
+        }
+    }
+}
+
+impl Default for TempEntsT {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// spike impacts ricochet one time in five
+fn spike_sound<H: TempEntityHandler>(handler: &mut H, rand: &mut RandT, pos: Vec3T) {
+    if rand.rand() % 5 != 0 {
+        handler.start_sound("weapons/tink1.wav", pos);
+    } else {
+        let name = match rand.rand() & 3 {
+            1 => "weapons/ric1.wav",
+            2 => "weapons/ric2.wav",
+            _ => "weapons/ric3.wav",
+        };
+        handler.start_sound(name, pos);
+    }
+}
+
+/// the dlight of a rocket explosion
+fn explosion_light(dlights: &mut [DlightT], pos: Vec3T, time: c_double) {
+    if let Some(dl) = alloc_dlight(dlights, 0, time) {
+        dl.origin = pos;
+        dl.radius = 350.0;
+        dl.die = (time + 0.5) as c_float;
+        dl.decay = 300.0;
+    }
+}
+
+/// Formerly: part of CL_ParseTEnt
+/// spawn what a temp entity leaves behind: particles and sounds through handler, dlights in
+/// dlights (cl_dlights) and beams in tents; rand picks the ricochet sounds.  false if a beam
+/// didn't fit in the beam list
+pub fn spawn_temp_entity<H: TempEntityHandler>(
+    event: &TempEntityT,
+    tents: &mut TempEntsT,
+    dlights: &mut [DlightT],
+    time: c_double,
+    handler: &mut H,
+    rand: &mut RandT,
+) -> bool {
+    if handler.spawn(event) {
+        return true;
+    }
+
+    let origin = Vec3T::default();
+    match *event {
+        TempEntityT::Point(r#type, pos) => match r#type {
+            TempEntityEvent::WizSpike => {
+                handler.run_particle_effect(pos, origin, 20, 30);
+                handler.start_sound("wizard/hit.wav", pos);
+            }
+            TempEntityEvent::KnightSpike => {
+                handler.run_particle_effect(pos, origin, 226, 20);
+                handler.start_sound("hknight/hit.wav", pos);
+            }
+            TempEntityEvent::Spike => {
+                handler.run_particle_effect(pos, origin, 0, 10);
+                spike_sound(handler, rand, pos);
+            }
+            TempEntityEvent::SuperSpike => {
+                handler.run_particle_effect(pos, origin, 0, 20);
+                spike_sound(handler, rand, pos);
+            }
+            TempEntityEvent::Gunshot => {
+                handler.run_particle_effect(pos, origin, 0, 20);
+            }
+            TempEntityEvent::Explosion => {
+                handler.particle_explosion(pos);
+                explosion_light(dlights, pos, time);
+                handler.start_sound("weapons/r_exp3.wav", pos);
+            }
+            TempEntityEvent::TarExplosion => {
+                handler.blob_explosion(pos);
+                handler.start_sound("weapons/r_exp3.wav", pos);
+            }
+            TempEntityEvent::LavaSplash => handler.lava_splash(pos),
+            TempEntityEvent::Teleport => handler.teleport_splash(pos),
+            _ => {}
+        },
+        TempEntityT::Explosion2 {
+            pos,
+            color_start,
+            color_length,
+        } => {
+            handler.particle_explosion2(pos, color_start, color_length);
+            explosion_light(dlights, pos, time);
+            handler.start_sound("weapons/r_exp3.wav", pos);
+        }
+        TempEntityT::Beam {
+            r#type,
+            entity,
+            start,
+            end,
+        } => {
+            let model = handler.beam_model(beam_model_name(r#type));
+            return tents.add_beam(entity, model, start, end, time);
+        }
+        TempEntityT::Custom(_) => {}
+    }
+    return true;
+}
+
+impl TempEntityHandler for () {
+    fn run_particle_effect(&mut self, _org: Vec3T, _dir: Vec3T, _color: c_int, _count: c_int) {}
+    fn particle_explosion(&mut self, _org: Vec3T) {}
+    fn particle_explosion2(&mut self, _org: Vec3T, _color_start: c_int, _color_length: c_int) {}
+    fn blob_explosion(&mut self, _org: Vec3T) {}
+    fn lava_splash(&mut self, _org: Vec3T) {}
+    fn teleport_splash(&mut self, _org: Vec3T) {}
+    fn start_sound(&mut self, _name: &str, _org: Vec3T) {}
+    /// without models, beams are tracked but not drawn
+    fn beam_model(&mut self, _name: &str) -> *mut QModelT {
+        return null_mut();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestHandler {
+        model: QModelT,
+        sounds: Vec<String>,
+        custom: c_int,
+    }
+
+    impl TempEntityHandler for TestHandler {
+        fn run_particle_effect(&mut self, _org: Vec3T, _dir: Vec3T, _color: c_int, _count: c_int) {}
+        fn particle_explosion(&mut self, _org: Vec3T) {}
+        fn particle_explosion2(&mut self, _org: Vec3T, _color_start: c_int, _color_length: c_int) {}
+        fn blob_explosion(&mut self, _org: Vec3T) {}
+        fn lava_splash(&mut self, _org: Vec3T) {}
+        fn teleport_splash(&mut self, _org: Vec3T) {}
+        fn start_sound(&mut self, name: &str, _org: Vec3T) {
+            self.sounds.push(name.to_string());
+        }
+        fn beam_model(&mut self, _name: &str) -> *mut QModelT {
+            return &mut self.model;
+        }
+        fn parse_custom(&mut self, r#type: c_int, msg: &mut MsgReaderT, _flags: c_uint) -> bool {
+            if r#type != 50 {
+                return false;
+            }
+            self.custom = msg.read_byte();
+            return true;
+        }
+    }
+
+    fn handler() -> TestHandler {
+        return TestHandler {
+            model: QModelT::default(),
+            sounds: Vec::new(),
+            custom: 0,
+        };
+    }
+
+    /// lightning from entity 1, (0 0 0) to (100 0 0)
+    const LIGHTNING: [Byte; 15] = [5, 1, 0, 0, 0, 0, 0, 0, 0, 0x20, 3, 0, 0, 0, 0];
+
+    #[test]
+    fn explosion_spawns_light_and_sound() {
+        let mut h = handler();
+        let mut tents = TempEntsT::new();
+        let mut dlights = [DlightT::default(); 8];
+        let mut rand = RandT::new(0);
+
+        // explosion at (8 16 24)
+        let mut msg = MsgReaderT::new(&[3, 64, 0, 128, 0, 192, 0]);
+        let event = parse_temp_entity(&mut msg, 0, &mut h).unwrap();
+        assert!(spawn_temp_entity(
+            &event,
+            &mut tents,
+            &mut dlights,
+            1.0,
+            &mut h,
+            &mut rand
+        ));
+        assert_eq!(dlights[0].radius, 350.0);
+        assert_eq!(dlights[0].origin[2], 24.0);
+        assert_eq!(h.sounds, ["weapons/r_exp3.wav"]);
+    }
+
+    #[test]
+    fn spike_sounds_follow_rand() {
+        let (mut a, mut b) = (handler(), handler());
+        let (mut ra, mut rb) = (RandT::new(7), RandT::new(7));
+        let mut tents = TempEntsT::new();
+        let mut dlights = [DlightT::default(); 8];
+        let event = TempEntityT::Point(TempEntityEvent::Spike, Vec3T::default());
+        for _ in 0..20 {
+            spawn_temp_entity(&event, &mut tents, &mut dlights, 1.0, &mut a, &mut ra);
+            spawn_temp_entity(&event, &mut tents, &mut dlights, 1.0, &mut b, &mut rb);
+        }
+        assert_eq!(a.sounds, b.sounds);
+        assert!(a.sounds.iter().any(|s| s == "weapons/tink1.wav"));
+    }
+
+    #[test]
+    fn beams_are_laid_out_until_they_expire() {
+        let mut h = handler();
+        let mut tents = TempEntsT::new();
+        let mut dlights = [DlightT::default(); 8];
+        let mut rand = RandT::new(0);
+        let mut msg = MsgReaderT::new(&LIGHTNING);
+        let event = parse_temp_entity(&mut msg, 0, &mut h).unwrap();
+        assert!(spawn_temp_entity(
+            &event,
+            &mut tents,
+            &mut dlights,
+            1.0,
+            &mut h,
+            &mut rand
+        ));
+
+        let mut visedicts = [null_mut(); MAX_VISEDICTS];
+        let mut numvisedicts = 1;
+        tents.update(
+            1.1,
+            1,
+            Vec3T::default(),
+            null_mut(),
+            &mut visedicts,
+            &mut numvisedicts,
+        );
+        assert_eq!(numvisedicts, 5);
+        assert!(visedicts[0].is_null());
+        assert_eq!(tents.entities().len(), 4);
+        assert_eq!(tents.entities()[1].origin[0], 30.0);
+        assert_eq!(
+            visedicts[2],
+            &tents.entities()[1] as *const EntityT as *mut EntityT
+        );
+
+        // the same time gives the same rolls, so paused beams hold still
+        let rolls: Vec<c_float> = tents.entities().iter().map(|e| e.angles[2]).collect();
+        let mut numvisedicts = 0;
+        tents.update(
+            1.1,
+            1,
+            Vec3T::default(),
+            null_mut(),
+            &mut visedicts,
+            &mut numvisedicts,
+        );
+        let again: Vec<c_float> = tents.entities().iter().map(|e| e.angles[2]).collect();
+        assert_eq!(rolls, again);
+
+        let mut numvisedicts = 0;
+        tents.update(
+            1.3,
+            1,
+            Vec3T::default(),
+            null_mut(),
+            &mut visedicts,
+            &mut numvisedicts,
+        );
+        assert_eq!(numvisedicts, 0);
+        assert!(tents.entities().is_empty());
+    }
+
+    #[test]
+    fn beams_without_models_are_kept() {
+        let mut tents = TempEntsT::new();
+        let mut dlights = [DlightT::default(); 8];
+        let mut rand = RandT::new(0);
+        let mut msg = MsgReaderT::new(&LIGHTNING);
+        let event = parse_temp_entity(&mut msg, 0, &mut ()).unwrap();
+        assert!(spawn_temp_entity(
+            &event,
+            &mut tents,
+            &mut dlights,
+            1.0,
+            &mut (),
+            &mut rand
+        ));
+
+        // the beam holds its slot while it lives, but isn't drawn
+        let slot = tents.beams.iter().position(|b| b.entity == 1).unwrap();
+        for i in 0..MAX_BEAMS as c_int - 1 {
+            assert!(tents.add_beam(i + 10, null_mut(), Vec3T::default(), Vec3T::default(), 1.1));
+        }
+        assert!(!tents.add_beam(99, null_mut(), Vec3T::default(), Vec3T::default(), 1.1));
+        assert_eq!(tents.beams[slot].entity, 1);
+
+        let mut visedicts = [null_mut(); MAX_VISEDICTS];
+        let mut numvisedicts = 0;
+        tents.update(
+            1.1,
+            1,
+            Vec3T::default(),
+            null_mut(),
+            &mut visedicts,
+            &mut numvisedicts,
+        );
+        assert_eq!(numvisedicts, 0);
+
+        // expired beams free their slots
+        assert!(tents.add_beam(99, null_mut(), Vec3T::default(), Vec3T::default(), 1.25));
+    }
+
+    #[test]
+    fn custom_types_go_to_the_handler() {
+        let mut h = handler();
+        let mut msg = MsgReaderT::new(&[50, 9]);
+        match parse_temp_entity(&mut msg, 0, &mut h).unwrap() {
+            TempEntityT::Custom(50) => {}
+            _ => panic!("expected a custom temp entity"),
+        }
+        assert_eq!(h.custom, 9);
+
+        let mut msg = MsgReaderT::new(&[51]);
+        assert_eq!(
+            parse_temp_entity(&mut msg, 0, &mut h).err(),
+            Some(ParseError::BadTempEntity(51))
+        );
+    }
+}
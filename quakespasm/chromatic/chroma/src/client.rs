@@ -118,7 +118,7 @@ pub const NUM_CSHIFTS: usize = 4;
 /// signon messages to receive before connected
 pub const SIGNONS: u32 = 4;
 
-pub const MAX_DLIGHTS: usize = 64;
+pub const MAX_DLIGHTS: usize = 128;
 pub const MAX_DLIGHTS_BITS: usize = (MAX_DLIGHTS + 31) >> 5;
 
 #[derive(Clone, Copy)]
@@ -21,7 +21,9 @@ Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
 */
 
 // common.rs -- misc functions used in client and server
-use std::os::raw::c_int;
+use mathlib::q_rint;
+use protocol::RMQProtocolFlags;
+use std::os::raw::{c_double, c_float, c_int, c_uint};
 use std::ptr::null_mut;
 use {Byte, QBoolean};
 
@@ -93,6 +95,210 @@ impl Default for LinkT {
     }
 }
 
+/// reads a message the way the MSG_Read functions read net_message, from any buffer (a demo
+/// message, a captured packet)
+pub struct MsgReaderT<'a> {
+    data: &'a [Byte],
+    pub readcount: usize,
+    /// set when a read ran past the end of the message
+    pub badread: bool,
+}
+
+impl<'a> MsgReaderT<'a> {
+    pub fn new(data: &'a [Byte]) -> Self {
+        Self {
+            data,
+            readcount: 0,
+            badread: false,
+        }
+    }
+
+    /// the next n bytes, or None and badread if there aren't that many left
+    fn take(&mut self, n: usize) -> Option<&'a [Byte]> {
+        if self.readcount + n > self.data.len() {
+            self.badread = true;
+            return None;
+        }
+        let bytes = &self.data[self.readcount..self.readcount + n];
+        self.readcount += n;
+        return Some(bytes);
+    }
+
+    /// true if every byte has been read
+    pub fn at_end(&self) -> bool {
+        return self.readcount >= self.data.len();
+    }
+
+    /// returns -1 and sets badread if no more characters are available
+    pub fn read_char(&mut self) -> c_int {
+        return self.take(1).map_or(-1, |b| b[0] as i8 as c_int);
+    }
+
+    pub fn read_byte(&mut self) -> c_int {
+        return self.take(1).map_or(-1, |b| b[0] as c_int);
+    }
+
+    pub fn read_short(&mut self) -> c_int {
+        return self
+            .take(2)
+            .map_or(-1, |b| i16::from_le_bytes([b[0], b[1]]) as c_int);
+    }
+
+    pub fn read_long(&mut self) -> c_int {
+        return self
+            .take(4)
+            .map_or(-1, |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as c_int);
+    }
+
+    pub fn read_float(&mut self) -> c_float {
+        return self
+            .take(4)
+            .map_or(0.0, |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    }
+
+    /// reads up to the terminating 0, without it; at most 2047 characters like MSG_ReadString
+    pub fn read_string(&mut self) -> Vec<Byte> {
+        let mut string = Vec::new();
+        while string.len() < 2047 {
+            match self.read_byte() {
+                -1 | 0 => break,
+                c => string.push(c as Byte),
+            }
+        }
+        return string;
+    }
+
+    /// original behavior, 13.3 fixed point coords, max range +-4096
+    pub fn read_coord16(&mut self) -> c_float {
+        return (self.read_short() as c_double * (1.0 / 8.0)) as c_float;
+    }
+
+    /// 16.8 fixed point coords, max range +-32768
+    pub fn read_coord24(&mut self) -> c_float {
+        let whole = self.read_short() as c_double;
+        return (whole + self.read_byte() as c_double * (1.0 / 255.0)) as c_float;
+    }
+
+    pub fn read_coord(&mut self, flags: c_uint) -> c_float {
+        let protoflags = RMQProtocolFlags::from_bits_truncate(flags);
+        if protoflags.contains(RMQProtocolFlags::FloatCoord) {
+            return self.read_float();
+        } else if protoflags.contains(RMQProtocolFlags::Int32Coord) {
+            return (self.read_long() as c_double * (1.0 / 16.0)) as c_float;
+        } else if protoflags.contains(RMQProtocolFlags::F24bitCoord) {
+            return self.read_coord24();
+        }
+        return self.read_coord16();
+    }
+
+    pub fn read_angle(&mut self, flags: c_uint) -> c_float {
+        let protoflags = RMQProtocolFlags::from_bits_truncate(flags);
+        if protoflags.contains(RMQProtocolFlags::FloatAngle) {
+            return self.read_float();
+        } else if protoflags.contains(RMQProtocolFlags::ShortAngle) {
+            return (self.read_short() as c_double * (360.0 / 65536.0)) as c_float;
+        }
+        return (self.read_char() as c_double * (360.0 / 256.0)) as c_float;
+    }
+
+    /// for PROTOCOL_FITZQUAKE
+    pub fn read_angle16(&mut self, flags: c_uint) -> c_float {
+        let protoflags = RMQProtocolFlags::from_bits_truncate(flags);
+        if protoflags.contains(RMQProtocolFlags::FloatAngle) {
+            return self.read_float();
+        }
+        return (self.read_short() as c_double * (360.0 / 65536.0)) as c_float;
+    }
+}
+
+/// builds a message the way the MSG_Write functions fill a sizebuf_t, growing as needed
+pub struct MsgWriterT {
+    pub data: Vec<Byte>,
+}
+
+impl MsgWriterT {
+    pub fn new() -> Self {
+        Self { data: Vec::new() }
+    }
+
+    pub fn write_char(&mut self, c: c_int) {
+        self.data.push(c as Byte);
+    }
+
+    pub fn write_byte(&mut self, c: c_int) {
+        self.data.push(c as Byte);
+    }
+
+    pub fn write_short(&mut self, c: c_int) {
+        self.data.extend_from_slice(&(c as i16).to_le_bytes());
+    }
+
+    pub fn write_long(&mut self, c: c_int) {
+        self.data.extend_from_slice(&c.to_le_bytes());
+    }
+
+    pub fn write_float(&mut self, f: c_float) {
+        self.data.extend_from_slice(&f.to_le_bytes());
+    }
+
+    /// writes s and a terminating 0
+    pub fn write_string(&mut self, s: &[Byte]) {
+        self.data.extend_from_slice(s);
+        self.data.push(0);
+    }
+
+    /// original behavior, 13.3 fixed point coords, max range +-4096
+    pub fn write_coord16(&mut self, f: c_float) {
+        self.write_short(q_rint(f * 8.0));
+    }
+
+    /// 16.8 fixed point coords, max range +-32768
+    pub fn write_coord24(&mut self, f: c_float) {
+        self.write_short(f as c_int);
+        self.write_byte((f * 255.0) as c_int % 255);
+    }
+
+    pub fn write_coord(&mut self, f: c_float, flags: c_uint) {
+        let protoflags = RMQProtocolFlags::from_bits_truncate(flags);
+        if protoflags.contains(RMQProtocolFlags::FloatCoord) {
+            self.write_float(f);
+        } else if protoflags.contains(RMQProtocolFlags::Int32Coord) {
+            self.write_long(q_rint(f * 16.0));
+        } else if protoflags.contains(RMQProtocolFlags::F24bitCoord) {
+            self.write_coord24(f);
+        } else {
+            self.write_coord16(f);
+        }
+    }
+
+    pub fn write_angle(&mut self, f: c_float, flags: c_uint) {
+        let protoflags = RMQProtocolFlags::from_bits_truncate(flags);
+        if protoflags.contains(RMQProtocolFlags::FloatAngle) {
+            self.write_float(f);
+        } else if protoflags.contains(RMQProtocolFlags::ShortAngle) {
+            self.write_short(q_rint(f * 65536.0 / 360.0) & 65535);
+        } else {
+            self.write_byte(q_rint(f * 256.0 / 360.0) & 255);
+        }
+    }
+
+    /// for PROTOCOL_FITZQUAKE
+    pub fn write_angle16(&mut self, f: c_float, flags: c_uint) {
+        let protoflags = RMQProtocolFlags::from_bits_truncate(flags);
+        if protoflags.contains(RMQProtocolFlags::FloatAngle) {
+            self.write_float(f);
+        } else {
+            self.write_short(q_rint(f * 65536.0 / 360.0) & 65535);
+        }
+    }
+}
+
+impl Default for MsgWriterT {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /*
 
 All of Quake's data access is through a hierchal file system, but the contents
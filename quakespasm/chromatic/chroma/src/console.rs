@@ -27,6 +27,45 @@ const CON_MINSIZE: usize = 16384;
 #[allow(unused)]
 const NUM_CON_TIMES: usize = 4;
 
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use Byte;
+
+/// cmd_source_t src_command
+const SRC_COMMAND: c_int = 1;
+
+thread_local! {
+    /// what Con_Printf printed while execute_captured runs a command
+    static CON_REDIRECT: RefCell<Option<Vec<Byte>>> = const { RefCell::new(None) };
+}
+
+extern "C" {
+    fn Cmd_ExecuteString(text: *const c_char, src: c_int);
+}
+
+/// run command as if typed at the console; everything it printed meanwhile
+///
+/// # Safety
+/// the command runs on the engine's state; only call this from the main thread
+pub unsafe fn execute_captured(command: &[Byte]) -> Vec<Byte> {
+    let text = match CString::new(command) {
+        Ok(text) => text,
+        Err(_) => return Vec::new(),
+    };
+    let outer = CON_REDIRECT.with(|redirect| redirect.replace(Some(Vec::new())));
+    Cmd_ExecuteString(text.as_ptr(), SRC_COMMAND);
+    let output = CON_REDIRECT
+        .with(|redirect| redirect.replace(outer))
+        .unwrap_or_default();
+    CON_REDIRECT.with(|redirect| {
+        if let Some(ref mut outer) = *redirect.borrow_mut() {
+            outer.extend_from_slice(&output);
+        }
+    });
+    return output;
+}
+
 #[allow(non_snake_case)]
 pub mod capi {
     use super::NUM_CON_TIMES;
@@ -174,6 +213,21 @@ pub mod capi {
         file.into_raw_handle(); // don't close the fd on drop()
     }
 
+    /// Formerly: none
+    /// keep msg for execute_captured, if it's running a command
+    ///
+    /// # Safety
+    ///
+    /// msg must be a string
+    #[no_mangle]
+    pub unsafe extern "C" fn Con_Redirect(msg: *const c_char) {
+        super::CON_REDIRECT.with(|redirect| {
+            if let Some(ref mut output) = *redirect.borrow_mut() {
+                output.extend_from_slice(CStr::from_ptr(msg).to_bytes());
+            }
+        });
+    }
+
     #[no_mangle]
     pub unsafe extern "C" fn Con_Linefeed() {
         if con_backscroll != 0 {
@@ -60,7 +60,9 @@ interface from being ambiguous.
 
 */
 
+use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_float, c_uint};
+use Byte;
 
 bitflags! {
     #[derive(Default)]
@@ -101,19 +103,91 @@ pub struct CVarT {
     pub next: *mut CVarT,
 }
 
+/// Formerly: the Cvar_FindVarAfter walks
+/// the names and strings of the cvars with all of a set of flags, in list order
+pub struct CVarIterT {
+    /// where the next step starts; None once the walk is over
+    prev: Option<CString>,
+    flags: CVarFlags,
+}
+
+impl Iterator for CVarIterT {
+    type Item = (Vec<Byte>, Vec<Byte>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let prev = self.prev.take()?;
+        let var = unsafe { capi::Cvar_FindVarAfter(prev.as_ptr(), self.flags).as_ref()? };
+        let name = unsafe { CStr::from_ptr(var.name) };
+        let string = if var.string.is_null() {
+            Vec::new()
+        } else {
+            unsafe { CStr::from_ptr(var.string).to_bytes().to_vec() }
+        };
+        self.prev = Some(name.to_owned());
+        return Some((name.to_bytes().to_vec(), string));
+    }
+}
+
+/// the cvars with flags after the one named prev: all of them for an empty prev, and none
+/// for a prev that isn't a cvar
+///
+/// # Safety
+/// the cvar list is walked unsynchronized; only use this from the main thread, and don't
+/// keep it across cvar registration
+pub unsafe fn cvars_after(prev: &[Byte], flags: CVarFlags) -> CVarIterT {
+    return CVarIterT {
+        prev: CString::new(prev).ok(),
+        flags,
+    };
+}
+
+/// the Notify cvars as they were at the last look, to find the ones that have changed
+/// however they were set
+pub struct NotifyWatchT {
+    strings: Vec<(Vec<Byte>, Vec<Byte>)>,
+}
+
+impl NotifyWatchT {
+    /// watch the Notify cvars as they are now
+    ///
+    /// # Safety
+    /// see cvars_after
+    pub unsafe fn new() -> Self {
+        Self {
+            strings: cvars_after(b"", CVarFlags::Notify).collect(),
+        }
+    }
+
+    /// the Notify cvars whose strings changed since the last look, with the new strings;
+    /// cvars registered since then aren't changes
+    ///
+    /// # Safety
+    /// see cvars_after
+    pub unsafe fn changes(&mut self) -> Vec<(Vec<Byte>, Vec<Byte>)> {
+        let strings: Vec<(Vec<Byte>, Vec<Byte>)> = cvars_after(b"", CVarFlags::Notify).collect();
+        let changed = strings
+            .iter()
+            .filter(|(name, string)| self.strings.iter().any(|(n, s)| n == name && s != string))
+            .cloned()
+            .collect();
+        self.strings = strings;
+        return changed;
+    }
+}
+
 #[allow(bad_style)]
 pub mod capi {
-    use super::{CVarCallbackT, CVarFlags, CVarT};
+    use super::{CVarCallbackT, CVarFlags, CVarT, NotifyWatchT};
     use libc::{fileno, get_osfhandle, FILE};
     use std::os::windows::io::FromRawHandle;
     use std::os::windows::io::IntoRawHandle;
     use std::os::windows::raw::HANDLE;
     use std::{
-        ffi::CStr,
+        ffi::{CStr, CString},
         fs::File,
         io::Write,
         os::raw::{c_char, c_float},
-        ptr::null_mut,
+        ptr::{addr_of_mut, null_mut},
     };
 
     #[no_mangle]
@@ -201,6 +275,41 @@ pub mod capi {
         }
     }
 
+    static mut NOTIFY_WATCH: Option<NotifyWatchT> = None;
+
+    /// Formerly: the Cvar_SetCallback (var, Host_Callback_Notify) calls
+    /// call notify on every Notify cvar without a callback of its own whose string changed
+    /// since the last call; the others had theirs called when they were set
+    ///
+    /// # Safety
+    ///
+    /// only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn Cvar_NotifyChanges(notify: CVarCallbackT) {
+        let watch = &mut *addr_of_mut!(NOTIFY_WATCH);
+        let changes = match *watch {
+            Some(ref mut watch) => watch.changes(),
+            None => {
+                *watch = Some(NotifyWatchT::new());
+                return;
+            }
+        };
+        let notify = match notify {
+            Some(notify) => notify,
+            None => return,
+        };
+        for (name, _) in changes {
+            let name = match CString::new(name) {
+                Ok(name) => name,
+                Err(_) => continue,
+            };
+            let var = Cvar_FindVar(name.as_ptr());
+            if !var.is_null() && !(*var).flags.contains(CVarFlags::Callback) {
+                notify(var);
+            }
+        }
+    }
+
     /*
     ============
     Cvar_WriteVariables
@@ -32,11 +32,12 @@ use client::MAX_DLIGHTS_BITS;
 use gl::types::*;
 use gl_texmgr::GlTextureT;
 use libc::intptr_t;
+use mathlib::dot_product;
 use modelgen::{SyncTypeT, TriVertexT};
 use render::EFragT;
 use spritegn::SpriteFrameTypeT;
 use std::os::raw::{c_char, c_float, c_int, c_schar, c_short, c_uint, c_ushort};
-use std::ptr::null_mut;
+use std::ptr::{self, null_mut};
 use zone::CacheUserT;
 use VecT;
 use {Byte, QBoolean, Vec3T, MAX_QPATH};
@@ -965,4 +966,78 @@ impl Default for QModelT {
     }
 }
 
+/// Formerly: Mod_PointInLeaf
+/// the leaf of a brush model's bsp tree that contains p
+pub fn point_in_leaf(p: Vec3T, model: &QModelT) -> *mut MLeafT {
+    if model.nodes.is_null() {
+        panic!("Mod_PointInLeaf: bad model");
+    }
+
+    let mut node = model.nodes;
+    loop {
+        let n = unsafe { &*node };
+        if n.contents < 0 {
+            return node as *mut MLeafT;
+        }
+        let plane = unsafe { &*n.plane };
+        let d = dot_product(p, plane.normal) - plane.dist;
+        node = if d > 0.0 {
+            n.children[0]
+        } else {
+            n.children[1]
+        };
+    }
+}
+
+/// bytes in a pvs row, one bit per leaf not counting leaf 0
+pub fn pvs_bytes(model: &QModelT) -> usize {
+    return (model.numleafs as usize + 7) >> 3;
+}
+
+/// Formerly: Mod_DecompressVis
+/// expand run-length compressed visibility, zero bytes are followed by a repeat count
+///
+/// # Safety
+/// input must be null or point at a complete compressed row
+pub unsafe fn decompress_vis(input: *const Byte, model: &QModelT) -> Vec<Byte> {
+    let row = pvs_bytes(model);
+    if input.is_null() {
+        // no vis info, so make all visible
+        return vec![0xff; row];
+    }
+
+    let mut out = Vec::with_capacity(row);
+    let mut input = input;
+    while out.len() < row {
+        if *input != 0 {
+            out.push(*input);
+            input = input.add(1);
+            continue;
+        }
+
+        let c = *input.add(1) as usize;
+        input = input.add(2);
+        if out.len() + c > row {
+            // output overrun, the rest is invisible
+            break;
+        }
+        out.resize(out.len() + c, 0);
+    }
+    out.resize(row, 0);
+    return out;
+}
+
+/// Formerly: Mod_LeafPVS
+pub fn leaf_pvs(leaf: &MLeafT, model: &QModelT) -> Vec<Byte> {
+    if ptr::eq(leaf, model.leafs) {
+        return no_vis_pvs(model);
+    }
+    return unsafe { decompress_vis(leaf.compressed_vis, model) };
+}
+
+/// Formerly: Mod_NoVisPVS
+pub fn no_vis_pvs(model: &QModelT) -> Vec<Byte> {
+    return vec![0xff; pvs_bytes(model)];
+}
+
 pub mod capi {}
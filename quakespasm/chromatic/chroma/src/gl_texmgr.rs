@@ -21,11 +21,19 @@ Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
 
 // gl_texmgr.rs -- fitzquake's texture manager. manages opengl texture images
 
+use crc::capi::CRC_Block;
 use gl::types::*;
 use gl_model::QModelT;
+use render::{BOTTOM_RANGE, TOP_RANGE};
 use std::os::raw::{c_char, c_int, c_schar, c_uint, c_ushort};
 use std::ptr::null_mut;
-use MAX_QPATH;
+use std::slice;
+use {Byte, MAX_QPATH};
+
+pub const MAX_GLTEXTURES: usize = 4096;
+
+/// bytes per texel of SrcFormat::LightMap data
+pub const LIGHTMAP_BYTES: usize = 4;
 
 bitflags! {
     #[derive(Default)]
@@ -85,34 +93,35 @@ pub type SrcOffsetT = libc::uintptr_t;
 #[repr(C)]
 pub struct GlTextureT {
     /// managed by texture manager
-    texnum: GLuint,
-    next: *mut GlTextureT,
-    owner: *mut QModelT,
+    pub texnum: GLuint,
+    /// unused by TexMgr, which owns its textures directly
+    pub next: *mut GlTextureT,
+    pub owner: *mut QModelT,
     /// managed by image loading
-    name: [c_char; 64],
+    pub name: [c_char; 64],
     /// width of image as it exists in opengl
-    width: c_uint,
+    pub width: c_uint,
     /// height of image as it exists in opengl
-    height: c_uint,
-    flags: c_uint,
+    pub height: c_uint,
+    pub flags: c_uint,
     /// relative filepath to data source, or "" if source is in memory
-    source_file: [c_char; MAX_QPATH],
+    pub source_file: [c_char; MAX_QPATH],
     /// byte offset into file, or memory address
-    source_offset: SrcOffsetT,
+    pub source_offset: SrcOffsetT,
     /// format of pixel data (indexed, lightmap, or rgba)
-    source_format: SrcFormat,
+    pub source_format: SrcFormat,
     /// width of image in source data
-    source_width: c_uint,
+    pub source_width: c_uint,
     /// height of image in source data
-    source_height: c_uint,
+    pub source_height: c_uint,
     /// generated by source data before modifications
-    source_crc: c_ushort,
+    pub source_crc: c_ushort,
     /// 0-13 shirt color, or -1 if never colormapped
-    shirt: c_schar,
+    pub shirt: c_schar,
     /// 0-13 pants color, or -1 if never colormapped
-    pants: c_schar,
+    pub pants: c_schar,
     /// used for rendering; matches r_framecount if texture was bound this frame
-    visframe: c_int,
+    pub visframe: c_int,
 }
 
 impl GlTextureT {
@@ -144,4 +153,1456 @@ impl Default for GlTextureT {
     }
 }
 
+/*
+================================================================================
+
+    BACKENDS
+
+================================================================================
+*/
+
+/// The rendering api half of the texture manager.  TexMgr does all of the bookkeeping and image
+/// processing; a backend only owns the texture objects and receives the final pixel data.
+pub trait TextureBackend {
+    /// allocate a new texture object and return its name
+    fn gen_texture(&mut self) -> GLuint;
+    fn delete_texture(&mut self, texnum: GLuint);
+    /// upload one mip level of RGBA (or lightmap) data for glt
+    fn upload(
+        &mut self,
+        glt: &GlTextureT,
+        level: c_int,
+        width: c_uint,
+        height: c_uint,
+        data: &[Byte],
+    );
+    fn set_filter_modes(&mut self, glt: &GlTextureT);
+    /// largest texture dimension the backend can handle
+    fn max_texture_size(&self) -> c_uint;
+    /// true if non-power-of-two textures can be uploaded as-is
+    fn npot_supported(&self) -> bool;
+}
+
+/// GL_TEXTURE_MAX_ANISOTROPY_EXT
+const GL_TEXTURE_MAX_ANISOTROPY_EXT: GLenum = 0x84FE;
+
+#[derive(Clone, Copy)]
+pub struct GlModeT {
+    pub magfilter: GLenum,
+    pub minfilter: GLenum,
+    pub name: &'static str,
+}
+
+pub const GLMODES: [GlModeT; 6] = [
+    GlModeT {
+        magfilter: gl::NEAREST,
+        minfilter: gl::NEAREST,
+        name: "GL_NEAREST",
+    },
+    GlModeT {
+        magfilter: gl::NEAREST,
+        minfilter: gl::NEAREST_MIPMAP_NEAREST,
+        name: "GL_NEAREST_MIPMAP_NEAREST",
+    },
+    GlModeT {
+        magfilter: gl::NEAREST,
+        minfilter: gl::NEAREST_MIPMAP_LINEAR,
+        name: "GL_NEAREST_MIPMAP_LINEAR",
+    },
+    GlModeT {
+        magfilter: gl::LINEAR,
+        minfilter: gl::LINEAR,
+        name: "GL_LINEAR",
+    },
+    GlModeT {
+        magfilter: gl::LINEAR,
+        minfilter: gl::LINEAR_MIPMAP_NEAREST,
+        name: "GL_LINEAR_MIPMAP_NEAREST",
+    },
+    GlModeT {
+        magfilter: gl::LINEAR,
+        minfilter: gl::LINEAR_MIPMAP_LINEAR,
+        name: "GL_LINEAR_MIPMAP_LINEAR",
+    },
+];
+
+/// OpenGL texture backend; requires a current context with loaded gl function pointers.
+pub struct GlBackend {
+    pub solid_format: GLint,
+    pub alpha_format: GLint,
+    pub lightmap_format: GLenum,
+    /// index into GLMODES, set by gl_texturemode
+    pub glmode_idx: usize,
+    /// gl_texture_anisotropy
+    pub anisotropy: GLfloat,
+    pub hardware_maxsize: c_uint,
+    pub texture_npot: bool,
+}
+
+impl GlBackend {
+    pub fn new(texture_npot: bool) -> Self {
+        let mut maxsize: GLint = 0;
+        unsafe { gl::GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut maxsize) };
+
+        Self {
+            solid_format: gl::RGB as GLint,
+            alpha_format: gl::RGBA as GLint,
+            lightmap_format: gl::RGBA,
+            glmode_idx: GLMODES.len() - 1,
+            anisotropy: 1.0,
+            hardware_maxsize: maxsize.max(0) as c_uint,
+            texture_npot,
+        }
+    }
+
+    fn bind(&self, glt: &GlTextureT) {
+        unsafe { gl::BindTexture(gl::TEXTURE_2D, glt.texnum) };
+    }
+}
+
+impl TextureBackend for GlBackend {
+    fn gen_texture(&mut self) -> GLuint {
+        let mut texnum: GLuint = 0;
+        unsafe { gl::GenTextures(1, &mut texnum) };
+        return texnum;
+    }
+
+    fn delete_texture(&mut self, texnum: GLuint) {
+        unsafe { gl::DeleteTextures(1, &texnum) };
+    }
+
+    fn upload(
+        &mut self,
+        glt: &GlTextureT,
+        level: c_int,
+        width: c_uint,
+        height: c_uint,
+        data: &[Byte],
+    ) {
+        let (internalformat, format) = if glt.source_format == SrcFormat::LightMap {
+            (self.lightmap_format as GLint, self.lightmap_format)
+        } else if TexPref::from_bits_truncate(glt.flags).contains(TexPref::Alpha) {
+            (self.alpha_format, gl::RGBA)
+        } else {
+            (self.solid_format, gl::RGBA)
+        };
+
+        self.bind(glt);
+        unsafe {
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                level,
+                internalformat,
+                width as GLsizei,
+                height as GLsizei,
+                0,
+                format,
+                gl::UNSIGNED_BYTE,
+                data.as_ptr() as *const GLvoid,
+            );
+        }
+    }
+
+    fn set_filter_modes(&mut self, glt: &GlTextureT) {
+        let flags = TexPref::from_bits_truncate(glt.flags);
+        let mode = GLMODES[self.glmode_idx];
+
+        let (magfilter, minfilter) = if flags.contains(TexPref::Nearest) {
+            (gl::NEAREST, gl::NEAREST)
+        } else if flags.contains(TexPref::Linear) {
+            (gl::LINEAR, gl::LINEAR)
+        } else if flags.contains(TexPref::Mipmap) {
+            (mode.magfilter, mode.minfilter)
+        } else {
+            (mode.magfilter, mode.magfilter)
+        };
+
+        self.bind(glt);
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, magfilter as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, minfilter as GLint);
+            if flags.contains(TexPref::Mipmap) {
+                gl::TexParameterf(
+                    gl::TEXTURE_2D,
+                    GL_TEXTURE_MAX_ANISOTROPY_EXT,
+                    self.anisotropy,
+                );
+            }
+        }
+    }
+
+    fn max_texture_size(&self) -> c_uint {
+        return self.hardware_maxsize;
+    }
+
+    fn npot_supported(&self) -> bool {
+        return self.texture_npot;
+    }
+}
+
+/// Everything a backend was asked to do, as recorded by NullBackend.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BackendCall {
+    GenTexture(GLuint),
+    DeleteTexture(GLuint),
+    Upload {
+        texnum: GLuint,
+        level: c_int,
+        width: c_uint,
+        height: c_uint,
+    },
+    SetFilterModes(GLuint),
+}
+
+/// Backend that uploads nothing and records every call, for running TexMgr without a GPU.
+pub struct NullBackend {
+    pub calls: Vec<BackendCall>,
+    pub hardware_maxsize: c_uint,
+    pub texture_npot: bool,
+    next_texnum: GLuint,
+}
+
+impl NullBackend {
+    pub fn new(hardware_maxsize: c_uint, texture_npot: bool) -> Self {
+        Self {
+            calls: Vec::new(),
+            hardware_maxsize,
+            texture_npot,
+            next_texnum: 1,
+        }
+    }
+
+    /// texture names that were generated and never deleted
+    pub fn live_textures(&self) -> Vec<GLuint> {
+        let mut live = Vec::new();
+        for call in &self.calls {
+            match *call {
+                BackendCall::GenTexture(texnum) => live.push(texnum),
+                BackendCall::DeleteTexture(texnum) => live.retain(|&t| t != texnum),
+                _ => {}
+            }
+        }
+        return live;
+    }
+}
+
+impl Default for NullBackend {
+    fn default() -> Self {
+        Self::new(4096, true)
+    }
+}
+
+impl TextureBackend for NullBackend {
+    fn gen_texture(&mut self) -> GLuint {
+        let texnum = self.next_texnum;
+        self.next_texnum += 1;
+        self.calls.push(BackendCall::GenTexture(texnum));
+        return texnum;
+    }
+
+    fn delete_texture(&mut self, texnum: GLuint) {
+        self.calls.push(BackendCall::DeleteTexture(texnum));
+    }
+
+    fn upload(
+        &mut self,
+        glt: &GlTextureT,
+        level: c_int,
+        width: c_uint,
+        height: c_uint,
+        _data: &[Byte],
+    ) {
+        self.calls.push(BackendCall::Upload {
+            texnum: glt.texnum,
+            level,
+            width,
+            height,
+        });
+    }
+
+    fn set_filter_modes(&mut self, glt: &GlTextureT) {
+        self.calls.push(BackendCall::SetFilterModes(glt.texnum));
+    }
+
+    fn max_texture_size(&self) -> c_uint {
+        return self.hardware_maxsize;
+    }
+
+    fn npot_supported(&self) -> bool {
+        return self.texture_npot;
+    }
+}
+
+/*
+================================================================================
+
+    TEXTURE MANAGER
+
+================================================================================
+*/
+
+fn name_bytes(name: &[c_char]) -> &[u8] {
+    let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+    return unsafe { &*(&name[..len] as *const [c_char] as *const [u8]) };
+}
+
+fn copy_name(dst: &mut [c_char], src: &str) {
+    let len = src.len().min(dst.len() - 1);
+    for (d, s) in dst.iter_mut().zip(src.as_bytes()[..len].iter()) {
+        *d = *s as c_char;
+    }
+    dst[len] = 0;
+}
+
+/// Formerly: TexMgr_Pad
+/// return smallest power of two greater than or equal to s
+pub fn texmgr_pad(s: c_uint) -> c_uint {
+    return s.next_power_of_two();
+}
+
+/// size in bytes of width * height pixels of source data in format
+pub fn source_size(format: SrcFormat, width: c_uint, height: c_uint) -> usize {
+    let texels = width as usize * height as usize;
+    return match format {
+        SrcFormat::Indexed => texels,
+        SrcFormat::LightMap => texels * LIGHTMAP_BYTES,
+        SrcFormat::RGBA => texels * 4,
+    };
+}
+
+fn rgba(r: Byte, g: Byte, b: Byte, a: Byte) -> u32 {
+    return u32::from_le_bytes([r, g, b, a]);
+}
+
+/// The palettes TexMgr_LoadPalette builds from gfx/palette.lmp.
+pub struct PalettesT {
+    /// standard palette, 255 is transparent
+    pub standard: [u32; 256],
+    /// fullbright palette, 0-223 are black (for additive blending)
+    pub fbright: [u32; 256],
+    /// nobright palette, 224-255 are black (for additive blending)
+    pub nobright: [u32; 256],
+    pub fbright_fence: [u32; 256],
+    pub nobright_fence: [u32; 256],
+    /// conchars palette, 0 and 255 are transparent
+    pub conchars: [u32; 256],
+}
+
+impl PalettesT {
+    /// build all palettes from 256 rgb triples
+    pub fn new(pal: &[Byte]) -> Self {
+        let mut standard = [0; 256];
+        for (i, c) in pal.chunks(3).take(256).enumerate() {
+            standard[i] = rgba(c[0], c[1], c[2], 255);
+        }
+        standard[255] &= 0x00ffffff;
+
+        let mut fbright = standard;
+        let mut nobright = standard;
+        for i in 0..256 {
+            if i < 224 {
+                fbright[i] = rgba(0, 0, 0, 255);
+            } else {
+                fbright[i] |= 0xff000000;
+                nobright[i] = rgba(0, 0, 0, 255);
+            }
+        }
+
+        let mut fbright_fence = fbright;
+        fbright_fence[255] = 0;
+        let mut nobright_fence = nobright;
+        nobright_fence[255] = 0;
+
+        let mut conchars = standard;
+        conchars[0] &= 0x00ffffff;
+
+        Self {
+            standard,
+            fbright,
+            nobright,
+            fbright_fence,
+            nobright_fence,
+            conchars,
+        }
+    }
+}
+
+impl Default for PalettesT {
+    fn default() -> Self {
+        Self::new(&[0; 768])
+    }
+}
+
+/// Formerly: the arguments of TexMgr_LoadImage
+/// an image to load, and where it can be read from again
+#[derive(Clone, Copy)]
+pub struct TexImageT<'a> {
+    pub owner: *mut QModelT,
+    pub name: &'a str,
+    pub width: c_uint,
+    pub height: c_uint,
+    pub format: SrcFormat,
+    pub data: &'a [Byte],
+    /// relative filepath to data source, or "" if source is in memory
+    pub source_file: &'a str,
+    /// byte offset into file, or memory address
+    pub source_offset: SrcOffsetT,
+    pub flags: TexPref,
+}
+
+/// Where TexMgr re-reads texture data from when rebuilding textures; normally the engine's
+/// search path (COM_FOpenFile / Image_LoadImage).  Images in memory are read directly.
+pub trait ImageSource {
+    /// 'size' bytes starting at 'offset' within 'file', e.g. a miptex lump inside a bsp
+    fn read_lump(&mut self, file: &str, offset: SrcOffsetT, size: usize) -> Option<Vec<Byte>>;
+    /// a standalone image file (tga, pcx, lmp), decoded to RGBA; returns data, width, height
+    fn load_image(&mut self, file: &str) -> Option<(Vec<Byte>, c_uint, c_uint)>;
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReloadError {
+    /// no file or memory address was recorded, or the file could not be read
+    InvalidSource,
+    /// the source changed since the texture was loaded; the texture is left untouched
+    CrcMismatch { expected: c_ushort, found: c_ushort },
+}
+
+/// Texture bookkeeping, independent of the rendering api.  Handles returned by load_image stay
+/// valid until the texture is freed; models keep them as *mut GlTextureT exactly as in C.
+pub struct TexMgr<B: TextureBackend> {
+    backend: B,
+    /// boxed so the handles stay put when the list grows or shrinks
+    #[allow(clippy::vec_box)]
+    textures: Vec<Box<GlTextureT>>,
+    palettes: PalettesT,
+    /// gl_picmip
+    pub picmip: c_uint,
+    /// gl_max_size; 0 for no limit
+    pub max_size: c_uint,
+    /// gl_fullbrights
+    pub fullbrights: bool,
+    warpimagesize: c_uint,
+}
+
+impl<B: TextureBackend> TexMgr<B> {
+    pub fn new(backend: B) -> Self {
+        Self {
+            backend,
+            textures: Vec::new(),
+            palettes: PalettesT::default(),
+            picmip: 0,
+            max_size: 0,
+            fullbrights: true,
+            warpimagesize: 0,
+        }
+    }
+
+    pub fn backend(&self) -> &B {
+        return &self.backend;
+    }
+
+    pub fn backend_mut(&mut self) -> &mut B {
+        return &mut self.backend;
+    }
+
+    /// Formerly: TexMgr_LoadPalette
+    pub fn load_palette(&mut self, pal: &[Byte]) {
+        self.palettes = PalettesT::new(pal);
+    }
+
+    pub fn palettes(&self) -> &PalettesT {
+        return &self.palettes;
+    }
+
+    pub fn num_textures(&self) -> usize {
+        return self.textures.len();
+    }
+
+    pub fn warp_image_size(&self) -> c_uint {
+        return self.warpimagesize;
+    }
+
+    /// active textures, most recently created first
+    pub fn textures(&self) -> impl Iterator<Item = &GlTextureT> {
+        return self.textures.iter().rev().map(|glt| &**glt);
+    }
+
+    /// Formerly: TexMgr_FindTexture
+    pub fn find_texture(&mut self, owner: *mut QModelT, name: &str) -> *mut GlTextureT {
+        for glt in self.textures.iter_mut().rev() {
+            if glt.owner == owner && name_bytes(&glt.name) == name.as_bytes() {
+                return &mut **glt;
+            }
+        }
+        return null_mut();
+    }
+
+    /// Formerly: TexMgr_NewTexture
+    fn new_texture(&mut self) -> *mut GlTextureT {
+        if self.textures.len() == MAX_GLTEXTURES {
+            panic!("numgltextures == MAX_GLTEXTURES");
+        }
+
+        let mut glt = Box::new(GlTextureT::default());
+        glt.texnum = self.backend.gen_texture();
+        self.textures.push(glt);
+        return &mut **self.textures.last_mut().unwrap();
+    }
+
+    fn position(&self, glt: *const GlTextureT) -> Option<usize> {
+        return self.textures.iter().position(|t| std::ptr::eq(&**t, glt));
+    }
+
+    /// Formerly: TexMgr_FreeTexture
+    pub fn free_texture(&mut self, kill: *mut GlTextureT) {
+        if let Some(pos) = self.position(kill) {
+            let glt = self.textures.remove(pos);
+            self.backend.delete_texture(glt.texnum);
+        }
+    }
+
+    /// Formerly: TexMgr_FreeTextures
+    /// free every texture whose flags match 'flags' in the bits set in 'mask';
+    /// free_textures(TexPref::empty(), TexPref::Persist) frees everything not persistent
+    pub fn free_textures(&mut self, flags: TexPref, mask: TexPref) {
+        let backend = &mut self.backend;
+        self.textures.retain(|glt| {
+            if TexPref::from_bits_truncate(glt.flags) & mask == flags & mask {
+                backend.delete_texture(glt.texnum);
+                return false;
+            }
+            return true;
+        });
+    }
+
+    /// Formerly: TexMgr_FreeTexturesForOwner
+    pub fn free_textures_for_owner(&mut self, owner: *mut QModelT) {
+        let backend = &mut self.backend;
+        self.textures.retain(|glt| {
+            if glt.owner == owner {
+                backend.delete_texture(glt.texnum);
+                return false;
+            }
+            return true;
+        });
+    }
+
+    /// Formerly: TexMgr_NewGame
+    pub fn new_game(&mut self) {
+        self.free_textures(TexPref::empty(), TexPref::Persist);
+    }
+
+    /// Formerly: TexMgr_SafeTextureSize
+    /// return a size with hardware and user prefs in mind
+    pub fn safe_texture_size(&self, s: c_uint) -> c_uint {
+        let mut s = s;
+        if !self.backend.npot_supported() {
+            s = texmgr_pad(s);
+        }
+        if self.max_size > 0 {
+            s = s.min(texmgr_pad(self.max_size));
+        }
+        return s.min(self.backend.max_texture_size());
+    }
+
+    /// Formerly: TexMgr_PadConditional
+    /// only pad if a texture of that size would be padded. (used for tex coords)
+    pub fn pad_conditional(&self, s: c_uint) -> c_uint {
+        if s < self.safe_texture_size(s) {
+            return texmgr_pad(s);
+        }
+        return s;
+    }
+
+    /// Formerly: TexMgr_LoadImage
+    /// the one entry point for loading all textures
+    pub fn load_image(&mut self, image: &TexImageT) -> *mut GlTextureT {
+        let TexImageT {
+            owner,
+            name,
+            width,
+            height,
+            format,
+            data,
+            source_file,
+            source_offset,
+            flags,
+        } = *image;
+        // a truncated lump or a bad size; there's nothing to upload
+        let size = source_size(format, width, height);
+        if data.len() < size {
+            return null_mut();
+        }
+        let data = &data[..size];
+
+        // cache check
+        let crc = unsafe { CRC_Block(data.as_ptr(), size as c_int) };
+        let mut glt = null_mut();
+        if flags.contains(TexPref::Overwrite) {
+            glt = self.find_texture(owner, name);
+        }
+        if glt.is_null() {
+            glt = self.new_texture();
+        } else if unsafe { (*glt).source_crc } == crc {
+            return glt;
+        }
+
+        {
+            let glt = unsafe { &mut *glt };
+            glt.owner = owner;
+            copy_name(&mut glt.name, name);
+            glt.width = width;
+            glt.height = height;
+            glt.flags = flags.bits();
+            glt.shirt = -1;
+            glt.pants = -1;
+            copy_name(&mut glt.source_file, source_file);
+            glt.source_offset = source_offset;
+            glt.source_format = format;
+            glt.source_width = width;
+            glt.source_height = height;
+            glt.source_crc = crc;
+        }
+        self.upload(glt, data);
+        return glt;
+    }
+
+    /// hand source data to the loader for glt's format
+    fn upload(&mut self, glt: *mut GlTextureT, data: &[Byte]) {
+        let glt = unsafe { &mut *glt };
+        if data.len() < source_size(glt.source_format, glt.width, glt.height) {
+            return;
+        }
+        match glt.source_format {
+            SrcFormat::Indexed => self.load_image8(glt, data),
+            SrcFormat::LightMap => self.load_lightmap(glt, data),
+            SrcFormat::RGBA => self.load_image32(glt, data.to_vec()),
+        }
+    }
+
+    /// Formerly: TexMgr_LoadImage32
+    /// handles 32bit source data
+    fn load_image32(&mut self, glt: &mut GlTextureT, data: Vec<Byte>) {
+        let flags = TexPref::from_bits_truncate(glt.flags);
+        let mut data = data;
+        if data.len() < (glt.width * glt.height * 4) as usize {
+            return;
+        }
+
+        // resample up
+        if !self.backend.npot_supported() {
+            data = resample_texture(&data, glt.width, glt.height);
+            glt.width = texmgr_pad(glt.width);
+            glt.height = texmgr_pad(glt.height);
+        }
+
+        // mipmap down
+        let picmip = if flags.contains(TexPref::NoPicMip) {
+            0
+        } else {
+            self.picmip.min(31)
+        };
+        let mipwidth = self.safe_texture_size(glt.width >> picmip);
+        let mipheight = self.safe_texture_size(glt.height >> picmip);
+        while glt.width > mipwidth && glt.width > 1 {
+            mipmap_w(&mut data, glt.width, glt.height);
+            glt.width >>= 1;
+            if flags.contains(TexPref::Alpha) {
+                alpha_edge_fix(&mut data, glt.width, glt.height);
+            }
+        }
+        while glt.height > mipheight && glt.height > 1 {
+            mipmap_h(&mut data, glt.width, glt.height);
+            glt.height >>= 1;
+            if flags.contains(TexPref::Alpha) {
+                alpha_edge_fix(&mut data, glt.width, glt.height);
+            }
+        }
+
+        // upload
+        let (mut mipwidth, mut mipheight) = (glt.width, glt.height);
+        self.backend.upload(
+            glt,
+            0,
+            mipwidth,
+            mipheight,
+            &data[..(mipwidth * mipheight * 4) as usize],
+        );
+
+        // upload mipmaps
+        if flags.contains(TexPref::Mipmap) {
+            let mut miplevel = 1;
+            while mipwidth > 1 || mipheight > 1 {
+                if mipwidth > 1 {
+                    mipmap_w(&mut data, mipwidth, mipheight);
+                    mipwidth >>= 1;
+                }
+                if mipheight > 1 {
+                    mipmap_h(&mut data, mipwidth, mipheight);
+                    mipheight >>= 1;
+                }
+                let len = (mipwidth * mipheight * 4) as usize;
+                self.backend
+                    .upload(glt, miplevel, mipwidth, mipheight, &data[..len]);
+                miplevel += 1;
+            }
+        }
+
+        // set filter modes
+        self.backend.set_filter_modes(glt);
+    }
+
+    /// Formerly: TexMgr_LoadImage8
+    /// handles 8bit source data, then passes it to LoadImage32
+    fn load_image8(&mut self, glt: &mut GlTextureT, data: &[Byte]) {
+        let mut flags = TexPref::from_bits_truncate(glt.flags);
+
+        // detect false alpha cases
+        if flags.contains(TexPref::Alpha)
+            && !flags.contains(TexPref::Conchars)
+            && !data.contains(&255)
+        {
+            flags.remove(TexPref::Alpha);
+            glt.flags = flags.bits();
+        }
+
+        // choose palette and padbyte
+        let alpha = flags.contains(TexPref::Alpha);
+        let (usepal, padbyte) = if flags.contains(TexPref::FullBright) {
+            (
+                if alpha {
+                    &self.palettes.fbright_fence
+                } else {
+                    &self.palettes.fbright
+                },
+                0,
+            )
+        } else if flags.contains(TexPref::NoBright) && self.fullbrights {
+            (
+                if alpha {
+                    &self.palettes.nobright_fence
+                } else {
+                    &self.palettes.nobright
+                },
+                0,
+            )
+        } else if flags.contains(TexPref::Conchars) {
+            (&self.palettes.conchars, 0)
+        } else {
+            (&self.palettes.standard, 255)
+        };
+
+        // pad each dimention, but only if it's not going to be downsampled later
+        let mut padded = data.to_vec();
+        let (mut padw, mut padh) = (false, false);
+        if flags.contains(TexPref::Pad) {
+            if glt.width < self.safe_texture_size(glt.width) {
+                padded = pad_image_w(&padded, glt.width, glt.height, padbyte);
+                glt.width = texmgr_pad(glt.width);
+                padw = true;
+            }
+            if glt.height < self.safe_texture_size(glt.height) {
+                padded = pad_image_h(&padded, glt.width, glt.height, padbyte);
+                glt.height = texmgr_pad(glt.height);
+                padh = true;
+            }
+        }
+
+        // convert to 32bit
+        let mut rgba = Vec::with_capacity(padded.len() * 4);
+        for &b in &padded {
+            rgba.extend_from_slice(&usepal[b as usize].to_le_bytes());
+        }
+
+        // fix edges
+        if alpha {
+            alpha_edge_fix(&mut rgba, glt.width, glt.height);
+        } else {
+            if padw {
+                pad_edge_fix_w(&mut rgba, glt.source_width, glt.width, glt.height);
+            }
+            if padh {
+                pad_edge_fix_h(&mut rgba, glt.source_height, glt.width, glt.height);
+            }
+        }
+
+        // upload it
+        self.load_image32(glt, rgba);
+    }
+
+    /// Formerly: TexMgr_LoadLightmap
+    fn load_lightmap(&mut self, glt: &mut GlTextureT, data: &[Byte]) {
+        self.backend.upload(glt, 0, glt.width, glt.height, data);
+        self.backend.set_filter_modes(glt);
+    }
+
+    /// Formerly: TexMgr_RecalcWarpImageSize
+    /// called after vid size changes; resizes every WarpImage texture to match
+    pub fn recalc_warp_image_size(&mut self, vid_width: c_uint, vid_height: c_uint) {
+        // find the new correct size
+        let mut size = self.safe_texture_size(512);
+        while size > vid_width && size > 1 {
+            size >>= 1;
+        }
+        while size > vid_height && size > 1 {
+            size >>= 1;
+        }
+        self.warpimagesize = size;
+
+        // ericw -- no early exit if the size is unchanged: after vid_restart the textures are
+        // reloaded at source_width/source_height, which might not match the old size.
+
+        // resize the textures
+        let dummy = vec![0; (size * size * 4) as usize];
+        for glt in self.textures.iter_mut() {
+            if TexPref::from_bits_truncate(glt.flags).contains(TexPref::WarpImage) {
+                self.backend.upload(glt, 0, size, size, &dummy);
+                glt.width = size;
+                glt.height = size;
+            }
+        }
+    }
+
+    /// Formerly: TexMgr_TextureMode_f's inner loop
+    /// reapply filter modes to all textures, e.g. after gl_texturemode or anisotropy changes
+    pub fn set_all_filter_modes(&mut self) {
+        for glt in self.textures.iter() {
+            self.backend.set_filter_modes(glt);
+        }
+    }
+}
+
+impl<B: TextureBackend> TexMgr<B> {
+    /// Formerly: TexMgr_ReloadImage
+    /// reloads a texture from its recorded source, and optionally applies new shirt and pants
+    /// colors.  if shirt and pants are -1,-1, use existing shirt and pants colors; if existing
+    /// shirt and pants colors are -1,-1, don't bother colormapping.  images in memory are
+    /// read again from where they were loaded, so lightmaps come back as they are now.
+    ///
+    /// # Safety
+    /// as read_source: an image in memory must still be at its source_offset
+    pub unsafe fn reload_image<S: ImageSource>(
+        &mut self,
+        handle: *mut GlTextureT,
+        shirt: c_int,
+        pants: c_int,
+        source: &mut S,
+    ) -> Result<(), ReloadError> {
+        let pos = self.position(handle).ok_or(ReloadError::InvalidSource)?;
+        let glt: *mut GlTextureT = &mut *self.textures[pos];
+        let glt = &mut *glt;
+
+        //
+        // get source data
+        //
+        let (mut data, width, height) =
+            read_source(glt, source).ok_or(ReloadError::InvalidSource)?;
+
+        // images in memory are expected to change; files aren't
+        if !name_bytes(&glt.source_file).is_empty() {
+            let crc = CRC_Block(data.as_ptr(), data.len() as c_int);
+            if crc != glt.source_crc {
+                return Err(ReloadError::CrcMismatch {
+                    expected: glt.source_crc,
+                    found: crc,
+                });
+            }
+        }
+
+        glt.source_width = width;
+        glt.source_height = height;
+        glt.width = width;
+        glt.height = height;
+
+        // apply shirt and pants colors
+        if shirt > -1 && pants > -1 && glt.source_format == SrcFormat::Indexed {
+            glt.shirt = shirt as c_schar;
+            glt.pants = pants as c_schar;
+        }
+        if glt.shirt > -1 && glt.pants > -1 {
+            let translation = colormap_translation(glt.shirt as c_int, glt.pants as c_int);
+            for b in data.iter_mut() {
+                *b = translation[*b as usize];
+            }
+        }
+
+        // upload it
+        self.upload(glt, &data);
+        return Ok(());
+    }
+
+    /// Formerly: TexMgr_ReloadImages
+    /// rebuild every texture from its source after the rendering context was recreated
+    /// (vid_restart); the old texture objects are gone, so each texture gets a new name.
+    /// textures that fail to reload keep their new, empty name and are reported by handle.
+    ///
+    /// # Safety
+    /// as reload_image
+    pub unsafe fn restart_images<S: ImageSource>(
+        &mut self,
+        source: &mut S,
+    ) -> Vec<(*mut GlTextureT, ReloadError)> {
+        for glt in self.textures.iter_mut() {
+            glt.texnum = self.backend.gen_texture();
+        }
+        return self.reload_matching(TexPref::empty(), source);
+    }
+
+    /// rebuild every texture in place, e.g. after picmip, gamma or palette changes; texture
+    /// names are kept, so nothing is leaked and nothing holding a handle notices.
+    ///
+    /// # Safety
+    /// as reload_image
+    pub unsafe fn reload_images<S: ImageSource>(
+        &mut self,
+        source: &mut S,
+    ) -> Vec<(*mut GlTextureT, ReloadError)> {
+        return self.reload_matching(TexPref::empty(), source);
+    }
+
+    /// Formerly: TexMgr_ReloadNobrightImages
+    /// called when gl_fullbrights changes
+    ///
+    /// # Safety
+    /// as reload_image
+    pub unsafe fn reload_nobright_images<S: ImageSource>(
+        &mut self,
+        source: &mut S,
+    ) -> Vec<(*mut GlTextureT, ReloadError)> {
+        return self.reload_matching(TexPref::NoBright, source);
+    }
+
+    unsafe fn reload_matching<S: ImageSource>(
+        &mut self,
+        flags: TexPref,
+        source: &mut S,
+    ) -> Vec<(*mut GlTextureT, ReloadError)> {
+        let handles: Vec<*mut GlTextureT> = self
+            .textures
+            .iter_mut()
+            .filter(|glt| TexPref::from_bits_truncate(glt.flags).contains(flags))
+            .map(|glt| &mut **glt as *mut GlTextureT)
+            .collect();
+
+        let mut failed = Vec::new();
+        for glt in handles {
+            if let Err(e) = self.reload_image(glt, -1, -1, source) {
+                failed.push((glt, e));
+            }
+        }
+        return failed;
+    }
+}
+
+/// read a texture's source data back from where it was loaded: a lump inside a file, a simple
+/// image file, or memory.  returns data, width, height
+///
+/// # Safety
+/// a texture with no source_file is read from the address in source_offset, which must still
+/// hold the source_size bytes it was loaded from
+pub unsafe fn read_source<S: ImageSource>(
+    glt: &GlTextureT,
+    source: &mut S,
+) -> Option<(Vec<Byte>, c_uint, c_uint)> {
+    if name_bytes(&glt.source_file).is_empty() && glt.source_offset != 0 {
+        // image in memory
+        let size = source_size(glt.source_format, glt.source_width, glt.source_height);
+        let data = slice::from_raw_parts(glt.source_offset as *const Byte, size);
+        return Some((data.to_vec(), glt.source_width, glt.source_height));
+    }
+    return read_file_source(glt, source);
+}
+
+/// read_source for a texture loaded from a file: a lump inside it, or the whole image
+fn read_file_source<S: ImageSource>(
+    glt: &GlTextureT,
+    source: &mut S,
+) -> Option<(Vec<Byte>, c_uint, c_uint)> {
+    let file = String::from_utf8_lossy(name_bytes(&glt.source_file)).into_owned();
+    let (mut width, mut height) = (glt.source_width, glt.source_height);
+    let size = source_size(glt.source_format, width, height);
+    let data = if file.is_empty() {
+        None
+    } else if glt.source_offset != 0 {
+        // lump inside file
+        source.read_lump(&file, glt.source_offset, size)
+    } else {
+        // simple file
+        source.load_image(&file).map(|(data, w, h)| {
+            width = w;
+            height = h;
+            data
+        })
+    };
+
+    let size = source_size(glt.source_format, width, height);
+    return match data {
+        Some(ref data) if data.len() >= size => Some((data[..size].to_vec(), width, height)),
+        _ => None,
+    };
+}
+
+/// translation table mapping the player color ranges to shirt and pants colors
+fn colormap_translation(shirt: c_int, pants: c_int) -> [Byte; 256] {
+    let mut translation = [0; 256];
+    for (i, t) in translation.iter_mut().enumerate() {
+        *t = i as Byte;
+    }
+
+    let shirt = shirt * 16;
+    let pants = pants * 16;
+    for i in 0..16 {
+        translation[TOP_RANGE + i as usize] = if shirt < 128 {
+            shirt + i
+        } else {
+            shirt + 15 - i
+        } as Byte;
+        translation[BOTTOM_RANGE + i as usize] = if pants < 128 {
+            pants + i
+        } else {
+            pants + 15 - i
+        } as Byte;
+    }
+    return translation;
+}
+
+/*
+================================================================================
+
+    IMAGE LOADING
+
+================================================================================
+*/
+
+/// Formerly: TexMgr_ResampleTexture
+/// bilinear resample of RGBA data up to power of two dimensions
+pub fn resample_texture(data: &[Byte], inwidth: c_uint, inheight: c_uint) -> Vec<Byte> {
+    let outwidth = texmgr_pad(inwidth);
+    let outheight = texmgr_pad(inheight);
+    if inwidth == outwidth && inheight == outheight {
+        return data.to_vec();
+    }
+
+    let (inwidth, inheight) = (inwidth as usize, inheight as usize);
+    let (outwidth, outheight) = (outwidth as usize, outheight as usize);
+    let xfrac = if outwidth > 1 {
+        ((inwidth - 1) << 16) / (outwidth - 1)
+    } else {
+        0
+    };
+    let yfrac = if outheight > 1 {
+        ((inheight - 1) << 16) / (outheight - 1)
+    } else {
+        0
+    };
+
+    let mut out = vec![0; outwidth * outheight * 4];
+    let mut y = 0;
+    for i in 0..outheight {
+        let mody = (y >> 8) & 0xff;
+        let imody = 256 - mody;
+        let row0 = (y >> 16).min(inheight - 1);
+        let row1 = (row0 + 1).min(inheight - 1);
+        let mut x = 0;
+        for j in 0..outwidth {
+            let modx = (x >> 8) & 0xff;
+            let imodx = 256 - modx;
+            let col0 = (x >> 16).min(inwidth - 1);
+            let col1 = (col0 + 1).min(inwidth - 1);
+            let nw = (row0 * inwidth + col0) * 4;
+            let ne = (row0 * inwidth + col1) * 4;
+            let sw = (row1 * inwidth + col0) * 4;
+            let se = (row1 * inwidth + col1) * 4;
+            let dest = (i * outwidth + j) * 4;
+            for c in 0..4 {
+                out[dest + c] = ((data[nw + c] as usize * imodx * imody
+                    + data[ne + c] as usize * modx * imody
+                    + data[sw + c] as usize * imodx * mody
+                    + data[se + c] as usize * modx * mody)
+                    >> 16) as Byte;
+            }
+            x += xfrac;
+        }
+        y += yfrac;
+    }
+    return out;
+}
+
+/// Formerly: TexMgr_MipMapW
+/// halve the width of RGBA data in place
+pub fn mipmap_w(data: &mut [Byte], width: c_uint, height: c_uint) {
+    let size = (width as usize * height as usize) >> 1;
+    for i in 0..size {
+        for c in 0..4 {
+            data[i * 4 + c] = ((data[i * 8 + c] as u32 + data[i * 8 + 4 + c] as u32) >> 1) as Byte;
+        }
+    }
+}
+
+/// Formerly: TexMgr_MipMapH
+/// halve the height of RGBA data in place
+pub fn mipmap_h(data: &mut [Byte], width: c_uint, height: c_uint) {
+    let rowbytes = width as usize * 4;
+    let mut out = 0;
+    for i in 0..(height as usize >> 1) {
+        let inrow = i * 2 * rowbytes;
+        for j in 0..rowbytes {
+            data[out] = ((data[inrow + j] as u32 + data[inrow + rowbytes + j] as u32) >> 1) as Byte;
+            out += 1;
+        }
+    }
+}
+
+/// Formerly: TexMgr_PadImageW
+/// return image with width padded up to power-of-two dimentions
+pub fn pad_image_w(data: &[Byte], width: c_uint, height: c_uint, padbyte: Byte) -> Vec<Byte> {
+    let outwidth = texmgr_pad(width) as usize;
+    let width = width as usize;
+    let mut out = Vec::with_capacity(outwidth * height as usize);
+    for row in data.chunks(width).take(height as usize) {
+        out.extend_from_slice(row);
+        out.resize(out.len() + outwidth - width, padbyte);
+    }
+    return out;
+}
+
+/// Formerly: TexMgr_PadImageH
+/// return image with height padded up to power-of-two dimentions
+pub fn pad_image_h(data: &[Byte], width: c_uint, height: c_uint, padbyte: Byte) -> Vec<Byte> {
+    let outheight = texmgr_pad(height) as usize;
+    let mut out = data[..(width * height) as usize].to_vec();
+    out.resize(width as usize * outheight, padbyte);
+    return out;
+}
+
+/// Formerly: TexMgr_AlphaEdgeFix
+/// eliminate pink edges on sprites, etc; operates in place on RGBA data
+pub fn alpha_edge_fix(data: &mut [Byte], width: c_uint, height: c_uint) {
+    let (width, height) = (width as usize, height as usize);
+    for i in 0..height {
+        let lastrow = width * 4 * if i == 0 { height - 1 } else { i - 1 };
+        let thisrow = width * 4 * i;
+        let nextrow = width * 4 * if i == height - 1 { 0 } else { i + 1 };
+
+        for j in 0..width {
+            let dest = thisrow + j * 4;
+            if data[dest + 3] != 0 {
+                continue; // not transparent
+            }
+
+            let lastpix = 4 * if j == 0 { width - 1 } else { j - 1 };
+            let thispix = 4 * j;
+            let nextpix = 4 * if j == width - 1 { 0 } else { j + 1 };
+
+            let mut n = 0;
+            let mut c = [0u32; 3];
+            for &row in &[lastrow, thisrow, nextrow] {
+                for &pix in &[lastpix, thispix, nextpix] {
+                    let b = row + pix;
+                    if data[b + 3] != 0 {
+                        c[0] += data[b] as u32;
+                        c[1] += data[b + 1] as u32;
+                        c[2] += data[b + 2] as u32;
+                        n += 1;
+                    }
+                }
+            }
+
+            // average all non-transparent neighbors
+            for (k, sum) in c.iter().enumerate() {
+                if let Some(average) = sum.checked_div(n) {
+                    data[dest + k] = average as Byte;
+                }
+            }
+        }
+    }
+}
+
+/// Formerly: TexMgr_PadEdgeFixW
+/// special case of AlphaEdgeFix for textures that only need it because they were padded;
+/// padw x padh is the padded size, width x height the original
+pub fn pad_edge_fix_w(data: &mut [Byte], width: c_uint, padw: c_uint, padh: c_uint) {
+    let (width, padw, padh) = (width as usize, padw as usize, padh as usize);
+    if width >= padw {
+        return;
+    }
+    for i in 0..padh {
+        let row = i * padw * 4;
+
+        // copy last full column to first empty column, leaving alpha byte at zero
+        let src = row + (width - 1) * 4;
+        for c in 0..3 {
+            data[src + 4 + c] = data[src + c];
+        }
+
+        // copy first full column to last empty column, leaving alpha byte at zero
+        let dst = row + (padw - 1) * 4;
+        for c in 0..3 {
+            data[dst + c] = data[row + c];
+        }
+    }
+}
+
+/// Formerly: TexMgr_PadEdgeFixH
+/// special case of AlphaEdgeFix for textures that only need it because they were padded
+pub fn pad_edge_fix_h(data: &mut [Byte], height: c_uint, padw: c_uint, padh: c_uint) {
+    let (height, padw, padh) = (height as usize, padw as usize, padh as usize);
+    if height >= padh {
+        return;
+    }
+    let rowbytes = padw * 4;
+
+    // copy last full row to first empty row, leaving alpha byte at zero
+    let src = (height - 1) * rowbytes;
+    let dst = height * rowbytes;
+    for i in 0..padw {
+        for c in 0..3 {
+            data[dst + i * 4 + c] = data[src + i * 4 + c];
+        }
+    }
+
+    // copy first full row to last empty row, leaving alpha byte at zero
+    let dst = (padh - 1) * rowbytes;
+    for i in 0..padw {
+        for c in 0..3 {
+            data[dst + i * 4 + c] = data[i * 4 + c];
+        }
+    }
+}
+
 pub mod capi {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// an rgba image of width x height, every pixel the same
+    fn solid(width: c_uint, height: c_uint, pixel: [Byte; 4]) -> Vec<Byte> {
+        return pixel
+            .iter()
+            .cycle()
+            .take((width * height * 4) as usize)
+            .cloned()
+            .collect();
+    }
+
+    fn image<'a>(name: &'a str, width: c_uint, height: c_uint, data: &'a [Byte]) -> TexImageT<'a> {
+        return TexImageT {
+            owner: null_mut(),
+            name,
+            width,
+            height,
+            format: SrcFormat::RGBA,
+            data,
+            source_file: "",
+            source_offset: data.as_ptr() as SrcOffsetT,
+            flags: TexPref::empty(),
+        };
+    }
+
+    /// the sizes of every mip level uploaded, in order
+    fn uploads(backend: &NullBackend) -> Vec<(c_int, c_uint, c_uint)> {
+        return backend
+            .calls
+            .iter()
+            .filter_map(|call| match *call {
+                BackendCall::Upload {
+                    level,
+                    width,
+                    height,
+                    ..
+                } => Some((level, width, height)),
+                _ => None,
+            })
+            .collect();
+    }
+
+    /// a backend that keeps the last level 0 upload
+    #[derive(Default)]
+    struct LastUploadT {
+        data: Vec<Byte>,
+    }
+
+    impl TextureBackend for LastUploadT {
+        fn gen_texture(&mut self) -> GLuint {
+            return 1;
+        }
+        fn delete_texture(&mut self, _texnum: GLuint) {}
+        fn upload(
+            &mut self,
+            _glt: &GlTextureT,
+            level: c_int,
+            _width: c_uint,
+            _height: c_uint,
+            data: &[Byte],
+        ) {
+            if level == 0 {
+                self.data = data.to_vec();
+            }
+        }
+        fn set_filter_modes(&mut self, _glt: &GlTextureT) {}
+        fn max_texture_size(&self) -> c_uint {
+            return 4096;
+        }
+        fn npot_supported(&self) -> bool {
+            return true;
+        }
+    }
+
+    /// a search path holding one lump
+    struct LumpSourceT(Vec<Byte>);
+
+    impl ImageSource for LumpSourceT {
+        fn read_lump(&mut self, _file: &str, offset: SrcOffsetT, size: usize) -> Option<Vec<Byte>> {
+            return self
+                .0
+                .get(offset as usize..offset as usize + size)
+                .map(|d| d.to_vec());
+        }
+        fn load_image(&mut self, _file: &str) -> Option<(Vec<Byte>, c_uint, c_uint)> {
+            return None;
+        }
+    }
+
+    #[test]
+    fn sizes_follow_picmip_and_limits() {
+        let mut texmgr = TexMgr::new(NullBackend::new(256, true));
+        assert_eq!(texmgr.safe_texture_size(100), 100);
+        assert_eq!(texmgr.safe_texture_size(1024), 256);
+        texmgr.max_size = 100;
+        assert_eq!(texmgr.safe_texture_size(200), 128);
+        texmgr.max_size = 0;
+
+        texmgr.picmip = 1;
+        let data = solid(64, 32, [10, 20, 30, 255]);
+        let mut wall = image("wall", 64, 32, &data);
+        wall.flags = TexPref::Mipmap;
+        let glt = texmgr.load_image(&wall);
+        assert_eq!(unsafe { ((*glt).width, (*glt).height) }, (32, 16));
+        let levels = [
+            (0, 32, 16),
+            (1, 16, 8),
+            (2, 8, 4),
+            (3, 4, 2),
+            (4, 2, 1),
+            (5, 1, 1),
+        ];
+        assert_eq!(uploads(texmgr.backend()), levels);
+
+        // picmip leaves NoPicMip textures alone
+        texmgr.backend_mut().calls.clear();
+        let mut sky = image("sky", 64, 32, &data);
+        sky.flags = TexPref::NoPicMip;
+        texmgr.load_image(&sky);
+        assert_eq!(uploads(texmgr.backend()), [(0, 64, 32)]);
+    }
+
+    #[test]
+    fn padding_fills_to_a_power_of_two() {
+        assert_eq!(texmgr_pad(3), 4);
+        assert_eq!(texmgr_pad(64), 64);
+        assert_eq!(
+            pad_image_w(&[1, 2, 3, 4, 5, 6], 3, 2, 9),
+            [1, 2, 3, 9, 4, 5, 6, 9]
+        );
+        assert_eq!(
+            pad_image_h(&[1, 2, 3, 4, 5, 6], 2, 3, 9),
+            [1, 2, 3, 4, 5, 6, 9, 9]
+        );
+
+        // only where the hardware would pad anyway
+        let data = [7; 3 * 5];
+        let mut pic = image("pic", 3, 5, &data);
+        pic.format = SrcFormat::Indexed;
+        pic.flags = TexPref::Pad;
+        let mut texmgr = TexMgr::new(NullBackend::new(4096, false));
+        texmgr.load_image(&pic);
+        assert_eq!(uploads(texmgr.backend()), [(0, 4, 8)]);
+        let mut texmgr = TexMgr::new(NullBackend::new(4096, true));
+        texmgr.load_image(&pic);
+        assert_eq!(uploads(texmgr.backend()), [(0, 3, 5)]);
+    }
+
+    #[test]
+    fn resampling_keeps_the_corners() {
+        let data = [0, 0, 0, 255, 255, 255, 255, 255, 90, 90, 90, 255];
+        let out = resample_texture(&data, 3, 1);
+        assert_eq!(out.len(), 4 * 4);
+        assert_eq!(&out[..4], &data[..4]);
+        assert_eq!(&out[12..], &data[8..]);
+        assert_eq!(resample_texture(&data[..8], 2, 1), &data[..8]);
+
+        let data = solid(3, 3, [50, 60, 70, 255]);
+        let mut texmgr = TexMgr::new(NullBackend::new(4096, false));
+        let glt = texmgr.load_image(&image("odd", 3, 3, &data));
+        assert_eq!(unsafe { ((*glt).width, (*glt).height) }, (4, 4));
+        assert_eq!(uploads(texmgr.backend()), [(0, 4, 4)]);
+    }
+
+    #[test]
+    fn truncated_data_is_not_loaded() {
+        let data = solid(8, 8, [0; 4]);
+        let mut texmgr = TexMgr::new(NullBackend::default());
+        let glt = texmgr.load_image(&image("short", 16, 16, &data));
+        assert!(glt.is_null());
+        assert_eq!(texmgr.num_textures(), 0);
+        assert!(uploads(texmgr.backend()).is_empty());
+    }
+
+    #[test]
+    fn overwrite_persist_and_owners() {
+        let mut texmgr = TexMgr::new(NullBackend::default());
+        let data = solid(4, 4, [1, 2, 3, 4]);
+        let mut skin = image("skin", 4, 4, &data);
+        skin.flags = TexPref::Overwrite;
+        let glt = texmgr.load_image(&skin);
+        assert_eq!(texmgr.load_image(&skin), glt);
+        assert_eq!(uploads(texmgr.backend()).len(), 1);
+
+        let mut conchars = image("conchars", 4, 4, &data);
+        conchars.flags = TexPref::Persist;
+        texmgr.load_image(&conchars);
+        let mut model = 0u8;
+        let mut owned = image("owned", 4, 4, &data);
+        owned.owner = &mut model as *mut u8 as *mut QModelT;
+        texmgr.load_image(&owned);
+        assert_eq!(texmgr.num_textures(), 3);
+
+        texmgr.free_textures_for_owner(owned.owner);
+        assert_eq!(texmgr.num_textures(), 2);
+        texmgr.new_game();
+        assert_eq!(texmgr.num_textures(), 1);
+        assert_eq!(texmgr.backend().live_textures().len(), 1);
+    }
+
+    #[test]
+    fn reload_reads_the_source_as_it_is_now() {
+        let mut texmgr = TexMgr::new(LastUploadT::default());
+        let mut lightmap = vec![10; 2 * 2 * LIGHTMAP_BYTES];
+        let mut light = image("lightmap", 2, 2, &lightmap);
+        light.format = SrcFormat::LightMap;
+        let glt = texmgr.load_image(&light);
+        let offset = light.source_offset;
+
+        // the lightmap changes in place, as dynamic lights do
+        lightmap[0] = 99;
+        assert_eq!(lightmap.as_ptr() as SrcOffsetT, offset);
+        let mut lumps = LumpSourceT(Vec::new());
+        unsafe { texmgr.reload_image(glt, -1, -1, &mut lumps) }.unwrap();
+        assert_eq!(texmgr.backend().data, lightmap);
+
+        // a lump that changed on disk is left alone
+        let mut lumps = LumpSourceT(vec![0, 0, 5, 5, 5, 5]);
+        let mut pic = image("pic", 2, 2, &lumps.0[2..]);
+        pic.format = SrcFormat::Indexed;
+        pic.source_file = "maps/e1m1.bsp";
+        pic.source_offset = 2;
+        let glt = texmgr.load_image(&pic);
+        lumps.0[3] = 6;
+        let result = unsafe { texmgr.reload_image(glt, -1, -1, &mut lumps) };
+        assert!(match result {
+            Err(ReloadError::CrcMismatch { .. }) => true,
+            _ => false,
+        });
+    }
+}
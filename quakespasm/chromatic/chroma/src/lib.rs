@@ -9,8 +9,14 @@ extern crate sdl2;
 
 pub mod bspfile;
 
+pub mod cl_bot;
+pub mod cl_input;
 pub mod cl_main;
 pub use cl_main::capi::*;
+pub mod cl_parse;
+pub mod cl_stats;
+pub use cl_stats::capi::*;
+pub mod cl_tent;
 
 pub mod client;
 pub use client::capi::*;
@@ -45,18 +51,34 @@ pub use host::capi::*;
 pub mod keys;
 pub use keys::capi::*;
 
+pub mod lightstyle;
+
 pub mod mathlib;
 
 pub mod net;
+pub mod net_capture;
 pub mod net_defs;
+pub mod net_dgrm;
+pub mod net_loop;
 pub mod net_main;
+pub mod net_rcon;
+pub mod net_sim;
 pub mod net_sys;
+pub mod net_udp;
 pub mod protocol;
 
 pub mod q_sound;
 
+pub mod r_alias;
+pub mod r_light;
+pub mod r_part;
+pub mod r_soft;
+pub mod r_world;
 pub mod render;
 
+pub mod sbar;
+pub use sbar::capi::*;
+
 pub mod spritegn;
 
 pub mod strl;
@@ -71,10 +93,12 @@ pub use wad::capi::*;
 pub mod vid;
 pub use vid::capi::*;
 
+pub mod view;
+
 pub mod zone;
 pub use zone::capi::*;
 
-use std::ops::Not;
+use std::ops::{Index, IndexMut, Not};
 use std::os::raw::{c_char, c_float, c_int, c_uchar, c_void};
 
 // Common types originally found in q_stdinc.h:
@@ -146,6 +170,10 @@ impl Vec3T {
     pub const fn default() -> Self {
         Vec3T([0.0, 0.0, 0.0])
     }
+
+    pub const fn new(x: VecT, y: VecT, z: VecT) -> Self {
+        Vec3T([x, y, z])
+    }
 }
 
 impl Default for Vec3T {
@@ -154,6 +182,26 @@ impl Default for Vec3T {
     }
 }
 
+impl From<[VecT; 3]> for Vec3T {
+    fn from(v: [VecT; 3]) -> Self {
+        Vec3T(v)
+    }
+}
+
+impl Index<usize> for Vec3T {
+    type Output = VecT;
+
+    fn index(&self, i: usize) -> &VecT {
+        &self.0[i]
+    }
+}
+
+impl IndexMut<usize> for Vec3T {
+    fn index_mut(&mut self, i: usize) -> &mut VecT {
+        &mut self.0[i]
+    }
+}
+
 pub type Fixed8T = c_int;
 pub type Fixed16T = c_int;
 pub type Byte = c_uchar;
@@ -0,0 +1,233 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2002-2009 John Fitzgibbons and others
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// lightstyle.rs -- light style string evaluation, shared by the renderers and tools
+
+/*
+a light style is a string of brightness steps, 'a' is no light, 'm' is normal light and 'z'
+is double bright.  the string plays back at ten characters a second and loops.
+*/
+
+use client::LightstyleT;
+use std::os::raw::{c_char, c_double, c_int};
+use MAX_STYLESTRING;
+
+/// characters a style advances each second
+pub const STYLE_RATE: c_double = 10.0;
+/// value step between adjacent characters
+pub const STYLE_STEP: c_int = 22;
+/// value of a style with no string, which is left alone by the renderer
+pub const STYLE_UNSET: c_int = 256;
+/// r_lerplightstyles 1 doesn't blend characters this many steps apart, so lights that switch
+/// on and off stay sharp
+pub const LERP_MAX_JUMP: c_int = (b'm' - b'a') as c_int;
+
+/// value of a single style character, in the 8.8 scale the lightmaps use
+pub fn char_value(c: c_char) -> c_int {
+    return (c as c_int - b'a' as c_int) * STYLE_STEP;
+}
+
+impl LightstyleT {
+    /// style with the given string, see set_map
+    pub fn from_map(map: &[u8]) -> Self {
+        let mut style = Self::default();
+        style.set_map(map);
+        return style;
+    }
+
+    /// Formerly: part of CL_ParseServerMessage (svc_lightstyle)
+    /// replace the style string, truncating it to fit, and recompute the cached average and peak
+    pub fn set_map(&mut self, map: &[u8]) {
+        let len = map
+            .iter()
+            .take(MAX_STYLESTRING - 1)
+            .take_while(|&&c| c != 0)
+            .count();
+        self.map = [0; MAX_STYLESTRING];
+        for (dst, &src) in self.map.iter_mut().zip(&map[..len]) {
+            *dst = src as c_char;
+        }
+        self.length = len as c_int;
+
+        if len == 0 {
+            self.average = b'm' as c_char;
+            self.peak = b'm' as c_char;
+            return;
+        }
+        let mut total = 0;
+        self.peak = b'a' as c_char;
+        for &c in &self.map[..len] {
+            total += c as c_int - b'a' as c_int;
+            self.peak = self.peak.max(c);
+        }
+        self.average = (total / len as c_int + b'a' as c_int) as c_char;
+    }
+
+    /// length of the style string; it's set from c too, so it's kept inside the map
+    fn len(&self) -> usize {
+        return self.length.max(0).min(MAX_STYLESTRING as c_int) as usize;
+    }
+
+    /// the style string, without the terminator
+    pub fn map_bytes(&self) -> Vec<u8> {
+        return self.map[..self.len()].iter().map(|&c| c as u8).collect();
+    }
+
+    /// seconds the style takes to loop
+    pub fn period(&self) -> c_double {
+        return self.len() as c_double / STYLE_RATE;
+    }
+
+    /// character value at string position i, wrapping around the end
+    fn char_at(&self, i: c_int) -> c_int {
+        return char_value(self.map[i.rem_euclid(self.len() as c_int) as usize]);
+    }
+
+    /// Formerly: part of R_AnimateLight
+    /// 8.8 fraction of base light value at time.  flat is r_flatlightstyles: 1 uses the average,
+    /// 2 the peak.  lerp is r_lerplightstyles: 0 steps from character to character, 1 blends
+    /// them except for abrupt changes, 2 blends them all
+    pub fn value(&self, time: c_double, flat: c_int, lerp: c_int) -> c_int {
+        if self.len() == 0 {
+            return STYLE_UNSET;
+        }
+        match flat {
+            2 => return char_value(self.peak),
+            1 => return char_value(self.average),
+            _ => {}
+        }
+
+        let pos = (time * STYLE_RATE).floor();
+        let i = pos as c_int;
+        let a = self.char_at(i);
+        if lerp <= 0 || self.len() == 1 {
+            return a;
+        }
+        let b = self.char_at(i + 1);
+        if lerp == 1 && (b - a).abs() >= LERP_MAX_JUMP * STYLE_STEP {
+            return a;
+        }
+        let frac = time * STYLE_RATE - pos;
+        return a + ((b - a) as c_double * frac) as c_int;
+    }
+
+    /// values over one loop of the style, taking samples evenly spaced values from time 0, for
+    /// previewing flicker patterns
+    pub fn curve(&self, samples: usize, flat: c_int, lerp: c_int) -> Vec<c_int> {
+        let period = self.period();
+        return (0..samples)
+            .map(|n| self.value(period * n as c_double / samples as c_double, flat, lerp))
+            .collect();
+    }
+}
+
+/// value of style num at time, or None if there is no such style
+pub fn style_value(
+    lightstyles: &[LightstyleT],
+    num: usize,
+    time: c_double,
+    flat: c_int,
+    lerp: c_int,
+) -> Option<c_int> {
+    return lightstyles.get(num).map(|s| s.value(time, flat, lerp));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_and_loops() {
+        let style = LightstyleT::from_map(b"amz");
+        assert_eq!(style.period(), 0.3);
+        assert_eq!(style.value(0.0, 0, 0), 0);
+        assert_eq!(style.value(0.15, 0, 0), 12 * STYLE_STEP);
+        assert_eq!(style.value(0.25, 0, 0), 25 * STYLE_STEP);
+        assert_eq!(style.value(0.35, 0, 0), 0);
+        assert_eq!(style.value(-0.05, 0, 0), 25 * STYLE_STEP);
+        // between characters, away from rounding at the boundaries
+        let curve = style.curve(6, 0, 0);
+        assert_eq!(curve.len(), 6);
+        assert_eq!(
+            [curve[1], curve[3], curve[5]],
+            [0, 12 * STYLE_STEP, 25 * STYLE_STEP]
+        );
+    }
+
+    #[test]
+    fn lerp_blends_unless_the_jump_is_abrupt() {
+        let style = LightstyleT::from_map(b"mnaz");
+        let m = char_value(b'm' as c_char);
+        let n = char_value(b'n' as c_char);
+        // m to n is blended by both modes
+        assert_eq!(style.value(0.05, 0, 1), (m + n) / 2);
+        assert_eq!(style.value(0.05, 0, 2), (m + n) / 2);
+        // n to a and a to z are switches, kept sharp by 1
+        assert_eq!(style.value(0.15, 0, 1), n);
+        assert_eq!(style.value(0.25, 0, 1), 0);
+        assert_eq!(style.value(0.15, 0, 2), n / 2);
+        assert_eq!(style.value(0.25, 0, 2), 25 * STYLE_STEP / 2);
+        // z wraps back to m
+        assert_eq!(style.value(0.35, 0, 2), (25 * STYLE_STEP + m) / 2);
+
+        // a single character has nothing to blend with
+        assert_eq!(
+            LightstyleT::from_map(b"q").value(0.05, 0, 2),
+            char_value(b'q' as c_char)
+        );
+    }
+
+    #[test]
+    fn flat_uses_the_average_or_peak() {
+        let style = LightstyleT::from_map(b"aaz");
+        assert_eq!(style.value(0.0, 1, 0), char_value(b'i' as c_char));
+        assert_eq!(style.value(0.0, 2, 0), char_value(b'z' as c_char));
+
+        let empty = LightstyleT::from_map(b"");
+        assert_eq!(empty.value(0.0, 0, 2), STYLE_UNSET);
+        assert_eq!(empty.value(0.0, 1, 0), STYLE_UNSET);
+        assert_eq!(style_value(&[style], 1, 0.0, 0, 0), None);
+    }
+
+    #[test]
+    fn strings_are_truncated_at_the_terminator_or_the_limit() {
+        assert_eq!(LightstyleT::from_map(b"ab\0cd").map_bytes(), b"ab");
+        let long = vec![b'k'; MAX_STYLESTRING * 2];
+        assert_eq!(
+            LightstyleT::from_map(&long).map_bytes().len(),
+            MAX_STYLESTRING - 1
+        );
+    }
+
+    #[test]
+    fn bad_lengths_from_c_stay_inside_the_map() {
+        let mut style = LightstyleT::from_map(b"mz");
+        style.length = -5;
+        assert!(style.map_bytes().is_empty());
+        assert_eq!(style.value(1.0, 0, 2), STYLE_UNSET);
+
+        style.length = MAX_STYLESTRING as c_int + 100;
+        assert_eq!(style.map_bytes().len(), MAX_STYLESTRING);
+        assert_eq!(style.value(0.15, 0, 0), char_value(b'z' as c_char));
+        assert_eq!(style.value(0.25, 0, 0), char_value(0));
+    }
+}
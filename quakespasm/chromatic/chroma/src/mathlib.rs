@@ -21,10 +21,129 @@ Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
 
 */
 
+use std::os::raw::c_int;
+use Vec3T;
+use VecT;
+
 // from joequake
-pub fn q_rint(x: f32) -> u32 {
+pub fn q_rint(x: f32) -> c_int {
     if x > 0.0 {
-        return (x + 0.5) as u32;
+        return (x + 0.5) as c_int;
+    }
+    return (x - 0.5) as c_int;
+}
+
+pub fn dot_product(a: Vec3T, b: Vec3T) -> VecT {
+    return a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+}
+
+pub fn vector_add(a: Vec3T, b: Vec3T) -> Vec3T {
+    return Vec3T::new(a[0] + b[0], a[1] + b[1], a[2] + b[2]);
+}
+
+pub fn vector_subtract(a: Vec3T, b: Vec3T) -> Vec3T {
+    return Vec3T::new(a[0] - b[0], a[1] - b[1], a[2] - b[2]);
+}
+
+pub fn vector_scale(v: Vec3T, scale: VecT) -> Vec3T {
+    return Vec3T::new(v[0] * scale, v[1] * scale, v[2] * scale);
+}
+
+/// a + scale * b
+pub fn vector_ma(a: Vec3T, scale: VecT, b: Vec3T) -> Vec3T {
+    return Vec3T::new(
+        a[0] + scale * b[0],
+        a[1] + scale * b[1],
+        a[2] + scale * b[2],
+    );
+}
+
+pub fn cross_product(a: Vec3T, b: Vec3T) -> Vec3T {
+    return Vec3T::new(
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    );
+}
+
+pub fn vector_length(v: Vec3T) -> VecT {
+    return dot_product(v, v).sqrt();
+}
+
+/// normalizes v in place and returns its original length
+pub fn vector_normalize(v: &mut Vec3T) -> VecT {
+    let length = vector_length(*v);
+    if length != 0.0 {
+        *v = vector_scale(*v, 1.0 / length);
+    }
+    return length;
+}
+
+/// Formerly: AngleVectors
+/// returns forward, right and up vectors for pitch/yaw/roll angles in degrees
+pub fn angle_vectors(angles: Vec3T) -> (Vec3T, Vec3T, Vec3T) {
+    let (sy, cy) = angles[1].to_radians().sin_cos();
+    let (sp, cp) = angles[0].to_radians().sin_cos();
+    let (sr, cr) = angles[2].to_radians().sin_cos();
+
+    let forward = Vec3T::new(cp * cy, cp * sy, -sp);
+    let right = Vec3T::new(-sr * sp * cy + cr * sy, -sr * sp * sy - cr * cy, -sr * cp);
+    let up = Vec3T::new(cr * sp * cy + sr * sy, cr * sp * sy - sr * cy, cr * cp);
+    return (forward, right, up);
+}
+
+/// Formerly: anglemod
+/// wrap an angle in degrees into [0, 360), quantized to 16 bits
+pub fn anglemod(a: VecT) -> VecT {
+    let quantized = (a as f64 * (65536.0 / 360.0)) as i32 & 65535;
+    return ((360.0 / 65536.0) * quantized as f64) as VecT;
+}
+
+/// seedable replacement for the C library rand, same range and the same sequence as the
+/// Microsoft C runtime's for a given seed
+#[derive(Clone, Copy)]
+pub struct RandT {
+    state: u32,
+}
+
+impl RandT {
+    pub const MAX: c_int = 0x7fff;
+
+    pub const fn new(seed: u32) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn seed(&mut self, seed: u32) {
+        self.state = seed;
+    }
+
+    /// next number in 0..=RandT::MAX
+    pub fn rand(&mut self) -> c_int {
+        self.state = self.state.wrapping_mul(214013).wrapping_add(2531011);
+        return ((self.state >> 16) & 0x7fff) as c_int;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rand_matches_the_microsoft_runtime() {
+        // srand(1) then rand() with the Visual C++ runtime
+        let mut rand = RandT::new(1);
+        let first: Vec<c_int> = (0..10).map(|_| rand.rand()).collect();
+        assert_eq!(
+            first,
+            [41, 18467, 6334, 26500, 19169, 15724, 11478, 29358, 26962, 24464]
+        );
+
+        rand.seed(1);
+        assert_eq!(rand.rand(), 41);
+        let mut other = RandT::new(2);
+        assert_ne!(other.rand(), 41);
+
+        let mut rand = RandT::new(0xdead_beef);
+        assert!((0..10000).all(|_| (0..=RandT::MAX).contains(&rand.rand())));
     }
-    return (x - 0.5) as u32;
 }
@@ -28,5 +28,88 @@ Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
     whole engine
 */
 
+use net_dgrm::DatagramStatsT;
+use std::io;
+use Byte;
+
 pub const NET_NAMELEN: usize = 64;
 pub const NET_MAXMESSAGE: usize = 64000;
+
+/// a message read from a connection, see NetConnection::get_message
+#[derive(Clone)]
+pub enum NetMessageT {
+    /// 1 from NET_GetMessage
+    Reliable(Vec<Byte>),
+    /// 2 from NET_GetMessage
+    Unreliable(Vec<Byte>),
+}
+
+/// why a connection call failed; -1 from the NET_ functions
+#[derive(Clone, Debug, PartialEq)]
+pub enum NetError {
+    /// the other end closed the connection or stopped answering
+    Disconnected,
+    /// a message over NET_MAXMESSAGE, or an unreliable one over MAX_DATAGRAM
+    Overflow,
+    /// send_message while the last reliable message is still unacknowledged
+    CantSend,
+    /// the driver failed to read or write
+    Io(io::ErrorKind),
+    /// a connect that didn't get a connection, with m_return_reason: the server's
+    /// CCREP_REJECT, or "No Response"
+    Rejected(String),
+}
+
+impl From<io::Error> for NetError {
+    fn from(e: io::Error) -> Self {
+        return NetError::Io(e.kind());
+    }
+}
+
+/// a connected qsocket_t, as cls.netcon or host_client->netconnection.  Drivers hand these out
+pub trait NetConnection {
+    /// Formerly: NET_GetMessage
+    /// the next message, or None if nothing is waiting
+    fn get_message(&mut self) -> Result<Option<NetMessageT>, NetError>;
+    /// Formerly: NET_SendMessage
+    /// send a message reliably, in order; can_send_message is false until it's acknowledged
+    fn send_message(&mut self, data: &[Byte]) -> Result<(), NetError>;
+    /// Formerly: NET_SendUnreliableMessage
+    fn send_unreliable_message(&mut self, data: &[Byte]) -> Result<(), NetError>;
+    /// Formerly: NET_CanSendMessage
+    /// true if a reliable message can be sent
+    fn can_send_message(&mut self) -> bool;
+    /// Formerly: NET_Close
+    fn close(&mut self);
+    /// Formerly: NET_QSocketGetAddressString
+    /// who is on the other end
+    fn address(&self) -> String;
+    /// the traffic so far, for connections that keep count
+    fn stats(&self) -> Option<DatagramStatsT> {
+        return None;
+    }
+}
+
+impl<C: NetConnection + ?Sized> NetConnection for Box<C> {
+    fn get_message(&mut self) -> Result<Option<NetMessageT>, NetError> {
+        return (**self).get_message();
+    }
+    fn send_message(&mut self, data: &[Byte]) -> Result<(), NetError> {
+        return (**self).send_message(data);
+    }
+    fn send_unreliable_message(&mut self, data: &[Byte]) -> Result<(), NetError> {
+        return (**self).send_unreliable_message(data);
+    }
+    fn can_send_message(&mut self) -> bool {
+        return (**self).can_send_message();
+    }
+    fn close(&mut self) {
+        (**self).close();
+    }
+    fn address(&self) -> String {
+        return (**self).address();
+    }
+    fn stats(&self) -> Option<DatagramStatsT> {
+        return (**self).stats();
+    }
+}
@@ -0,0 +1,865 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// net_capture.rs -- packet capture: the datagrams of a socket written to a file with their
+// times, and a socket that plays them back
+
+use net::{NetConnection, NetError};
+use net_defs::{NetDriver, NetHeaderFlags, NetLanSocket, NET_HEADERSIZE};
+use net_dgrm::DatagramSocketT;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::SocketAddr;
+use std::os::raw::{c_double, c_uint};
+use std::path::Path;
+use std::time::Instant;
+use Byte;
+
+/// the first bytes of a capture file
+pub const CAPTURE_MAGIC: &[Byte; 5] = b"QCAP1";
+/// the host names the replay driver takes, followed by the capture file
+pub const REPLAY_PREFIX: &str = "replay:";
+
+/// a datagram that went through a captured socket
+#[derive(Clone, Debug, PartialEq)]
+pub struct CaptureRecordT {
+    /// seconds since the capture started
+    pub time: c_double,
+    /// true if the socket sent it, false if it came in
+    pub sent: bool,
+    /// where it went or came from
+    pub addr: SocketAddr,
+    pub data: Vec<Byte>,
+}
+
+impl CaptureRecordT {
+    /// time as a little endian double, a direction byte, the address as a string behind its
+    /// length byte, the data behind its little endian 16 bit length
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let addr = self.addr.to_string();
+        out.write_all(&self.time.to_le_bytes())?;
+        out.write_all(&[self.sent as Byte, addr.len() as Byte])?;
+        out.write_all(addr.as_bytes())?;
+        out.write_all(&(self.data.len() as u16).to_le_bytes())?;
+        out.write_all(&self.data)?;
+        return Ok(());
+    }
+
+    /// the next record, or None at the end of the capture
+    pub fn read_from<R: Read>(input: &mut R) -> io::Result<Option<Self>> {
+        let mut time = [0; 8];
+        match input.read_exact(&mut time) {
+            Ok(()) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let mut head = [0; 2];
+        input.read_exact(&mut head)?;
+        let mut addr = vec![0; head[1] as usize];
+        input.read_exact(&mut addr)?;
+        let addr = String::from_utf8_lossy(&addr)
+            .parse()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad capture address"))?;
+        let mut length = [0; 2];
+        input.read_exact(&mut length)?;
+        let mut data = vec![0; u16::from_le_bytes(length) as usize];
+        input.read_exact(&mut data)?;
+        return Ok(Some(Self {
+            time: c_double::from_le_bytes(time),
+            sent: head[0] != 0,
+            addr,
+            data,
+        }));
+    }
+}
+
+/// every record of a capture
+pub fn read_capture<R: Read>(input: &mut R) -> io::Result<Vec<CaptureRecordT>> {
+    let mut magic = [0; 5];
+    input.read_exact(&mut magic)?;
+    if magic != *CAPTURE_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a capture file",
+        ));
+    }
+    let mut records = Vec::new();
+    while let Some(record) = CaptureRecordT::read_from(input)? {
+        records.push(record);
+    }
+    return Ok(records);
+}
+
+/// an open capture: records go out with their time since it started
+pub struct CaptureWriterT {
+    out: Box<dyn Write>,
+    /// when the capture started
+    epoch: Instant,
+}
+
+impl CaptureWriterT {
+    /// start a capture into out; times count from now
+    pub fn new(mut out: Box<dyn Write>) -> io::Result<Self> {
+        out.write_all(CAPTURE_MAGIC)?;
+        return Ok(Self {
+            out,
+            epoch: Instant::now(),
+        });
+    }
+
+    /// start a capture into a new file at path
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let file = File::create(path)?;
+        return Self::new(Box::new(BufWriter::new(file)));
+    }
+
+    /// a datagram that was sent to or came from addr
+    pub fn record(&mut self, sent: bool, addr: &SocketAddr, data: &[Byte]) -> io::Result<()> {
+        let record = CaptureRecordT {
+            time: self.epoch.elapsed().as_secs_f64(),
+            sent,
+            addr: *addr,
+            data: data.to_vec(),
+        };
+        return record.write_to(&mut self.out);
+    }
+}
+
+impl Drop for CaptureWriterT {
+    fn drop(&mut self) {
+        let _ = self.out.flush();
+    }
+}
+
+/// a socket that writes what goes through it to a capture, when one is open
+pub struct CaptureSocketT<S: NetLanSocket> {
+    socket: S,
+    capture: Option<CaptureWriterT>,
+}
+
+impl<S: NetLanSocket> CaptureSocketT<S> {
+    /// socket, not capturing yet
+    pub fn new(socket: S) -> Self {
+        Self {
+            socket,
+            capture: None,
+        }
+    }
+
+    pub fn socket(&self) -> &S {
+        return &self.socket;
+    }
+
+    pub fn socket_mut(&mut self) -> &mut S {
+        return &mut self.socket;
+    }
+
+    pub fn capturing(&self) -> bool {
+        return self.capture.is_some();
+    }
+
+    /// start a capture into out; times count from now
+    pub fn capture_to(&mut self, out: Box<dyn Write>) -> io::Result<()> {
+        self.capture = Some(CaptureWriterT::new(out)?);
+        return Ok(());
+    }
+
+    /// start a capture into a new file at path
+    pub fn capture_file(&mut self, path: &Path) -> io::Result<()> {
+        self.capture = Some(CaptureWriterT::create(path)?);
+        return Ok(());
+    }
+
+    pub fn stop_capture(&mut self) {
+        self.capture = None;
+    }
+
+    /// a capture that can't be written is given up, the socket goes on
+    fn record(&mut self, sent: bool, addr: &SocketAddr, data: &[Byte]) {
+        let failed = match self.capture {
+            Some(ref mut capture) => capture.record(sent, addr, data).is_err(),
+            None => false,
+        };
+        if failed {
+            self.capture = None;
+        }
+    }
+}
+
+impl<S: NetLanSocket> NetLanSocket for CaptureSocketT<S> {
+    fn read(&mut self, buf: &mut [Byte]) -> io::Result<Option<(usize, SocketAddr)>> {
+        let read = self.socket.read(buf)?;
+        if let Some((len, addr)) = read {
+            if self.capture.is_some() {
+                self.record(false, &addr, &buf[..len]);
+            }
+        }
+        return Ok(read);
+    }
+
+    fn write(&mut self, data: &[Byte], addr: &SocketAddr) -> io::Result<usize> {
+        let len = self.socket.write(data, addr)?;
+        if self.capture.is_some() {
+            self.record(true, addr, data);
+        }
+        return Ok(len);
+    }
+}
+
+/// plays back the datagrams a captured socket received; what is sent goes nowhere, but
+/// reliable data is acknowledged so the sender carries on
+pub struct ReplaySocketT {
+    received: VecDeque<CaptureRecordT>,
+    /// where the captured socket heard from first
+    remote: Option<SocketAddr>,
+    /// hold each datagram back until as long after the first read as it came in after the
+    /// start of the capture; otherwise everything is there at once
+    pub realtime: bool,
+    /// when the first read was
+    start: Option<Instant>,
+    /// acks for what was sent, ahead of the capture
+    acks: VecDeque<Vec<Byte>>,
+}
+
+/// a connection control packet, from before the connection was made
+fn is_control(data: &[Byte]) -> bool {
+    if data.len() < 4 {
+        return false;
+    }
+    let header = c_uint::from_be_bytes([data[0], data[1], data[2], data[3]]);
+    return NetHeaderFlags::from_bits_truncate(header).contains(NetHeaderFlags::Ctl);
+}
+
+impl ReplaySocketT {
+    /// the connection's datagrams of records; control packets, such as the CCREP_ACCEPT that
+    /// comes from the server's listening port, are left out
+    pub fn new(records: Vec<CaptureRecordT>) -> Self {
+        let received: VecDeque<CaptureRecordT> = records
+            .into_iter()
+            .filter(|r| !r.sent && !is_control(&r.data))
+            .collect();
+        Self {
+            remote: received.front().map(|r| r.addr),
+            received,
+            realtime: false,
+            start: None,
+            acks: VecDeque::new(),
+        }
+    }
+
+    /// the capture file at path
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut input = BufReader::new(File::open(path)?);
+        return Ok(Self::new(read_capture(&mut input)?));
+    }
+
+    /// where the captured socket heard from first; the other end of the connection
+    pub fn remote(&self) -> Option<SocketAddr> {
+        return self.remote;
+    }
+
+    /// true when every datagram has been read
+    pub fn finished(&self) -> bool {
+        return self.received.is_empty() && self.acks.is_empty();
+    }
+}
+
+impl NetLanSocket for ReplaySocketT {
+    fn read(&mut self, buf: &mut [Byte]) -> io::Result<Option<(usize, SocketAddr)>> {
+        let start = *self.start.get_or_insert_with(Instant::now);
+        let remote = match self.remote() {
+            Some(remote) => remote,
+            None => return Ok(None),
+        };
+        if let Some(ack) = self.acks.pop_front() {
+            buf[..ack.len()].copy_from_slice(&ack);
+            return Ok(Some((ack.len(), remote)));
+        }
+
+        if let Some(record) = self.received.front() {
+            if self.realtime && record.time > start.elapsed().as_secs_f64() {
+                return Ok(None);
+            }
+        }
+        let record = match self.received.pop_front() {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        let len = record.data.len().min(buf.len());
+        buf[..len].copy_from_slice(&record.data[..len]);
+        return Ok(Some((len, record.addr)));
+    }
+
+    fn write(&mut self, data: &[Byte], _addr: &SocketAddr) -> io::Result<usize> {
+        if data.len() >= NET_HEADERSIZE {
+            let header = c_uint::from_be_bytes([data[0], data[1], data[2], data[3]]);
+            let flags = NetHeaderFlags::from_bits_truncate(header);
+            if flags.contains(NetHeaderFlags::Data) {
+                let mut ack = Vec::with_capacity(NET_HEADERSIZE);
+                let length = NET_HEADERSIZE as c_uint | NetHeaderFlags::Ack.bits();
+                ack.extend_from_slice(&length.to_be_bytes());
+                ack.extend_from_slice(&data[4..8]);
+                self.acks.push_back(ack);
+            }
+        }
+        return Ok(data.len());
+    }
+}
+
+/// connects to captures: "replay:file" is a connection that reads what the captured client
+/// was sent
+pub struct ReplayDriverT {
+    /// play captures back at the speed they were recorded
+    pub realtime: bool,
+}
+
+impl ReplayDriverT {
+    pub const fn new() -> Self {
+        Self { realtime: false }
+    }
+
+    /// a connection reading the capture at path
+    pub fn open(&self, path: &Path) -> Result<DatagramSocketT<ReplaySocketT>, NetError> {
+        let mut socket = ReplaySocketT::open(path)?;
+        socket.realtime = self.realtime;
+        let remote = match socket.remote() {
+            Some(remote) => remote,
+            None => return Err(NetError::Disconnected),
+        };
+        return Ok(DatagramSocketT::new(socket, remote));
+    }
+}
+
+impl Default for ReplayDriverT {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NetDriver for ReplayDriverT {
+    fn name(&self) -> &str {
+        return "Replay";
+    }
+
+    fn listen(&mut self, _state: bool) {}
+
+    fn connect(&mut self, host: &str) -> Result<Option<Box<dyn NetConnection>>, NetError> {
+        if !host.starts_with(REPLAY_PREFIX) {
+            return Ok(None);
+        }
+        let connection = self.open(Path::new(&host[REPLAY_PREFIX.len()..]))?;
+        return Ok(Some(Box::new(connection)));
+    }
+
+    fn check_new_connections(&mut self) -> Result<Option<Box<dyn NetConnection>>, NetError> {
+        return Ok(None);
+    }
+}
+
+#[allow(bad_style)]
+pub mod capi {
+    use super::{CaptureWriterT, ReplayDriverT, ReplaySocketT, REPLAY_PREFIX};
+    use cl_main::capi::cls;
+    use client::CActiveT;
+    use net::{NetConnection, NetError, NetMessageT, NET_NAMELEN};
+    use net_defs::QSocketT;
+    use net_dgrm::DatagramSocketT;
+    use net_main::capi::{net_message, net_time};
+    use net_sys::SysSocketT;
+    use std::ffi::{CStr, CString};
+    use std::net::SocketAddr;
+    use std::os::raw::{c_char, c_double, c_int, c_void};
+    use std::path::PathBuf;
+    use std::ptr::{addr_of_mut, null_mut};
+    use std::slice;
+    use {Byte, QBoolean, QuakeParmsT, SizeBufT};
+
+    extern "C" {
+        static host_parms: *mut QuakeParmsT;
+        fn Cmd_Argc() -> c_int;
+        fn Cmd_Argv(arg: c_int) -> *const c_char;
+        fn Con_Printf(fmt: *const c_char, ...);
+        fn SZ_Write(buf: *mut SizeBufT, data: *const c_void, length: c_int);
+        fn NET_NewQSocket() -> *mut QSocketT;
+    }
+
+    /// the capture net_capture started, and the connection it's of
+    struct CaptureT {
+        writer: CaptureWriterT,
+        /// NULL until the next connection, when net_capture was given while disconnected
+        netcon: *mut QSocketT,
+        socket: SysSocketT,
+        connecttime: c_double,
+    }
+
+    impl CaptureT {
+        /// the capture is of netcon from now
+        unsafe fn bind(&mut self, netcon: *mut QSocketT) {
+            self.netcon = netcon;
+            self.socket = (*netcon).socket;
+            self.connecttime = (*netcon).connecttime;
+        }
+    }
+
+    /// cls.netcon, if it's an open connection of the datagram driver; only those are on a
+    /// system socket
+    unsafe fn datagram_netcon() -> Option<*mut QSocketT> {
+        let netcon = cls.netcon;
+        if netcon.is_null() || (*netcon).disconnected == QBoolean::True || (*netcon).socket == 0 {
+            return None;
+        }
+        return Some(netcon);
+    }
+
+    static mut CAPTURE: Option<CaptureT> = None;
+    /// replays play at the speed they were captured, a frame's worth at a time
+    static mut REPLAY: ReplayDriverT = ReplayDriverT { realtime: true };
+
+    unsafe fn print(msg: &str) {
+        let msg = CString::new(msg).unwrap_or_default();
+        Con_Printf(b"%s\0".as_ptr() as *const c_char, msg.as_ptr());
+    }
+
+    /// name in the user's directory, unless it's a full path
+    unsafe fn user_path(name: &str) -> PathBuf {
+        let userdir = CStr::from_ptr((*host_parms).userdir)
+            .to_string_lossy()
+            .into_owned();
+        return PathBuf::from(userdir).join(name);
+    }
+
+    /// Formerly: none
+    /// net_capture <file>: write the datagrams of the connection to the server to file, in
+    /// the user's directory, until it closes; from the next connection if there's none, so
+    /// the capture can be replayed from the start.  net_capture on its own stops
+    ///
+    /// # Safety
+    ///
+    /// reads the command arguments and cls; only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn NET_Capture_f() {
+        let capture = &mut *addr_of_mut!(CAPTURE);
+        if Cmd_Argc() < 2 {
+            match capture.take() {
+                Some(_) => print("capture stopped.\n"),
+                None => print("usage: net_capture <file>\n"),
+            }
+            return;
+        }
+
+        let path = user_path(&CStr::from_ptr(Cmd_Argv(1)).to_string_lossy());
+        let writer = match CaptureWriterT::create(&path) {
+            Ok(writer) => writer,
+            Err(e) => {
+                print(&format!("couldn't create {}: {}\n", path.display(), e));
+                return;
+            }
+        };
+        let mut c = CaptureT {
+            writer,
+            netcon: null_mut(),
+            socket: 0,
+            connecttime: 0.0,
+        };
+        match datagram_netcon() {
+            Some(netcon) => {
+                c.bind(netcon);
+                print(&format!("capturing to {}\n", path.display()));
+            }
+            None => print(&format!(
+                "capturing the next connection to {}\n",
+                path.display()
+            )),
+        }
+        *capture = Some(c);
+    }
+
+    /// Formerly: none
+    /// the lan drivers' Read and Write: len bytes of data went through socketid, sent to addr
+    /// or come from it; they're captured if it's the socket net_capture is on
+    ///
+    /// # Safety
+    ///
+    /// addr must be a string and data hold len bytes; only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn NET_CaptureDatagram(
+        socketid: SysSocketT,
+        sent: QBoolean,
+        addr: *const c_char,
+        data: *const Byte,
+        len: c_int,
+    ) {
+        let capture = &mut *addr_of_mut!(CAPTURE);
+        let c = match *capture {
+            Some(ref mut c) => c,
+            None => return,
+        };
+        if c.netcon.is_null() {
+            // waiting for the next connection
+            match datagram_netcon() {
+                Some(netcon) if (*netcon).socket == socketid => c.bind(netcon),
+                _ => return,
+            }
+        }
+        if c.socket != socketid || len <= 0 {
+            return;
+        }
+        // the connection closed, and the socket may have gone to another
+        if c.netcon != cls.netcon
+            || (*c.netcon).disconnected == QBoolean::True
+            || (*c.netcon).connecttime != c.connecttime
+        {
+            *capture = None;
+            print("capture stopped.\n");
+            return;
+        }
+
+        let addr: SocketAddr = match CStr::from_ptr(addr).to_string_lossy().parse() {
+            Ok(addr) => addr,
+            Err(_) => return,
+        };
+        let data = slice::from_raw_parts(data, len as usize);
+        if let Err(e) = c.writer.record(sent == QBoolean::True, &addr, data) {
+            *capture = None;
+            print(&format!("capture stopped: {}\n", e));
+        }
+    }
+
+    /// the replay a qsocket_t of the replay driver reads
+    unsafe fn replay<'a>(sock: *mut QSocketT) -> Option<&'a mut DatagramSocketT<ReplaySocketT>> {
+        let replay = (*sock).driverdata as *mut DatagramSocketT<ReplaySocketT>;
+        return replay.as_mut();
+    }
+
+    /// Formerly: none
+    /// the replay driver, for clients only
+    ///
+    /// # Safety
+    ///
+    /// reads cls; only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn Replay_Init() -> c_int {
+        if cls.state == CActiveT::Dedicated {
+            return -1;
+        }
+        return 0;
+    }
+
+    #[no_mangle]
+    pub extern "C" fn Replay_Shutdown() {}
+
+    #[no_mangle]
+    pub extern "C" fn Replay_Listen(_state: QBoolean) {}
+
+    #[no_mangle]
+    pub extern "C" fn Replay_SearchForHosts(_xmit: QBoolean) {}
+
+    /// Formerly: none
+    /// "replay:file" is a connection that reads the capture file, in the user's directory;
+    /// NULL for any other host
+    ///
+    /// # Safety
+    ///
+    /// host must be a string; only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn Replay_Connect(host: *const c_char) -> *mut QSocketT {
+        let host = CStr::from_ptr(host).to_string_lossy().into_owned();
+        if !host.starts_with(REPLAY_PREFIX) {
+            return null_mut();
+        }
+        let path = user_path(&host[REPLAY_PREFIX.len()..]);
+        let mut replay = match (*addr_of_mut!(REPLAY)).open(&path) {
+            Ok(replay) => replay,
+            Err(NetError::Io(kind)) => {
+                print(&format!("couldn't replay {}: {:?}\n", path.display(), kind));
+                return null_mut();
+            }
+            Err(_) => {
+                print(&format!("{} has nothing to replay\n", path.display()));
+                return null_mut();
+            }
+        };
+        let sock = NET_NewQSocket();
+        if sock.is_null() {
+            print("Replay_Connect: no qsocket available\n");
+            return null_mut();
+        }
+        replay.set_net_time(net_time);
+        (*sock).driverdata = Box::into_raw(Box::new(replay)) as *mut c_void;
+        let len = host.len().min(NET_NAMELEN - 1);
+        for (d, &c) in (*sock)
+            .address
+            .iter_mut()
+            .zip(host.as_bytes()[..len].iter())
+        {
+            *d = c as c_char;
+        }
+        (*sock).address[len] = 0;
+        return sock;
+    }
+
+    #[no_mangle]
+    pub extern "C" fn Replay_CheckNewConnections() -> *mut QSocketT {
+        return null_mut();
+    }
+
+    /// Formerly: none
+    /// the next message of the capture into net_message: 1 for a reliable one, 2 for an
+    /// unreliable one, 0 for none yet, -1 once the capture has run out
+    ///
+    /// # Safety
+    ///
+    /// sock must be a qsocket_t of the replay driver; only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn Replay_GetMessage(sock: *mut QSocketT) -> c_int {
+        let replay = match replay(sock) {
+            Some(replay) => replay,
+            None => return -1,
+        };
+        replay.set_net_time(net_time);
+        let (ret, data) = match replay.get_message() {
+            Ok(Some(NetMessageT::Reliable(data))) => (1, data),
+            Ok(Some(NetMessageT::Unreliable(data))) => (2, data),
+            Ok(None) if replay.socket().finished() => return -1,
+            Ok(None) => return 0,
+            Err(_) => return -1,
+        };
+        let message = &mut *addr_of_mut!(net_message);
+        message.cursize = 0;
+        SZ_Write(message, data.as_ptr() as *const c_void, data.len() as c_int);
+        return ret;
+    }
+
+    /// Formerly: none
+    /// sent nowhere, but acknowledged as the server would
+    ///
+    /// # Safety
+    ///
+    /// sock must be a qsocket_t of the replay driver and data a sizebuf
+    #[no_mangle]
+    pub unsafe extern "C" fn Replay_SendMessage(sock: *mut QSocketT, data: *mut SizeBufT) -> c_int {
+        let data = slice::from_raw_parts((*data).data, (*data).cursize as usize);
+        return match replay(sock).map(|r| r.send_message(data)) {
+            Some(Ok(())) => 1,
+            _ => -1,
+        };
+    }
+
+    /// Formerly: none
+    ///
+    /// # Safety
+    ///
+    /// sock must be a qsocket_t of the replay driver and data a sizebuf
+    #[no_mangle]
+    pub unsafe extern "C" fn Replay_SendUnreliableMessage(
+        sock: *mut QSocketT,
+        data: *mut SizeBufT,
+    ) -> c_int {
+        let data = slice::from_raw_parts((*data).data, (*data).cursize as usize);
+        return match replay(sock).map(|r| r.send_unreliable_message(data)) {
+            Some(Ok(())) => 1,
+            _ => -1,
+        };
+    }
+
+    /// # Safety
+    ///
+    /// sock must be a qsocket_t of the replay driver
+    #[no_mangle]
+    pub unsafe extern "C" fn Replay_CanSendMessage(sock: *mut QSocketT) -> QBoolean {
+        return match replay(sock).map(|r| r.can_send_message()) {
+            Some(true) => QBoolean::True,
+            _ => QBoolean::False,
+        };
+    }
+
+    #[no_mangle]
+    pub extern "C" fn Replay_CanSendUnreliableMessage(_sock: *mut QSocketT) -> QBoolean {
+        return QBoolean::True;
+    }
+
+    /// Formerly: none
+    /// NET_Close frees the qsocket_t after
+    ///
+    /// # Safety
+    ///
+    /// sock must be a qsocket_t of the replay driver
+    #[no_mangle]
+    pub unsafe extern "C" fn Replay_Close(sock: *mut QSocketT) {
+        let replay = (*sock).driverdata as *mut DatagramSocketT<ReplaySocketT>;
+        (*sock).driverdata = null_mut();
+        if !replay.is_null() {
+            drop(Box::from_raw(replay));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use net::NetMessageT;
+    use net_defs::tests::{HubT, MemorySocketT};
+    use std::fs;
+
+    fn record(time: c_double, sent: bool, data: &[Byte]) -> CaptureRecordT {
+        return CaptureRecordT {
+            time,
+            sent,
+            addr: "10.0.0.1:26000".parse().unwrap(),
+            data: data.to_vec(),
+        };
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let records = vec![record(0.0, true, b"hello"), record(0.25, false, b"")];
+        let mut file = CAPTURE_MAGIC.to_vec();
+        for record in &records {
+            record.write_to(&mut file).unwrap();
+        }
+        assert_eq!(read_capture(&mut &file[..]).unwrap(), records);
+
+        file[0] = b'X';
+        let err = read_capture(&mut &file[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn realtime_replay_holds_back_and_runs_out() {
+        let mut socket = ReplaySocketT::new(vec![
+            record(0.0, false, b"now"),
+            record(0.0, true, b"sent"),
+            record(60.0, false, b"later"),
+        ]);
+        socket.realtime = true;
+        let mut buf = [0; 16];
+        assert_eq!(
+            socket.read(&mut buf).unwrap(),
+            Some((3, socket.remote().unwrap()))
+        );
+        assert_eq!(socket.read(&mut buf).unwrap(), None);
+        assert!(!socket.finished());
+
+        let mut socket = ReplaySocketT::new(vec![record(0.0, false, b"now")]);
+        socket.realtime = true;
+        assert!(socket.read(&mut buf).unwrap().is_some());
+        assert!(socket.finished());
+        assert_eq!(socket.read(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn replay_leaves_out_the_control_packets() {
+        let mut accept = record(0.0, false, &(NetHeaderFlags::Ctl.bits() | 9).to_be_bytes());
+        accept.data.extend_from_slice(&[0x81, 0x51, 0x65, 0, 0]);
+        accept.addr = "10.0.0.1:26000".parse().unwrap();
+        let mut data = record(0.1, false, b"data");
+        data.addr = "10.0.0.1:26001".parse().unwrap();
+        let mut socket = ReplaySocketT::new(vec![accept, data]);
+        assert_eq!(socket.remote(), Some("10.0.0.1:26001".parse().unwrap()));
+
+        let mut buf = [0; 16];
+        assert_eq!(
+            socket.read(&mut buf).unwrap(),
+            Some((4, "10.0.0.1:26001".parse().unwrap()))
+        );
+        assert!(socket.finished());
+    }
+
+    #[test]
+    fn replay_acks_reliable_data() {
+        let mut socket = ReplaySocketT::new(vec![record(0.0, false, b"x")]);
+        let remote = socket.remote().unwrap();
+        let flags = NetHeaderFlags::Data | NetHeaderFlags::Eom;
+        let mut packet = (9 | flags.bits()).to_be_bytes().to_vec();
+        packet.extend_from_slice(&[0, 0, 0, 7, 42]);
+        socket.write(&packet, &remote).unwrap();
+        socket.write(b"short", &remote).unwrap();
+
+        let mut buf = [0; 16];
+        let (len, from) = socket.read(&mut buf).unwrap().unwrap();
+        assert_eq!(from, remote);
+        let header = c_uint::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        assert_eq!(
+            header,
+            NET_HEADERSIZE as c_uint | NetHeaderFlags::Ack.bits()
+        );
+        assert_eq!(&buf[4..len], &[0, 0, 0, 7]);
+        assert_eq!(socket.read(&mut buf).unwrap(), Some((1, remote)));
+        assert!(socket.finished());
+    }
+
+    #[test]
+    fn capture_replays_what_the_client_was_sent() {
+        let hub = HubT::default();
+        let server_socket = MemorySocketT::new(&hub, "10.0.0.1:26001");
+        let client_socket = MemorySocketT::new(&hub, "10.0.0.2:5000");
+        let (server_addr, client_addr) = (server_socket.addr(), client_socket.addr());
+        let mut server = DatagramSocketT::new(server_socket, client_addr);
+        let mut capture = CaptureSocketT::new(client_socket);
+        let path = std::env::temp_dir().join("net_capture_test.qcap");
+        capture.capture_file(&path).unwrap();
+        assert!(capture.capturing());
+        let mut client = DatagramSocketT::new(capture, server_addr);
+
+        let messages: Vec<Vec<Byte>> = (0..3).map(|i| vec![i as Byte; 500 + i * 1500]).collect();
+        let mut reliable = Vec::new();
+        let mut unreliable = 0;
+        let mut next = 0;
+        let mut time = 0.0;
+        while reliable.len() < messages.len() {
+            time += 0.05;
+            server.set_net_time(time);
+            client.set_net_time(time);
+            if next < messages.len() && server.can_send_message() {
+                server.send_message(&messages[next]).unwrap();
+                next += 1;
+            }
+            server.send_unreliable_message(b"state").unwrap();
+            while server.get_message().unwrap().is_some() {}
+            while let Some(message) = client.get_message().unwrap() {
+                match message {
+                    NetMessageT::Reliable(data) => reliable.push(data),
+                    NetMessageT::Unreliable(_) => unreliable += 1,
+                }
+            }
+        }
+        drop(client);
+
+        let mut driver = ReplayDriverT::new();
+        assert!(driver.connect("local").unwrap().is_none());
+        let host = format!("{}{}", REPLAY_PREFIX, path.display());
+        let mut replay = driver.connect(&host).unwrap().unwrap();
+        assert_eq!(replay.address(), server_addr.to_string());
+        let mut replayed = Vec::new();
+        let mut replayed_unreliable = 0;
+        while let Some(message) = replay.get_message().unwrap() {
+            match message {
+                NetMessageT::Reliable(data) => replayed.push(data),
+                NetMessageT::Unreliable(_) => replayed_unreliable += 1,
+            }
+        }
+        let _ = fs::remove_file(&path);
+        assert_eq!(replayed, messages);
+        assert_eq!(replayed_unreliable, unreliable);
+    }
+}
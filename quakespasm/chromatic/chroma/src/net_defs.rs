@@ -21,13 +21,17 @@
  * 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
  */
 
-use net::{NET_MAXMESSAGE, NET_NAMELEN};
+use net::{NetConnection, NetError, NET_MAXMESSAGE, NET_NAMELEN};
+use net_dgrm::DatagramStatsT;
 use net_sys::SysSocketT;
+use std::io;
 use std::mem::size_of;
+use std::net::SocketAddr;
 use std::os::raw::{c_char, c_double, c_int, c_short, c_uchar, c_uint, c_void};
 use Byte;
 use {QBoolean, MAX_DATAGRAM};
 
+#[derive(Clone, Copy)]
 #[repr(C)]
 pub struct QSockAddr {
     /*
@@ -65,6 +69,32 @@ bitflags! {
 
 pub const NET_PROTOCOL_VERSION: u32 = 3;
 
+/// Formerly: the socket calls of net_landriver_t
+/// an open datagram socket of a lan driver
+pub trait NetLanSocket {
+    /// Formerly: Read
+    /// the next datagram and who sent it, or None if nothing is waiting
+    fn read(&mut self, buf: &mut [Byte]) -> io::Result<Option<(usize, SocketAddr)>>;
+    /// Formerly: Write
+    fn write(&mut self, data: &[Byte], addr: &SocketAddr) -> io::Result<usize>;
+}
+
+/// Formerly: net_driver_t
+/// a way of making connections; what is done over them is NetConnection
+pub trait NetDriver {
+    /// Formerly: name
+    fn name(&self) -> &str;
+    /// Formerly: Listen
+    /// start or stop taking connections
+    fn listen(&mut self, state: bool);
+    /// Formerly: Connect
+    /// a connection to host, or None if host isn't one for this driver
+    fn connect(&mut self, host: &str) -> Result<Option<Box<dyn NetConnection>>, NetError>;
+    /// Formerly: CheckNewConnections
+    /// the next client to have connected, if any
+    fn check_new_connections(&mut self) -> Result<Option<Box<dyn NetConnection>>, NetError>;
+}
+
 /**
 
 This is the network info/connection protocol.  It is used to find Quake
@@ -90,6 +120,11 @@ CCREQ_PLAYER_INFO
 CCREQ_RULE_INFO
         string	rule
 
+CCREQ_RCON
+        long    challenge               0 to ask for one
+        string  digest                  hex HMAC-SHA256 of challenge and command, keyed with rcon_password
+        string  command
+
 CCREP_ACCEPT
         long	port
 
@@ -116,6 +151,10 @@ CCREP_RULE_INFO
         string	rule
         string	value
 
+CCREP_RCON
+        long    challenge               the one to sign the command with, 0 with the output
+        string  output                  the command's console output, or why it wasn't run
+
     note:
         There are two address forms used above.  The short form is just a
         port number.  The address that goes along with the port is defined as
@@ -135,12 +174,14 @@ pub enum ClientConnectMessage {
     ReqServerInfo = 0x02,
     ReqPlayerInfo = 0x03,
     ReqRuleInfo = 0x04,
+    ReqRcon = 0x05,
 
     RepAccept = 0x81,
     RepReject = 0x82,
     RepServerInfo = 0x83,
     RepPlayerInfo = 0x84,
     RepRuleInfo = 0x85,
+    RepRcon = 0x86,
 }
 
 #[allow(non_snake_case)]
@@ -173,4 +214,78 @@ pub struct QSocketT {
 
     pub addr: QSockAddr,
     pub address: [c_char; NET_NAMELEN],
+
+    /// when the unacknowledged fragment went out, 0 once it had to go again
+    pub fragmentTime: c_double,
+    pub stats: DatagramStatsT,
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::NetLanSocket;
+    use mathlib::RandT;
+    use std::cell::RefCell;
+    use std::collections::{HashMap, VecDeque};
+    use std::io;
+    use std::net::SocketAddr;
+    use std::os::raw::c_int;
+    use std::rc::Rc;
+    use Byte;
+
+    /// the datagrams waiting at every address of an in-memory network
+    pub type HubT = Rc<RefCell<HashMap<SocketAddr, VecDeque<(Vec<Byte>, SocketAddr)>>>>;
+
+    /// a lan socket on an in-memory network; it loses loss percent of what it writes, picked
+    /// by a seeded generator so runs repeat
+    pub struct MemorySocketT {
+        hub: HubT,
+        addr: SocketAddr,
+        loss: u32,
+        rand: RandT,
+    }
+
+    impl MemorySocketT {
+        pub fn new(hub: &HubT, addr: &str) -> Self {
+            return Self::lossy(hub, addr, 0, 0);
+        }
+
+        pub fn lossy(hub: &HubT, addr: &str, loss: u32, seed: u32) -> Self {
+            let addr: SocketAddr = addr.parse().unwrap();
+            hub.borrow_mut().entry(addr).or_default();
+            return Self {
+                hub: hub.clone(),
+                addr,
+                loss,
+                rand: RandT::new(seed),
+            };
+        }
+
+        pub fn addr(&self) -> SocketAddr {
+            return self.addr;
+        }
+    }
+
+    impl NetLanSocket for MemorySocketT {
+        fn read(&mut self, buf: &mut [Byte]) -> io::Result<Option<(usize, SocketAddr)>> {
+            let mut hub = self.hub.borrow_mut();
+            return Ok(hub
+                .get_mut(&self.addr)
+                .unwrap()
+                .pop_front()
+                .map(|(data, from)| {
+                    buf[..data.len()].copy_from_slice(&data);
+                    (data.len(), from)
+                }));
+        }
+
+        fn write(&mut self, data: &[Byte], addr: &SocketAddr) -> io::Result<usize> {
+            if (self.rand.rand() % 100) < self.loss as c_int {
+                return Ok(data.len());
+            }
+            if let Some(queue) = self.hub.borrow_mut().get_mut(addr) {
+                queue.push_back((data.to_vec(), self.addr));
+            }
+            return Ok(data.len());
+        }
+    }
 }
@@ -0,0 +1,1901 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// net_dgrm.rs -- the datagram protocol: reliable and unreliable messages over a lan socket
+
+use common::{MsgReaderT, MsgWriterT};
+use cvar::{cvars_after, CVarFlags};
+use net::{NetConnection, NetError, NetMessageT, NET_MAXMESSAGE};
+use net_defs::{
+    ClientConnectMessage, NetHeaderFlags, NetLanSocket, NET_DATAGRAMSIZE, NET_HEADERSIZE,
+    NET_PROTOCOL_VERSION,
+};
+use std::fmt;
+use std::net::SocketAddr;
+use std::os::raw::{c_double, c_int, c_uint};
+use {Byte, MAX_DATAGRAM, MAX_SCOREBOARD};
+
+/// seconds before an unacknowledged reliable fragment goes out again
+pub const RESEND_TIME: c_double = 1.0;
+/// seconds _Datagram_Connect waits for an answer before asking again
+pub const CONNECT_RETRY_TIME: c_double = 2.5;
+/// how many times _Datagram_Connect asks
+pub const CONNECT_TRIES: c_int = 3;
+/// a connect request from a connected address within this many seconds is a resend
+pub const DUPLICATE_CONNECT_TIME: c_double = 2.0;
+/// seconds a server query waits for answers, 20 polls of Test_Poll
+pub const QUERY_TIME: c_double = 2.0;
+/// a server or rule info query that gets no answer in this many seconds is sent again
+pub const QUERY_RESEND_TIME: c_double = 0.5;
+/// the game_name of every request
+pub const GAME_NAME: &[Byte] = b"QUAKE";
+
+/// seconds of traffic the byte rates are measured over
+pub const RATE_WINDOW: c_double = 1.0;
+
+/// Formerly: the statistic counters of net_dgrm.c and net_main.c, kept for each socket
+/// as datagramstats_t
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct DatagramStatsT {
+    pub messages_sent: c_int,
+    pub messages_received: c_int,
+    pub unreliable_messages_sent: c_int,
+    pub unreliable_messages_received: c_int,
+    pub packets_sent: c_int,
+    pub packets_resent: c_int,
+    pub packets_received: c_int,
+    pub received_duplicate_count: c_int,
+    pub short_packet_count: c_int,
+    pub dropped_datagrams: c_int,
+    /// every datagram, headers and acks included
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// bytes per second over the last RATE_WINDOW; 0 on the engine's sockets, which don't
+    /// measure it
+    pub send_rate: c_double,
+    pub receive_rate: c_double,
+    /// smoothed seconds from a reliable fragment to its ack, 0 until one comes back; fragments
+    /// that were resent aren't measured
+    pub rtt: c_double,
+}
+
+impl DatagramStatsT {
+    pub const fn default() -> Self {
+        Self {
+            messages_sent: 0,
+            messages_received: 0,
+            unreliable_messages_sent: 0,
+            unreliable_messages_received: 0,
+            packets_sent: 0,
+            packets_resent: 0,
+            packets_received: 0,
+            received_duplicate_count: 0,
+            short_packet_count: 0,
+            dropped_datagrams: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            send_rate: 0.0,
+            receive_rate: 0.0,
+            rtt: 0.0,
+        }
+    }
+
+    /// the counters of both, stopping at their maximum rather than wrapping
+    pub fn add(&mut self, other: &DatagramStatsT) {
+        self.messages_sent = self.messages_sent.saturating_add(other.messages_sent);
+        self.messages_received = self
+            .messages_received
+            .saturating_add(other.messages_received);
+        self.unreliable_messages_sent = self
+            .unreliable_messages_sent
+            .saturating_add(other.unreliable_messages_sent);
+        self.unreliable_messages_received = self
+            .unreliable_messages_received
+            .saturating_add(other.unreliable_messages_received);
+        self.packets_sent = self.packets_sent.saturating_add(other.packets_sent);
+        self.packets_resent = self.packets_resent.saturating_add(other.packets_resent);
+        self.packets_received = self.packets_received.saturating_add(other.packets_received);
+        self.received_duplicate_count = self
+            .received_duplicate_count
+            .saturating_add(other.received_duplicate_count);
+        self.short_packet_count = self
+            .short_packet_count
+            .saturating_add(other.short_packet_count);
+        self.dropped_datagrams = self
+            .dropped_datagrams
+            .saturating_add(other.dropped_datagrams);
+        self.bytes_sent = self.bytes_sent.saturating_add(other.bytes_sent);
+        self.bytes_received = self.bytes_received.saturating_add(other.bytes_received);
+        self.send_rate += other.send_rate;
+        self.receive_rate += other.receive_rate;
+    }
+
+    fn sample_rtt(&mut self, sample: c_double) {
+        if self.rtt == 0.0 {
+            self.rtt = sample;
+        } else {
+            self.rtt += (sample - self.rtt) * 0.125;
+        }
+    }
+}
+
+impl Default for DatagramStatsT {
+    fn default() -> Self {
+        Self::default()
+    }
+}
+
+/// Formerly: NET_Stats_f
+/// the counters as net_stats prints them; the rates only where they're measured
+impl fmt::Display for DatagramStatsT {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "unreliable messages sent   = {}",
+            self.unreliable_messages_sent
+        )?;
+        writeln!(
+            f,
+            "unreliable messages recv   = {}",
+            self.unreliable_messages_received
+        )?;
+        writeln!(f, "reliable messages sent     = {}", self.messages_sent)?;
+        writeln!(f, "reliable messages received = {}", self.messages_received)?;
+        writeln!(f, "packetsSent                = {}", self.packets_sent)?;
+        writeln!(f, "packetsReSent              = {}", self.packets_resent)?;
+        writeln!(f, "packetsReceived            = {}", self.packets_received)?;
+        writeln!(
+            f,
+            "receivedDuplicateCount     = {}",
+            self.received_duplicate_count
+        )?;
+        writeln!(
+            f,
+            "shortPacketCount           = {}",
+            self.short_packet_count
+        )?;
+        writeln!(f, "droppedDatagrams           = {}", self.dropped_datagrams)?;
+        writeln!(f, "bytes sent                 = {}", self.bytes_sent)?;
+        writeln!(f, "bytes received             = {}", self.bytes_received)?;
+        if self.send_rate != 0.0 || self.receive_rate != 0.0 {
+            writeln!(f, "bytes/sec sent             = {:.0}", self.send_rate)?;
+            writeln!(f, "bytes/sec received         = {:.0}", self.receive_rate)?;
+        }
+        writeln!(
+            f,
+            "rtt                        = {:.0} ms",
+            self.rtt * 1000.0
+        )?;
+        return Ok(());
+    }
+}
+
+/// counts bytes over RATE_WINDOW
+#[derive(Default)]
+struct RateMeterT {
+    window_start: c_double,
+    bytes: u64,
+}
+
+impl RateMeterT {
+    /// bytes went by
+    fn add(&mut self, bytes: usize) {
+        self.bytes += bytes as u64;
+    }
+
+    /// the rate of the window, when time ends it
+    fn update(&mut self, time: c_double) -> Option<c_double> {
+        let elapsed = time - self.window_start;
+        if elapsed < RATE_WINDOW {
+            return None;
+        }
+        let rate = self.bytes as c_double / elapsed;
+        self.window_start = time;
+        self.bytes = 0;
+        return Some(rate);
+    }
+}
+
+/// Formerly: packetBuffer
+/// a datagram: length and flags, sequence, both big endian, then the data
+fn packet(flags: NetHeaderFlags, sequence: c_uint, data: &[Byte]) -> Vec<Byte> {
+    let length = (NET_HEADERSIZE + data.len()) as c_uint;
+    let mut packet = Vec::with_capacity(length as usize);
+    packet.extend_from_slice(&(length | flags.bits()).to_be_bytes());
+    packet.extend_from_slice(&sequence.to_be_bytes());
+    packet.extend_from_slice(data);
+    return packet;
+}
+
+/// a qsocket_t of the datagram driver: one connection to addr over socket
+pub struct DatagramSocketT<S: NetLanSocket> {
+    socket: S,
+    /// the other end; packets from anywhere else are forged
+    addr: SocketAddr,
+    /// net_time, see set_net_time
+    net_time: c_double,
+    last_send_time: c_double,
+    /// when the unacknowledged fragment first went out, and whether it had to go again
+    fragment_time: c_double,
+    fragment_resent: bool,
+
+    disconnected: bool,
+    can_send: bool,
+    send_next: bool,
+
+    ack_sequence: c_uint,
+    send_sequence: c_uint,
+    unreliable_send_sequence: c_uint,
+    /// what is left of the reliable message, starting at the unacknowledged fragment
+    send_message: Vec<Byte>,
+
+    receive_sequence: c_uint,
+    unreliable_receive_sequence: c_uint,
+    /// the fragments of the reliable message being received
+    receive_message: Vec<Byte>,
+
+    pub stats: DatagramStatsT,
+    send_meter: RateMeterT,
+    receive_meter: RateMeterT,
+}
+
+impl<S: NetLanSocket> DatagramSocketT<S> {
+    pub fn new(socket: S, addr: SocketAddr) -> Self {
+        Self {
+            socket,
+            addr,
+            net_time: 0.0,
+            last_send_time: 0.0,
+            fragment_time: 0.0,
+            fragment_resent: false,
+            disconnected: false,
+            can_send: true,
+            send_next: false,
+            ack_sequence: 0,
+            send_sequence: 0,
+            unreliable_send_sequence: 0,
+            send_message: Vec::new(),
+            receive_sequence: 0,
+            unreliable_receive_sequence: 0,
+            receive_message: Vec::new(),
+            stats: DatagramStatsT::default(),
+            send_meter: RateMeterT::default(),
+            receive_meter: RateMeterT::default(),
+        }
+    }
+
+    /// Formerly: SetNetTime
+    /// the time resends are measured against
+    pub fn set_net_time(&mut self, time: c_double) {
+        self.net_time = time;
+    }
+
+    pub fn addr(&self) -> &SocketAddr {
+        return &self.addr;
+    }
+
+    pub fn socket(&self) -> &S {
+        return &self.socket;
+    }
+
+    pub fn socket_mut(&mut self) -> &mut S {
+        return &mut self.socket;
+    }
+
+    /// a datagram to the other end
+    fn write(&mut self, packet: &[Byte]) -> Result<(), NetError> {
+        self.socket.write(packet, &self.addr)?;
+        self.stats.bytes_sent += packet.len() as u64;
+        self.send_meter.add(packet.len());
+        return Ok(());
+    }
+
+    /// Formerly: SendMessageNext, ReSendMessage
+    /// send the first fragment of send_message as sequence
+    fn send_fragment(&mut self, sequence: c_uint) -> Result<(), NetError> {
+        let (data_len, eom) = if self.send_message.len() <= MAX_DATAGRAM {
+            (self.send_message.len(), NetHeaderFlags::Eom)
+        } else {
+            (MAX_DATAGRAM, NetHeaderFlags::empty())
+        };
+
+        let packet = packet(
+            NetHeaderFlags::Data | eom,
+            sequence,
+            &self.send_message[..data_len],
+        );
+
+        self.send_next = false;
+
+        self.write(&packet)?;
+
+        self.last_send_time = self.net_time;
+        return Ok(());
+    }
+
+    /// Formerly: SendMessageNext
+    fn send_message_next(&mut self) -> Result<(), NetError> {
+        let sequence = self.send_sequence;
+        self.send_sequence = self.send_sequence.wrapping_add(1);
+        self.send_fragment(sequence)?;
+        self.fragment_time = self.net_time;
+        self.fragment_resent = false;
+        self.stats.packets_sent += 1;
+        return Ok(());
+    }
+
+    /// Formerly: ReSendMessage
+    fn resend_message(&mut self) -> Result<(), NetError> {
+        let sequence = self.send_sequence.wrapping_sub(1);
+        self.send_fragment(sequence)?;
+        self.fragment_resent = true;
+        self.stats.packets_resent += 1;
+        return Ok(());
+    }
+
+    /// Formerly: Datagram_CanSendUnreliableMessage
+    pub fn can_send_unreliable_message(&self) -> bool {
+        return true;
+    }
+}
+
+impl<S: NetLanSocket> NetConnection for DatagramSocketT<S> {
+    /// Formerly: Datagram_GetMessage
+    fn get_message(&mut self) -> Result<Option<NetMessageT>, NetError> {
+        if self.disconnected {
+            return Err(NetError::Disconnected);
+        }
+
+        if !self.can_send && (self.net_time - self.last_send_time) > RESEND_TIME {
+            self.resend_message()?;
+        }
+
+        if let Some(rate) = self.send_meter.update(self.net_time) {
+            self.stats.send_rate = rate;
+        }
+        if let Some(rate) = self.receive_meter.update(self.net_time) {
+            self.stats.receive_rate = rate;
+        }
+
+        let mut ret = None;
+        let mut buf = [0; NET_DATAGRAMSIZE];
+        while let Some((read, readaddr)) = self.socket.read(&mut buf)? {
+            if readaddr != self.addr {
+                // forged packet
+                continue;
+            }
+            self.stats.bytes_received += read as u64;
+            self.receive_meter.add(read);
+
+            if read < NET_HEADERSIZE {
+                self.stats.short_packet_count += 1;
+                continue;
+            }
+
+            let header = c_uint::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+            let flags =
+                NetHeaderFlags::from_bits_truncate(header & !NetHeaderFlags::LengthMask.bits());
+            let length = (header & NetHeaderFlags::LengthMask.bits()) as usize;
+            if length < NET_HEADERSIZE || length > read {
+                self.stats.short_packet_count += 1;
+                continue;
+            }
+
+            if flags.contains(NetHeaderFlags::Ctl) {
+                continue;
+            }
+
+            let sequence = c_uint::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]);
+            let data = &buf[NET_HEADERSIZE..length];
+            self.stats.packets_received += 1;
+
+            if flags.contains(NetHeaderFlags::Unreliable) {
+                if sequence < self.unreliable_receive_sequence {
+                    // got a stale datagram
+                    break;
+                }
+                if sequence != self.unreliable_receive_sequence {
+                    // a peer can jump the sequence by anything
+                    let count =
+                        (sequence - self.unreliable_receive_sequence).min(c_int::MAX as c_uint);
+                    self.stats.dropped_datagrams =
+                        self.stats.dropped_datagrams.saturating_add(count as c_int);
+                }
+                self.unreliable_receive_sequence = sequence.wrapping_add(1);
+
+                self.stats.unreliable_messages_received += 1;
+                ret = Some(NetMessageT::Unreliable(data.to_vec()));
+                break;
+            }
+
+            if flags.contains(NetHeaderFlags::Ack) {
+                if sequence != self.send_sequence.wrapping_sub(1) {
+                    // stale ACK received
+                    continue;
+                }
+                if sequence == self.ack_sequence {
+                    self.ack_sequence = self.ack_sequence.wrapping_add(1);
+                } else {
+                    // duplicate ACK received
+                    continue;
+                }
+                if !self.fragment_resent {
+                    self.stats.sample_rtt(self.net_time - self.fragment_time);
+                }
+                if self.send_message.len() > MAX_DATAGRAM {
+                    self.send_message.drain(..MAX_DATAGRAM);
+                    self.send_next = true;
+                } else {
+                    self.send_message.clear();
+                    self.can_send = true;
+                }
+                continue;
+            }
+
+            if flags.contains(NetHeaderFlags::Data) {
+                let ack = packet(NetHeaderFlags::Ack, sequence, &[]);
+                self.write(&ack)?;
+
+                if sequence != self.receive_sequence {
+                    self.stats.received_duplicate_count += 1;
+                    continue;
+                }
+                self.receive_sequence = self.receive_sequence.wrapping_add(1);
+
+                if self.receive_message.len() + data.len() > NET_MAXMESSAGE {
+                    self.receive_message.clear();
+                    return Err(NetError::Overflow);
+                }
+                self.receive_message.extend_from_slice(data);
+
+                if flags.contains(NetHeaderFlags::Eom) {
+                    let message = std::mem::take(&mut self.receive_message);
+                    self.stats.messages_received += 1;
+                    ret = Some(NetMessageT::Reliable(message));
+                    break;
+                }
+                continue;
+            }
+        }
+
+        if self.send_next {
+            self.send_message_next()?;
+        }
+
+        return Ok(ret);
+    }
+
+    /// Formerly: Datagram_SendMessage
+    fn send_message(&mut self, data: &[Byte]) -> Result<(), NetError> {
+        if self.disconnected {
+            return Err(NetError::Disconnected);
+        }
+        if data.len() > NET_MAXMESSAGE {
+            return Err(NetError::Overflow);
+        }
+        if !self.can_send {
+            return Err(NetError::CantSend);
+        }
+
+        self.send_message = data.to_vec();
+        self.can_send = false;
+        self.stats.messages_sent += 1;
+        return self.send_message_next();
+    }
+
+    /// Formerly: Datagram_SendUnreliableMessage
+    fn send_unreliable_message(&mut self, data: &[Byte]) -> Result<(), NetError> {
+        if self.disconnected {
+            return Err(NetError::Disconnected);
+        }
+        if data.len() > MAX_DATAGRAM {
+            return Err(NetError::Overflow);
+        }
+
+        let sequence = self.unreliable_send_sequence;
+        self.unreliable_send_sequence = self.unreliable_send_sequence.wrapping_add(1);
+        let packet = packet(NetHeaderFlags::Unreliable, sequence, data);
+        self.write(&packet)?;
+
+        self.stats.packets_sent += 1;
+        self.stats.unreliable_messages_sent += 1;
+        return Ok(());
+    }
+
+    /// Formerly: Datagram_CanSendMessage
+    fn can_send_message(&mut self) -> bool {
+        if self.send_next && self.send_message_next().is_err() {
+            return false;
+        }
+
+        return self.can_send;
+    }
+
+    /// Formerly: Datagram_Close
+    fn close(&mut self) {
+        self.disconnected = true;
+    }
+
+    fn address(&self) -> String {
+        return self.addr.to_string();
+    }
+
+    fn stats(&self) -> Option<DatagramStatsT> {
+        return Some(self.stats);
+    }
+}
+
+/// why a packet isn't a connection control message
+#[derive(Debug, PartialEq)]
+pub enum CtlError {
+    /// the header is -1, lacks the Ctl flag or has other flags
+    NotCtl,
+    /// the header length isn't the packet's
+    BadLength,
+    /// not a ClientConnectMessage
+    BadCommand(c_int),
+    /// the message ended early
+    Truncated,
+}
+
+/// the answer to CCREQ_SERVER_INFO
+#[derive(Clone, Debug, PartialEq)]
+pub struct CtlServerInfoT {
+    /// the long address form, "address:port"
+    pub address: Vec<Byte>,
+    /// hostname.string
+    pub host_name: Vec<Byte>,
+    /// sv.name
+    pub level_name: Vec<Byte>,
+    /// net_activeconnections
+    pub current_players: c_int,
+    /// svs.maxclients
+    pub max_players: c_int,
+    pub protocol_version: c_int,
+}
+
+/// the answer to CCREQ_PLAYER_INFO
+#[derive(Clone, Debug, PartialEq)]
+pub struct CtlPlayerInfoT {
+    /// counts active clients only, not slots
+    pub player_number: c_int,
+    pub name: Vec<Byte>,
+    pub colors: c_int,
+    pub frags: c_int,
+    /// seconds since the client connected
+    pub connect_time: c_int,
+    pub address: Vec<Byte>,
+}
+
+/// a message of the connection control protocol, see the comment in net_defs.rs
+#[derive(Clone, Debug, PartialEq)]
+pub enum CtlMessageT {
+    /// CCREQ_CONNECT
+    ReqConnect {
+        game_name: Vec<Byte>,
+        net_protocol_version: c_int,
+    },
+    /// CCREQ_SERVER_INFO
+    ReqServerInfo {
+        game_name: Vec<Byte>,
+        net_protocol_version: c_int,
+    },
+    /// CCREQ_PLAYER_INFO
+    ReqPlayerInfo { player_number: c_int },
+    /// CCREQ_RULE_INFO, the rule before the one wanted, empty for the first
+    ReqRuleInfo { rule: Vec<Byte> },
+    /// CCREP_ACCEPT, the short address form
+    RepAccept { port: c_int },
+    /// CCREP_REJECT
+    RepReject { reason: Vec<Byte> },
+    /// CCREP_SERVER_INFO
+    RepServerInfo(CtlServerInfoT),
+    /// CCREP_PLAYER_INFO
+    RepPlayerInfo(CtlPlayerInfoT),
+    /// CCREP_RULE_INFO, an empty rule when there are no more
+    RepRuleInfo { rule: Vec<Byte>, value: Vec<Byte> },
+    /// CCREQ_RCON, with challenge 0 to ask for a challenge
+    ReqRcon {
+        challenge: c_int,
+        digest: Vec<Byte>,
+        command: Vec<Byte>,
+    },
+    /// CCREP_RCON, a challenge, or with challenge 0 the output
+    RepRcon { challenge: c_int, output: Vec<Byte> },
+}
+
+impl CtlMessageT {
+    /// a CCREQ_CONNECT for this version of the game
+    pub fn connect() -> Self {
+        return CtlMessageT::ReqConnect {
+            game_name: GAME_NAME.to_vec(),
+            net_protocol_version: NET_PROTOCOL_VERSION as c_int,
+        };
+    }
+
+    /// a CCREQ_SERVER_INFO for this version of the game
+    pub fn server_info() -> Self {
+        return CtlMessageT::ReqServerInfo {
+            game_name: GAME_NAME.to_vec(),
+            net_protocol_version: NET_PROTOCOL_VERSION as c_int,
+        };
+    }
+
+    /// the packet, behind a Ctl header
+    pub fn encode(&self) -> Vec<Byte> {
+        let mut msg = MsgWriterT::new();
+        // save space for the header, filled in later
+        msg.write_long(0);
+        match *self {
+            CtlMessageT::ReqConnect {
+                ref game_name,
+                net_protocol_version,
+            } => {
+                msg.write_byte(ClientConnectMessage::ReqConnect as c_int);
+                msg.write_string(game_name);
+                msg.write_byte(net_protocol_version);
+            }
+            CtlMessageT::ReqServerInfo {
+                ref game_name,
+                net_protocol_version,
+            } => {
+                msg.write_byte(ClientConnectMessage::ReqServerInfo as c_int);
+                msg.write_string(game_name);
+                msg.write_byte(net_protocol_version);
+            }
+            CtlMessageT::ReqPlayerInfo { player_number } => {
+                msg.write_byte(ClientConnectMessage::ReqPlayerInfo as c_int);
+                msg.write_byte(player_number);
+            }
+            CtlMessageT::ReqRuleInfo { ref rule } => {
+                msg.write_byte(ClientConnectMessage::ReqRuleInfo as c_int);
+                msg.write_string(rule);
+            }
+            CtlMessageT::RepAccept { port } => {
+                msg.write_byte(ClientConnectMessage::RepAccept as c_int);
+                msg.write_long(port);
+            }
+            CtlMessageT::RepReject { ref reason } => {
+                msg.write_byte(ClientConnectMessage::RepReject as c_int);
+                msg.write_string(reason);
+            }
+            CtlMessageT::RepServerInfo(ref info) => {
+                msg.write_byte(ClientConnectMessage::RepServerInfo as c_int);
+                msg.write_string(&info.address);
+                msg.write_string(&info.host_name);
+                msg.write_string(&info.level_name);
+                msg.write_byte(info.current_players);
+                msg.write_byte(info.max_players);
+                msg.write_byte(info.protocol_version);
+            }
+            CtlMessageT::RepPlayerInfo(ref info) => {
+                msg.write_byte(ClientConnectMessage::RepPlayerInfo as c_int);
+                msg.write_byte(info.player_number);
+                msg.write_string(&info.name);
+                msg.write_long(info.colors);
+                msg.write_long(info.frags);
+                msg.write_long(info.connect_time);
+                msg.write_string(&info.address);
+            }
+            CtlMessageT::RepRuleInfo {
+                ref rule,
+                ref value,
+            } => {
+                msg.write_byte(ClientConnectMessage::RepRuleInfo as c_int);
+                // the last answer is just the command
+                if !rule.is_empty() {
+                    msg.write_string(rule);
+                    msg.write_string(value);
+                }
+            }
+            CtlMessageT::ReqRcon {
+                challenge,
+                ref digest,
+                ref command,
+            } => {
+                msg.write_byte(ClientConnectMessage::ReqRcon as c_int);
+                msg.write_long(challenge);
+                msg.write_string(digest);
+                msg.write_string(command);
+            }
+            CtlMessageT::RepRcon {
+                challenge,
+                ref output,
+            } => {
+                msg.write_byte(ClientConnectMessage::RepRcon as c_int);
+                msg.write_long(challenge);
+                msg.write_string(output);
+            }
+        }
+        let control = NetHeaderFlags::Ctl.bits()
+            | (msg.data.len() as c_uint & NetHeaderFlags::LengthMask.bits());
+        msg.data[..4].copy_from_slice(&control.to_be_bytes());
+        return msg.data;
+    }
+
+    /// the message in a packet, if it's a well formed control message
+    pub fn decode(packet: &[Byte]) -> Result<Self, CtlError> {
+        if packet.len() < 4 {
+            return Err(CtlError::Truncated);
+        }
+        let control = c_uint::from_be_bytes([packet[0], packet[1], packet[2], packet[3]]);
+        if control == !0
+            || control & !NetHeaderFlags::LengthMask.bits() != NetHeaderFlags::Ctl.bits()
+        {
+            return Err(CtlError::NotCtl);
+        }
+        if (control & NetHeaderFlags::LengthMask.bits()) as usize != packet.len() {
+            return Err(CtlError::BadLength);
+        }
+
+        let mut msg = MsgReaderT::new(&packet[4..]);
+        let command = msg.read_byte();
+        let message = match command {
+            0x01 => CtlMessageT::ReqConnect {
+                game_name: msg.read_string(),
+                net_protocol_version: msg.read_byte(),
+            },
+            0x02 => CtlMessageT::ReqServerInfo {
+                game_name: msg.read_string(),
+                net_protocol_version: msg.read_byte(),
+            },
+            0x03 => CtlMessageT::ReqPlayerInfo {
+                player_number: msg.read_byte(),
+            },
+            0x04 => CtlMessageT::ReqRuleInfo {
+                rule: msg.read_string(),
+            },
+            0x05 => CtlMessageT::ReqRcon {
+                challenge: msg.read_long(),
+                digest: msg.read_string(),
+                command: msg.read_string(),
+            },
+            0x81 => CtlMessageT::RepAccept {
+                port: msg.read_long(),
+            },
+            0x82 => CtlMessageT::RepReject {
+                reason: msg.read_string(),
+            },
+            0x83 => CtlMessageT::RepServerInfo(CtlServerInfoT {
+                address: msg.read_string(),
+                host_name: msg.read_string(),
+                level_name: msg.read_string(),
+                current_players: msg.read_byte(),
+                max_players: msg.read_byte(),
+                protocol_version: msg.read_byte(),
+            }),
+            0x84 => CtlMessageT::RepPlayerInfo(CtlPlayerInfoT {
+                player_number: msg.read_byte(),
+                name: msg.read_string(),
+                colors: msg.read_long(),
+                frags: msg.read_long(),
+                connect_time: msg.read_long(),
+                address: msg.read_string(),
+            }),
+            0x85 => {
+                if msg.at_end() {
+                    CtlMessageT::RepRuleInfo {
+                        rule: Vec::new(),
+                        value: Vec::new(),
+                    }
+                } else {
+                    CtlMessageT::RepRuleInfo {
+                        rule: msg.read_string(),
+                        value: msg.read_string(),
+                    }
+                }
+            }
+            0x86 => CtlMessageT::RepRcon {
+                challenge: msg.read_long(),
+                output: msg.read_string(),
+            },
+            -1 => return Err(CtlError::Truncated),
+            _ => return Err(CtlError::BadCommand(command)),
+        };
+        if msg.badread {
+            return Err(CtlError::Truncated);
+        }
+        return Ok(message);
+    }
+}
+
+/// what a listening server answers the connection control protocol with
+pub trait CtlServerHandler {
+    /// hostname.string
+    fn host_name(&self) -> Vec<Byte>;
+    /// sv.name
+    fn level_name(&self) -> Vec<Byte>;
+    /// net_activeconnections
+    fn current_players(&self) -> c_int;
+    /// svs.maxclients
+    fn max_players(&self) -> c_int;
+    /// the player_number-th active client, not counting empty slots
+    fn player_info(&self, player_number: c_int) -> Option<CtlPlayerInfoT>;
+    /// Formerly: Cvar_FindVarAfter
+    /// the name and value of the serverinfo cvar after prev, the first one for an empty prev
+    fn rule_after(&self, prev: &[Byte]) -> Option<(Vec<Byte>, Vec<Byte>)>;
+    /// Formerly: NET_NewQSocket, dfunc.Open_Socket, dfunc.Connect
+    /// open a connection to addr; the port it's on, or why the client is rejected
+    fn accept(&mut self, addr: &SocketAddr) -> Result<c_int, Vec<Byte>>;
+    /// Formerly: NET_Close
+    /// addr is coming back in from a crash or disconnect, drop its old connection
+    fn drop_connection(&mut self, addr: &SocketAddr);
+    /// the answer to a CCREQ_RCON from addr, or None to say nothing
+    fn rcon(
+        &mut self,
+        addr: &SocketAddr,
+        challenge: c_int,
+        digest: &[Byte],
+        command: &[Byte],
+        net_time: c_double,
+    ) -> Option<CtlMessageT>;
+}
+
+/// a server with nothing to tell and no room
+impl CtlServerHandler for () {
+    fn host_name(&self) -> Vec<Byte> {
+        return Vec::new();
+    }
+    fn level_name(&self) -> Vec<Byte> {
+        return Vec::new();
+    }
+    fn current_players(&self) -> c_int {
+        return 0;
+    }
+    fn max_players(&self) -> c_int {
+        return 0;
+    }
+    fn player_info(&self, _player_number: c_int) -> Option<CtlPlayerInfoT> {
+        return None;
+    }
+    fn rule_after(&self, _prev: &[Byte]) -> Option<(Vec<Byte>, Vec<Byte>)> {
+        return None;
+    }
+    fn accept(&mut self, _addr: &SocketAddr) -> Result<c_int, Vec<Byte>> {
+        return Err(b"Server is full.\n".to_vec());
+    }
+    fn drop_connection(&mut self, _addr: &SocketAddr) {}
+    fn rcon(
+        &mut self,
+        _addr: &SocketAddr,
+        _challenge: c_int,
+        _digest: &[Byte],
+        _command: &[Byte],
+        _net_time: c_double,
+    ) -> Option<CtlMessageT> {
+        return None;
+    }
+}
+
+/// a connection the server handed out
+struct CtlConnectionT {
+    addr: SocketAddr,
+    connecttime: c_double,
+    port: c_int,
+}
+
+/// the accepting side of the connection control protocol, on the listening socket
+pub struct CtlServerT<S: NetLanSocket> {
+    socket: S,
+    /// where the socket listens, for CCREP_SERVER_INFO
+    address: SocketAddr,
+    /// net_time, see set_net_time
+    net_time: c_double,
+    connections: Vec<CtlConnectionT>,
+}
+
+impl<S: NetLanSocket> CtlServerT<S> {
+    pub fn new(socket: S, address: SocketAddr) -> Self {
+        Self {
+            socket,
+            address,
+            net_time: 0.0,
+            connections: Vec::new(),
+        }
+    }
+
+    /// Formerly: SetNetTime
+    pub fn set_net_time(&mut self, time: c_double) {
+        self.net_time = time;
+    }
+
+    pub fn socket_mut(&mut self) -> &mut S {
+        return &mut self.socket;
+    }
+
+    /// forget a connection the server closed, so its address can connect anew
+    pub fn disconnect(&mut self, addr: &SocketAddr) {
+        self.connections.retain(|c| c.addr != *addr);
+    }
+
+    /// a reply that can't be sent is only logged, the other requests still get answered
+    fn reply(&mut self, message: &CtlMessageT, addr: &SocketAddr) {
+        if let Err(e) = self.socket.write(&message.encode(), addr) {
+            eprintln!("CheckNewConnections: couldn't reply to {}: {:?}", addr, e);
+        }
+    }
+
+    /// Formerly: _Datagram_CheckNewConnections
+    /// answer every control request waiting; the address of a client that was accepted, if any.
+    /// Call again until it returns None
+    pub fn check_new_connections<H: CtlServerHandler>(
+        &mut self,
+        handler: &mut H,
+    ) -> Result<Option<SocketAddr>, NetError> {
+        let mut buf = [0; NET_DATAGRAMSIZE];
+        while let Some((len, clientaddr)) = self.socket.read(&mut buf)? {
+            let request = match CtlMessageT::decode(&buf[..len]) {
+                Ok(request) => request,
+                Err(_) => continue,
+            };
+            match request {
+                CtlMessageT::ReqServerInfo { ref game_name, .. } => {
+                    if game_name[..] != *GAME_NAME {
+                        continue;
+                    }
+                    let info = CtlServerInfoT {
+                        address: self.address.to_string().into_bytes(),
+                        host_name: handler.host_name(),
+                        level_name: handler.level_name(),
+                        current_players: handler.current_players(),
+                        max_players: handler.max_players(),
+                        protocol_version: NET_PROTOCOL_VERSION as c_int,
+                    };
+                    self.reply(&CtlMessageT::RepServerInfo(info), &clientaddr);
+                }
+                CtlMessageT::ReqPlayerInfo { player_number } => {
+                    if let Some(info) = handler.player_info(player_number) {
+                        self.reply(&CtlMessageT::RepPlayerInfo(info), &clientaddr);
+                    }
+                }
+                CtlMessageT::ReqRuleInfo { ref rule } => {
+                    let (rule, value) = handler.rule_after(rule).unwrap_or_default();
+                    self.reply(&CtlMessageT::RepRuleInfo { rule, value }, &clientaddr);
+                }
+                CtlMessageT::ReqRcon {
+                    challenge,
+                    ref digest,
+                    ref command,
+                } => {
+                    let net_time = self.net_time;
+                    if let Some(reply) =
+                        handler.rcon(&clientaddr, challenge, digest, command, net_time)
+                    {
+                        self.reply(&reply, &clientaddr);
+                    }
+                }
+                CtlMessageT::ReqConnect {
+                    ref game_name,
+                    net_protocol_version,
+                } => {
+                    if game_name[..] != *GAME_NAME {
+                        continue;
+                    }
+                    if net_protocol_version != NET_PROTOCOL_VERSION as c_int {
+                        let reason = b"Incompatible version.\n".to_vec();
+                        self.reply(&CtlMessageT::RepReject { reason }, &clientaddr);
+                        continue;
+                    }
+                    if let Some(accepted) = self.check_connect(handler, &clientaddr)? {
+                        return Ok(Some(accepted));
+                    }
+                }
+                // replies aren't for the server
+                _ => {}
+            }
+        }
+        return Ok(None);
+    }
+
+    /// a CCREQ_CONNECT from clientaddr; the address if it got a new connection
+    fn check_connect<H: CtlServerHandler>(
+        &mut self,
+        handler: &mut H,
+        clientaddr: &SocketAddr,
+    ) -> Result<Option<SocketAddr>, NetError> {
+        // see if this guy is already connected
+        let known = self
+            .connections
+            .iter()
+            .position(|c| c.addr.ip() == clientaddr.ip());
+        if let Some(i) = known {
+            let (addr, connecttime, port) = {
+                let c = &self.connections[i];
+                (c.addr, c.connecttime, c.port)
+            };
+            // is this a duplicate connection request?
+            if addr == *clientaddr && self.net_time - connecttime < DUPLICATE_CONNECT_TIME {
+                // yes, so send a duplicate reply
+                self.reply(&CtlMessageT::RepAccept { port }, clientaddr);
+                return Ok(None);
+            }
+            // it's somebody coming back in from a crash/disconnect
+            // so close the old connection and let their retry get them back in
+            self.connections.remove(i);
+            handler.drop_connection(&addr);
+            return Ok(None);
+        }
+
+        match handler.accept(clientaddr) {
+            Ok(port) => {
+                self.connections.push(CtlConnectionT {
+                    addr: *clientaddr,
+                    connecttime: self.net_time,
+                    port,
+                });
+                // send him back the info about the server connection he has been allocated
+                self.reply(&CtlMessageT::RepAccept { port }, clientaddr);
+                return Ok(Some(*clientaddr));
+            }
+            Err(reason) => {
+                // no room; try to let him know
+                self.reply(&CtlMessageT::RepReject { reason }, clientaddr);
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// where a connection attempt stands
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConnectStateT {
+    /// waiting for an answer
+    Trying,
+    /// the server gave us a connection at this address
+    Accepted(SocketAddr),
+    /// CCREP_REJECT and its reason
+    Rejected(Vec<Byte>),
+    /// m_return_reason: "No Response", "Network Error" or "Bad Response"
+    Failed(&'static str),
+}
+
+/// Formerly: _Datagram_Connect
+/// the connecting side of the connection control protocol; poll it until it isn't Trying,
+/// then talk to the accepted address with a DatagramSocketT over the same socket
+pub struct CtlClientT {
+    /// the server's listening address
+    addr: SocketAddr,
+    state: ConnectStateT,
+    reps: c_int,
+    start_time: c_double,
+}
+
+impl CtlClientT {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            state: ConnectStateT::Trying,
+            reps: 0,
+            start_time: 0.0,
+        }
+    }
+
+    pub fn state(&self) -> &ConnectStateT {
+        return &self.state;
+    }
+
+    /// true when the attempt is over, one way or the other
+    pub fn done(&self) -> bool {
+        return self.state != ConnectStateT::Trying;
+    }
+
+    fn send_request<S: NetLanSocket>(&mut self, socket: &mut S, net_time: c_double) {
+        self.reps += 1;
+        self.start_time = net_time;
+        if socket
+            .write(&CtlMessageT::connect().encode(), &self.addr)
+            .is_err()
+        {
+            self.state = ConnectStateT::Failed("Network Error");
+        }
+    }
+
+    /// send the request, or again when the last went unanswered, and read the answer
+    pub fn poll<S: NetLanSocket>(&mut self, socket: &mut S, net_time: c_double) -> &ConnectStateT {
+        if self.done() {
+            return &self.state;
+        }
+        if self.reps == 0 {
+            self.send_request(socket, net_time);
+        }
+
+        let mut buf = [0; NET_DATAGRAMSIZE];
+        while !self.done() {
+            let (len, readaddr) = match socket.read(&mut buf) {
+                Ok(Some(read)) => read,
+                Ok(None) => break,
+                Err(_) => {
+                    self.state = ConnectStateT::Failed("Network Error");
+                    break;
+                }
+            };
+            // is it from the right place?
+            if readaddr != self.addr {
+                continue;
+            }
+            let reply = match CtlMessageT::decode(&buf[..len]) {
+                Ok(reply) => reply,
+                Err(CtlError::NotCtl) | Err(CtlError::BadLength) => continue,
+                Err(_) => {
+                    self.state = ConnectStateT::Failed("Bad Response");
+                    break;
+                }
+            };
+            self.state = match reply {
+                CtlMessageT::RepReject { reason } => ConnectStateT::Rejected(reason),
+                CtlMessageT::RepAccept { port } if port > 0 && port < 65536 => {
+                    let mut addr = self.addr;
+                    addr.set_port(port as u16);
+                    ConnectStateT::Accepted(addr)
+                }
+                _ => ConnectStateT::Failed("Bad Response"),
+            };
+        }
+
+        if !self.done() && net_time - self.start_time >= CONNECT_RETRY_TIME {
+            if self.reps < CONNECT_TRIES {
+                // still trying...
+                self.send_request(socket, net_time);
+            } else {
+                self.state = ConnectStateT::Failed("No Response");
+            }
+        }
+        return &self.state;
+    }
+}
+
+/// Formerly: Test_f, Test2_f
+/// asks a server about itself, its players and its rules
+pub struct ServerQueryT {
+    addr: SocketAddr,
+    start_time: c_double,
+    /// CCREP_SERVER_INFO, once it's in
+    pub info: Option<CtlServerInfoT>,
+    /// the players that answered, by player number
+    pub players: Vec<CtlPlayerInfoT>,
+    /// the serverinfo cvars, in the order the server walks them
+    pub rules: Vec<(Vec<Byte>, Vec<Byte>)>,
+    rules_done: bool,
+    /// when CCREQ_SERVER_INFO was last sent
+    info_time: c_double,
+    /// the rule asked for after, and when
+    rule_request: Vec<Byte>,
+    rule_time: c_double,
+}
+
+impl ServerQueryT {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            start_time: 0.0,
+            info: None,
+            players: Vec::new(),
+            rules: Vec::new(),
+            rules_done: false,
+            info_time: 0.0,
+            rule_request: Vec::new(),
+            rule_time: 0.0,
+        }
+    }
+
+    /// send the first requests; players are asked for once the server says how many it has
+    pub fn start<S: NetLanSocket>(
+        &mut self,
+        socket: &mut S,
+        net_time: c_double,
+    ) -> Result<(), NetError> {
+        self.start_time = net_time;
+        self.info_time = net_time;
+        socket.write(&CtlMessageT::server_info().encode(), &self.addr)?;
+        self.rule_request.clear();
+        return self.request_rule(socket, net_time);
+    }
+
+    /// ask for the rule after rule_request
+    fn request_rule<S: NetLanSocket>(
+        &mut self,
+        socket: &mut S,
+        net_time: c_double,
+    ) -> Result<(), NetError> {
+        self.rule_time = net_time;
+        let request = CtlMessageT::ReqRuleInfo {
+            rule: self.rule_request.clone(),
+        };
+        socket.write(&request.encode(), &self.addr)?;
+        return Ok(());
+    }
+
+    /// true when every answer is in, or the time is up
+    pub fn done(&self, net_time: c_double) -> bool {
+        if net_time - self.start_time >= QUERY_TIME {
+            return true;
+        }
+        return match self.info {
+            Some(ref info) => {
+                self.rules_done && self.players.len() >= info.current_players as usize
+            }
+            None => false,
+        };
+    }
+
+    /// Formerly: Test_Poll, Test2_Poll
+    /// read the answers waiting, asking for more where they lead; true when done.  Server
+    /// and rule info that doesn't come is asked for again, so one dropped packet doesn't cut
+    /// the answers short
+    pub fn poll<S: NetLanSocket>(
+        &mut self,
+        socket: &mut S,
+        net_time: c_double,
+    ) -> Result<bool, NetError> {
+        let mut buf = [0; NET_DATAGRAMSIZE];
+        while let Some((len, readaddr)) = socket.read(&mut buf)? {
+            if readaddr != self.addr {
+                continue;
+            }
+            match CtlMessageT::decode(&buf[..len]) {
+                Ok(CtlMessageT::RepServerInfo(info)) => {
+                    if self.info.is_none() {
+                        let maxusers = info.max_players.max(0).min(MAX_SCOREBOARD as c_int);
+                        for player_number in 0..maxusers {
+                            let request = CtlMessageT::ReqPlayerInfo { player_number };
+                            socket.write(&request.encode(), &self.addr)?;
+                        }
+                    }
+                    self.info = Some(info);
+                }
+                Ok(CtlMessageT::RepPlayerInfo(info)) => {
+                    let number = info.player_number;
+                    if !self.players.iter().any(|p| p.player_number == number) {
+                        let at = self.players.iter().position(|p| p.player_number > number);
+                        self.players.insert(at.unwrap_or(self.players.len()), info);
+                    }
+                }
+                Ok(CtlMessageT::RepRuleInfo { rule, value }) => {
+                    if self.rules_done {
+                        continue;
+                    }
+                    if rule.is_empty() {
+                        self.rules_done = true;
+                        continue;
+                    }
+                    // the answer to a request that was sent twice
+                    if self.rules.iter().any(|r| r.0 == rule) {
+                        continue;
+                    }
+                    self.rule_request = rule.clone();
+                    self.request_rule(socket, net_time)?;
+                    self.rules.push((rule, value));
+                }
+                // unexpected response
+                _ => {}
+            }
+        }
+        if self.info.is_none() && net_time - self.info_time >= QUERY_RESEND_TIME {
+            self.info_time = net_time;
+            socket.write(&CtlMessageT::server_info().encode(), &self.addr)?;
+        }
+        if !self.rules_done && net_time - self.rule_time >= QUERY_RESEND_TIME {
+            self.request_rule(socket, net_time)?;
+        }
+        return Ok(self.done(net_time));
+    }
+}
+
+/// the answers the way the test and test2 commands print them
+impl fmt::Display for ServerQueryT {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref info) = self.info {
+            writeln!(
+                f,
+                "{}\n  {}  {}/{}  protocol {}\n  {}",
+                String::from_utf8_lossy(&info.host_name),
+                String::from_utf8_lossy(&info.level_name),
+                info.current_players,
+                info.max_players,
+                info.protocol_version,
+                String::from_utf8_lossy(&info.address)
+            )?;
+        }
+        for player in &self.players {
+            writeln!(
+                f,
+                "{}\n  frags:{:3}  colors:{} {}  time:{}\n  {}",
+                String::from_utf8_lossy(&player.name),
+                player.frags,
+                player.colors >> 4,
+                player.colors & 0x0f,
+                player.connect_time / 60,
+                String::from_utf8_lossy(&player.address)
+            )?;
+        }
+        for (name, value) in &self.rules {
+            writeln!(
+                f,
+                "{:<16.16}  {:<16.16}",
+                String::from_utf8_lossy(name),
+                String::from_utf8_lossy(value)
+            )?;
+        }
+        return Ok(());
+    }
+}
+
+/// answers rule queries with the ServerInfo cvars, and leaves everything else to handler
+pub struct CVarRulesT<H: CtlServerHandler> {
+    pub handler: H,
+}
+
+impl<H: CtlServerHandler> CVarRulesT<H> {
+    /// # Safety
+    /// the cvars are read as the queries come in; only use this from the main thread
+    pub unsafe fn new(handler: H) -> Self {
+        Self { handler }
+    }
+}
+
+impl<H: CtlServerHandler> CtlServerHandler for CVarRulesT<H> {
+    fn host_name(&self) -> Vec<Byte> {
+        return self.handler.host_name();
+    }
+    fn level_name(&self) -> Vec<Byte> {
+        return self.handler.level_name();
+    }
+    fn current_players(&self) -> c_int {
+        return self.handler.current_players();
+    }
+    fn max_players(&self) -> c_int {
+        return self.handler.max_players();
+    }
+    fn player_info(&self, player_number: c_int) -> Option<CtlPlayerInfoT> {
+        return self.handler.player_info(player_number);
+    }
+    /// Formerly: the CCREQ_RULE_INFO case of _Datagram_CheckNewConnections
+    fn rule_after(&self, prev: &[Byte]) -> Option<(Vec<Byte>, Vec<Byte>)> {
+        return unsafe { cvars_after(prev, CVarFlags::ServerInfo).next() };
+    }
+    fn accept(&mut self, addr: &SocketAddr) -> Result<c_int, Vec<Byte>> {
+        return self.handler.accept(addr);
+    }
+    fn drop_connection(&mut self, addr: &SocketAddr) {
+        self.handler.drop_connection(addr);
+    }
+    fn rcon(
+        &mut self,
+        addr: &SocketAddr,
+        challenge: c_int,
+        digest: &[Byte],
+        command: &[Byte],
+        net_time: c_double,
+    ) -> Option<CtlMessageT> {
+        return self
+            .handler
+            .rcon(addr, challenge, digest, command, net_time);
+    }
+}
+
+#[allow(bad_style)]
+pub mod capi {
+    use super::{CVarRulesT, CtlMessageT, CtlServerHandler};
+    use std::ffi::CStr;
+    use std::os::raw::{c_char, c_int};
+    use std::ptr::copy_nonoverlapping;
+    use SizeBufT;
+
+    /// Formerly: the CCREQ_RULE_INFO case of _Datagram_CheckNewConnections
+    /// the CCREP_RULE_INFO packet for the ServerInfo cvar after prev, as CVarRulesT answers
+    /// it, in reply; an empty reply if it doesn't fit
+    ///
+    /// # Safety
+    ///
+    /// prev must be a string, and reply a sizebuf with maxsize bytes of data
+    #[no_mangle]
+    pub unsafe extern "C" fn Datagram_RuleInfo(prev: *const c_char, reply: *mut SizeBufT) {
+        let rules = CVarRulesT::new(());
+        let (rule, value) = rules
+            .rule_after(CStr::from_ptr(prev).to_bytes())
+            .unwrap_or_default();
+        let packet = CtlMessageT::RepRuleInfo { rule, value }.encode();
+        let reply = &mut *reply;
+        reply.cursize = 0;
+        if packet.len() > reply.maxsize as usize {
+            return;
+        }
+        copy_nonoverlapping(packet.as_ptr(), reply.data, packet.len());
+        reply.cursize = packet.len() as c_int;
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use cvar::capi::cvar_vars;
+    use cvar::{self, CVarT, NotifyWatchT};
+    use net_defs::tests::{HubT, MemorySocketT};
+    use net_rcon::RconServerT;
+    use std::cell::RefCell;
+    use std::ffi::{CStr, CString};
+    use std::io;
+    use std::os::raw::c_char;
+    use std::ptr::null_mut;
+    use SizeBufT;
+
+    const CLIENT: &str = "10.0.0.1:26000";
+    const SERVER: &str = "10.0.0.2:26000";
+
+    /// both ends of a connection over a network that loses loss percent of the datagrams
+    fn pair(
+        loss: u32,
+    ) -> (
+        DatagramSocketT<MemorySocketT>,
+        DatagramSocketT<MemorySocketT>,
+    ) {
+        let hub = HubT::default();
+        let client = MemorySocketT::lossy(&hub, CLIENT, loss, 7);
+        let server = MemorySocketT::lossy(&hub, SERVER, loss, 11);
+        let (client_addr, server_addr) = (client.addr(), server.addr());
+        return (
+            DatagramSocketT::new(client, server_addr),
+            DatagramSocketT::new(server, client_addr),
+        );
+    }
+
+    /// messages of every size up to NET_MAXMESSAGE, most of them fragmented
+    fn messages() -> Vec<Vec<Byte>> {
+        return (0..20)
+            .map(|i| {
+                let len = i * 3331 % NET_MAXMESSAGE + 1;
+                (0..len).map(|j| (j * 7 + i) as Byte).collect()
+            })
+            .collect();
+    }
+
+    #[test]
+    fn reliable_messages_survive_loss() {
+        let (mut client, mut server) = pair(30);
+        let messages = messages();
+        let mut received = Vec::new();
+        let mut unreliable = 0;
+        let mut next = 0;
+        let mut time = 0.0;
+        while received.len() < messages.len() {
+            assert!(time < 10000.0, "stuck after {} messages", received.len());
+            time += 0.3;
+            client.set_net_time(time);
+            server.set_net_time(time);
+            if next < messages.len() && client.can_send_message() {
+                client.send_message(&messages[next]).unwrap();
+                next += 1;
+            }
+            client.send_unreliable_message(b"u").unwrap();
+
+            while client.get_message().unwrap().is_some() {}
+            while let Some(message) = server.get_message().unwrap() {
+                match message {
+                    NetMessageT::Reliable(data) => received.push(data),
+                    NetMessageT::Unreliable(_) => unreliable += 1,
+                }
+            }
+        }
+
+        assert!(received == messages);
+        assert!(unreliable > 0);
+        assert!(client.stats.packets_resent > 0);
+        assert!(server.stats.dropped_datagrams > 0);
+        assert!(server.stats.received_duplicate_count > 0);
+        assert_eq!(client.stats.messages_sent, messages.len() as c_int);
+        assert_eq!(server.stats.messages_received, messages.len() as c_int);
+    }
+
+    #[test]
+    fn one_reliable_message_at_a_time() {
+        let (mut client, mut server) = pair(0);
+        client.send_message(b"first").unwrap();
+        assert!(!client.can_send_message());
+        assert_eq!(client.send_message(b"second"), Err(NetError::CantSend));
+
+        // the ack frees the channel, and times the round trip
+        client.set_net_time(0.25);
+        server.set_net_time(0.25);
+        match server.get_message().unwrap() {
+            Some(NetMessageT::Reliable(data)) => assert_eq!(data, b"first"),
+            _ => panic!("expected the reliable message"),
+        }
+        assert!(client.get_message().unwrap().is_none());
+        assert!(client.can_send_message());
+        assert_eq!(client.stats.rtt, 0.25);
+    }
+
+    #[test]
+    fn unreliable_drops_and_stale_datagrams() {
+        let (mut client, mut server) = pair(0);
+        client.send_unreliable_message(b"0").unwrap();
+        client.send_unreliable_message(b"1").unwrap();
+        client.send_unreliable_message(b"2").unwrap();
+        assert!(server.get_message().unwrap().is_some());
+
+        // lose sequence 1 on the way
+        let mut buf = [0; NET_DATAGRAMSIZE];
+        assert!(server.socket_mut().read(&mut buf).unwrap().is_some());
+        match server.get_message().unwrap() {
+            Some(NetMessageT::Unreliable(data)) => assert_eq!(data, b"2"),
+            _ => panic!("expected the unreliable message"),
+        }
+        assert_eq!(server.stats.dropped_datagrams, 1);
+
+        // and sequence 0 arrives again, late
+        let stale = packet(NetHeaderFlags::Unreliable, 0, b"0");
+        let server_addr = *client.addr();
+        client.socket_mut().write(&stale, &server_addr).unwrap();
+        assert!(server.get_message().unwrap().is_none());
+        assert_eq!(server.stats.unreliable_messages_received, 2);
+
+        // a sequence far ahead counts what it skipped, as far as the counter goes
+        let jump = packet(NetHeaderFlags::Unreliable, c_uint::MAX, b"3");
+        client.socket_mut().write(&jump, &server_addr).unwrap();
+        assert!(server.get_message().unwrap().is_some());
+        assert_eq!(server.stats.dropped_datagrams, c_int::MAX);
+
+        let mut total = server.stats;
+        total.add(&server.stats);
+        assert_eq!(total.dropped_datagrams, c_int::MAX);
+        assert_eq!(total.unreliable_messages_received, 6);
+    }
+
+    #[test]
+    fn forged_and_short_packets_are_ignored() {
+        let hub = HubT::default();
+        let mut server =
+            DatagramSocketT::new(MemorySocketT::new(&hub, SERVER), CLIENT.parse().unwrap());
+        let mut forger = MemorySocketT::new(&hub, "10.0.0.3:26000");
+        let mut client = MemorySocketT::new(&hub, CLIENT);
+        let server_addr = SERVER.parse().unwrap();
+
+        let data = packet(NetHeaderFlags::Unreliable, 0, b"forged");
+        forger.write(&data, &server_addr).unwrap();
+        client
+            .write(&data[..NET_HEADERSIZE - 1], &server_addr)
+            .unwrap();
+        assert!(server.get_message().unwrap().is_none());
+        assert_eq!(server.stats.short_packet_count, 1);
+        assert_eq!(server.stats.packets_received, 0);
+    }
+
+    #[test]
+    fn limits_and_close() {
+        let (mut client, _server) = pair(0);
+        let big = vec![0; NET_MAXMESSAGE + 1];
+        assert_eq!(client.send_message(&big), Err(NetError::Overflow));
+        let big = vec![0; MAX_DATAGRAM + 1];
+        assert_eq!(
+            client.send_unreliable_message(&big),
+            Err(NetError::Overflow)
+        );
+
+        client.close();
+        assert_eq!(client.get_message().err(), Some(NetError::Disconnected));
+        assert_eq!(client.send_message(b"x"), Err(NetError::Disconnected));
+    }
+
+    #[test]
+    fn every_control_message_round_trips() {
+        let messages = vec![
+            CtlMessageT::connect(),
+            CtlMessageT::server_info(),
+            CtlMessageT::ReqPlayerInfo { player_number: 3 },
+            CtlMessageT::ReqRuleInfo {
+                rule: b"fraglimit".to_vec(),
+            },
+            CtlMessageT::RepAccept { port: 26001 },
+            CtlMessageT::RepReject {
+                reason: b"Server is full.\n".to_vec(),
+            },
+            CtlMessageT::RepServerInfo(CtlServerInfoT {
+                address: SERVER.as_bytes().to_vec(),
+                host_name: b"quake".to_vec(),
+                level_name: b"e1m1".to_vec(),
+                current_players: 2,
+                max_players: 8,
+                protocol_version: NET_PROTOCOL_VERSION as c_int,
+            }),
+            CtlMessageT::RepPlayerInfo(CtlPlayerInfoT {
+                player_number: 1,
+                name: b"player".to_vec(),
+                colors: 0x4d,
+                frags: -2,
+                connect_time: 95,
+                address: CLIENT.as_bytes().to_vec(),
+            }),
+            CtlMessageT::RepRuleInfo {
+                rule: b"teamplay".to_vec(),
+                value: b"1".to_vec(),
+            },
+            CtlMessageT::RepRuleInfo {
+                rule: Vec::new(),
+                value: Vec::new(),
+            },
+            CtlMessageT::ReqRcon {
+                challenge: 0x1234_5678,
+                digest: vec![0xab; 16],
+                command: b"status".to_vec(),
+            },
+            CtlMessageT::RepRcon {
+                challenge: 0,
+                output: b"] status\n".to_vec(),
+            },
+        ];
+        for message in messages {
+            assert_eq!(CtlMessageT::decode(&message.encode()), Ok(message));
+        }
+    }
+
+    /// a MemorySocketT that can't send anything to one address
+    struct UnreachableT {
+        socket: MemorySocketT,
+        unreachable: SocketAddr,
+    }
+
+    impl NetLanSocket for UnreachableT {
+        fn read(&mut self, buf: &mut [Byte]) -> io::Result<Option<(usize, SocketAddr)>> {
+            return self.socket.read(buf);
+        }
+
+        fn write(&mut self, data: &[Byte], addr: &SocketAddr) -> io::Result<usize> {
+            if *addr == self.unreachable {
+                return Err(io::ErrorKind::AddrNotAvailable.into());
+            }
+            return self.socket.write(data, addr);
+        }
+    }
+
+    #[test]
+    fn a_reply_that_fails_doesnt_stop_the_others() {
+        let hub = HubT::default();
+        let unreachable = MemorySocketT::new(&hub, "10.0.0.3:26000");
+        let socket = UnreachableT {
+            socket: MemorySocketT::new(&hub, SERVER),
+            unreachable: unreachable.addr(),
+        };
+        let mut ctl = CtlServerT::new(socket, SERVER.parse().unwrap());
+        let mut server = StandInServerT::new(b"quake", b"e1m1", 8);
+        let mut client = MemorySocketT::new(&hub, CLIENT);
+        let mut unreachable = unreachable;
+        let server_addr = SERVER.parse().unwrap();
+
+        let request = CtlMessageT::server_info().encode();
+        unreachable.write(&request, &server_addr).unwrap();
+        client.write(&request, &server_addr).unwrap();
+        unreachable
+            .write(&CtlMessageT::connect().encode(), &server_addr)
+            .unwrap();
+        client
+            .write(&CtlMessageT::connect().encode(), &server_addr)
+            .unwrap();
+
+        // the unreachable client is still accepted, its reply is lost; then the other
+        assert_eq!(
+            ctl.check_new_connections(&mut server),
+            Ok(Some(unreachable.addr()))
+        );
+        assert_eq!(
+            ctl.check_new_connections(&mut server),
+            Ok(Some(client.addr()))
+        );
+        let mut buf = [0; NET_DATAGRAMSIZE];
+        let mut replies = Vec::new();
+        while let Some((len, _)) = client.read(&mut buf).unwrap() {
+            replies.push(CtlMessageT::decode(&buf[..len]).unwrap());
+        }
+        assert_eq!(replies.len(), 2);
+        assert!(replies[1] == CtlMessageT::RepAccept { port: 26002 });
+        assert!(unreachable.read(&mut buf).unwrap().is_none());
+    }
+
+    /// a cvar of flags, not registered
+    fn cvar(name: &'static [Byte], string: &'static [Byte], flags: CVarFlags) -> CVarT {
+        return CVarT {
+            name: name.as_ptr() as *const c_char,
+            string: string.as_ptr() as *const c_char,
+            flags,
+            value: 0.0,
+            default_string: string.as_ptr() as *const c_char,
+            callback: None,
+            next: null_mut(),
+        };
+    }
+
+    /// the reply Datagram_RuleInfo gives to a query for the rule after prev
+    fn rule_info(prev: &[Byte], maxsize: c_int) -> Vec<Byte> {
+        let mut data = [0; 64];
+        let mut reply = SizeBufT::default();
+        reply.data = data.as_mut_ptr();
+        reply.maxsize = maxsize;
+        let prev = CString::new(prev).unwrap();
+        unsafe { capi::Datagram_RuleInfo(prev.as_ptr(), &mut reply) };
+        return data[..reply.cursize as usize].to_vec();
+    }
+
+    #[test]
+    fn rules_and_notify_come_from_the_cvars() {
+        let both = CVarFlags::Notify | CVarFlags::ServerInfo;
+        let mut vars = [
+            cvar(b"fraglimit\0", b"10\0", both),
+            cvar(b"sensitivity\0", b"3\0", CVarFlags::Archive),
+            cvar(b"teamplay\0", b"0\0", both),
+        ];
+        vars[1].next = &mut vars[2];
+        vars[0].next = &mut vars[1];
+        unsafe { cvar_vars = &mut vars[0] };
+
+        let rule = |rule: &[Byte], value: &[Byte]| CtlMessageT::RepRuleInfo {
+            rule: rule.to_vec(),
+            value: value.to_vec(),
+        };
+        assert_eq!(rule_info(b"", 64), rule(b"fraglimit", b"10").encode());
+        assert_eq!(
+            rule_info(b"fraglimit", 64),
+            rule(b"teamplay", b"0").encode()
+        );
+        assert_eq!(rule_info(b"teamplay", 64), rule(b"", b"").encode());
+        assert_eq!(rule_info(b"nosuchcvar", 64), rule(b"", b"").encode());
+        assert!(rule_info(b"", 8).is_empty());
+
+        let mut watch = unsafe { NotifyWatchT::new() };
+        assert!(unsafe { watch.changes() }.is_empty());
+        // set through the list, as Cvar_Set would
+        unsafe {
+            let sensitivity = (*cvar_vars).next;
+            (*sensitivity).string = b"5\0".as_ptr() as *const c_char;
+            (*(*sensitivity).next).string = b"1\0".as_ptr() as *const c_char;
+        }
+        let changes = unsafe { watch.changes() };
+        assert_eq!(changes, [(b"teamplay".to_vec(), b"1".to_vec())]);
+        assert!(unsafe { watch.changes() }.is_empty());
+
+        // the first call only starts watching; then fraglimit's own callback tells of it
+        unsafe extern "C" fn notify(var: *mut CVarT) {
+            let name = CStr::from_ptr((*var).name).to_bytes().to_vec();
+            NOTIFIED.with(|notified| notified.borrow_mut().push(name));
+        }
+        unsafe extern "C" fn callback(_: *mut CVarT) {}
+        unsafe {
+            cvar::capi::Cvar_NotifyChanges(Some(notify));
+            cvar::capi::Cvar_SetCallback(&mut vars[0], Some(callback));
+            vars[0].string = b"20\0".as_ptr() as *const c_char;
+            vars[2].string = b"2\0".as_ptr() as *const c_char;
+            cvar::capi::Cvar_NotifyChanges(Some(notify));
+        }
+        NOTIFIED.with(|notified| assert_eq!(*notified.borrow(), [b"teamplay".to_vec()]));
+
+        unsafe { cvar_vars = null_mut() };
+    }
+
+    thread_local! {
+        /// the cvars the notify callback was called on
+        static NOTIFIED: RefCell<Vec<Vec<Byte>>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// a server that only speaks the connection control protocol, answering from its fields; for
+    /// testing clients and query tools
+    pub struct StandInServerT {
+        pub host_name: Vec<Byte>,
+        pub level_name: Vec<Byte>,
+        pub max_players: c_int,
+        /// the active players, in order
+        pub players: Vec<CtlPlayerInfoT>,
+        /// the serverinfo cvars, in the order Cvar_FindVarAfter walks them
+        pub rules: Vec<(Vec<Byte>, Vec<Byte>)>,
+        /// reject every connection with this reason, instead of accepting
+        pub reject: Option<Vec<Byte>>,
+        /// the port the next accepted connection is given
+        pub next_port: c_int,
+        /// every address accepted, in order
+        pub accepted: Vec<SocketAddr>,
+        /// every address whose old connection was dropped
+        pub dropped: Vec<SocketAddr>,
+        /// rcon, with no password until one is set
+        pub rcon: RconServerT,
+        /// every rcon command run, in order; each answers with its echo
+        pub commands: Vec<Vec<Byte>>,
+    }
+
+    impl StandInServerT {
+        pub fn new(host_name: &[Byte], level_name: &[Byte], max_players: c_int) -> Self {
+            Self {
+                host_name: host_name.to_vec(),
+                level_name: level_name.to_vec(),
+                max_players,
+                players: Vec::new(),
+                rules: Vec::new(),
+                reject: None,
+                next_port: 26001,
+                accepted: Vec::new(),
+                dropped: Vec::new(),
+                rcon: RconServerT::new(b""),
+                commands: Vec::new(),
+            }
+        }
+    }
+
+    impl CtlServerHandler for StandInServerT {
+        fn host_name(&self) -> Vec<Byte> {
+            return self.host_name.clone();
+        }
+        fn level_name(&self) -> Vec<Byte> {
+            return self.level_name.clone();
+        }
+        fn current_players(&self) -> c_int {
+            return self.players.len() as c_int;
+        }
+        fn max_players(&self) -> c_int {
+            return self.max_players;
+        }
+        fn player_info(&self, player_number: c_int) -> Option<CtlPlayerInfoT> {
+            let mut info = self.players.get(player_number as usize)?.clone();
+            info.player_number = player_number;
+            return Some(info);
+        }
+        fn rule_after(&self, prev: &[Byte]) -> Option<(Vec<Byte>, Vec<Byte>)> {
+            let next = if prev.is_empty() {
+                0
+            } else {
+                self.rules.iter().position(|r| r.0[..] == *prev)? + 1
+            };
+            return self.rules.get(next).cloned();
+        }
+        fn accept(&mut self, addr: &SocketAddr) -> Result<c_int, Vec<Byte>> {
+            if let Some(ref reason) = self.reject {
+                return Err(reason.clone());
+            }
+            if self.accepted.len() >= self.max_players as usize {
+                return Err(b"Server is full.\n".to_vec());
+            }
+            let port = self.next_port;
+            self.next_port += 1;
+            self.accepted.push(*addr);
+            return Ok(port);
+        }
+        fn drop_connection(&mut self, addr: &SocketAddr) {
+            self.accepted.retain(|a| a != addr);
+            self.dropped.push(*addr);
+        }
+        fn rcon(
+            &mut self,
+            addr: &SocketAddr,
+            challenge: c_int,
+            digest: &[Byte],
+            command: &[Byte],
+            net_time: c_double,
+        ) -> Option<CtlMessageT> {
+            let commands = &mut self.commands;
+            let mut execute = |command: &[Byte]| {
+                commands.push(command.to_vec());
+                let mut output = b"] ".to_vec();
+                output.extend_from_slice(command);
+                output.push(b'\n');
+                return output;
+            };
+            return self
+                .rcon
+                .request(addr, challenge, digest, command, net_time, &mut execute);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// net_loop.rs -- the loopback driver: a client and a server in the same process
+
+use net::{NetConnection, NetError, NetMessageT, NET_MAXMESSAGE};
+use net_defs::NetDriver;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use Byte;
+
+/// the host name the loopback driver connects to
+pub const LOOP_HOST: &str = "local";
+
+/// what one end of the loop has been sent
+struct LoopEndT {
+    messages: VecDeque<NetMessageT>,
+    /// Formerly: receiveMessageLength
+    /// the bytes the messages take up in the C buffer
+    length: usize,
+    /// Formerly: canSend
+    can_send: bool,
+    /// false once this end is closed
+    open: bool,
+}
+
+impl LoopEndT {
+    fn new() -> Self {
+        Self {
+            messages: VecDeque::new(),
+            length: 0,
+            can_send: true,
+            open: true,
+        }
+    }
+}
+
+/// Formerly: IntAlign
+/// a message's size in the buffer: a type byte, two length bytes and an alignment byte, then
+/// the data padded to an int
+fn buffer_size(length: usize) -> usize {
+    return (length + 4 + 3) & !3;
+}
+
+/// Formerly: loop_client, loop_server
+/// one end of a loopback connection; dropping it closes it
+pub struct LoopSocketT {
+    ends: Rc<RefCell<[LoopEndT; 2]>>,
+    /// 0 for the client, 1 for the server
+    side: usize,
+}
+
+impl LoopSocketT {
+    /// the client and server ends of a new connection
+    pub fn pair() -> (Self, Self) {
+        let ends = Rc::new(RefCell::new([LoopEndT::new(), LoopEndT::new()]));
+        let client = Self {
+            ends: ends.clone(),
+            side: 0,
+        };
+        let server = Self { ends, side: 1 };
+        return (client, server);
+    }
+
+    fn send(&mut self, message: NetMessageT, length: usize) -> Result<bool, NetError> {
+        let mut ends = self.ends.borrow_mut();
+        let peer = &mut ends[1 - self.side];
+        if !peer.open {
+            return Err(NetError::Disconnected);
+        }
+        if peer.length + length + 4 > NET_MAXMESSAGE {
+            return Ok(false);
+        }
+        peer.length += buffer_size(length);
+        peer.messages.push_back(message);
+        return Ok(true);
+    }
+}
+
+impl NetConnection for LoopSocketT {
+    /// Formerly: Loop_GetMessage
+    fn get_message(&mut self) -> Result<Option<NetMessageT>, NetError> {
+        let mut ends = self.ends.borrow_mut();
+        let message = match ends[self.side].messages.pop_front() {
+            Some(message) => message,
+            None if ends[self.side].open && ends[1 - self.side].open => return Ok(None),
+            None => return Err(NetError::Disconnected),
+        };
+        let length = match message {
+            NetMessageT::Reliable(ref data) => {
+                ends[1 - self.side].can_send = true;
+                data.len()
+            }
+            NetMessageT::Unreliable(ref data) => data.len(),
+        };
+        ends[self.side].length -= buffer_size(length);
+        return Ok(Some(message));
+    }
+
+    /// Formerly: Loop_SendMessage
+    fn send_message(&mut self, data: &[Byte]) -> Result<(), NetError> {
+        if !self.send(NetMessageT::Reliable(data.to_vec()), data.len())? {
+            return Err(NetError::Overflow);
+        }
+        self.ends.borrow_mut()[self.side].can_send = false;
+        return Ok(());
+    }
+
+    /// Formerly: Loop_SendUnreliableMessage
+    /// a message that doesn't fit is dropped
+    fn send_unreliable_message(&mut self, data: &[Byte]) -> Result<(), NetError> {
+        self.send(NetMessageT::Unreliable(data.to_vec()), data.len())?;
+        return Ok(());
+    }
+
+    /// Formerly: Loop_CanSendMessage
+    fn can_send_message(&mut self) -> bool {
+        let ends = self.ends.borrow();
+        return ends[1 - self.side].open && ends[self.side].can_send;
+    }
+
+    /// Formerly: Loop_Close
+    fn close(&mut self) {
+        let mut ends = self.ends.borrow_mut();
+        let end = &mut ends[self.side];
+        end.open = false;
+        end.messages.clear();
+        end.length = 0;
+        end.can_send = true;
+    }
+
+    fn address(&self) -> String {
+        return if self.side == 0 { "localhost" } else { "LOCAL" }.to_string();
+    }
+}
+
+impl Drop for LoopSocketT {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// Formerly: the Loop_ functions of net_drivers[0]
+pub struct LoopDriverT {
+    /// Formerly: localconnectpending
+    /// the server end of a connect that check_new_connections hasn't handed out
+    pending: Option<LoopSocketT>,
+}
+
+impl LoopDriverT {
+    pub fn new() -> Self {
+        Self { pending: None }
+    }
+
+    /// Formerly: Loop_Connect
+    /// the client end of a new connection to the local server, for LOOP_HOST only
+    pub fn connect_local(&mut self, host: &str) -> Option<LoopSocketT> {
+        if host != LOOP_HOST {
+            return None;
+        }
+        let (client, server) = LoopSocketT::pair();
+        self.pending = Some(server);
+        return Some(client);
+    }
+
+    /// Formerly: Loop_CheckNewConnections
+    pub fn check_new_local_connections(&mut self) -> Option<LoopSocketT> {
+        return self.pending.take();
+    }
+}
+
+impl Default for LoopDriverT {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NetDriver for LoopDriverT {
+    fn name(&self) -> &str {
+        return "Loopback";
+    }
+
+    /// Formerly: Loop_Listen
+    fn listen(&mut self, _state: bool) {}
+
+    fn connect(&mut self, host: &str) -> Result<Option<Box<dyn NetConnection>>, NetError> {
+        return Ok(self
+            .connect_local(host)
+            .map(|client| Box::new(client) as Box<dyn NetConnection>));
+    }
+
+    fn check_new_connections(&mut self) -> Result<Option<Box<dyn NetConnection>>, NetError> {
+        return Ok(self
+            .check_new_local_connections()
+            .map(|server| Box::new(server) as Box<dyn NetConnection>));
+    }
+}
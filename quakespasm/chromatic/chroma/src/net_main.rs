@@ -19,10 +19,699 @@ Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
 
 */
 
+use net::{NetConnection, NetError, NetMessageT};
+use net_defs::{NetLanSocket, NET_DATAGRAMSIZE, NET_PROTOCOL_VERSION};
+use net_dgrm::{CtlMessageT, DatagramStatsT};
+use std::cell::Cell;
+use std::cmp::Reverse;
+use std::fmt;
+use std::fmt::Write;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::os::raw::{c_double, c_int};
+use std::rc::Rc;
+use std::time::Instant;
+use Byte;
+
+/// how many servers a list keeps
+pub const HOSTCACHESIZE: usize = 8;
+/// Slist_Send asks again every this many seconds...
+pub const SLIST_SEND_INTERVAL: c_double = 0.75;
+/// ...as long as the list is younger than this
+pub const SLIST_SEND_TIME: c_double = 0.5;
+/// seconds Slist_Poll waits for answers
+pub const SLIST_TIME: c_double = 1.5;
+
+/// Formerly: PrintSlistHeader
+pub const SLIST_HEADER: &str =
+    "Server          Map             Users\n--------------- --------------- -----\n";
+
+/// Formerly: hostcache_t
+/// a server that answered CCREQ_SERVER_INFO
+#[derive(Clone, Debug, PartialEq)]
+pub struct HostCacheT {
+    /// the host name, made unique in the list; a * in front for another protocol version
+    pub name: Vec<Byte>,
+    pub map: Vec<Byte>,
+    /// the address as a string, what connect takes
+    pub cname: String,
+    pub users: c_int,
+    pub maxusers: c_int,
+    pub protocol: c_int,
+    /// where the answer came from
+    pub addr: SocketAddr,
+    /// seconds between the last request and the answer
+    pub ping: c_double,
+}
+
+/// Formerly: NET_SlistPrintServer
+/// the line PrintSlist shows for the server
+impl fmt::Display for HostCacheT {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = String::from_utf8_lossy(&self.name);
+        let map = String::from_utf8_lossy(&self.map);
+        if self.maxusers != 0 {
+            return write!(
+                f,
+                "{:<15.15} {:<15.15} {:2}/{:2}",
+                name, map, self.users, self.maxusers
+            );
+        }
+        return write!(f, "{:<15.15} {:<15.15}", name, map);
+    }
+}
+
+/// a column to sort a server list by
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SlistSortKey {
+    /// ignoring case, like NET_SlistSort
+    Name,
+    Map,
+    /// the fullest first
+    Users,
+    Protocol,
+    /// the fastest first
+    Ping,
+}
+
+/// Formerly: NET_Slist_f, Slist_Send, Slist_Poll, _Datagram_SearchForHosts
+/// a search for servers: asks every target for CCREQ_SERVER_INFO and collects the answers
+pub struct SlistT {
+    /// where requests go: a broadcast address, explicit hosts, or both
+    targets: Vec<SocketAddr>,
+    /// the socket's own address, whose answers to our broadcast are ignored
+    pub local_addr: Option<SocketAddr>,
+    /// a full list ignores further answers
+    pub max_hosts: usize,
+    /// Formerly: hostcache
+    hosts: Vec<HostCacheT>,
+    /// Formerly: slistInProgress
+    in_progress: bool,
+    /// Formerly: slistStartTime
+    start_time: c_double,
+    /// when Slist_Send runs next
+    next_send: Option<c_double>,
+    last_send_time: c_double,
+    /// Formerly: slistLastShown
+    last_shown: usize,
+}
+
+impl SlistT {
+    /// a search of the targets
+    pub fn new(targets: Vec<SocketAddr>) -> Self {
+        Self {
+            targets,
+            local_addr: None,
+            max_hosts: HOSTCACHESIZE,
+            hosts: Vec::new(),
+            in_progress: false,
+            start_time: 0.0,
+            next_send: None,
+            last_send_time: 0.0,
+            last_shown: 0,
+        }
+    }
+
+    /// a search of the local network, servers listening on port
+    pub fn broadcast(port: u16) -> Self {
+        return Self::new(vec![SocketAddr::from((Ipv4Addr::BROADCAST, port))]);
+    }
+
+    pub fn targets(&self) -> &[SocketAddr] {
+        return &self.targets;
+    }
+
+    /// ask host too
+    pub fn add_target(&mut self, host: SocketAddr) {
+        if !self.targets.contains(&host) {
+            self.targets.push(host);
+        }
+    }
+
+    /// Formerly: slistInProgress
+    pub fn in_progress(&self) -> bool {
+        return self.in_progress;
+    }
+
+    /// Formerly: hostcache, hostCacheCount
+    pub fn hosts(&self) -> &[HostCacheT] {
+        return &self.hosts;
+    }
+
+    /// Formerly: NET_Slist_f
+    /// forget the last list and search again; false if a search is going on
+    pub fn start(&mut self, net_time: c_double) -> bool {
+        if self.in_progress {
+            return false;
+        }
+        self.in_progress = true;
+        self.start_time = net_time;
+        self.next_send = Some(net_time);
+        self.hosts.clear();
+        self.last_shown = 0;
+        return true;
+    }
+
+    /// Formerly: Slist_Send, Slist_Poll
+    /// send the requests that are due and take in the answers; true when the search is over
+    pub fn poll<S: NetLanSocket>(
+        &mut self,
+        socket: &mut S,
+        net_time: c_double,
+    ) -> Result<bool, NetError> {
+        if !self.in_progress {
+            return Ok(true);
+        }
+
+        if self.next_send.is_some_and(|t| net_time >= t) {
+            let request = CtlMessageT::server_info().encode();
+            for target in &self.targets {
+                socket.write(&request, target)?;
+            }
+            self.last_send_time = net_time;
+            self.next_send = if net_time - self.start_time < SLIST_SEND_TIME {
+                Some(net_time + SLIST_SEND_INTERVAL)
+            } else {
+                None
+            };
+        }
+
+        let mut buf = [0; NET_DATAGRAMSIZE];
+        while let Some((len, readaddr)) = socket.read(&mut buf)? {
+            // don't answer our own query
+            if Some(readaddr) == self.local_addr {
+                continue;
+            }
+            if let Ok(CtlMessageT::RepServerInfo(info)) = CtlMessageT::decode(&buf[..len]) {
+                self.add_host(
+                    readaddr,
+                    info.host_name,
+                    info.level_name,
+                    info.current_players,
+                    info.max_players,
+                    info.protocol_version,
+                    net_time - self.last_send_time,
+                );
+            }
+        }
+
+        if net_time - self.start_time >= SLIST_TIME {
+            self.in_progress = false;
+        }
+        return Ok(!self.in_progress);
+    }
+
+    /// put an answer in the list, unless the server is already in it or the list is full
+    #[allow(clippy::too_many_arguments)]
+    fn add_host(
+        &mut self,
+        addr: SocketAddr,
+        mut name: Vec<Byte>,
+        map: Vec<Byte>,
+        users: c_int,
+        maxusers: c_int,
+        protocol: c_int,
+        ping: c_double,
+    ) {
+        // is it already there?
+        if self.hosts.iter().any(|h| h.addr == addr) {
+            return;
+        }
+        // is the cache full?
+        if self.hosts.len() >= self.max_hosts {
+            return;
+        }
+
+        if protocol != NET_PROTOCOL_VERSION as c_int {
+            name.truncate(14);
+            name.insert(0, b'*');
+        }
+
+        // check for a name conflict
+        while self
+            .hosts
+            .iter()
+            .any(|h| h.name.eq_ignore_ascii_case(&name))
+        {
+            let i = name.len();
+            if i == 0 || (i < 15 && name[i - 1] > b'8') {
+                name.push(b'0');
+            } else {
+                name[i - 1] = name[i - 1].wrapping_add(1);
+            }
+        }
+
+        self.hosts.push(HostCacheT {
+            name,
+            map,
+            cname: addr.to_string(),
+            users,
+            maxusers,
+            protocol,
+            addr,
+            ping,
+        });
+    }
+
+    /// Formerly: NET_SlistSort
+    pub fn sort(&mut self, key: SlistSortKey) {
+        match key {
+            SlistSortKey::Name => self.hosts.sort_by(|a, b| {
+                a.name
+                    .to_ascii_lowercase()
+                    .cmp(&b.name.to_ascii_lowercase())
+            }),
+            SlistSortKey::Map => self.hosts.sort_by(|a, b| a.map.cmp(&b.map)),
+            SlistSortKey::Users => self.hosts.sort_by_key(|h| Reverse(h.users)),
+            SlistSortKey::Protocol => self.hosts.sort_by_key(|h| h.protocol),
+            SlistSortKey::Ping => self
+                .hosts
+                .sort_by(|a, b| a.ping.partial_cmp(&b.ping).unwrap_or(a.name.cmp(&b.name))),
+        }
+        self.last_shown = 0;
+    }
+
+    /// Formerly: PrintSlist
+    /// the servers that came in since the last call
+    pub fn take_unshown(&mut self) -> &[HostCacheT] {
+        let first = self.last_shown.min(self.hosts.len());
+        self.last_shown = self.hosts.len();
+        return &self.hosts[first..];
+    }
+
+    /// Formerly: PrintSlistTrailer
+    pub fn trailer(&self) -> &'static str {
+        if self.hosts.is_empty() {
+            return "No Quake servers found.\n\n";
+        }
+        return "== end list ==\n\n";
+    }
+}
+
+/// Formerly: NET_Stats_f
+/// what net_stats prints: the totals of every connection without an argument, each
+/// connection's own for "*", or those of the connection at an address
+pub fn net_stats(connections: &[&dyn NetConnection], arg: Option<&str>) -> String {
+    let stats: Vec<(String, DatagramStatsT)> = connections
+        .iter()
+        .filter_map(|c| c.stats().map(|stats| (c.address(), stats)))
+        .collect();
+    return print_stats(&stats, arg);
+}
+
+/// Formerly: NET_Stats_f, PrintStats
+/// net_stats for the counters of connections by address
+pub fn print_stats(stats: &[(String, DatagramStatsT)], arg: Option<&str>) -> String {
+    let mut out = String::new();
+    match arg {
+        None => {
+            let mut total = DatagramStatsT::default();
+            for (_, stats) in stats {
+                total.add(stats);
+            }
+            let _ = write!(out, "{}", total);
+        }
+        Some(arg) => {
+            for (address, stats) in stats {
+                if arg != "*" && !address.eq_ignore_ascii_case(arg) {
+                    continue;
+                }
+                let _ = write!(out, "{}\n{}\n", address, stats);
+            }
+        }
+    }
+    return out;
+}
+
+/// Formerly: NET_Stats_f
+/// net_stats for the engine's sockets, by address with how long each has been connected:
+/// the counters since startup without an argument, retired holding those of sockets that
+/// were since reused; otherwise as print_stats, with each socket's average rates since it
+/// connected
+pub fn print_socket_stats(
+    sockets: &[(String, DatagramStatsT, c_double)],
+    retired: &DatagramStatsT,
+    arg: Option<&str>,
+) -> String {
+    let mut out = String::new();
+    match arg {
+        None => {
+            let mut total = *retired;
+            for (_, stats, _) in sockets {
+                total.add(stats);
+            }
+            let _ = write!(out, "since startup:\n{}", total);
+        }
+        Some(arg) => {
+            for (address, stats, connected) in sockets {
+                if arg != "*" && !address.eq_ignore_ascii_case(arg) {
+                    continue;
+                }
+                let _ = write!(out, "{}\n{}", address, stats);
+                if *connected > 0.0 {
+                    let _ = writeln!(
+                        out,
+                        "average since connect      = {:.0} bytes/sec sent, {:.0} received",
+                        stats.bytes_sent as c_double / connected,
+                        stats.bytes_received as c_double / connected
+                    );
+                }
+                out.push('\n');
+            }
+        }
+    }
+    return out;
+}
+
+/// the default of net_messagetimeout
+pub const MESSAGE_TIMEOUT: c_double = 300.0;
+/// Formerly: the 5 seconds of SV_SendNop
+/// how long a connection goes without sending before it sends a NOP
+pub const KEEPALIVE_TIME: c_double = 5.0;
+/// svc_nop, and clc_nop the other way
+pub const NOP: Byte = 1;
+
+/// where net_time comes from
+pub trait NetClock {
+    /// seconds from some fixed start
+    fn now(&self) -> c_double;
+}
+
+/// Formerly: SetNetTime
+/// net_time from Sys_DoubleTime, as the rest of the engine sees it
+pub struct HostClockT;
+
+impl NetClock for HostClockT {
+    fn now(&self) -> c_double {
+        return capi::SetNetTime();
+    }
+}
+
+/// the wall clock, from when it was made
+pub struct SysClockT {
+    epoch: Instant,
+}
+
+impl SysClockT {
+    pub fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+        }
+    }
+}
+
+impl Default for SysClockT {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NetClock for SysClockT {
+    fn now(&self) -> c_double {
+        return self.epoch.elapsed().as_secs_f64();
+    }
+}
+
+/// a clock that only moves when it is told to; its clones share the time
+#[derive(Clone, Default)]
+pub struct ManualClockT {
+    time: Rc<Cell<c_double>>,
+}
+
+impl ManualClockT {
+    pub fn new(time: c_double) -> Self {
+        Self {
+            time: Rc::new(Cell::new(time)),
+        }
+    }
+
+    pub fn set(&self, time: c_double) {
+        self.time.set(time);
+    }
+
+    pub fn advance(&self, seconds: c_double) {
+        self.time.set(self.time.get() + seconds);
+    }
+}
+
+impl NetClock for ManualClockT {
+    fn now(&self) -> c_double {
+        return self.time.get();
+    }
+}
+
+/// the value of net_messagetimeout
+///
+/// # Safety
+/// the cvar is read unsynchronized; only call this from the main thread
+pub unsafe fn message_timeout() -> c_double {
+    return capi::net_messagetimeout.value as c_double;
+}
+
+/// Formerly: net_messagetimeout
+/// the seconds a connection can go without a message, looked up on every check like the
+/// original, so a change applies to connections already open
+#[derive(Clone)]
+pub enum MessageTimeoutT {
+    /// the net_messagetimeout cvar
+    Cvar,
+    /// a value shared by everything holding a clone, set through any of them
+    Shared(Rc<Cell<c_double>>),
+}
+
+impl MessageTimeoutT {
+    /// net_messagetimeout
+    ///
+    /// # Safety
+    /// get reads the cvar unsynchronized; only use this from the main thread
+    pub unsafe fn cvar() -> Self {
+        return MessageTimeoutT::Cvar;
+    }
+
+    /// seconds, until set says otherwise
+    pub fn shared(seconds: c_double) -> Self {
+        return MessageTimeoutT::Shared(Rc::new(Cell::new(seconds)));
+    }
+
+    pub fn get(&self) -> c_double {
+        return match *self {
+            // cvar() is the only way to get here, and it is unsafe
+            MessageTimeoutT::Cvar => unsafe { message_timeout() },
+            MessageTimeoutT::Shared(ref seconds) => seconds.get(),
+        };
+    }
+
+    /// change a shared timeout for every holder; the cvar is left to the console
+    pub fn set(&self, seconds: c_double) {
+        if let MessageTimeoutT::Shared(ref shared) = *self {
+            shared.set(seconds);
+        }
+    }
+}
+
+impl Default for MessageTimeoutT {
+    fn default() -> Self {
+        Self::shared(MESSAGE_TIMEOUT)
+    }
+}
+
+/// why a NetSocketT closed
+#[derive(Clone, Debug, PartialEq)]
+pub enum CloseReasonT {
+    /// Formerly: NET_Close
+    /// this end closed it
+    Closed,
+    /// nothing came from the other end for this many seconds
+    TimedOut(c_double),
+    /// the connection failed underneath
+    Error(NetError),
+}
+
+impl fmt::Display for CloseReasonT {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match *self {
+            CloseReasonT::Closed => write!(f, "closed"),
+            CloseReasonT::TimedOut(idle) => write!(f, "timed out after {:.1} seconds", idle),
+            CloseReasonT::Error(NetError::Disconnected) => write!(f, "disconnected"),
+            CloseReasonT::Error(ref e) => write!(f, "{:?}", e),
+        };
+    }
+}
+
+/// Formerly: the times of qsocket_t, and what NET_GetMessage does with them
+/// a connection that closes once nothing has come from it for timeout seconds, and sends a
+/// reliable NOP after keepalive seconds of sending nothing, so the other end doesn't do the
+/// same to it.  For the connections of the drivers here, such as UdpDriverT's; the engine's
+/// qsockets still time out in net_main.c's NET_GetMessage and get their NOPs from
+/// SV_SendNop.  The loopback driver's connections don't need one
+pub struct NetSocketT<C: NetConnection> {
+    connection: C,
+    clock: Rc<dyn NetClock>,
+    pub timeout: MessageTimeoutT,
+    /// 0 for no keepalives
+    pub keepalive: c_double,
+    /// Formerly: connecttime
+    connecttime: c_double,
+    /// Formerly: lastMessageTime
+    last_message_time: c_double,
+    /// Formerly: lastSendTime
+    last_send_time: c_double,
+    /// NOPs sent so far
+    pub keepalives_sent: u32,
+    /// Formerly: disconnected
+    close_reason: Option<CloseReasonT>,
+}
+
+impl<C: NetConnection> NetSocketT<C> {
+    /// Formerly: NET_NewQSocket
+    pub fn new(connection: C, clock: Rc<dyn NetClock>, timeout: MessageTimeoutT) -> Self {
+        let now = clock.now();
+        Self {
+            connection,
+            clock,
+            timeout,
+            keepalive: KEEPALIVE_TIME,
+            connecttime: now,
+            last_message_time: now,
+            last_send_time: now,
+            keepalives_sent: 0,
+            close_reason: None,
+        }
+    }
+
+    pub fn connection(&self) -> &C {
+        return &self.connection;
+    }
+
+    pub fn connection_mut(&mut self) -> &mut C {
+        return &mut self.connection;
+    }
+
+    /// Formerly: NET_QSocketGetTime
+    pub fn connecttime(&self) -> c_double {
+        return self.connecttime;
+    }
+
+    pub fn last_message_time(&self) -> c_double {
+        return self.last_message_time;
+    }
+
+    pub fn last_send_time(&self) -> c_double {
+        return self.last_send_time;
+    }
+
+    /// None while it's open
+    pub fn close_reason(&self) -> Option<&CloseReasonT> {
+        return self.close_reason.as_ref();
+    }
+
+    fn close_with(&mut self, reason: CloseReasonT) -> NetError {
+        if self.close_reason.is_none() {
+            self.connection.close();
+            self.close_reason = Some(reason);
+        }
+        return NetError::Disconnected;
+    }
+
+    /// a failure of the connection underneath closes it
+    fn check<T>(&mut self, result: Result<T, NetError>) -> Result<T, NetError> {
+        return match result {
+            Err(NetError::CantSend) => Err(NetError::CantSend),
+            Err(NetError::Overflow) => Err(NetError::Overflow),
+            Err(e) => Err(self.close_with(CloseReasonT::Error(e))),
+            Ok(x) => Ok(x),
+        };
+    }
+
+    /// send a NOP if nothing has been sent for keepalive seconds and the reliable channel is
+    /// free
+    fn send_keepalive(&mut self, now: c_double) -> Result<(), NetError> {
+        if self.keepalive <= 0.0 || now - self.last_send_time <= self.keepalive {
+            return Ok(());
+        }
+        if !self.connection.can_send_message() {
+            return Ok(());
+        }
+        let result = self.connection.send_message(&[NOP]);
+        self.check(result)?;
+        self.last_send_time = now;
+        self.keepalives_sent += 1;
+        return Ok(());
+    }
+}
+
+impl<C: NetConnection> NetConnection for NetSocketT<C> {
+    /// Formerly: NET_GetMessage
+    /// the keepalive goes out from here, so it has to be called every frame like the
+    /// original
+    fn get_message(&mut self) -> Result<Option<NetMessageT>, NetError> {
+        if self.close_reason.is_some() {
+            return Err(NetError::Disconnected);
+        }
+        let now = self.clock.now();
+        let result = self.connection.get_message();
+        let message = self.check(result)?;
+        if message.is_some() {
+            self.last_message_time = now;
+            return Ok(message);
+        }
+
+        // see if this connection has timed out
+        let idle = now - self.last_message_time;
+        if idle > self.timeout.get() {
+            return Err(self.close_with(CloseReasonT::TimedOut(idle)));
+        }
+        self.send_keepalive(now)?;
+        return Ok(None);
+    }
+
+    /// Formerly: NET_SendMessage
+    fn send_message(&mut self, data: &[Byte]) -> Result<(), NetError> {
+        if self.close_reason.is_some() {
+            return Err(NetError::Disconnected);
+        }
+        let result = self.connection.send_message(data);
+        self.check(result)?;
+        self.last_send_time = self.clock.now();
+        return Ok(());
+    }
+
+    /// Formerly: NET_SendUnreliableMessage
+    fn send_unreliable_message(&mut self, data: &[Byte]) -> Result<(), NetError> {
+        if self.close_reason.is_some() {
+            return Err(NetError::Disconnected);
+        }
+        let result = self.connection.send_unreliable_message(data);
+        self.check(result)?;
+        self.last_send_time = self.clock.now();
+        return Ok(());
+    }
+
+    /// Formerly: NET_CanSendMessage
+    fn can_send_message(&mut self) -> bool {
+        return self.close_reason.is_none() && self.connection.can_send_message();
+    }
+
+    /// Formerly: NET_Close
+    fn close(&mut self) {
+        self.close_with(CloseReasonT::Closed);
+    }
+
+    fn address(&self) -> String {
+        return self.connection.address();
+    }
+
+    fn stats(&self) -> Option<DatagramStatsT> {
+        return self.connection.stats();
+    }
+}
+
 pub mod capi {
+    use std::ffi::{CStr, CString};
     use std::os::raw::{c_char, c_double, c_int};
-    use std::ptr::null_mut;
+    use std::ptr::{addr_of, addr_of_mut, null_mut};
     use net::NET_NAMELEN;
+    use net_dgrm::DatagramStatsT;
+    use super::print_socket_stats;
     use net_defs::QSocketT;
     use ::{SizeBufT, QBoolean};
     use cvar::{CVarT, CVarFlags};
@@ -114,13 +803,101 @@ pub mod capi {
         next: std::ptr::null_mut(),
     };
 
+    // the network simulator, see NetSimSettingsT
+    #[no_mangle]
+    pub static mut net_fakelag: CVarT = CVarT {
+        name: b"net_fakelag\0".as_ptr() as *const c_char,
+        string: b"0\0".as_ptr() as *const c_char,
+        flags: CVarFlags::None,
+        value: 0.0,
+        default_string: b"0\0".as_ptr() as *const c_char,
+        callback: None,
+        next: std::ptr::null_mut(),
+    };
+    #[no_mangle]
+    pub static mut net_fakejitter: CVarT = CVarT {
+        name: b"net_fakejitter\0".as_ptr() as *const c_char,
+        string: b"0\0".as_ptr() as *const c_char,
+        flags: CVarFlags::None,
+        value: 0.0,
+        default_string: b"0\0".as_ptr() as *const c_char,
+        callback: None,
+        next: std::ptr::null_mut(),
+    };
+    #[no_mangle]
+    pub static mut net_fakeloss: CVarT = CVarT {
+        name: b"net_fakeloss\0".as_ptr() as *const c_char,
+        string: b"0\0".as_ptr() as *const c_char,
+        flags: CVarFlags::None,
+        value: 0.0,
+        default_string: b"0\0".as_ptr() as *const c_char,
+        callback: None,
+        next: std::ptr::null_mut(),
+    };
+    #[no_mangle]
+    pub static mut net_fakedup: CVarT = CVarT {
+        name: b"net_fakedup\0".as_ptr() as *const c_char,
+        string: b"0\0".as_ptr() as *const c_char,
+        flags: CVarFlags::None,
+        value: 0.0,
+        default_string: b"0\0".as_ptr() as *const c_char,
+        callback: None,
+        next: std::ptr::null_mut(),
+    };
+    #[no_mangle]
+    pub static mut net_fakereorder: CVarT = CVarT {
+        name: b"net_fakereorder\0".as_ptr() as *const c_char,
+        string: b"0\0".as_ptr() as *const c_char,
+        flags: CVarFlags::None,
+        value: 0.0,
+        default_string: b"0\0".as_ptr() as *const c_char,
+        callback: None,
+        next: std::ptr::null_mut(),
+    };
+    #[no_mangle]
+    pub static mut net_fakeseed: CVarT = CVarT {
+        name: b"net_fakeseed\0".as_ptr() as *const c_char,
+        string: b"0\0".as_ptr() as *const c_char,
+        flags: CVarFlags::None,
+        value: 0.0,
+        default_string: b"0\0".as_ptr() as *const c_char,
+        callback: None,
+        next: std::ptr::null_mut(),
+    };
+    #[no_mangle]
+    pub static mut rcon_password: CVarT = CVarT {
+        name: b"rcon_password\0".as_ptr() as *const c_char,
+        string: b"\0".as_ptr() as *const c_char,
+        flags: CVarFlags::None,
+        value: 0.0,
+        default_string: b"\0".as_ptr() as *const c_char,
+        callback: None,
+        next: std::ptr::null_mut(),
+    };
+    #[no_mangle]
+    pub static mut rcon_address: CVarT = CVarT {
+        name: b"rcon_address\0".as_ptr() as *const c_char,
+        string: b"\0".as_ptr() as *const c_char,
+        flags: CVarFlags::None,
+        value: 0.0,
+        default_string: b"\0".as_ptr() as *const c_char,
+        callback: None,
+        next: std::ptr::null_mut(),
+    };
+
     #[no_mangle]
     pub static mut net_driverlevel: c_int = 0;
 
     #[no_mangle]
     pub static mut net_time: c_double = 0.0;
 
-    /*
+    extern "C" {
+        fn Sys_DoubleTime() -> c_double;
+        fn Cmd_Argc() -> c_int;
+        fn Cmd_Argv(arg: c_int) -> *const c_char;
+        fn Con_Printf(fmt: *const c_char, ...);
+    }
+
     #[no_mangle]
     pub extern "C" fn SetNetTime() -> c_double
     {
@@ -129,5 +906,320 @@ pub mod capi {
             return net_time;
         }
     }
-    */
+
+    /// the counters of the sockets NET_NewQSocket has reused since startup
+    static mut RETIRED_STATS: DatagramStatsT = DatagramStatsT::default();
+
+    /// the address and counters of every socket, active then free, and how long it was
+    /// connected
+    unsafe fn socket_stats() -> Vec<(String, DatagramStatsT, c_double)> {
+        let mut stats = Vec::new();
+        for list in &[net_activeSockets, net_freeSockets] {
+            let mut s = *list;
+            while let Some(sock) = s.as_ref() {
+                let address = CStr::from_ptr(sock.address.as_ptr());
+                // a closed socket stopped at its last message
+                let end = if sock.disconnected == QBoolean::True {
+                    sock.lastMessageTime
+                } else {
+                    net_time
+                };
+                let connected = end - sock.connecttime;
+                stats.push((address.to_string_lossy().into_owned(), sock.stats, connected));
+                s = sock.next;
+            }
+        }
+        return stats;
+    }
+
+    /// Formerly: none
+    /// NET_NewQSocket, before it clears a socket's counters: keep them for net_stats
+    ///
+    /// # Safety
+    ///
+    /// stats must point to a socket's counters; only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn NET_RetireStats(stats: *const DatagramStatsT) {
+        (*addr_of_mut!(RETIRED_STATS)).add(&*stats);
+    }
+
+    /// Formerly: NET_Stats_f
+    ///
+    /// # Safety
+    ///
+    /// a console command: the socket lists must be intact and Cmd_Argv must hold the
+    /// command being executed
+    #[no_mangle]
+    pub unsafe extern "C" fn NET_Stats_f() {
+        let arg = if Cmd_Argc() == 1 {
+            None
+        } else {
+            Some(CStr::from_ptr(Cmd_Argv(1)).to_string_lossy().into_owned())
+        };
+        let retired = &*addr_of!(RETIRED_STATS);
+        let text = print_socket_stats(&socket_stats(), retired, arg.as_deref());
+        for line in text.lines() {
+            if let Ok(line) = CString::new(line) {
+                Con_Printf(b"%s\n\0".as_ptr() as *const c_char, line.as_ptr());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use net_defs::tests::{HubT, MemorySocketT};
+    use net_dgrm::tests::StandInServerT;
+    use net_dgrm::{CtlPlayerInfoT, CtlServerT, DatagramSocketT};
+
+    type TimedT = NetSocketT<DatagramSocketT<MemorySocketT>>;
+
+    /// two ends of a datagram connection on clock, closed after timeout quiet seconds
+    fn timed_pair(hub: &HubT, clock: &ManualClockT, timeout: &MessageTimeoutT) -> (TimedT, TimedT) {
+        let a = MemorySocketT::new(hub, "10.0.0.1:26000");
+        let b = MemorySocketT::new(hub, "10.0.0.2:26000");
+        let (a_addr, b_addr) = (a.addr(), b.addr());
+        let a = DatagramSocketT::new(a, b_addr);
+        let b = DatagramSocketT::new(b, a_addr);
+        return (
+            NetSocketT::new(a, Rc::new(clock.clone()), timeout.clone()),
+            NetSocketT::new(b, Rc::new(clock.clone()), timeout.clone()),
+        );
+    }
+
+    /// a frame of socket; the reliable messages that came in
+    fn frame(clock: &ManualClockT, socket: &mut TimedT) -> Result<Vec<Vec<Byte>>, NetError> {
+        socket.connection_mut().set_net_time(clock.now());
+        let mut reliable = Vec::new();
+        while let Some(message) = socket.get_message()? {
+            if let NetMessageT::Reliable(data) = message {
+                reliable.push(data);
+            }
+        }
+        return Ok(reliable);
+    }
+
+    /// a stand-in server at addr with users players
+    fn stand_in(
+        hub: &HubT,
+        addr: &str,
+        name: &str,
+        map: &str,
+        users: usize,
+    ) -> (CtlServerT<MemorySocketT>, StandInServerT) {
+        let mut server = StandInServerT::new(name.as_bytes(), map.as_bytes(), 8);
+        for _ in 0..users {
+            server.players.push(CtlPlayerInfoT {
+                player_number: 0,
+                name: b"player".to_vec(),
+                colors: 0,
+                frags: 0,
+                connect_time: 0,
+                address: Vec::new(),
+            });
+        }
+        let socket = MemorySocketT::new(hub, addr);
+        let addr = socket.addr();
+        return (CtlServerT::new(socket, addr), server);
+    }
+
+    #[test]
+    fn slist_finds_stand_in_servers() {
+        let hub = HubT::default();
+        let mut servers = vec![
+            stand_in(&hub, "10.0.0.1:26000", "Fragfest", "dm4", 2),
+            stand_in(&hub, "10.0.0.2:26000", "fragfest", "e1m1", 0),
+            stand_in(&hub, "10.0.0.3:26000", "old", "start", 5),
+            stand_in(&hub, "10.0.0.4:26000", "Zeta", "dm6", 1),
+        ];
+        let mut socket = MemorySocketT::new(&hub, "10.0.0.9:5000");
+        let targets = [
+            "10.0.0.1:26000",
+            "10.0.0.2:26000",
+            "10.0.0.4:26000",
+            "10.0.0.7:26000",
+        ];
+        let mut slist = SlistT::new(targets.iter().map(|t| t.parse().unwrap()).collect());
+        slist.add_target("10.0.0.3:26000".parse().unwrap());
+        slist.add_target("10.0.0.3:26000".parse().unwrap());
+        assert_eq!(slist.targets().len(), 5);
+
+        assert!(slist.start(0.0));
+        assert!(!slist.start(0.0));
+        let mut time = 0.0;
+        let mut shown = 0;
+        while !slist.poll(&mut socket, time).unwrap() {
+            for (ctl, server) in servers.iter_mut() {
+                ctl.check_new_connections(server).unwrap();
+            }
+            time += 0.1;
+            shown += slist.take_unshown().len();
+        }
+        assert!((time - SLIST_TIME).abs() < 0.05);
+
+        // answers are deduped, name clashes renamed, and nobody is at 10.0.0.7
+        assert_eq!(shown, 4);
+        let names: Vec<&[Byte]> = slist.hosts().iter().map(|h| &h.name[..]).collect();
+        assert_eq!(names, [&b"Fragfest"[..], b"fragfest0", b"old", b"Zeta"]);
+        let ping = slist.hosts()[0].ping;
+        assert!(ping > 0.05 && ping < 0.15);
+
+        slist.sort(SlistSortKey::Users);
+        assert_eq!(slist.hosts()[0].users, 5);
+        slist.sort(SlistSortKey::Name);
+        assert_eq!(slist.hosts()[3].cname, "10.0.0.4:26000");
+        assert_eq!(
+            slist.hosts()[0].to_string(),
+            "Fragfest        dm4              2/ 8"
+        );
+        assert_eq!(slist.trailer(), "== end list ==\n\n");
+    }
+
+    #[test]
+    fn slist_without_servers() {
+        let hub = HubT::default();
+        let mut socket = MemorySocketT::new(&hub, "10.0.0.9:5000");
+        let mut slist = SlistT::broadcast(26000);
+        slist.start(0.0);
+        let mut time = 0.0;
+        while !slist.poll(&mut socket, time).unwrap() {
+            time += 0.5;
+        }
+        assert!(slist.hosts().is_empty());
+        assert!(!slist.in_progress());
+        assert_eq!(slist.trailer(), "No Quake servers found.\n\n");
+    }
+
+    #[test]
+    fn stats_totals_and_by_address() {
+        let mut one = DatagramStatsT::default();
+        one.messages_sent = 2;
+        one.bytes_sent = 100;
+        let mut two = DatagramStatsT::default();
+        two.messages_sent = 3;
+        two.rtt = 0.05;
+        let stats = vec![
+            ("10.0.0.1:26000".to_string(), one),
+            ("10.0.0.2:26000".to_string(), two),
+        ];
+
+        let total = print_stats(&stats, None);
+        assert!(total.contains("reliable messages sent     = 5\n"));
+        assert!(total.contains("bytes sent                 = 100\n"));
+
+        let each = print_stats(&stats, Some("*"));
+        assert!(each.starts_with("10.0.0.1:26000\n"));
+        assert!(each.contains("10.0.0.2:26000\n"));
+        assert!(each.contains("rtt                        = 50 ms\n"));
+
+        let one = print_stats(&stats, Some("10.0.0.2:26000"));
+        assert!(one.starts_with("10.0.0.2:26000\n"));
+        assert!(!one.contains("10.0.0.1"));
+        assert!(print_stats(&stats, Some("10.0.0.3:26000")).is_empty());
+    }
+
+    #[test]
+    fn socket_stats_count_since_startup() {
+        let mut retired = DatagramStatsT::default();
+        retired.messages_sent = 10;
+        let mut live = DatagramStatsT::default();
+        live.messages_sent = 2;
+        live.bytes_sent = 1000;
+        live.bytes_received = 500;
+        let sockets = vec![("10.0.0.1:26000".to_string(), live, 10.0)];
+
+        let total = print_socket_stats(&sockets, &retired, None);
+        assert!(total.starts_with("since startup:\n"));
+        assert!(total.contains("reliable messages sent     = 12\n"));
+        assert!(!total.contains("bytes/sec"));
+
+        let one = print_socket_stats(&sockets, &retired, Some("10.0.0.1:26000"));
+        assert!(one.starts_with("10.0.0.1:26000\n"));
+        assert!(one.contains("reliable messages sent     = 2\n"));
+        assert!(
+            one.contains("average since connect      = 100 bytes/sec sent, 50 received\n")
+        );
+        assert!(print_socket_stats(&sockets, &retired, Some("10.0.0.2:26000")).is_empty());
+    }
+
+    #[test]
+    fn idle_connections_get_keepalives() {
+        let hub = HubT::default();
+        let clock = ManualClockT::new(100.0);
+        let (mut a, mut b) = timed_pair(&hub, &clock, &MessageTimeoutT::shared(30.0));
+        let mut nops = 0;
+        for _ in 0..1200 {
+            clock.advance(0.1);
+            frame(&clock, &mut a).unwrap();
+            let received = frame(&clock, &mut b).unwrap();
+            nops += received.iter().filter(|data| data[..] == [NOP]).count();
+        }
+        assert!(a.close_reason().is_none() && b.close_reason().is_none());
+        assert!(a.keepalives_sent >= 20, "{}", a.keepalives_sent);
+        assert_eq!(nops as u32, a.keepalives_sent);
+        assert_eq!(a.connecttime(), 100.0);
+        assert!(a.last_message_time() > 200.0);
+        assert!(clock.now() - a.last_send_time() <= KEEPALIVE_TIME + 0.1);
+    }
+
+    #[test]
+    fn dead_peers_time_out() {
+        let hub = HubT::default();
+        let clock = ManualClockT::new(0.0);
+        let (mut a, _b) = timed_pair(&hub, &clock, &MessageTimeoutT::shared(30.0));
+        let mut error = None;
+        while error.is_none() && clock.now() < 40.0 {
+            clock.advance(0.1);
+            error = frame(&clock, &mut a).err();
+        }
+        assert_eq!(error, Some(NetError::Disconnected));
+        match a.close_reason() {
+            Some(&CloseReasonT::TimedOut(idle)) => assert!(idle > 30.0 && idle < 30.2),
+            reason => panic!("{:?}", reason),
+        }
+        assert_eq!(
+            a.close_reason().unwrap().to_string(),
+            "timed out after 30.0 seconds"
+        );
+        assert_eq!(a.get_message().err(), Some(NetError::Disconnected));
+        assert!(!a.can_send_message());
+    }
+
+    #[test]
+    fn timeout_changes_apply_to_open_connections() {
+        let hub = HubT::default();
+        let clock = ManualClockT::new(0.0);
+        let timeout = MessageTimeoutT::shared(300.0);
+        let (mut a, _b) = timed_pair(&hub, &clock, &timeout);
+        clock.advance(20.0);
+        frame(&clock, &mut a).unwrap();
+
+        timeout.set(10.0);
+        clock.advance(0.1);
+        assert_eq!(frame(&clock, &mut a), Err(NetError::Disconnected));
+        assert!(matches!(a.close_reason(), Some(&CloseReasonT::TimedOut(_))));
+
+        unsafe {
+            capi::net_messagetimeout.value = 45.0;
+            let cvar = MessageTimeoutT::cvar();
+            cvar.set(10.0);
+            assert_eq!(cvar.get(), 45.0);
+        }
+    }
+
+    #[test]
+    fn closing_gives_the_reason() {
+        let hub = HubT::default();
+        let clock = ManualClockT::new(0.0);
+        let (mut a, _b) = timed_pair(&hub, &clock, &MessageTimeoutT::default());
+        a.keepalive = 0.0;
+        clock.advance(10.0);
+        frame(&clock, &mut a).unwrap();
+        assert_eq!(a.keepalives_sent, 0);
+        a.close();
+        assert_eq!(a.close_reason(), Some(&CloseReasonT::Closed));
+        assert_eq!(a.close_reason().unwrap().to_string(), "closed");
+        assert_eq!(a.send_message(b"x"), Err(NetError::Disconnected));
+    }
 }
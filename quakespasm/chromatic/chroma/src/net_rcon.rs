@@ -0,0 +1,819 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// net_rcon.rs -- remote console: console commands sent over the connection control
+// protocol, signed with rcon_password against a challenge from the server
+
+/*
+
+A client first asks for a challenge, then sends the command with the hex HMAC-SHA256 of
+the challenge (4 bytes, little endian) and the command, keyed with the password.  The
+password itself never goes over the wire, and a challenge is good for one command, from
+one address, for RCON_CHALLENGE_TIME seconds.
+
+*/
+
+use console::execute_captured;
+use net::NetError;
+use net_defs::{NetLanSocket, NET_DATAGRAMSIZE};
+use net_dgrm::{CtlMessageT, CtlPlayerInfoT, CtlServerHandler, CONNECT_RETRY_TIME, CONNECT_TRIES};
+use net_main::capi::rcon_password as rcon_password_cvar;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{IpAddr, SocketAddr};
+use std::os::raw::{c_double, c_int};
+use Byte;
+
+/// seconds a challenge can be answered in
+pub const RCON_CHALLENGE_TIME: c_double = 5.0;
+/// challenges kept waiting at once; while they're all taken, new ones are refused
+pub const RCON_MAX_CHALLENGES: usize = 64;
+/// challenges an address can have waiting at once, whatever ports they were asked from, so
+/// one can't take them all
+pub const RCON_MAX_ADDR_CHALLENGES: usize = 4;
+/// bad passwords an address gets in RCON_FAILURE_TIME before it is ignored...
+pub const RCON_MAX_FAILURES: u32 = 5;
+/// ...until RCON_FAILURE_TIME has passed since the first of them
+pub const RCON_FAILURE_TIME: c_double = 60.0;
+/// the most of a command's output that is sent back, as MSG_ReadString stops there
+pub const RCON_MAX_OUTPUT: usize = 2047;
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// FIPS 180-4
+pub fn sha256(data: &[Byte]) -> [Byte; 32] {
+    let mut h: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in padded.chunks(64) {
+        let mut w = [0u32; 64];
+        for i in 0..16 {
+            let b = &block[i * 4..i * 4 + 4];
+            w[i] = u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let mut v = h;
+        for i in 0..64 {
+            let s1 = v[4].rotate_right(6) ^ v[4].rotate_right(11) ^ v[4].rotate_right(25);
+            let ch = (v[4] & v[5]) ^ (!v[4] & v[6]);
+            let t1 = v[7]
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(SHA256_K[i])
+                .wrapping_add(w[i]);
+            let s0 = v[0].rotate_right(2) ^ v[0].rotate_right(13) ^ v[0].rotate_right(22);
+            let maj = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+            let t2 = s0.wrapping_add(maj);
+            v = [
+                t1.wrapping_add(t2),
+                v[0],
+                v[1],
+                v[2],
+                v[3].wrapping_add(t1),
+                v[4],
+                v[5],
+                v[6],
+            ];
+        }
+        for i in 0..8 {
+            h[i] = h[i].wrapping_add(v[i]);
+        }
+    }
+
+    let mut digest = [0; 32];
+    for i in 0..8 {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&h[i].to_be_bytes());
+    }
+    return digest;
+}
+
+/// RFC 2104
+pub fn hmac_sha256(key: &[Byte], data: &[Byte]) -> [Byte; 32] {
+    let mut block = [0; 64];
+    if key.len() > 64 {
+        block[..32].copy_from_slice(&sha256(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner: Vec<Byte> = block.iter().map(|b| b ^ 0x36).collect();
+    inner.extend_from_slice(data);
+    let mut outer: Vec<Byte> = block.iter().map(|b| b ^ 0x5c).collect();
+    outer.extend_from_slice(&sha256(&inner));
+    return sha256(&outer);
+}
+
+/// what a CCREQ_RCON carries for command under challenge: the hex HMAC of both, keyed with
+/// the password
+pub fn rcon_digest(password: &[Byte], challenge: c_int, command: &[Byte]) -> Vec<Byte> {
+    let mut data = challenge.to_le_bytes().to_vec();
+    data.extend_from_slice(command);
+    let mut hex = Vec::with_capacity(64);
+    for b in hmac_sha256(password, &data).iter() {
+        hex.extend_from_slice(format!("{:02x}", b).as_bytes());
+    }
+    return hex;
+}
+
+/// the string of rcon_password
+///
+/// # Safety
+/// the cvar is read unsynchronized; only call this from the main thread
+pub unsafe fn rcon_password() -> Vec<Byte> {
+    let string = rcon_password_cvar.string;
+    if string.is_null() {
+        return Vec::new();
+    }
+    return CStr::from_ptr(string).to_bytes().to_vec();
+}
+
+/// a challenge handed out and not yet answered
+struct RconChallengeT {
+    addr: SocketAddr,
+    challenge: c_int,
+    time: c_double,
+}
+
+/// bad passwords from an address
+struct RconFailuresT {
+    count: u32,
+    /// when the first of them came
+    since: c_double,
+}
+
+/// the server side of rcon: hands out challenges, checks the commands signed with them,
+/// and ignores addresses that keep getting the password wrong
+pub struct RconServerT {
+    /// rcon_password; empty turns rcon off
+    pub password: Vec<Byte>,
+    challenges: Vec<RconChallengeT>,
+    failures: HashMap<IpAddr, RconFailuresT>,
+    /// keyed by the os, so challenges can't be guessed
+    keys: RandomState,
+    /// challenges made so far
+    count: u64,
+}
+
+impl RconServerT {
+    pub fn new(password: &[Byte]) -> Self {
+        Self {
+            password: password.to_vec(),
+            challenges: Vec::new(),
+            failures: HashMap::new(),
+            keys: RandomState::new(),
+            count: 0,
+        }
+    }
+
+    /// true if addr has run out of bad passwords for now
+    pub fn blocked(&self, addr: &SocketAddr, net_time: c_double) -> bool {
+        return match self.failures.get(&addr.ip()) {
+            Some(f) => f.count >= RCON_MAX_FAILURES && net_time - f.since < RCON_FAILURE_TIME,
+            None => false,
+        };
+    }
+
+    fn fail(&mut self, addr: &SocketAddr, net_time: c_double) {
+        self.failures
+            .retain(|_, f| net_time - f.since < RCON_FAILURE_TIME);
+        let f = self.failures.entry(addr.ip()).or_insert(RconFailuresT {
+            count: 0,
+            since: net_time,
+        });
+        f.count += 1;
+    }
+
+    /// a challenge for addr, or None if there's no room for one
+    fn new_challenge(&mut self, addr: &SocketAddr, net_time: c_double) -> Option<c_int> {
+        let waiting = self
+            .challenges
+            .iter()
+            .filter(|c| c.addr.ip() == addr.ip())
+            .count();
+        if waiting >= RCON_MAX_ADDR_CHALLENGES || self.challenges.len() >= RCON_MAX_CHALLENGES {
+            return None;
+        }
+        loop {
+            let mut hasher = self.keys.build_hasher();
+            hasher.write_u64(self.count);
+            self.count += 1;
+            let challenge = hasher.finish() as c_int;
+            if challenge == 0 {
+                continue;
+            }
+            self.challenges.push(RconChallengeT {
+                addr: *addr,
+                challenge,
+                time: net_time,
+            });
+            return Some(challenge);
+        }
+    }
+
+    /// the answer to a CCREQ_RCON from addr: a challenge for challenge 0, otherwise the
+    /// output of execute run on the command, or why it wasn't.  None for an address that
+    /// is being ignored
+    pub fn request(
+        &mut self,
+        addr: &SocketAddr,
+        challenge: c_int,
+        digest: &[Byte],
+        command: &[Byte],
+        net_time: c_double,
+        execute: &mut dyn FnMut(&[Byte]) -> Vec<Byte>,
+    ) -> Option<CtlMessageT> {
+        if self.blocked(addr, net_time) {
+            return None;
+        }
+        let reply = |output: &[Byte]| CtlMessageT::RepRcon {
+            challenge: 0,
+            output: output.to_vec(),
+        };
+        if self.password.is_empty() {
+            return Some(reply(b"rcon is disabled.\n"));
+        }
+
+        self.challenges
+            .retain(|c| net_time - c.time < RCON_CHALLENGE_TIME);
+        if challenge == 0 {
+            return match self.new_challenge(addr, net_time) {
+                Some(challenge) => Some(CtlMessageT::RepRcon {
+                    challenge,
+                    output: Vec::new(),
+                }),
+                None => Some(reply(b"Too many rcon challenges, try again later.\n")),
+            };
+        }
+
+        // a challenge is only good once
+        let i = self
+            .challenges
+            .iter()
+            .position(|c| c.addr == *addr && c.challenge == challenge);
+        match i {
+            Some(i) => self.challenges.remove(i),
+            None => return Some(reply(b"Bad rcon challenge.\n")),
+        };
+
+        let expected = rcon_digest(&self.password, challenge, command);
+        let differ = expected.len() != digest.len()
+            || expected.iter().zip(digest).fold(0, |d, (a, b)| d | (a ^ b)) != 0;
+        if differ {
+            self.fail(addr, net_time);
+            return Some(reply(b"Bad rcon password.\n"));
+        }
+
+        let mut output: Vec<Byte> = execute(command).into_iter().filter(|&c| c != 0).collect();
+        output.truncate(RCON_MAX_OUTPUT);
+        return Some(reply(&output));
+    }
+}
+
+/// a server handler with rcon against rcon_password, running commands on the console;
+/// everything else is left to handler
+pub struct ConsoleRconT<H: CtlServerHandler> {
+    pub handler: H,
+    rcon: RconServerT,
+}
+
+impl<H: CtlServerHandler> ConsoleRconT<H> {
+    /// # Safety
+    /// rcon_password is read and commands are run as the requests come in; only use this
+    /// from the main thread
+    pub unsafe fn new(handler: H) -> Self {
+        Self {
+            handler,
+            rcon: RconServerT::new(b""),
+        }
+    }
+}
+
+impl<H: CtlServerHandler> CtlServerHandler for ConsoleRconT<H> {
+    fn host_name(&self) -> Vec<Byte> {
+        return self.handler.host_name();
+    }
+    fn level_name(&self) -> Vec<Byte> {
+        return self.handler.level_name();
+    }
+    fn current_players(&self) -> c_int {
+        return self.handler.current_players();
+    }
+    fn max_players(&self) -> c_int {
+        return self.handler.max_players();
+    }
+    fn player_info(&self, player_number: c_int) -> Option<CtlPlayerInfoT> {
+        return self.handler.player_info(player_number);
+    }
+    fn rule_after(&self, prev: &[Byte]) -> Option<(Vec<Byte>, Vec<Byte>)> {
+        return self.handler.rule_after(prev);
+    }
+    fn accept(&mut self, addr: &SocketAddr) -> Result<c_int, Vec<Byte>> {
+        return self.handler.accept(addr);
+    }
+    fn drop_connection(&mut self, addr: &SocketAddr) {
+        self.handler.drop_connection(addr);
+    }
+    fn rcon(
+        &mut self,
+        addr: &SocketAddr,
+        challenge: c_int,
+        digest: &[Byte],
+        command: &[Byte],
+        net_time: c_double,
+    ) -> Option<CtlMessageT> {
+        self.rcon.password = unsafe { rcon_password() };
+        let mut execute = |command: &[Byte]| unsafe { execute_captured(command) };
+        return self
+            .rcon
+            .request(addr, challenge, digest, command, net_time, &mut execute);
+    }
+}
+
+/// where an rcon command stands
+#[derive(Clone, Debug, PartialEq)]
+pub enum RconStateT {
+    /// waiting for the challenge or the output
+    Waiting,
+    /// what the server said: the command's output, or why it wasn't run
+    Done(Vec<Byte>),
+    /// "No Response"
+    Failed(&'static str),
+}
+
+/// the client side of rcon: gets a challenge, then sends the command signed with it; poll
+/// it until it isn't Waiting
+pub struct RconClientT {
+    addr: SocketAddr,
+    password: Vec<Byte>,
+    command: Vec<Byte>,
+    state: RconStateT,
+    /// the server's challenge, 0 until it comes
+    challenge: c_int,
+    /// challenge requests sent
+    tries: c_int,
+    /// when the last request went out
+    send_time: Option<c_double>,
+}
+
+impl RconClientT {
+    pub fn new(addr: SocketAddr, password: &[Byte], command: &[Byte]) -> Self {
+        Self {
+            addr,
+            password: password.to_vec(),
+            command: command.to_vec(),
+            state: RconStateT::Waiting,
+            challenge: 0,
+            tries: 0,
+            send_time: None,
+        }
+    }
+
+    pub fn state(&self) -> &RconStateT {
+        return &self.state;
+    }
+
+    fn send<S: NetLanSocket>(
+        &mut self,
+        socket: &mut S,
+        request: &CtlMessageT,
+        net_time: c_double,
+    ) -> Result<(), NetError> {
+        socket.write(&request.encode(), &self.addr)?;
+        self.send_time = Some(net_time);
+        return Ok(());
+    }
+
+    fn ask_challenge<S: NetLanSocket>(
+        &mut self,
+        socket: &mut S,
+        net_time: c_double,
+    ) -> Result<(), NetError> {
+        self.tries += 1;
+        let request = CtlMessageT::ReqRcon {
+            challenge: 0,
+            digest: Vec::new(),
+            command: Vec::new(),
+        };
+        return self.send(socket, &request, net_time);
+    }
+
+    /// read the answers waiting and send what comes next.  Only the challenge is asked
+    /// for again; the command isn't, as its challenge is used up once it arrives
+    pub fn poll<S: NetLanSocket>(
+        &mut self,
+        socket: &mut S,
+        net_time: c_double,
+    ) -> Result<&RconStateT, NetError> {
+        if self.state != RconStateT::Waiting {
+            return Ok(&self.state);
+        }
+        let send_time = match self.send_time {
+            Some(send_time) => send_time,
+            None => {
+                self.ask_challenge(socket, net_time)?;
+                return Ok(&self.state);
+            }
+        };
+
+        let mut buf = [0; NET_DATAGRAMSIZE];
+        while let Some((len, readaddr)) = socket.read(&mut buf)? {
+            if readaddr != self.addr {
+                continue;
+            }
+            let (challenge, output) = match CtlMessageT::decode(&buf[..len]) {
+                Ok(CtlMessageT::RepRcon { challenge, output }) => (challenge, output),
+                // unexpected response
+                _ => continue,
+            };
+            if challenge == 0 {
+                self.state = RconStateT::Done(output);
+                return Ok(&self.state);
+            }
+            if self.challenge == 0 {
+                self.challenge = challenge;
+                let request = CtlMessageT::ReqRcon {
+                    challenge,
+                    digest: rcon_digest(&self.password, challenge, &self.command),
+                    command: self.command.clone(),
+                };
+                self.send(socket, &request, net_time)?;
+                return Ok(&self.state);
+            }
+        }
+
+        if net_time - send_time >= CONNECT_RETRY_TIME {
+            if self.challenge == 0 && self.tries < CONNECT_TRIES {
+                self.ask_challenge(socket, net_time)?;
+            } else {
+                self.state = RconStateT::Failed("No Response");
+            }
+        }
+        return Ok(&self.state);
+    }
+}
+
+#[allow(bad_style)]
+pub mod capi {
+    use super::{rcon_password, ConsoleRconT};
+    use cls;
+    use net::NetError;
+    use net_defs::QSocketT;
+    use net_dgrm::{CtlMessageT, CtlServerHandler};
+    use net_main::capi::{net_hostport, net_time, rcon_address};
+    use net_udp::{get_addr_from_name, UdpDriverT};
+    use std::cell::RefCell;
+    use std::ffi::{CStr, CString};
+    use std::os::raw::{c_char, c_int};
+    use std::ptr::copy_nonoverlapping;
+    use std::slice;
+    use SizeBufT;
+
+    extern "C" {
+        fn Cmd_Argc() -> c_int;
+        fn Cmd_Args() -> *const c_char;
+        fn Con_Printf(fmt: *const c_char, ...);
+        fn NET_QSocketGetAddressString(sock: *const QSocketT) -> *const c_char;
+    }
+
+    thread_local! {
+        /// the rcon of the engine's server, from the first CCREQ_RCON
+        static SERVER_RCON: RefCell<Option<ConsoleRconT<()>>> = const { RefCell::new(None) };
+    }
+
+    /// Formerly: none
+    /// the answer to the CCREQ_RCON in packet, from the address from, in place of it; an
+    /// empty packet when there's nothing to send back
+    ///
+    /// # Safety
+    ///
+    /// from must be a string, and packet a sizebuf holding a whole datagram; the command
+    /// runs on the engine's state, so only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn Datagram_Rcon(from: *const c_char, packet: *mut SizeBufT) {
+        let packet = &mut *packet;
+        let request =
+            CtlMessageT::decode(slice::from_raw_parts(packet.data, packet.cursize as usize));
+        packet.cursize = 0;
+        let addr = get_addr_from_name(&CStr::from_ptr(from).to_string_lossy(), 0);
+        let (addr, challenge, digest, command) = match (addr, request) {
+            (
+                Some(addr),
+                Ok(CtlMessageT::ReqRcon {
+                    challenge,
+                    digest,
+                    command,
+                }),
+            ) => (addr, challenge, digest, command),
+            _ => return,
+        };
+
+        // taken out while the command runs, in case it gets here again
+        let mut rcon = SERVER_RCON
+            .with(|server| server.borrow_mut().take())
+            .unwrap_or_else(|| ConsoleRconT::new(()));
+        let reply = rcon.rcon(&addr, challenge, &digest, &command, net_time);
+        SERVER_RCON.with(|server| *server.borrow_mut() = Some(rcon));
+
+        let reply = match reply {
+            Some(reply) => reply.encode(),
+            None => return,
+        };
+        if reply.len() > packet.maxsize as usize {
+            return;
+        }
+        copy_nonoverlapping(reply.as_ptr(), packet.data, reply.len());
+        packet.cursize = reply.len() as c_int;
+    }
+
+    /// the server rcon talks to: rcon_address, or else the one connected to
+    unsafe fn server_address() -> Option<String> {
+        let address = CStr::from_ptr(rcon_address.string).to_string_lossy();
+        if !address.is_empty() {
+            return Some(address.into_owned());
+        }
+        if cls.netcon.is_null() {
+            return None;
+        }
+        let address = CStr::from_ptr(NET_QSocketGetAddressString(cls.netcon));
+        return Some(address.to_string_lossy().into_owned());
+    }
+
+    /// Formerly: none
+    /// rcon <command>: run command on the server with rcon_password and print its output,
+    /// waiting for it like connect does
+    ///
+    /// # Safety
+    ///
+    /// reads the command arguments and cvars; only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn Rcon_f() {
+        if Cmd_Argc() < 2 {
+            Con_Printf(b"usage: rcon <command>\n\0".as_ptr() as *const c_char);
+            return;
+        }
+        let password = rcon_password();
+        if password.is_empty() {
+            Con_Printf(b"rcon_password is not set.\n\0".as_ptr() as *const c_char);
+            return;
+        }
+        let address = match server_address() {
+            Some(address) => address,
+            None => {
+                Con_Printf(
+                    b"Not connected, and rcon_address is not set.\n\0".as_ptr() as *const c_char
+                );
+                return;
+            }
+        };
+        let port = net_hostport as u16;
+        let addr = match get_addr_from_name(&address, port) {
+            Some(addr) => addr,
+            None => {
+                let address = CString::new(address).unwrap_or_default();
+                Con_Printf(
+                    b"Unable to resolve %s\n\0".as_ptr() as *const c_char,
+                    address.as_ptr(),
+                );
+                return;
+            }
+        };
+
+        let command = CStr::from_ptr(Cmd_Args()).to_bytes();
+        let mut driver = UdpDriverT::new((), port);
+        let output = match driver.rcon_addr(addr, &password, command) {
+            Ok(output) => output,
+            Err(NetError::Rejected(reason)) => format!("{}\n", reason).into_bytes(),
+            Err(e) => format!("rcon failed: {:?}\n", e).into_bytes(),
+        };
+        let output = CString::new(output).unwrap_or_default();
+        Con_Printf(b"%s\0".as_ptr() as *const c_char, output.as_ptr());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDR: &str = "10.0.0.1:26000";
+
+    fn hex(digest: &[Byte]) -> String {
+        return digest.iter().map(|b| format!("{:02x}", b)).collect();
+    }
+
+    #[test]
+    fn sha256_matches_fips_180_4() {
+        assert_eq!(
+            hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex(&sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(&sha256(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            hex(&sha256(&[b'a'; 1000000])),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+
+    #[test]
+    fn hmac_sha256_matches_rfc_4231() {
+        let key: Vec<Byte> = (1..=25).collect();
+        let cases: [(&[Byte], &[Byte], &str); 6] = [
+            (
+                &[0x0b; 20],
+                b"Hi There",
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            ),
+            (
+                b"Jefe",
+                b"what do ya want for nothing?",
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                &[0xaa; 20],
+                &[0xdd; 50],
+                "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+            ),
+            (
+                &key,
+                &[0xcd; 50],
+                "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+            ),
+            (
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ),
+            (
+                &[0xaa; 131],
+                b"This is a test using a larger than block-size key and a larger than \
+                  block-size data. The key needs to be hashed before being used by the HMAC \
+                  algorithm.",
+                "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+            ),
+        ];
+        for &(key, data, digest) in cases.iter() {
+            assert_eq!(hex(&hmac_sha256(key, data)), digest);
+        }
+    }
+
+    /// what server says to a request, with the commands it runs echoed back
+    fn ask(
+        server: &mut RconServerT,
+        addr: &str,
+        challenge: c_int,
+        digest: &[Byte],
+        command: &[Byte],
+        net_time: c_double,
+    ) -> Option<(c_int, Vec<Byte>)> {
+        let mut echo = |command: &[Byte]| command.to_vec();
+        let addr = addr.parse().unwrap();
+        return match server.request(&addr, challenge, digest, command, net_time, &mut echo) {
+            Some(CtlMessageT::RepRcon { challenge, output }) => Some((challenge, output)),
+            Some(_) => panic!("not an rcon reply"),
+            None => None,
+        };
+    }
+
+    #[test]
+    fn signed_commands_run_once() {
+        let mut server = RconServerT::new(b"secret");
+        let (challenge, _) = ask(&mut server, ADDR, 0, b"", b"", 0.0).unwrap();
+        assert_ne!(challenge, 0);
+
+        let digest = rcon_digest(b"secret", challenge, b"status");
+        let reply = ask(&mut server, ADDR, challenge, &digest, b"status", 1.0);
+        assert_eq!(reply, Some((0, b"status".to_vec())));
+        let reply = ask(&mut server, ADDR, challenge, &digest, b"status", 1.0);
+        assert_eq!(reply, Some((0, b"Bad rcon challenge.\n".to_vec())));
+
+        // a challenge is for the address it was given to, and only for so long
+        let (challenge, _) = ask(&mut server, ADDR, 0, b"", b"", 2.0).unwrap();
+        let digest = rcon_digest(b"secret", challenge, b"status");
+        let reply = ask(
+            &mut server,
+            "10.0.0.2:26000",
+            challenge,
+            &digest,
+            b"status",
+            2.0,
+        );
+        assert_eq!(reply, Some((0, b"Bad rcon challenge.\n".to_vec())));
+        let late = 2.0 + RCON_CHALLENGE_TIME;
+        let reply = ask(&mut server, ADDR, challenge, &digest, b"status", late);
+        assert_eq!(reply, Some((0, b"Bad rcon challenge.\n".to_vec())));
+
+        server.password.clear();
+        let reply = ask(&mut server, ADDR, 0, b"", b"", 10.0);
+        assert_eq!(reply, Some((0, b"rcon is disabled.\n".to_vec())));
+    }
+
+    #[test]
+    fn bad_passwords_get_an_address_ignored() {
+        let mut server = RconServerT::new(b"secret");
+        for _ in 0..RCON_MAX_FAILURES {
+            let (challenge, _) = ask(&mut server, ADDR, 0, b"", b"", 0.0).unwrap();
+            let digest = rcon_digest(b"guess", challenge, b"quit");
+            let reply = ask(&mut server, ADDR, challenge, &digest, b"quit", 0.0);
+            assert_eq!(reply, Some((0, b"Bad rcon password.\n".to_vec())));
+        }
+        assert_eq!(ask(&mut server, ADDR, 0, b"", b"", 1.0), None);
+        assert!(ask(&mut server, "10.0.0.2:26000", 0, b"", b"", 1.0).is_some());
+        assert!(ask(&mut server, ADDR, 0, b"", b"", RCON_FAILURE_TIME).is_some());
+    }
+
+    #[test]
+    fn challenges_are_limited() {
+        let mut server = RconServerT::new(b"secret");
+        let busy = Some((0, b"Too many rcon challenges, try again later.\n".to_vec()));
+        for _ in 0..RCON_MAX_ADDR_CHALLENGES {
+            let (challenge, _) = ask(&mut server, ADDR, 0, b"", b"", 0.0).unwrap();
+            assert_ne!(challenge, 0);
+        }
+        assert_eq!(ask(&mut server, ADDR, 0, b"", b"", 0.0), busy);
+
+        // fill the table from other addresses; what's waiting isn't pushed out
+        let mut port = 26001;
+        while server.challenges.len() < RCON_MAX_CHALLENGES {
+            let addr = format!("10.0.1.{}:{}", port % 200, port);
+            let (challenge, _) = ask(&mut server, &addr, 0, b"", b"", 1.0).unwrap();
+            assert_ne!(challenge, 0);
+            port += 1;
+        }
+        assert_eq!(ask(&mut server, "10.0.2.1:26000", 0, b"", b"", 1.0), busy);
+        assert_eq!(server.challenges[0].addr, ADDR.parse().unwrap());
+
+        // until they run out
+        let (challenge, _) = ask(&mut server, ADDR, 0, b"", b"", RCON_CHALLENGE_TIME).unwrap();
+        assert_ne!(challenge, 0);
+    }
+
+    #[test]
+    fn other_ports_dont_get_more_challenges() {
+        let mut server = RconServerT::new(b"secret");
+        let busy = Some((0, b"Too many rcon challenges, try again later.\n".to_vec()));
+        for port in 26000..26000 + RCON_MAX_CHALLENGES {
+            let addr = format!("10.0.0.1:{}", port);
+            let reply = ask(&mut server, &addr, 0, b"", b"", 0.0);
+            if port < 26000 + RCON_MAX_ADDR_CHALLENGES {
+                assert_ne!(reply.unwrap().0, 0);
+            } else {
+                assert_eq!(reply, busy);
+            }
+        }
+        assert_eq!(server.challenges.len(), RCON_MAX_ADDR_CHALLENGES);
+        let (challenge, _) = ask(&mut server, "10.0.0.2:26000", 0, b"", b"", 0.0).unwrap();
+        assert_ne!(challenge, 0);
+    }
+}
@@ -0,0 +1,890 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// net_sim.rs -- a bad network on purpose: latency, jitter, loss, reordering and duplication
+// put between a socket or driver and the layer above it
+
+use mathlib::RandT;
+use net::{NetConnection, NetError, NetMessageT};
+use net_defs::{NetDriver, NetLanSocket};
+use net_dgrm::DatagramStatsT;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+use std::os::raw::c_double;
+use std::rc::Rc;
+use Byte;
+
+/// how long past its time a reordered packet is held, so the ones behind it get ahead
+pub const REORDER_TIME: c_double = 0.05;
+
+/// the net_fake cvars.  A NetSimSocketT applies them to every datagram; a NetSimConnectionT
+/// works on whole messages, and only delays reliable ones
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NetSimSettingsT {
+    /// net_fakelag, milliseconds every packet is held
+    pub lag: c_double,
+    /// net_fakejitter, up to this many milliseconds more
+    pub jitter: c_double,
+    /// net_fakeloss, percent of packets dropped
+    pub loss: c_double,
+    /// net_fakedup, percent of packets sent twice
+    pub dup: c_double,
+    /// net_fakereorder, percent of packets held back REORDER_TIME
+    pub reorder: c_double,
+    /// net_fakeseed, the same seed makes the same network
+    pub seed: u32,
+}
+
+impl NetSimSettingsT {
+    /// the settings of the net_fake cvars
+    ///
+    /// # Safety
+    /// the cvars are read unsynchronized; only call this from the main thread
+    pub unsafe fn from_cvars() -> Self {
+        use net_main::capi::*;
+        return Self {
+            lag: net_fakelag.value as c_double,
+            jitter: net_fakejitter.value as c_double,
+            loss: net_fakeloss.value as c_double,
+            dup: net_fakedup.value as c_double,
+            reorder: net_fakereorder.value as c_double,
+            seed: net_fakeseed.value.max(0.0) as u32,
+        };
+    }
+
+    /// false if packets go through untouched
+    pub fn active(&self) -> bool {
+        return self.lag > 0.0
+            || self.jitter > 0.0
+            || self.loss > 0.0
+            || self.dup > 0.0
+            || self.reorder > 0.0;
+    }
+}
+
+/// in [0, 1)
+fn frac(rand: &mut RandT) -> c_double {
+    return rand.rand() as c_double / (RandT::MAX + 1) as c_double;
+}
+
+/// true percent times in a hundred
+fn chance(rand: &mut RandT, percent: c_double) -> bool {
+    return percent > 0.0 && frac(rand) * 100.0 < percent;
+}
+
+struct DelayedT<T> {
+    /// the net_time it comes out
+    due: c_double,
+    item: T,
+}
+
+/// packets on their way, in the order they come out
+pub struct NetSimQueueT<T> {
+    items: VecDeque<DelayedT<T>>,
+    rand: RandT,
+    /// when the last push_ordered item comes out
+    last_ordered: c_double,
+}
+
+impl<T: Clone> NetSimQueueT<T> {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            items: VecDeque::new(),
+            rand: RandT::new(seed),
+            last_ordered: 0.0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        return self.items.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.items.is_empty();
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    /// net_fakelag plus some of net_fakejitter, in seconds
+    fn delay(&mut self, settings: &NetSimSettingsT) -> c_double {
+        let jitter = settings.jitter.max(0.0) * frac(&mut self.rand);
+        return (settings.lag.max(0.0) + jitter) / 1000.0;
+    }
+
+    /// after everything due at the same time, so equal delays keep their order
+    fn insert(&mut self, due: c_double, item: T) {
+        let i = self
+            .items
+            .iter()
+            .position(|d| d.due > due)
+            .unwrap_or(self.items.len());
+        self.items.insert(i, DelayedT { due, item });
+    }
+
+    /// item sent at now; dropped, delayed, held back or doubled as settings say
+    pub fn push(&mut self, settings: &NetSimSettingsT, now: c_double, item: T) {
+        if chance(&mut self.rand, settings.loss) {
+            return;
+        }
+        if chance(&mut self.rand, settings.dup) {
+            let due = now + self.delay(settings);
+            self.insert(due, item.clone());
+        }
+        let mut due = now + self.delay(settings);
+        if chance(&mut self.rand, settings.reorder) {
+            due += REORDER_TIME;
+        }
+        self.insert(due, item);
+    }
+
+    /// item sent at now, only delayed, and never ahead of an earlier push_ordered
+    pub fn push_ordered(&mut self, settings: &NetSimSettingsT, now: c_double, item: T) {
+        let due = (now + self.delay(settings)).max(self.last_ordered);
+        self.last_ordered = due;
+        self.insert(due, item);
+    }
+
+    /// the next item due by now
+    pub fn peek(&self, now: c_double) -> Option<&T> {
+        return match self.items.front() {
+            Some(d) if d.due <= now => Some(&d.item),
+            _ => None,
+        };
+    }
+
+    pub fn pop(&mut self, now: c_double) -> Option<T> {
+        self.peek(now)?;
+        return self.items.pop_front().map(|d| d.item);
+    }
+}
+
+/// a lan socket with a bad network in both directions; the datagram layer above it has to
+/// cope with what comes out
+pub struct NetSimSocketT<S: NetLanSocket> {
+    socket: S,
+    pub settings: NetSimSettingsT,
+    net_time: c_double,
+    incoming: NetSimQueueT<(Vec<Byte>, SocketAddr)>,
+    outgoing: NetSimQueueT<(Vec<Byte>, SocketAddr)>,
+}
+
+impl<S: NetLanSocket> NetSimSocketT<S> {
+    pub fn new(socket: S, settings: NetSimSettingsT) -> Self {
+        Self {
+            socket,
+            settings,
+            net_time: 0.0,
+            incoming: NetSimQueueT::new(settings.seed),
+            outgoing: NetSimQueueT::new(settings.seed.wrapping_add(1)),
+        }
+    }
+
+    /// Formerly: SetNetTime
+    /// the time delays are measured against
+    pub fn set_net_time(&mut self, time: c_double) {
+        self.net_time = time;
+    }
+
+    pub fn socket(&self) -> &S {
+        return &self.socket;
+    }
+
+    pub fn socket_mut(&mut self) -> &mut S {
+        return &mut self.socket;
+    }
+
+    /// datagrams held in either direction
+    pub fn in_flight(&self) -> usize {
+        return self.incoming.len() + self.outgoing.len();
+    }
+
+    /// write what is due
+    fn flush(&mut self) -> io::Result<()> {
+        while let Some((data, addr)) = self.outgoing.pop(self.net_time) {
+            self.socket.write(&data, &addr)?;
+        }
+        return Ok(());
+    }
+}
+
+impl<S: NetLanSocket> NetLanSocket for NetSimSocketT<S> {
+    fn read(&mut self, buf: &mut [Byte]) -> io::Result<Option<(usize, SocketAddr)>> {
+        self.flush()?;
+        let mut packet = vec![0; buf.len()];
+        while let Some((len, addr)) = self.socket.read(&mut packet)? {
+            let settings = self.settings;
+            self.incoming
+                .push(&settings, self.net_time, (packet[..len].to_vec(), addr));
+        }
+        return match self.incoming.pop(self.net_time) {
+            Some((data, addr)) => {
+                let len = data.len().min(buf.len());
+                buf[..len].copy_from_slice(&data[..len]);
+                Ok(Some((len, addr)))
+            }
+            None => Ok(None),
+        };
+    }
+
+    /// the datagram counts as sent, whatever becomes of it
+    fn write(&mut self, data: &[Byte], addr: &SocketAddr) -> io::Result<usize> {
+        let settings = self.settings;
+        self.outgoing
+            .push(&settings, self.net_time, (data.to_vec(), *addr));
+        self.flush()?;
+        return Ok(data.len());
+    }
+}
+
+/// what a NetSimDriverT shares with its connections
+struct NetSimLinkT {
+    net_time: Cell<c_double>,
+    settings: Cell<NetSimSettingsT>,
+}
+
+/// a connection with a bad network in both directions.  Unreliable messages are dropped,
+/// delayed, reordered and doubled; reliable ones are only delayed, in order, as the transport
+/// below would have got them through in the end
+pub struct NetSimConnectionT<C: NetConnection> {
+    connection: C,
+    link: Rc<NetSimLinkT>,
+    incoming: NetSimQueueT<NetMessageT>,
+    outgoing: NetSimQueueT<NetMessageT>,
+    /// a reliable message is waiting in outgoing
+    sending: bool,
+}
+
+impl<C: NetConnection> NetSimConnectionT<C> {
+    fn new(connection: C, link: Rc<NetSimLinkT>, seed: u32) -> Self {
+        Self {
+            connection,
+            link,
+            incoming: NetSimQueueT::new(seed),
+            outgoing: NetSimQueueT::new(seed.wrapping_add(1)),
+            sending: false,
+        }
+    }
+
+    pub fn connection(&self) -> &C {
+        return &self.connection;
+    }
+
+    /// send what is due; a reliable message waits for the last one to be acknowledged
+    fn flush(&mut self) -> Result<(), NetError> {
+        let now = self.link.net_time.get();
+        loop {
+            match self.outgoing.peek(now) {
+                None => return Ok(()),
+                Some(&NetMessageT::Reliable(_)) if !self.connection.can_send_message() => {
+                    return Ok(());
+                }
+                Some(_) => {}
+            }
+            match self.outgoing.pop(now) {
+                Some(NetMessageT::Reliable(data)) => {
+                    self.sending = false;
+                    self.connection.send_message(&data)?;
+                }
+                Some(NetMessageT::Unreliable(data)) => {
+                    self.connection.send_unreliable_message(&data)?;
+                }
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+impl<C: NetConnection> NetConnection for NetSimConnectionT<C> {
+    fn get_message(&mut self) -> Result<Option<NetMessageT>, NetError> {
+        self.flush()?;
+        let now = self.link.net_time.get();
+        let settings = self.link.settings.get();
+        while let Some(message) = self.connection.get_message()? {
+            match message {
+                NetMessageT::Reliable(_) => self.incoming.push_ordered(&settings, now, message),
+                NetMessageT::Unreliable(_) => self.incoming.push(&settings, now, message),
+            }
+        }
+        return Ok(self.incoming.pop(now));
+    }
+
+    fn send_message(&mut self, data: &[Byte]) -> Result<(), NetError> {
+        if !self.can_send_message() {
+            return Err(NetError::CantSend);
+        }
+        let settings = self.link.settings.get();
+        let now = self.link.net_time.get();
+        self.outgoing
+            .push_ordered(&settings, now, NetMessageT::Reliable(data.to_vec()));
+        self.sending = true;
+        return self.flush();
+    }
+
+    fn send_unreliable_message(&mut self, data: &[Byte]) -> Result<(), NetError> {
+        let settings = self.link.settings.get();
+        let now = self.link.net_time.get();
+        self.outgoing
+            .push(&settings, now, NetMessageT::Unreliable(data.to_vec()));
+        return self.flush();
+    }
+
+    fn can_send_message(&mut self) -> bool {
+        if self.flush().is_err() {
+            return false;
+        }
+        return !self.sending && self.connection.can_send_message();
+    }
+
+    fn close(&mut self) {
+        self.incoming.clear();
+        self.outgoing.clear();
+        self.sending = false;
+        self.connection.close();
+    }
+
+    fn address(&self) -> String {
+        return self.connection.address();
+    }
+
+    fn stats(&self) -> Option<DatagramStatsT> {
+        return self.connection.stats();
+    }
+}
+
+/// wraps another driver, putting a bad network under every connection it makes; as for any
+/// NetSimConnectionT, loss, duplication and reordering only touch unreliable messages
+pub struct NetSimDriverT<D: NetDriver> {
+    pub driver: D,
+    link: Rc<NetSimLinkT>,
+    /// connections made so far; each gets its own numbers from the seed
+    connections: u32,
+}
+
+impl<D: NetDriver> NetSimDriverT<D> {
+    pub fn new(driver: D, settings: NetSimSettingsT) -> Self {
+        Self {
+            driver,
+            link: Rc::new(NetSimLinkT {
+                net_time: Cell::new(0.0),
+                settings: Cell::new(settings),
+            }),
+            connections: 0,
+        }
+    }
+
+    /// Formerly: SetNetTime
+    /// the time delays are measured against, for every connection
+    pub fn set_net_time(&mut self, time: c_double) {
+        self.link.net_time.set(time);
+    }
+
+    pub fn settings(&self) -> NetSimSettingsT {
+        return self.link.settings.get();
+    }
+
+    /// new settings for every connection; the seed only counts for new connections
+    pub fn set_settings(&mut self, settings: NetSimSettingsT) {
+        self.link.settings.set(settings);
+    }
+
+    fn wrap(&mut self, connection: Box<dyn NetConnection>) -> Box<dyn NetConnection> {
+        let seed = self
+            .link
+            .settings
+            .get()
+            .seed
+            .wrapping_add(self.connections.wrapping_mul(2));
+        self.connections += 1;
+        return Box::new(NetSimConnectionT::new(connection, self.link.clone(), seed));
+    }
+}
+
+impl<D: NetDriver> NetDriver for NetSimDriverT<D> {
+    fn name(&self) -> &str {
+        return self.driver.name();
+    }
+
+    fn listen(&mut self, state: bool) {
+        self.driver.listen(state);
+    }
+
+    fn connect(&mut self, host: &str) -> Result<Option<Box<dyn NetConnection>>, NetError> {
+        return Ok(self.driver.connect(host)?.map(|c| self.wrap(c)));
+    }
+
+    fn check_new_connections(&mut self) -> Result<Option<Box<dyn NetConnection>>, NetError> {
+        return Ok(self.driver.check_new_connections()?.map(|c| self.wrap(c)));
+    }
+}
+
+#[allow(bad_style)]
+pub mod capi {
+    use super::{NetSimQueueT, NetSimSettingsT};
+    use net_defs::{QSockAddr, QSocketT, NET_DATAGRAMSIZE};
+    use net_main::capi::net_time;
+    use net_sys::SysSocketT;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::os::raw::c_int;
+    use std::slice;
+    use Byte;
+
+    /// Formerly: the Read and Write of net_landriver_t
+    pub type LanIoT = unsafe extern "C" fn(SysSocketT, *mut Byte, c_int, *mut QSockAddr) -> c_int;
+
+    /// the datagrams of a qsocket on their way, as NetSimSocketT holds them
+    struct SimQueuesT {
+        incoming: NetSimQueueT<(Vec<Byte>, QSockAddr)>,
+        outgoing: NetSimQueueT<(Vec<Byte>, QSockAddr)>,
+    }
+
+    thread_local! {
+        /// by qsocket, from its first datagram with a net_fake cvar set until NET_SimClose
+        static QUEUES: RefCell<HashMap<usize, SimQueuesT>> = RefCell::new(HashMap::new());
+    }
+
+    /// the settings, if sock goes through the simulator
+    unsafe fn simulated(sock: *mut QSocketT) -> Option<NetSimSettingsT> {
+        let settings = NetSimSettingsT::from_cvars();
+        let key = sock as usize;
+        return QUEUES.with(|queues| {
+            let mut queues = queues.borrow_mut();
+            if !settings.active() && !queues.contains_key(&key) {
+                return None;
+            }
+            queues.entry(key).or_insert_with(|| SimQueuesT {
+                incoming: NetSimQueueT::new(settings.seed),
+                outgoing: NetSimQueueT::new(settings.seed.wrapping_add(1)),
+            });
+            return Some(settings);
+        });
+    }
+
+    /// write what is due
+    unsafe fn flush(sock: *mut QSocketT, write: LanIoT) -> c_int {
+        loop {
+            let next = QUEUES.with(|queues| {
+                let mut queues = queues.borrow_mut();
+                return queues.get_mut(&(sock as usize))?.outgoing.pop(net_time);
+            });
+            let (mut data, mut addr) = match next {
+                Some(next) => next,
+                None => return 0,
+            };
+            if write(
+                (*sock).socket,
+                data.as_mut_ptr(),
+                data.len() as c_int,
+                &mut addr,
+            ) == -1
+            {
+                return -1;
+            }
+        }
+    }
+
+    /// Formerly: sfunc.Write in net_dgrm.c
+    /// write a datagram of a connected qsocket, through the simulator while a net_fake cvar
+    /// is set; the datagram counts as sent, whatever becomes of it
+    ///
+    /// # Safety
+    ///
+    /// sock must be an open qsocket of the lan driver write belongs to, and buf len bytes long
+    #[no_mangle]
+    pub unsafe extern "C" fn NET_SimWrite(
+        sock: *mut QSocketT,
+        write: LanIoT,
+        buf: *mut Byte,
+        len: c_int,
+        addr: *mut QSockAddr,
+    ) -> c_int {
+        let settings = match simulated(sock) {
+            Some(settings) => settings,
+            None => return write((*sock).socket, buf, len, addr),
+        };
+        let data = slice::from_raw_parts(buf, len as usize).to_vec();
+        QUEUES.with(|queues| {
+            if let Some(sim) = queues.borrow_mut().get_mut(&(sock as usize)) {
+                sim.outgoing.push(&settings, net_time, (data, *addr));
+            }
+        });
+        if flush(sock, write) == -1 {
+            return -1;
+        }
+        return len;
+    }
+
+    /// Formerly: sfunc.Read in net_dgrm.c
+    /// the next datagram for a connected qsocket, 0 if none is due
+    ///
+    /// # Safety
+    ///
+    /// sock must be an open qsocket of the lan driver read and write belong to, and buf len
+    /// bytes long
+    #[no_mangle]
+    pub unsafe extern "C" fn NET_SimRead(
+        sock: *mut QSocketT,
+        read: LanIoT,
+        write: LanIoT,
+        buf: *mut Byte,
+        len: c_int,
+        addr: *mut QSockAddr,
+    ) -> c_int {
+        let settings = match simulated(sock) {
+            Some(settings) => settings,
+            None => return read((*sock).socket, buf, len, addr),
+        };
+        if flush(sock, write) == -1 {
+            return -1;
+        }
+        let mut packet = [0; NET_DATAGRAMSIZE];
+        let mut from = *addr;
+        loop {
+            let ret = read(
+                (*sock).socket,
+                packet.as_mut_ptr(),
+                packet.len() as c_int,
+                &mut from,
+            );
+            if ret == -1 {
+                return -1;
+            }
+            if ret <= 0 {
+                break;
+            }
+            let data = packet[..ret as usize].to_vec();
+            QUEUES.with(|queues| {
+                if let Some(sim) = queues.borrow_mut().get_mut(&(sock as usize)) {
+                    sim.incoming.push(&settings, net_time, (data, from));
+                }
+            });
+        }
+        let next = QUEUES.with(|queues| {
+            let mut queues = queues.borrow_mut();
+            return queues.get_mut(&(sock as usize))?.incoming.pop(net_time);
+        });
+        return match next {
+            Some((data, from)) => {
+                let len = data.len().min(len.max(0) as usize);
+                slice::from_raw_parts_mut(buf, len).copy_from_slice(&data[..len]);
+                *addr = from;
+                len as c_int
+            }
+            None => 0,
+        };
+    }
+
+    /// Formerly: Datagram_Close
+    /// drop what the simulator holds for sock
+    #[no_mangle]
+    pub extern "C" fn NET_SimClose(sock: *mut QSocketT) {
+        QUEUES.with(|queues| queues.borrow_mut().remove(&(sock as usize)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use net_defs::tests::{HubT, MemorySocketT};
+    use net_dgrm::DatagramSocketT;
+    use net_loop::LoopDriverT;
+    use std::os::raw::c_int;
+
+    fn bad_network(seed: u32) -> NetSimSettingsT {
+        return NetSimSettingsT {
+            lag: 80.0,
+            jitter: 60.0,
+            loss: 20.0,
+            dup: 10.0,
+            reorder: 10.0,
+            seed,
+        };
+    }
+
+    /// reliable messages from one end to the other; what came through, and the resends,
+    /// drops and time it took
+    fn transfer(settings: NetSimSettingsT) -> (Vec<Vec<Byte>>, c_int, c_int, c_double) {
+        let hub = HubT::default();
+        let a = MemorySocketT::new(&hub, "10.0.0.1:26000");
+        let b = MemorySocketT::new(&hub, "10.0.0.2:26000");
+        let (a_addr, b_addr) = (a.addr(), b.addr());
+        let mut a = DatagramSocketT::new(NetSimSocketT::new(a, settings), b_addr);
+        let mut b = DatagramSocketT::new(NetSimSocketT::new(b, settings), a_addr);
+
+        let messages: Vec<Vec<Byte>> = (0..10)
+            .map(|i| {
+                (0..(i * 3331 % 9000 + 1))
+                    .map(|j| (j * 7 + i) as Byte)
+                    .collect()
+            })
+            .collect();
+        let mut received = Vec::new();
+        let mut next = 0;
+        let mut time = 0.0;
+        while received.len() < messages.len() && time < 600.0 {
+            time += 0.01;
+            a.set_net_time(time);
+            b.set_net_time(time);
+            a.socket_mut().set_net_time(time);
+            b.socket_mut().set_net_time(time);
+            if next < messages.len() && a.can_send_message() {
+                a.send_message(&messages[next]).unwrap();
+                next += 1;
+            }
+            a.send_unreliable_message(b"state").unwrap();
+            while a.get_message().unwrap().is_some() {}
+            while let Some(message) = b.get_message().unwrap() {
+                if let NetMessageT::Reliable(data) = message {
+                    received.push(data);
+                }
+            }
+        }
+        assert_eq!(received, messages);
+        return (
+            received,
+            a.stats.packets_resent,
+            b.stats.dropped_datagrams,
+            time,
+        );
+    }
+
+    #[test]
+    fn same_seed_same_network() {
+        let first = transfer(bad_network(42));
+        let again = transfer(bad_network(42));
+        assert_eq!((first.1, first.2, first.3), (again.1, again.2, again.3));
+        assert!(first.1 > 0 && first.2 > 0);
+
+        let other = transfer(bad_network(43));
+        assert!((other.1, other.2, other.3) != (first.1, first.2, first.3));
+
+        let clean = transfer(NetSimSettingsT::default());
+        assert_eq!((clean.1, clean.2), (0, 0));
+        assert!(clean.3 < first.3);
+    }
+
+    #[test]
+    fn lag_holds_datagrams() {
+        let hub = HubT::default();
+        let settings = NetSimSettingsT {
+            lag: 100.0,
+            ..Default::default()
+        };
+        let mut socket = NetSimSocketT::new(MemorySocketT::new(&hub, "10.0.0.1:1"), settings);
+        let mut other = MemorySocketT::new(&hub, "10.0.0.2:1");
+        let other_addr = other.addr();
+        let mut buf = [0; 16];
+
+        socket.set_net_time(1.0);
+        socket.write(b"hi", &other_addr).unwrap();
+        assert_eq!(socket.in_flight(), 1);
+        assert!(other.read(&mut buf).unwrap().is_none());
+        socket.set_net_time(1.05);
+        socket.read(&mut buf).unwrap();
+        assert!(other.read(&mut buf).unwrap().is_none());
+        socket.set_net_time(1.1);
+        socket.read(&mut buf).unwrap();
+        assert_eq!(other.read(&mut buf).unwrap().unwrap().0, 2);
+
+        other
+            .write(b"back", &"10.0.0.1:1".parse().unwrap())
+            .unwrap();
+        assert!(socket.read(&mut buf).unwrap().is_none());
+        assert_eq!(socket.in_flight(), 1);
+        socket.set_net_time(1.25);
+        assert_eq!(socket.read(&mut buf).unwrap().unwrap().0, 4);
+        assert_eq!(socket.in_flight(), 0);
+    }
+
+    #[test]
+    fn queue_drops_doubles_and_reorders() {
+        let mut queue = NetSimQueueT::new(7);
+        let lossy = NetSimSettingsT {
+            loss: 100.0,
+            ..Default::default()
+        };
+        queue.push(&lossy, 0.0, 1);
+        assert!(queue.is_empty());
+
+        let doubled = NetSimSettingsT {
+            dup: 100.0,
+            ..Default::default()
+        };
+        queue.push(&doubled, 0.0, 2);
+        assert_eq!(
+            (queue.pop(0.0), queue.pop(0.0), queue.pop(0.0)),
+            (Some(2), Some(2), None)
+        );
+
+        let reordered = NetSimSettingsT {
+            reorder: 100.0,
+            ..Default::default()
+        };
+        queue.push(&reordered, 0.0, 3);
+        queue.push(&NetSimSettingsT::default(), 0.01, 4);
+        assert_eq!(queue.pop(0.01), Some(4));
+        assert_eq!(queue.pop(0.01), None);
+        assert_eq!(queue.pop(REORDER_TIME), Some(3));
+    }
+
+    #[test]
+    fn driver_keeps_reliable_messages_in_order() {
+        let settings = NetSimSettingsT {
+            lag: 50.0,
+            jitter: 30.0,
+            loss: 30.0,
+            dup: 20.0,
+            reorder: 20.0,
+            seed: 1,
+        };
+        let mut driver = NetSimDriverT::new(LoopDriverT::new(), settings);
+        driver.listen(true);
+        let mut client = driver.connect("local").unwrap().unwrap();
+        let mut server = driver.check_new_connections().unwrap().unwrap();
+        assert_eq!(driver.name(), "Loopback");
+
+        let mut reliable = Vec::new();
+        let mut unreliable = 0;
+        let mut sent = 0;
+        let mut time = 0.0;
+        for frame in 0..400 {
+            time += 0.01;
+            driver.set_net_time(time);
+            if sent < 20 && client.can_send_message() {
+                client.send_message(&[sent]).unwrap();
+                sent += 1;
+            }
+            if frame < 100 {
+                client.send_unreliable_message(b"move").unwrap();
+            }
+            while let Some(message) = server.get_message().unwrap() {
+                match message {
+                    NetMessageT::Reliable(data) => reliable.push(data[0]),
+                    NetMessageT::Unreliable(_) => unreliable += 1,
+                }
+            }
+            while client.get_message().unwrap().is_some() {}
+        }
+        assert_eq!(reliable, (0..20).collect::<Vec<Byte>>());
+        assert!(unreliable > 50 && unreliable < 100, "{}", unreliable);
+    }
+
+    mod engine {
+        use super::super::capi::*;
+        use net_defs::{QSockAddr, QSocketT};
+        use net_main::capi::{net_fakelag, net_time};
+        use net_sys::SysSocketT;
+        use std::cell::RefCell;
+        use std::collections::VecDeque;
+        use std::mem::zeroed;
+        use std::os::raw::c_int;
+        use std::slice;
+        use Byte;
+
+        thread_local! {
+            /// what the stand-in lan driver wrote, and what it has to read
+            static WRITTEN: RefCell<Vec<Vec<Byte>>> = RefCell::new(Vec::new());
+            static TO_READ: RefCell<VecDeque<Vec<Byte>>> = RefCell::new(VecDeque::new());
+        }
+
+        unsafe extern "C" fn lan_write(
+            _socket: SysSocketT,
+            buf: *mut Byte,
+            len: c_int,
+            _addr: *mut QSockAddr,
+        ) -> c_int {
+            let data = slice::from_raw_parts(buf, len as usize).to_vec();
+            WRITTEN.with(|w| w.borrow_mut().push(data));
+            return len;
+        }
+
+        unsafe extern "C" fn lan_read(
+            _socket: SysSocketT,
+            buf: *mut Byte,
+            len: c_int,
+            _addr: *mut QSockAddr,
+        ) -> c_int {
+            return match TO_READ.with(|r| r.borrow_mut().pop_front()) {
+                Some(data) => {
+                    let len = data.len().min(len as usize);
+                    slice::from_raw_parts_mut(buf, len).copy_from_slice(&data[..len]);
+                    len as c_int
+                }
+                None => 0,
+            };
+        }
+
+        fn written() -> usize {
+            return WRITTEN.with(|w| w.borrow().len());
+        }
+
+        #[test]
+        fn datagrams_wait_out_net_fakelag() {
+            unsafe {
+                let mut sock: Box<QSocketT> = Box::new(zeroed());
+                let sock = &mut *sock as *mut QSocketT;
+                let mut addr: QSockAddr = zeroed();
+                let mut buf = [0; 16];
+
+                // nothing set: straight through
+                net_time = 1.0;
+                assert_eq!(
+                    NET_SimWrite(sock, lan_write, b"a".as_ptr() as *mut Byte, 1, &mut addr),
+                    1
+                );
+                assert_eq!(written(), 1);
+
+                net_fakelag.value = 100.0;
+                assert_eq!(
+                    NET_SimWrite(sock, lan_write, b"bc".as_ptr() as *mut Byte, 2, &mut addr),
+                    2
+                );
+                assert_eq!(written(), 1);
+                TO_READ.with(|r| r.borrow_mut().push_back(b"in".to_vec()));
+                assert_eq!(
+                    NET_SimRead(sock, lan_read, lan_write, buf.as_mut_ptr(), 16, &mut addr),
+                    0
+                );
+
+                // the lag is over; the queue is emptied even with the cvar off again
+                net_fakelag.value = 0.0;
+                net_time = 1.1;
+                assert_eq!(
+                    NET_SimRead(sock, lan_read, lan_write, buf.as_mut_ptr(), 16, &mut addr),
+                    2
+                );
+                assert_eq!(&buf[..2], b"in");
+                assert_eq!(WRITTEN.with(|w| w.borrow()[1].clone()), b"bc");
+
+                NET_SimClose(sock);
+                TO_READ.with(|r| r.borrow_mut().push_back(b"xyz".to_vec()));
+                assert_eq!(
+                    NET_SimRead(sock, lan_read, lan_write, buf.as_mut_ptr(), 16, &mut addr),
+                    3
+                );
+            }
+        }
+    }
+}
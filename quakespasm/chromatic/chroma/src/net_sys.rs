@@ -21,4 +21,7 @@
  * 51 Franklin Street, Fifth Floor, Boston, MA  02110-1301  USA
  */
 
+#[cfg(windows)]
 pub type SysSocketT = std::os::windows::raw::SOCKET;
+#[cfg(unix)]
+pub type SysSocketT = std::os::raw::c_int;
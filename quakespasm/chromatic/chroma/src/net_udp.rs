@@ -0,0 +1,507 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2007-2008 Kristian Duske
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// net_udp.rs -- the udp driver on std::net, for IPv4 and IPv6
+//
+// the engine's net_drivers[] still has the C datagram driver and net_udp.c; this one is for
+// Rust hosts and tools, and isn't registered with the engine
+
+use net::{NetConnection, NetError, NetMessageT, NET_NAMELEN};
+use net_capture::CaptureSocketT;
+use net_defs::{NetDriver, NetLanSocket};
+use net_dgrm::{
+    ConnectStateT, CtlClientT, CtlMessageT, CtlPlayerInfoT, CtlServerHandler, CtlServerT,
+    DatagramSocketT, DatagramStatsT,
+};
+use net_main::{MessageTimeoutT, NetClock, NetSocketT, SysClockT};
+use net_rcon::{RconClientT, RconStateT};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::os::raw::{c_double, c_int};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+use Byte;
+
+/// Formerly: DEFAULTnet_hostport
+pub const DEFAULT_HOSTPORT: u16 = 26000;
+
+/// how long connect sleeps between looks at its socket
+const CONNECT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Formerly: UDP_GetAddrFromName, UDP_StringToAddr
+/// an address from "host", "host:port", "a.b.c.d", "::1", "[::1]:port" and the like; port is
+/// used when the name has none
+pub fn get_addr_from_name(name: &str, port: u16) -> Option<SocketAddr> {
+    if let Ok(addr) = name.parse::<SocketAddr>() {
+        return Some(addr);
+    }
+    let bare = name.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = bare.parse::<IpAddr>() {
+        return Some(SocketAddr::new(ip, port));
+    }
+
+    // a host name, maybe with a port
+    let (host, port) = match name.rfind(':') {
+        Some(i) => match name[i + 1..].parse::<u16>() {
+            Ok(port) => (&name[..i], port),
+            Err(_) => return None,
+        },
+        None => (name, port),
+    };
+    if host.is_empty() {
+        return None;
+    }
+    let mut addrs = (host, port).to_socket_addrs().ok()?;
+    return addrs.next();
+}
+
+/// Formerly: UDP_AddrToString
+/// the address as get_addr_from_name takes it, cut to fit a qsocket_t's address; IPv4
+/// clients of an IPv6 socket show as IPv4
+pub fn addr_to_string(addr: &SocketAddr) -> String {
+    let mut string = match *addr {
+        SocketAddr::V6(ref v6) => match v6.ip().to_ipv4_mapped() {
+            Some(v4) => SocketAddr::new(IpAddr::V4(v4), v6.port()).to_string(),
+            None => addr.to_string(),
+        },
+        SocketAddr::V4(_) => addr.to_string(),
+    };
+    string.truncate(NET_NAMELEN - 1);
+    return string;
+}
+
+/// Formerly: the sys_socket_t of net_udp.c
+/// a non-blocking udp socket
+pub struct UdpSocketT {
+    socket: UdpSocket,
+}
+
+impl UdpSocketT {
+    /// Formerly: UDP_OpenSocket
+    /// a socket bound to addr; port 0 for any free port
+    pub fn open(addr: SocketAddr) -> io::Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+        if addr.is_ipv4() {
+            // Formerly: UDP_MakeSocketBroadcastCapable
+            socket.set_broadcast(true)?;
+        }
+        return Ok(Self { socket });
+    }
+
+    /// a socket on any free port, to talk to addr's family
+    pub fn open_for(addr: &SocketAddr) -> io::Result<Self> {
+        let any = if addr.is_ipv4() {
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        } else {
+            IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+        };
+        return Self::open(SocketAddr::new(any, 0));
+    }
+
+    /// Formerly: UDP_GetSocketAddr
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        return self.socket.local_addr();
+    }
+}
+
+impl NetLanSocket for UdpSocketT {
+    /// Formerly: UDP_Read
+    fn read(&mut self, buf: &mut [Byte]) -> io::Result<Option<(usize, SocketAddr)>> {
+        match self.socket.recv_from(buf) {
+            Ok((len, addr)) => return Ok(Some((len, addr))),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+            // an icmp port unreachable for something we sent earlier
+            Err(ref e) if e.kind() == io::ErrorKind::ConnectionReset => return Ok(None),
+            Err(e) => return Err(e),
+        }
+    }
+
+    /// Formerly: UDP_Write
+    fn write(&mut self, data: &[Byte], addr: &SocketAddr) -> io::Result<usize> {
+        match self.socket.send_to(data, addr) {
+            Ok(len) => return Ok(len),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(0),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// a datagram connection over udp; its net_time follows the driver's clock
+pub struct UdpConnectionT {
+    socket: DatagramSocketT<CaptureSocketT<UdpSocketT>>,
+    clock: Rc<dyn NetClock>,
+}
+
+impl UdpConnectionT {
+    /// a connection to addr over socket, captured into a file in capture if there is one
+    fn new(
+        socket: UdpSocketT,
+        addr: SocketAddr,
+        clock: Rc<dyn NetClock>,
+        capture: &Option<PathBuf>,
+    ) -> Self {
+        let mut socket = CaptureSocketT::new(socket);
+        if let Some(ref dir) = *capture {
+            let name = addr_to_string(&addr).replace([':', '[', ']'], "_");
+            // a capture that can't be opened is skipped
+            let _ = socket.capture_file(&dir.join(name + ".qcap"));
+        }
+        let mut connection = Self {
+            socket: DatagramSocketT::new(socket, addr),
+            clock,
+        };
+        connection.set_net_time();
+        return connection;
+    }
+
+    pub fn socket(&self) -> &DatagramSocketT<CaptureSocketT<UdpSocketT>> {
+        return &self.socket;
+    }
+
+    /// to start or stop a capture
+    pub fn socket_mut(&mut self) -> &mut DatagramSocketT<CaptureSocketT<UdpSocketT>> {
+        return &mut self.socket;
+    }
+
+    /// Formerly: SetNetTime
+    fn set_net_time(&mut self) {
+        let time = self.clock.now();
+        self.socket.set_net_time(time);
+    }
+}
+
+impl NetConnection for UdpConnectionT {
+    fn get_message(&mut self) -> Result<Option<NetMessageT>, NetError> {
+        self.set_net_time();
+        return self.socket.get_message();
+    }
+    fn send_message(&mut self, data: &[Byte]) -> Result<(), NetError> {
+        self.set_net_time();
+        return self.socket.send_message(data);
+    }
+    fn send_unreliable_message(&mut self, data: &[Byte]) -> Result<(), NetError> {
+        self.set_net_time();
+        return self.socket.send_unreliable_message(data);
+    }
+    fn can_send_message(&mut self) -> bool {
+        self.set_net_time();
+        return self.socket.can_send_message();
+    }
+    fn close(&mut self) {
+        self.socket.close();
+    }
+    fn address(&self) -> String {
+        return addr_to_string(self.socket.addr());
+    }
+    fn stats(&self) -> Option<DatagramStatsT> {
+        return Some(self.socket.stats);
+    }
+}
+
+/// hands the server's answers through, and opens a socket for each client the server takes
+struct UdpAcceptT<'a, H: 'a> {
+    handler: &'a mut H,
+    family: SocketAddr,
+    opened: Option<UdpSocketT>,
+}
+
+impl<'a, H: CtlServerHandler> CtlServerHandler for UdpAcceptT<'a, H> {
+    fn host_name(&self) -> Vec<Byte> {
+        return self.handler.host_name();
+    }
+    fn level_name(&self) -> Vec<Byte> {
+        return self.handler.level_name();
+    }
+    fn current_players(&self) -> c_int {
+        return self.handler.current_players();
+    }
+    fn max_players(&self) -> c_int {
+        return self.handler.max_players();
+    }
+    fn player_info(&self, player_number: c_int) -> Option<CtlPlayerInfoT> {
+        return self.handler.player_info(player_number);
+    }
+    fn rule_after(&self, prev: &[Byte]) -> Option<(Vec<Byte>, Vec<Byte>)> {
+        return self.handler.rule_after(prev);
+    }
+    /// the handler decides; the port it gives is replaced with the new socket's
+    fn accept(&mut self, addr: &SocketAddr) -> Result<c_int, Vec<Byte>> {
+        self.handler.accept(addr)?;
+        let socket = match UdpSocketT::open_for(&self.family) {
+            Ok(socket) => socket,
+            Err(_) => return Err(b"Server is full.\n".to_vec()),
+        };
+        let port = match socket.local_addr() {
+            Ok(local) => local.port() as c_int,
+            Err(_) => return Err(b"Server is full.\n".to_vec()),
+        };
+        self.opened = Some(socket);
+        return Ok(port);
+    }
+    fn drop_connection(&mut self, addr: &SocketAddr) {
+        self.handler.drop_connection(addr);
+    }
+    fn rcon(
+        &mut self,
+        addr: &SocketAddr,
+        challenge: c_int,
+        digest: &[Byte],
+        command: &[Byte],
+        net_time: c_double,
+    ) -> Option<CtlMessageT> {
+        return self
+            .handler
+            .rcon(addr, challenge, digest, command, net_time);
+    }
+}
+
+/// Formerly: the Datagram_ functions of net_drivers[1] over net_landrivers[0]
+/// connections over udp; a listening server takes them on hostport, for both IPv4 and IPv6
+pub struct UdpDriverT<H: CtlServerHandler> {
+    /// what the server tells queries, and who it lets in
+    pub handler: H,
+    /// Formerly: net_hostport
+    pub hostport: u16,
+    /// a directory to capture every new connection into, a file for each named after the
+    /// address of the other end
+    pub capture: Option<PathBuf>,
+    /// seconds a connection can go without a message before it's closed; every connection
+    /// shares it, so a change applies to those already open
+    pub timeout: MessageTimeoutT,
+    /// where net_time comes from; connections keep the clock they were made with, and
+    /// connect_addr waits on it
+    pub clock: Rc<dyn NetClock>,
+    /// the listening sockets, one for each family that could be bound
+    control: Vec<CtlServerT<UdpSocketT>>,
+}
+
+impl<H: CtlServerHandler> UdpDriverT<H> {
+    pub fn new(handler: H, hostport: u16) -> Self {
+        Self {
+            handler,
+            hostport,
+            capture: None,
+            timeout: MessageTimeoutT::default(),
+            clock: Rc::new(SysClockT::new()),
+            control: Vec::new(),
+        }
+    }
+
+    /// Formerly: listening
+    pub fn listening(&self) -> bool {
+        return !self.control.is_empty();
+    }
+
+    /// Formerly: my_tcpip_address
+    /// the addresses the server listens on
+    pub fn local_addrs(&mut self) -> Vec<SocketAddr> {
+        return self
+            .control
+            .iter_mut()
+            .filter_map(|c| c.socket_mut().local_addr().ok())
+            .collect();
+    }
+
+    fn net_time(&self) -> c_double {
+        return self.clock.now();
+    }
+
+    /// connection, closed when it has been quiet for timeout seconds
+    fn timed(&self, connection: UdpConnectionT) -> Box<dyn NetConnection> {
+        return Box::new(NetSocketT::new(
+            connection,
+            self.clock.clone(),
+            self.timeout.clone(),
+        ));
+    }
+
+    /// Formerly: _Datagram_Connect
+    /// connect to addr, waiting for the answer like the original
+    pub fn connect_addr(&mut self, addr: SocketAddr) -> Result<UdpConnectionT, NetError> {
+        let mut socket = UdpSocketT::open_for(&addr)?;
+        let mut client = CtlClientT::new(addr);
+        loop {
+            let net_time = self.net_time();
+            match *client.poll(&mut socket, net_time) {
+                ConnectStateT::Trying => thread::sleep(CONNECT_POLL_INTERVAL),
+                ConnectStateT::Accepted(addr) => {
+                    return Ok(UdpConnectionT::new(
+                        socket,
+                        addr,
+                        self.clock.clone(),
+                        &self.capture,
+                    ));
+                }
+                ConnectStateT::Rejected(ref reason) => {
+                    let reason = String::from_utf8_lossy(reason);
+                    return Err(NetError::Rejected(reason.trim_end().to_string()));
+                }
+                ConnectStateT::Failed(reason) => {
+                    return Err(NetError::Rejected(reason.to_string()));
+                }
+            }
+        }
+    }
+
+    /// run command on the server at addr with rcon, waiting for its output like connect_addr
+    pub fn rcon_addr(
+        &mut self,
+        addr: SocketAddr,
+        password: &[Byte],
+        command: &[Byte],
+    ) -> Result<Vec<Byte>, NetError> {
+        let mut socket = UdpSocketT::open_for(&addr)?;
+        let mut client = RconClientT::new(addr, password, command);
+        loop {
+            let net_time = self.net_time();
+            match *client.poll(&mut socket, net_time)? {
+                RconStateT::Waiting => thread::sleep(CONNECT_POLL_INTERVAL),
+                RconStateT::Done(ref output) => return Ok(output.clone()),
+                RconStateT::Failed(reason) => {
+                    return Err(NetError::Rejected(reason.to_string()));
+                }
+            }
+        }
+    }
+
+    /// Formerly: Datagram_CheckNewConnections
+    pub fn check_new_udp_connections(&mut self) -> Result<Option<UdpConnectionT>, NetError> {
+        let net_time = self.net_time();
+        for control in &mut self.control {
+            let family = control.socket_mut().local_addr()?;
+            let mut accept = UdpAcceptT {
+                handler: &mut self.handler,
+                family,
+                opened: None,
+            };
+            control.set_net_time(net_time);
+            let clientaddr = match control.check_new_connections(&mut accept)? {
+                Some(clientaddr) => clientaddr,
+                None => continue,
+            };
+            if let Some(socket) = accept.opened.take() {
+                let clock = self.clock.clone();
+                let connection = UdpConnectionT::new(socket, clientaddr, clock, &self.capture);
+                return Ok(Some(connection));
+            }
+        }
+        return Ok(None);
+    }
+}
+
+impl<H: CtlServerHandler> NetDriver for UdpDriverT<H> {
+    fn name(&self) -> &str {
+        return "Datagram";
+    }
+
+    /// Formerly: Datagram_Listen, UDP_Listen
+    /// where the IPv6 socket takes IPv4 as well, binding IPv4 on its own fails and is skipped
+    fn listen(&mut self, state: bool) {
+        self.control.clear();
+        if !state {
+            return;
+        }
+        let any: [IpAddr; 2] = [Ipv6Addr::UNSPECIFIED.into(), Ipv4Addr::UNSPECIFIED.into()];
+        for ip in &any {
+            let addr = SocketAddr::new(*ip, self.hostport);
+            if let Ok(socket) = UdpSocketT::open(addr) {
+                self.control.push(CtlServerT::new(socket, addr));
+            }
+        }
+    }
+
+    fn connect(&mut self, host: &str) -> Result<Option<Box<dyn NetConnection>>, NetError> {
+        let addr = match get_addr_from_name(host, self.hostport) {
+            Some(addr) => addr,
+            // Could not resolve host
+            None => return Ok(None),
+        };
+        let connection = self.connect_addr(addr)?;
+        return Ok(Some(self.timed(connection)));
+    }
+
+    fn check_new_connections(&mut self) -> Result<Option<Box<dyn NetConnection>>, NetError> {
+        return Ok(self
+            .check_new_udp_connections()?
+            .map(|connection| self.timed(connection)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(name: &str) -> Option<String> {
+        return get_addr_from_name(name, DEFAULT_HOSTPORT).map(|a| a.to_string());
+    }
+
+    #[test]
+    fn names_with_and_without_a_port() {
+        assert_eq!(addr("10.0.0.1").unwrap(), "10.0.0.1:26000");
+        assert_eq!(addr("10.0.0.1:27500").unwrap(), "10.0.0.1:27500");
+        assert_eq!(addr("::1").unwrap(), "[::1]:26000");
+        assert_eq!(addr("[::1]").unwrap(), "[::1]:26000");
+        assert_eq!(addr("[::1]:27500").unwrap(), "[::1]:27500");
+        assert_eq!(addr("fe80::1").unwrap(), "[fe80::1]:26000");
+        assert_eq!(
+            addr("[::ffff:10.0.0.1]:27500").unwrap(),
+            "[::ffff:10.0.0.1]:27500"
+        );
+    }
+
+    #[test]
+    fn names_with_a_bad_port() {
+        assert_eq!(addr("10.0.0.1:"), None);
+        assert_eq!(addr("10.0.0.1:port"), None);
+        assert_eq!(addr("10.0.0.1:65536"), None);
+        assert_eq!(addr("[::1]:-1"), None);
+        assert_eq!(addr("quake.example:port"), None);
+        assert_eq!(addr(":26000"), None);
+    }
+
+    #[test]
+    fn addresses_print_as_they_are_read() {
+        for name in &["10.0.0.1:27500", "[::1]:26000", "[fe80::1]:26000"] {
+            let a = get_addr_from_name(name, DEFAULT_HOSTPORT).unwrap();
+            assert_eq!(addr_to_string(&a), *name);
+        }
+
+        // an IPv4 client of an IPv6 socket
+        let mapped = get_addr_from_name("[::ffff:10.0.0.1]:27500", 0).unwrap();
+        assert_eq!(addr_to_string(&mapped), "10.0.0.1:27500");
+        let back = get_addr_from_name(&addr_to_string(&mapped), 0).unwrap();
+        assert_eq!(back, "10.0.0.1:27500".parse().unwrap());
+    }
+
+    #[test]
+    fn the_longest_address_fits_a_qsocket() {
+        let longest: SocketAddr = "[ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff%4294967295]:65535"
+            .parse()
+            .unwrap();
+        let string = addr_to_string(&longest);
+        assert!(string.len() < NET_NAMELEN);
+        assert_eq!(get_addr_from_name(&string, 0), Some(longest));
+    }
+}
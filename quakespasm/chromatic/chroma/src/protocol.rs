@@ -206,7 +206,7 @@ pub fn ent_alpha_encode(a: f32) -> u32 {
     if a == 0.0 {
         return ENTALPHA_DEFAULT;
     }
-    return q_rint(clamp(a * 254.0 + 1.0, 1.0, 255.0));
+    return q_rint(clamp(a * 254.0 + 1.0, 1.0, 255.0)) as u32;
 }
 
 /// Formerly: ENTALPHA_DECODE
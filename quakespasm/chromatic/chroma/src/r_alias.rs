@@ -0,0 +1,582 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2002-2009 John Fitzgibbons and others
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// r_alias.rs -- alias model pose blending and entity movement interpolation
+
+use gl_model::{AliasHdrT, ModelFlags, QModelT};
+use mathlib::vector_subtract;
+use render::{EntityT, LerpFlags};
+use std::os::raw::{c_double, c_float, c_int, c_short};
+use Vec3T;
+
+/// interval assumed between animation frames and entity updates when the server doesn't say
+pub const LERP_INTERVAL: c_float = 0.1;
+
+/// number of entries in VERTEX_NORMALS
+pub const NUMVERTEXNORMALS: usize = 162;
+
+/// Formerly: r_avertexnormals
+/// the precalculated normals alias model vertices index with lightnormalindex
+pub static VERTEX_NORMALS: [[c_float; 3]; NUMVERTEXNORMALS] = [
+    [-0.525731, 0.000000, 0.850651],
+    [-0.442863, 0.238856, 0.864188],
+    [-0.295242, 0.000000, 0.955423],
+    [-0.309017, 0.500000, 0.809017],
+    [-0.162460, 0.262866, 0.951056],
+    [0.000000, 0.000000, 1.000000],
+    [0.000000, 0.850651, 0.525731],
+    [-0.147621, 0.716567, 0.681718],
+    [0.147621, 0.716567, 0.681718],
+    [0.000000, 0.525731, 0.850651],
+    [0.309017, 0.500000, 0.809017],
+    [0.525731, 0.000000, 0.850651],
+    [0.295242, 0.000000, 0.955423],
+    [0.442863, 0.238856, 0.864188],
+    [0.162460, 0.262866, 0.951056],
+    [-0.681718, 0.147621, 0.716567],
+    [-0.809017, 0.309017, 0.500000],
+    [-0.587785, 0.425325, 0.688191],
+    [-0.850651, 0.525731, 0.000000],
+    [-0.864188, 0.442863, 0.238856],
+    [-0.716567, 0.681718, 0.147621],
+    [-0.688191, 0.587785, 0.425325],
+    [-0.500000, 0.809017, 0.309017],
+    [-0.238856, 0.864188, 0.442863],
+    [-0.425325, 0.688191, 0.587785],
+    [-0.716567, 0.681718, -0.147621],
+    [-0.500000, 0.809017, -0.309017],
+    [-0.525731, 0.850651, 0.000000],
+    [0.000000, 0.850651, -0.525731],
+    [-0.238856, 0.864188, -0.442863],
+    [0.000000, 0.955423, -0.295242],
+    [-0.262866, 0.951056, -0.162460],
+    [0.000000, 1.000000, 0.000000],
+    [0.000000, 0.955423, 0.295242],
+    [-0.262866, 0.951056, 0.162460],
+    [0.238856, 0.864188, 0.442863],
+    [0.262866, 0.951056, 0.162460],
+    [0.500000, 0.809017, 0.309017],
+    [0.238856, 0.864188, -0.442863],
+    [0.262866, 0.951056, -0.162460],
+    [0.500000, 0.809017, -0.309017],
+    [0.850651, 0.525731, 0.000000],
+    [0.716567, 0.681718, 0.147621],
+    [0.716567, 0.681718, -0.147621],
+    [0.525731, 0.850651, 0.000000],
+    [0.425325, 0.688191, 0.587785],
+    [0.864188, 0.442863, 0.238856],
+    [0.688191, 0.587785, 0.425325],
+    [0.809017, 0.309017, 0.500000],
+    [0.681718, 0.147621, 0.716567],
+    [0.587785, 0.425325, 0.688191],
+    [0.955423, 0.295242, 0.000000],
+    [1.000000, 0.000000, 0.000000],
+    [0.951056, 0.162460, 0.262866],
+    [0.850651, -0.525731, 0.000000],
+    [0.955423, -0.295242, 0.000000],
+    [0.864188, -0.442863, 0.238856],
+    [0.951056, -0.162460, 0.262866],
+    [0.809017, -0.309017, 0.500000],
+    [0.681718, -0.147621, 0.716567],
+    [0.850651, 0.000000, 0.525731],
+    [0.864188, 0.442863, -0.238856],
+    [0.809017, 0.309017, -0.500000],
+    [0.951056, 0.162460, -0.262866],
+    [0.525731, 0.000000, -0.850651],
+    [0.681718, 0.147621, -0.716567],
+    [0.681718, -0.147621, -0.716567],
+    [0.850651, 0.000000, -0.525731],
+    [0.809017, -0.309017, -0.500000],
+    [0.864188, -0.442863, -0.238856],
+    [0.951056, -0.162460, -0.262866],
+    [0.147621, 0.716567, -0.681718],
+    [0.309017, 0.500000, -0.809017],
+    [0.425325, 0.688191, -0.587785],
+    [0.442863, 0.238856, -0.864188],
+    [0.587785, 0.425325, -0.688191],
+    [0.688191, 0.587785, -0.425325],
+    [-0.147621, 0.716567, -0.681718],
+    [-0.309017, 0.500000, -0.809017],
+    [0.000000, 0.525731, -0.850651],
+    [-0.525731, 0.000000, -0.850651],
+    [-0.442863, 0.238856, -0.864188],
+    [-0.295242, 0.000000, -0.955423],
+    [-0.162460, 0.262866, -0.951056],
+    [0.000000, 0.000000, -1.000000],
+    [0.295242, 0.000000, -0.955423],
+    [0.162460, 0.262866, -0.951056],
+    [-0.442863, -0.238856, -0.864188],
+    [-0.309017, -0.500000, -0.809017],
+    [-0.162460, -0.262866, -0.951056],
+    [0.000000, -0.850651, -0.525731],
+    [-0.147621, -0.716567, -0.681718],
+    [0.147621, -0.716567, -0.681718],
+    [0.000000, -0.525731, -0.850651],
+    [0.309017, -0.500000, -0.809017],
+    [0.442863, -0.238856, -0.864188],
+    [0.162460, -0.262866, -0.951056],
+    [0.238856, -0.864188, -0.442863],
+    [0.500000, -0.809017, -0.309017],
+    [0.425325, -0.688191, -0.587785],
+    [0.716567, -0.681718, -0.147621],
+    [0.688191, -0.587785, -0.425325],
+    [0.587785, -0.425325, -0.688191],
+    [0.000000, -0.955423, -0.295242],
+    [0.000000, -1.000000, 0.000000],
+    [0.262866, -0.951056, -0.162460],
+    [0.000000, -0.850651, 0.525731],
+    [0.000000, -0.955423, 0.295242],
+    [0.238856, -0.864188, 0.442863],
+    [0.262866, -0.951056, 0.162460],
+    [0.500000, -0.809017, 0.309017],
+    [0.716567, -0.681718, 0.147621],
+    [0.525731, -0.850651, 0.000000],
+    [-0.238856, -0.864188, -0.442863],
+    [-0.500000, -0.809017, -0.309017],
+    [-0.262866, -0.951056, -0.162460],
+    [-0.850651, -0.525731, 0.000000],
+    [-0.716567, -0.681718, -0.147621],
+    [-0.716567, -0.681718, 0.147621],
+    [-0.525731, -0.850651, 0.000000],
+    [-0.500000, -0.809017, 0.309017],
+    [-0.238856, -0.864188, 0.442863],
+    [-0.262866, -0.951056, 0.162460],
+    [-0.864188, -0.442863, 0.238856],
+    [-0.809017, -0.309017, 0.500000],
+    [-0.688191, -0.587785, 0.425325],
+    [-0.681718, -0.147621, 0.716567],
+    [-0.442863, -0.238856, 0.864188],
+    [-0.587785, -0.425325, 0.688191],
+    [-0.309017, -0.500000, 0.809017],
+    [-0.147621, -0.716567, 0.681718],
+    [-0.425325, -0.688191, 0.587785],
+    [-0.162460, -0.262866, 0.951056],
+    [0.442863, -0.238856, 0.864188],
+    [0.162460, -0.262866, 0.951056],
+    [0.309017, -0.500000, 0.809017],
+    [0.147621, -0.716567, 0.681718],
+    [0.000000, -0.525731, 0.850651],
+    [0.425325, -0.688191, 0.587785],
+    [0.587785, -0.425325, 0.688191],
+    [0.688191, -0.587785, 0.425325],
+    [-0.955423, 0.295242, 0.000000],
+    [-0.951056, 0.162460, 0.262866],
+    [-1.000000, 0.000000, 0.000000],
+    [-0.850651, 0.000000, 0.525731],
+    [-0.955423, -0.295242, 0.000000],
+    [-0.951056, -0.162460, 0.262866],
+    [-0.864188, 0.442863, -0.238856],
+    [-0.951056, 0.162460, -0.262866],
+    [-0.809017, 0.309017, -0.500000],
+    [-0.864188, -0.442863, -0.238856],
+    [-0.951056, -0.162460, -0.262866],
+    [-0.809017, -0.309017, -0.500000],
+    [-0.681718, 0.147621, -0.716567],
+    [-0.681718, -0.147621, -0.716567],
+    [-0.850651, 0.000000, -0.525731],
+    [-0.688191, 0.587785, -0.425325],
+    [-0.587785, 0.425325, -0.688191],
+    [-0.425325, 0.688191, -0.587785],
+    [-0.425325, -0.688191, -0.587785],
+    [-0.587785, -0.425325, -0.688191],
+    [-0.688191, -0.587785, -0.425325],
+];
+
+/// Formerly: lerpdata_t
+/// struct for passing lerp information to drawing functions
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct LerpDataT {
+    pub pose1: c_short,
+    pub pose2: c_short,
+    /// 0 is pose1, 1 is pose2
+    pub blend: c_float,
+    pub origin: Vec3T,
+    pub angles: Vec3T,
+}
+
+impl LerpDataT {
+    pub const fn default() -> Self {
+        Self {
+            pose1: 0,
+            pose2: 0,
+            blend: 0.0,
+            origin: Vec3T::new(0.0, 0.0, 0.0),
+            angles: Vec3T::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+impl Default for LerpDataT {
+    fn default() -> Self {
+        Self::default()
+    }
+}
+
+fn lerp_flags(e: &EntityT) -> LerpFlags {
+    return LerpFlags::from_bits_truncate(e.lerpflags as _);
+}
+
+fn clear_lerp_flag(e: &mut EntityT, flag: LerpFlags) {
+    e.lerpflags = (lerp_flags(e) - flag).bits() as _;
+}
+
+/// fraction of the way from start to finish at time, clamped to [0, 1]
+fn lerp_blend(time: c_double, start: c_float, finish: c_float) -> c_float {
+    let interval = (finish - start) as c_double;
+    if interval <= 0.0 {
+        return 1.0;
+    }
+    return ((time - start as c_double) / interval).clamp(0.0, 1.0) as c_float;
+}
+
+/// change from one set of angles to another, each taking the short way around the circle
+pub fn angle_delta(from: Vec3T, to: Vec3T) -> Vec3T {
+    let mut d = vector_subtract(to, from);
+    for i in 0..3 {
+        if d[i] > 180.0 {
+            d[i] -= 360.0;
+        }
+        if d[i] < -180.0 {
+            d[i] += 360.0;
+        }
+    }
+    return d;
+}
+
+/// Formerly: R_SetupAliasFrame
+/// pick the pose for frame at time and advance the entity's animation lerp.  lerpmodels is
+/// r_lerpmodels: 0 never blends, 1 blends unless the model has NoLerp, 2 always blends.
+/// returns (pose1, pose2, blend)
+pub fn setup_alias_frame(
+    e: &mut EntityT,
+    model: &QModelT,
+    paliashdr: &AliasHdrT,
+    frame: c_int,
+    time: c_double,
+    lerpmodels: c_int,
+) -> (c_short, c_short, c_float) {
+    let frame = if frame >= 0 && frame < paliashdr.numframes {
+        frame
+    } else {
+        0
+    };
+    let desc = unsafe { &*paliashdr.frames.as_ptr().add(frame as usize) };
+
+    let mut posenum = desc.firstpose;
+    let numposes = desc.numposes;
+    if numposes > 1 && desc.interval > 0.0 {
+        e.lerptime = desc.interval;
+        posenum += (time / e.lerptime as c_double) as c_int % numposes;
+    } else {
+        e.lerptime = LERP_INTERVAL;
+    }
+    let posenum = posenum as c_short;
+
+    let flags = lerp_flags(e);
+    if flags.contains(LerpFlags::ResetAnim) {
+        // kill any lerp in progress
+        e.lerpstart = 0.0;
+        e.previouspose = posenum;
+        e.currentpose = posenum;
+        clear_lerp_flag(e, LerpFlags::ResetAnim);
+    } else if e.currentpose != posenum {
+        // pose changed, start new lerp
+        if flags.contains(LerpFlags::ResetAnim2) {
+            // defer lerping one more time
+            e.lerpstart = 0.0;
+            e.previouspose = posenum;
+            e.currentpose = posenum;
+            clear_lerp_flag(e, LerpFlags::ResetAnim2);
+        } else {
+            e.lerpstart = time as c_float;
+            e.previouspose = e.currentpose;
+            e.currentpose = posenum;
+        }
+    }
+
+    let nolerp = ModelFlags::from_bits_truncate(model.flags as _).contains(ModelFlags::NoLerp);
+    if lerpmodels == 0 || (nolerp && lerpmodels != 2) {
+        // don't lerp
+        return (posenum, posenum, 1.0);
+    }
+    let blend = if lerp_flags(e).contains(LerpFlags::Finish) && numposes == 1 {
+        lerp_blend(time, e.lerpstart, e.lerpfinish)
+    } else {
+        lerp_blend(time, e.lerpstart, e.lerpstart + e.lerptime)
+    };
+    return (e.previouspose, e.currentpose, blend);
+}
+
+/// Formerly: R_SetupEntityTransform
+/// advance the entity's movement lerp and return its (origin, angles) at time.  lerpmove is
+/// r_lerpmove, and must be false for the view model, which moves with the view
+pub fn setup_entity_transform(e: &mut EntityT, time: c_double, lerpmove: bool) -> (Vec3T, Vec3T) {
+    let flags = lerp_flags(e);
+    if flags.contains(LerpFlags::ResetMove) {
+        // kill any lerps in progress
+        e.movelerpstart = 0.0;
+        e.previousorigin = e.origin;
+        e.currentorigin = e.origin;
+        e.previousangles = e.angles;
+        e.currentangles = e.angles;
+        clear_lerp_flag(e, LerpFlags::ResetMove);
+    } else if e.origin.0 != e.currentorigin.0 || e.angles.0 != e.currentangles.0 {
+        // origin/angles changed, start new lerp
+        e.movelerpstart = time as c_float;
+        e.previousorigin = e.currentorigin;
+        e.currentorigin = e.origin;
+        e.previousangles = e.currentangles;
+        e.currentangles = e.angles;
+    }
+
+    let flags = lerp_flags(e);
+    if !lerpmove || !flags.contains(LerpFlags::MoveStep) {
+        // don't lerp
+        return (e.origin, e.angles);
+    }
+    let blend = if flags.contains(LerpFlags::Finish) {
+        lerp_blend(time, e.movelerpstart, e.lerpfinish)
+    } else {
+        lerp_blend(time, e.movelerpstart, e.movelerpstart + LERP_INTERVAL)
+    };
+
+    let d = vector_subtract(e.currentorigin, e.previousorigin);
+    let mut origin = e.previousorigin;
+    let a = angle_delta(e.previousangles, e.currentangles);
+    let mut angles = e.previousangles;
+    for i in 0..3 {
+        origin[i] += d[i] * blend;
+        angles[i] += a[i] * blend;
+    }
+    return (origin, angles);
+}
+
+/// blended pose and transform of an alias model entity at time, see setup_alias_frame and
+/// setup_entity_transform
+pub fn lerp_alias_entity(
+    e: &mut EntityT,
+    model: &QModelT,
+    paliashdr: &AliasHdrT,
+    time: c_double,
+    lerpmodels: c_int,
+    lerpmove: bool,
+) -> LerpDataT {
+    let frame = e.frame;
+    let (pose1, pose2, blend) = setup_alias_frame(e, model, paliashdr, frame, time, lerpmodels);
+    let (origin, angles) = setup_entity_transform(e, time, lerpmove);
+    return LerpDataT {
+        pose1,
+        pose2,
+        blend,
+        origin,
+        angles,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gl_model::MAliasFrameDescT;
+    use Byte;
+
+    /// a header followed by the rest of its frames, as the loader lays them out
+    #[repr(C)]
+    struct AliasT {
+        hdr: AliasHdrT,
+        _frames: [MAliasFrameDescT; 2],
+    }
+
+    /// frame 0 is pose 0, frame 1 is pose 1, frame 2 is a group cycling poses 2-4 every 0.2s
+    fn alias() -> Box<AliasT> {
+        let mut a = Box::new(AliasT {
+            hdr: AliasHdrT::default(),
+            _frames: [MAliasFrameDescT::default(); 2],
+        });
+        a.hdr.numframes = 3;
+        let frames = a.hdr.frames.as_mut_ptr();
+        for (i, &(firstpose, numposes, interval)) in
+            [(0, 1, 0.0), (1, 1, 0.0), (2, 3, 0.2)].iter().enumerate()
+        {
+            let desc = unsafe { &mut *frames.add(i) };
+            desc.firstpose = firstpose;
+            desc.numposes = numposes;
+            desc.interval = interval;
+        }
+        return a;
+    }
+
+    fn flags(f: LerpFlags) -> Byte {
+        return f.bits() as Byte;
+    }
+
+    #[test]
+    fn pose_changes_blend_over_the_interval() {
+        let a = alias();
+        let model = QModelT::default();
+        let mut e = EntityT::default();
+
+        assert_eq!(
+            setup_alias_frame(&mut e, &model, &a.hdr, 1, 10.0, 1),
+            (0, 1, 0.0)
+        );
+        assert_eq!(e.lerpstart, 10.0);
+        assert_eq!(e.lerptime, LERP_INTERVAL);
+        let blend = setup_alias_frame(&mut e, &model, &a.hdr, 1, 10.05, 1).2;
+        assert!((blend - 0.5).abs() < 1e-4);
+        assert_eq!(
+            setup_alias_frame(&mut e, &model, &a.hdr, 1, 10.5, 1),
+            (0, 1, 1.0)
+        );
+
+        // and back, from where it was
+        assert_eq!(
+            setup_alias_frame(&mut e, &model, &a.hdr, 0, 11.0, 1),
+            (1, 0, 0.0)
+        );
+    }
+
+    #[test]
+    fn groups_cycle_their_poses() {
+        let a = alias();
+        let model = QModelT::default();
+        let mut e = EntityT::default();
+        for &(time, pose) in [(0.1, 2), (0.3, 3), (0.5, 4), (0.7, 2)].iter() {
+            let (_, pose2, _) = setup_alias_frame(&mut e, &model, &a.hdr, 2, time, 1);
+            assert_eq!(pose2, pose);
+            assert_eq!(e.lerptime, 0.2);
+        }
+
+        // frames out of range play frame 0
+        assert_eq!(setup_alias_frame(&mut e, &model, &a.hdr, 7, 1.0, 0).1, 0);
+        assert_eq!(setup_alias_frame(&mut e, &model, &a.hdr, -1, 1.0, 0).1, 0);
+    }
+
+    #[test]
+    fn resets_and_finish() {
+        let a = alias();
+        let model = QModelT::default();
+        let mut e = EntityT::default();
+
+        e.lerpflags = flags(LerpFlags::ResetAnim);
+        assert_eq!(
+            setup_alias_frame(&mut e, &model, &a.hdr, 1, 5.0, 1),
+            (1, 1, 1.0)
+        );
+        assert_eq!(e.lerpflags, 0);
+
+        // ResetAnim2 defers one more pose change
+        e.lerpflags = flags(LerpFlags::ResetAnim2);
+        assert_eq!(
+            setup_alias_frame(&mut e, &model, &a.hdr, 0, 6.0, 1),
+            (0, 0, 1.0)
+        );
+        assert_eq!(
+            setup_alias_frame(&mut e, &model, &a.hdr, 1, 7.0, 1),
+            (0, 1, 0.0)
+        );
+
+        // the server's interval replaces 0.1
+        e.lerpflags = flags(LerpFlags::Finish);
+        e.lerpfinish = 7.5;
+        assert_eq!(setup_alias_frame(&mut e, &model, &a.hdr, 1, 7.25, 1).2, 0.5);
+    }
+
+    #[test]
+    fn lerpmodels_and_nolerp() {
+        let a = alias();
+        let mut model = QModelT::default();
+        let mut e = EntityT::default();
+        assert_eq!(
+            setup_alias_frame(&mut e, &model, &a.hdr, 1, 1.0, 0),
+            (1, 1, 1.0)
+        );
+
+        model.flags = ModelFlags::NoLerp.bits() as _;
+        assert_eq!(
+            setup_alias_frame(&mut e, &model, &a.hdr, 0, 2.0, 1),
+            (0, 0, 1.0)
+        );
+        assert_eq!(
+            setup_alias_frame(&mut e, &model, &a.hdr, 1, 3.0, 2),
+            (0, 1, 0.0)
+        );
+    }
+
+    #[test]
+    fn movement_takes_the_short_way_round() {
+        let mut e = EntityT::default();
+        e.lerpflags = flags(LerpFlags::MoveStep | LerpFlags::ResetMove);
+        e.angles = Vec3T::new(0.0, 350.0, 0.0);
+        setup_entity_transform(&mut e, 1.0, true);
+        assert_eq!(e.lerpflags, flags(LerpFlags::MoveStep));
+
+        e.origin = Vec3T::new(10.0, 0.0, 0.0);
+        e.angles = Vec3T::new(0.0, 10.0, 0.0);
+        let (origin, angles) = setup_entity_transform(&mut e, 2.0, true);
+        assert_eq!((origin[0], angles[1]), (0.0, 350.0));
+        let (origin, angles) = setup_entity_transform(&mut e, 2.05, true);
+        assert!((origin[0] - 5.0).abs() < 1e-3);
+        assert!((angles[1] - 360.0).abs() < 1e-3);
+
+        // no lerp for the view model
+        let (origin, angles) = setup_entity_transform(&mut e, 2.05, false);
+        assert_eq!((origin[0], angles[1]), (10.0, 10.0));
+
+        assert_eq!(
+            angle_delta(
+                Vec3T::new(170.0, 10.0, 0.0),
+                Vec3T::new(-170.0, 350.0, 90.0)
+            )
+            .0,
+            [20.0, -20.0, 90.0]
+        );
+    }
+
+    #[test]
+    fn lerp_data_combines_pose_and_move() {
+        let a = alias();
+        let model = QModelT::default();
+        let mut e = EntityT::default();
+        e.frame = 1;
+        e.origin = Vec3T::new(1.0, 2.0, 3.0);
+        let lerpdata = lerp_alias_entity(&mut e, &model, &a.hdr, 4.0, 1, true);
+        assert_eq!(
+            (lerpdata.pose1, lerpdata.pose2, lerpdata.blend),
+            (0, 1, 0.0)
+        );
+        assert_eq!(lerpdata.origin.0, [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn vertex_normals_are_unit_vectors() {
+        for n in VERTEX_NORMALS.iter() {
+            let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+            assert!((len - 1.0).abs() < 1e-5);
+        }
+        // lightnormalindex values the shading of a model depends on
+        assert_eq!(VERTEX_NORMALS[5], [0.0, 0.0, 1.0]);
+        assert_eq!(VERTEX_NORMALS[32], [0.0, 1.0, 0.0]);
+        assert_eq!(VERTEX_NORMALS[52], [1.0, 0.0, 0.0]);
+        assert_eq!(VERTEX_NORMALS[84], [0.0, 0.0, -1.0]);
+    }
+}
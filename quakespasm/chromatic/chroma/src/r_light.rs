@@ -0,0 +1,729 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2002-2009 John Fitzgibbons and others
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// r_light.rs -- lightmap building and light sampling, independent of the renderer
+
+use bspfile::MAXLIGHTMAPS;
+use client::{DlightT, LightstyleT, MAX_DLIGHTS, MAX_DLIGHTS_BITS};
+use gl_model::{MNodeT, MSurfaceT, QModelT, SurfaceFlags};
+use mathlib::{dot_product, vector_length, vector_ma, vector_subtract};
+use std::os::raw::{c_double, c_int};
+use std::slice;
+use {Byte, QBoolean, Vec3T};
+
+/// lightmap dimensions (smax, tmax) of a surface, in luxels
+pub fn lightmap_size(surf: &MSurfaceT) -> (usize, usize) {
+    return (
+        (surf.extents[0] >> 4) as usize + 1,
+        (surf.extents[1] >> 4) as usize + 1,
+    );
+}
+
+/// number of light styles in use by a surface
+pub fn num_styles(surf: &MSurfaceT) -> usize {
+    return surf.styles.iter().take_while(|&&s| s != 255).count();
+}
+
+/// Formerly: R_AnimateLight
+/// light style values at time, 'm' is normal light, 'a' is no light, 'z' is double bright.
+/// flat is r_flatlightstyles and lerp r_lerplightstyles, see LightstyleT::value
+pub fn animate_light(
+    lightstyles: &[LightstyleT],
+    time: c_double,
+    flat: c_int,
+    lerp: c_int,
+    lightstylevalue: &mut [c_int],
+) {
+    for (value, style) in lightstylevalue.iter_mut().zip(lightstyles) {
+        *value = style.value(time, flat, lerp);
+    }
+}
+
+/*
+=============================================================================
+
+DYNAMIC LIGHTS
+
+=============================================================================
+*/
+
+fn dlight_bit(num: usize) -> (usize, u32) {
+    return (num >> 5, 1 << (num & 31));
+}
+
+/// true if dlight num is marked on surf
+pub fn surface_lit_by(surf: &MSurfaceT, num: usize) -> bool {
+    let (word, bit) = dlight_bit(num);
+    return word < MAX_DLIGHTS_BITS && surf.dlightbits[word] & bit != 0;
+}
+
+/// Formerly: R_MarkLights
+/// flag the surfaces below node that dlight num reaches; surfaces belong to world, which
+/// brush models share.  num indexes cl_dlights, so lights from MAX_DLIGHTS on are not marked
+pub fn mark_lights(
+    world: &QModelT,
+    light: &DlightT,
+    num: usize,
+    node: &MNodeT,
+    dlightframecount: c_int,
+) {
+    if num >= MAX_DLIGHTS {
+        return;
+    }
+    let (word, bit) = dlight_bit(num);
+
+    let mut node = node;
+    let dist = loop {
+        if node.contents < 0 {
+            return;
+        }
+
+        let splitplane = unsafe { &*node.plane };
+        let dist = if splitplane.r#type < 3 {
+            light.origin[splitplane.r#type as usize] - splitplane.dist
+        } else {
+            dot_product(light.origin, splitplane.normal) - splitplane.dist
+        };
+
+        if dist > light.radius {
+            node = unsafe { &*node.children[0] };
+        } else if dist < -light.radius {
+            node = unsafe { &*node.children[1] };
+        } else {
+            break dist;
+        }
+    };
+
+    let maxdist = light.radius * light.radius;
+    // mark the polygons
+    for i in 0..node.numsurfaces {
+        let surf = unsafe { &mut *world.surfaces.add((node.firstsurface + i) as usize) };
+        let plane = unsafe { &*surf.plane };
+        let tex = unsafe { &*surf.texinfo };
+        let impact = vector_ma(light.origin, -dist, plane.normal);
+
+        // clamp center of light to corner and check brightness
+        let l = dot_product(
+            impact,
+            Vec3T::new(tex.vecs[0][0], tex.vecs[0][1], tex.vecs[0][2]),
+        ) + tex.vecs[0][3]
+            - surf.texturemins[0] as f32;
+        let s = ((l + 0.5) as c_int).max(0).min(surf.extents[0] as c_int);
+        let s = (l - s as f32) as c_int;
+        let l = dot_product(
+            impact,
+            Vec3T::new(tex.vecs[1][0], tex.vecs[1][1], tex.vecs[1][2]),
+        ) + tex.vecs[1][3]
+            - surf.texturemins[1] as f32;
+        let t = ((l + 0.5) as c_int).max(0).min(surf.extents[1] as c_int);
+        let t = (l - t as f32) as c_int;
+
+        // compare to minimum light
+        if ((s * s + t * t) as f32 + dist * dist) < maxdist {
+            if surf.dlightframe != dlightframecount {
+                // not dynamic until now; clear every word, the bits of lights in other words
+                // are left over from an earlier frame
+                surf.dlightbits = [0; MAX_DLIGHTS_BITS];
+                surf.dlightbits[word] = bit;
+                surf.dlightframe = dlightframecount;
+            } else {
+                // already dynamic
+                surf.dlightbits[word] |= bit;
+            }
+        }
+    }
+
+    for &child in &node.children {
+        let child = unsafe { &*child };
+        if child.contents >= 0 {
+            mark_lights(world, light, num, child, dlightframecount);
+        }
+    }
+}
+
+/// Formerly: R_PushDlights
+/// mark the world surfaces touched by every live dlight.  framecount is the last frame drawn;
+/// returns the frame the marks are for
+pub fn push_dlights(
+    world: &QModelT,
+    dlights: &[DlightT],
+    time: c_double,
+    framecount: c_int,
+) -> c_int {
+    // because the count hasn't advanced yet for this frame
+    let dlightframecount = framecount + 1;
+    if !world.nodes.is_null() {
+        let headnode = unsafe { &*world.nodes };
+        push_dlights_for_node(world, headnode, dlights, time, dlightframecount);
+    }
+    return dlightframecount;
+}
+
+/// mark the surfaces below node touched by every live dlight; brush models pass their own
+/// head node and the dlightframecount push_dlights returned
+pub fn push_dlights_for_node(
+    world: &QModelT,
+    node: &MNodeT,
+    dlights: &[DlightT],
+    time: c_double,
+    dlightframecount: c_int,
+) {
+    for (i, l) in dlights.iter().enumerate().take(MAX_DLIGHTS) {
+        if (l.die as c_double) < time || l.radius == 0.0 {
+            continue;
+        }
+        mark_lights(world, l, i, node, dlightframecount);
+    }
+}
+
+/// Formerly: R_AddDynamicLights
+/// add the dlights marked on a surface to its blocklights
+pub fn add_dynamic_lights(surf: &MSurfaceT, dlights: &[DlightT], blocklights: &mut [u32]) {
+    let (smax, tmax) = lightmap_size(surf);
+    let plane = unsafe { &*surf.plane };
+    let tex = unsafe { &*surf.texinfo };
+
+    for (lnum, dl) in dlights.iter().enumerate().take(MAX_DLIGHTS) {
+        if !surface_lit_by(surf, lnum) {
+            continue; // not lit by this light
+        }
+
+        let dist = dot_product(dl.origin, plane.normal) - plane.dist;
+        let rad = dl.radius - dist.abs();
+        if rad < dl.minlight {
+            continue;
+        }
+        let minlight = rad - dl.minlight;
+
+        let impact = vector_ma(dl.origin, -dist, plane.normal);
+        let local0 = dot_product(
+            impact,
+            Vec3T::new(tex.vecs[0][0], tex.vecs[0][1], tex.vecs[0][2]),
+        ) + tex.vecs[0][3]
+            - surf.texturemins[0] as f32;
+        let local1 = dot_product(
+            impact,
+            Vec3T::new(tex.vecs[1][0], tex.vecs[1][1], tex.vecs[1][2]),
+        ) + tex.vecs[1][3]
+            - surf.texturemins[1] as f32;
+
+        // lit support via lordhavoc
+        let cred = dl.color[0] * 256.0;
+        let cgreen = dl.color[1] * 256.0;
+        let cblue = dl.color[2] * 256.0;
+        let mut bl = blocklights.chunks_mut(3);
+        for t in 0..tmax {
+            let td = ((local1 - (t * 16) as f32) as c_int).abs();
+            for s in 0..smax {
+                let bl = bl.next().unwrap();
+                let sd = ((local0 - (s * 16) as f32) as c_int).abs();
+                let dist = if sd > td {
+                    sd + (td >> 1)
+                } else {
+                    td + (sd >> 1)
+                } as f32;
+                if dist < minlight {
+                    let brightness = rad - dist;
+                    bl[0] = bl[0].wrapping_add((brightness * cred) as c_int as u32);
+                    bl[1] = bl[1].wrapping_add((brightness * cgreen) as c_int as u32);
+                    bl[2] = bl[2].wrapping_add((brightness * cblue) as c_int as u32);
+                }
+            }
+        }
+    }
+}
+
+/*
+=============================================================================
+
+LIGHTMAP BUILDING
+
+=============================================================================
+*/
+
+/// Formerly: the first half of R_BuildLightMap
+/// sum every light style and this frame's dlights into rgb blocklights (8.8 fixed point),
+/// recording what was used in the surface's cache fields; no lightdata in the world means
+/// fullbright
+pub fn build_blocklights(
+    surf: &mut MSurfaceT,
+    lightdata: bool,
+    lightstylevalue: &[c_int],
+    dlights: &[DlightT],
+    framecount: c_int,
+) -> Vec<u32> {
+    surf.cached_dlight = QBoolean::from(surf.dlightframe == framecount);
+
+    let (smax, tmax) = lightmap_size(surf);
+    let size = smax * tmax;
+
+    if !lightdata {
+        // set to full bright if no light data
+        return vec![0xffffffff; size * 3];
+    }
+
+    // clear to no light
+    let mut blocklights = vec![0u32; size * 3];
+
+    // add all the lightmaps
+    if !surf.samples.is_null() {
+        let numstyles = num_styles(surf).min(MAXLIGHTMAPS);
+        let samples = unsafe { slice::from_raw_parts(surf.samples, size * 3 * numstyles) };
+        for (map, lightmap) in samples.chunks(size * 3).enumerate() {
+            let scale = lightstylevalue[surf.styles[map] as usize];
+            surf.cached_light[map] = scale; // 8.8 fraction
+            for (bl, &l) in blocklights.iter_mut().zip(lightmap) {
+                *bl = bl.wrapping_add(l as u32 * scale as u32);
+            }
+        }
+    }
+
+    // add all the dynamic lights
+    if surf.dlightframe == framecount {
+        add_dynamic_lights(surf, dlights, &mut blocklights);
+    }
+    return blocklights;
+}
+
+/// Formerly: the second half of R_BuildLightMap
+/// bound, invert, and shift blocklights into RGBA lightmap texels
+pub fn store_lightmap(blocklights: &[u32], overbright: bool) -> Vec<Byte> {
+    let shift = if overbright { 8 } else { 7 };
+    let mut dest = Vec::with_capacity(blocklights.len() / 3 * 4);
+    for bl in blocklights.chunks(3) {
+        for &c in bl {
+            dest.push((c >> shift).min(255) as Byte);
+        }
+        dest.push(255);
+    }
+    return dest;
+}
+
+/// Formerly: R_BuildLightMap
+/// RGBA lightmap for a surface with the current light style values and dlights
+pub fn build_lightmap(
+    surf: &mut MSurfaceT,
+    lightdata: bool,
+    lightstylevalue: &[c_int],
+    dlights: &[DlightT],
+    framecount: c_int,
+    overbright: bool,
+) -> Vec<Byte> {
+    let blocklights = build_blocklights(surf, lightdata, lightstylevalue, dlights, framecount);
+    return store_lightmap(&blocklights, overbright);
+}
+
+/// Formerly: the check in R_RenderDynamicLightmaps
+/// true if the lightmap built last no longer matches: a light style changed, or a dlight
+/// touches the surface this frame or did when it was built
+pub fn lightmap_modified(surf: &MSurfaceT, lightstylevalue: &[c_int], framecount: c_int) -> bool {
+    if SurfaceFlags::from_bits_truncate(surf.flags as u32).contains(SurfaceFlags::DrawTiled) {
+        return false; // not a lightmapped surface
+    }
+
+    // check for lightmap modification
+    let numstyles = num_styles(surf).min(MAXLIGHTMAPS);
+    if (0..numstyles)
+        .any(|map| lightstylevalue[surf.styles[map] as usize] != surf.cached_light[map])
+    {
+        return true;
+    }
+
+    // dynamic this frame, or dynamic previously
+    return surf.dlightframe == framecount || surf.cached_dlight == QBoolean::True;
+}
+
+/// Formerly: R_RenderDynamicLightmaps
+/// the surface's rebuilt lightmap if it changed since it was last built, else None
+pub fn update_lightmap(
+    surf: &mut MSurfaceT,
+    lightdata: bool,
+    lightstylevalue: &[c_int],
+    dlights: &[DlightT],
+    framecount: c_int,
+    overbright: bool,
+) -> Option<Vec<Byte>> {
+    if !lightmap_modified(surf, lightstylevalue, framecount) {
+        return None;
+    }
+    return Some(build_lightmap(
+        surf,
+        lightdata,
+        lightstylevalue,
+        dlights,
+        framecount,
+        overbright,
+    ));
+}
+
+/// Formerly: the dlight part of R_SetupAliasLighting
+/// add dlights that reach a point to its light color
+pub fn add_dlights_to_point(
+    lightcolor: Vec3T,
+    p: Vec3T,
+    dlights: &[DlightT],
+    time: c_double,
+) -> Vec3T {
+    let mut lightcolor = lightcolor;
+    for dl in dlights {
+        if dl.die as c_double >= time {
+            let add = dl.radius - vector_length(vector_subtract(p, dl.origin));
+            if add > 0.0 {
+                lightcolor = vector_ma(lightcolor, add, dl.color);
+            }
+        }
+    }
+    return lightcolor;
+}
+
+/*
+=============================================================================
+
+LIGHT SAMPLING
+
+=============================================================================
+*/
+
+unsafe fn recursive_light_point(
+    color: &mut Vec3T,
+    world: &QModelT,
+    lightstylevalue: &[c_int],
+    node: *const MNodeT,
+    start: Vec3T,
+    end: Vec3T,
+) -> bool {
+    let mut node = &*node;
+    let (front, back) = loop {
+        if node.contents < 0 {
+            return false; // didn't hit anything
+        }
+
+        // calculate mid point
+        let plane = &*node.plane;
+        let (front, back) = if plane.r#type < 3 {
+            let t = plane.r#type as usize;
+            (start[t] - plane.dist, end[t] - plane.dist)
+        } else {
+            (
+                dot_product(start, plane.normal) - plane.dist,
+                dot_product(end, plane.normal) - plane.dist,
+            )
+        };
+
+        // LordHavoc: optimized recursion
+        if (back < 0.0) == (front < 0.0) {
+            node = &*node.children[(front < 0.0) as usize];
+            continue;
+        }
+        break (front, back);
+    };
+
+    let frac = front / (front - back);
+    let mid = Vec3T::new(
+        start[0] + (end[0] - start[0]) * frac,
+        start[1] + (end[1] - start[1]) * frac,
+        start[2] + (end[2] - start[2]) * frac,
+    );
+
+    // go down front side
+    if recursive_light_point(
+        color,
+        world,
+        lightstylevalue,
+        node.children[(front < 0.0) as usize],
+        start,
+        mid,
+    ) {
+        return true; // hit something
+    }
+
+    // check for impact on this node
+    let surfaces = slice::from_raw_parts(
+        world.surfaces.add(node.firstsurface as usize),
+        node.numsurfaces as usize,
+    );
+    for surf in surfaces {
+        if SurfaceFlags::from_bits_truncate(surf.flags as u32).contains(SurfaceFlags::DrawTiled) {
+            continue; // no lightmaps
+        }
+
+        // ericw -- use 64-bit precision, or the zombie at the start of jam3_ericw.bsp is lit wrong
+        let tex = &*surf.texinfo;
+        let dot = |v: &[f32; 4]| {
+            mid[0] as f64 * v[0] as f64
+                + mid[1] as f64 * v[1] as f64
+                + mid[2] as f64 * v[2] as f64
+                + v[3] as f64
+        };
+        let mut ds = dot(&tex.vecs[0]) as c_int;
+        let mut dt = dot(&tex.vecs[1]) as c_int;
+
+        if ds < surf.texturemins[0] as c_int || dt < surf.texturemins[1] as c_int {
+            continue;
+        }
+
+        ds -= surf.texturemins[0] as c_int;
+        dt -= surf.texturemins[1] as c_int;
+
+        if ds > surf.extents[0] as c_int || dt > surf.extents[1] as c_int {
+            continue;
+        }
+
+        if !surf.samples.is_null() {
+            // LordHavoc: enhanced to interpolate lighting
+            let (smax, tmax) = lightmap_size(surf);
+            let line3 = smax * 3;
+            let (dsfrac, dtfrac) = (ds & 15, dt & 15);
+            let mut c = [[0 as c_int; 3]; 4];
+
+            let mut lightmap = surf
+                .samples
+                .add(((dt >> 4) as usize * smax + (ds >> 4) as usize) * 3);
+            for map in 0..num_styles(surf).min(MAXLIGHTMAPS) {
+                let scale = lightstylevalue[surf.styles[map] as usize] as f32 * (1.0 / 256.0);
+                // the four luxels around the point
+                for (corner, &ofs) in c.iter_mut().zip(&[0, 3, line3, line3 + 3]) {
+                    for (i, v) in corner.iter_mut().enumerate() {
+                        *v += (*lightmap.add(ofs + i) as f32 * scale) as c_int;
+                    }
+                }
+                lightmap = lightmap.add(smax * tmax * 3);
+            }
+
+            for (i, color) in color.0.iter_mut().enumerate() {
+                let (c00, c01, c10, c11) = (c[0][i], c[1][i], c[2][i], c[3][i]);
+                let top = (((c01 - c00) * dsfrac) >> 4) + c00;
+                let bottom = (((c11 - c10) * dsfrac) >> 4) + c10;
+                *color += ((((bottom - top) * dtfrac) >> 4) + top) as f32;
+            }
+        }
+        return true; // success
+    }
+
+    // go down back side
+    return recursive_light_point(
+        color,
+        world,
+        lightstylevalue,
+        node.children[(front >= 0.0) as usize],
+        mid,
+        end,
+    );
+}
+
+/// Formerly: R_LightPoint
+/// rgb light color at a point, sampled from the world lightmap below it
+pub fn light_point(world: &QModelT, lightstylevalue: &[c_int], p: Vec3T) -> Vec3T {
+    if world.lightdata.is_null() {
+        return Vec3T::new(255.0, 255.0, 255.0);
+    }
+
+    let end = Vec3T::new(p[0], p[1], p[2] - 8192.0); // johnfitz -- was 2048
+
+    let mut lightcolor = Vec3T::default();
+    if !world.nodes.is_null() {
+        unsafe {
+            recursive_light_point(&mut lightcolor, world, lightstylevalue, world.nodes, p, end)
+        };
+    }
+    return lightcolor;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gl_model::{MLeafT, MPlaneT, MTexInfoT};
+
+    /// one node splitting on x = 0, with a 64x64 wall surface facing +x
+    struct TestWorld {
+        model: QModelT,
+        _plane: Box<MPlaneT>,
+        _texinfo: Box<MTexInfoT>,
+        surfs: Vec<MSurfaceT>,
+        nodes: Vec<MNodeT>,
+        _leafs: Vec<MLeafT>,
+    }
+
+    impl TestWorld {
+        fn new() -> Box<Self> {
+            let mut w = Box::new(TestWorld {
+                model: QModelT::default(),
+                _plane: Box::new(MPlaneT::default()),
+                _texinfo: Box::new(MTexInfoT::default()),
+                surfs: vec![MSurfaceT::default(); 1],
+                nodes: vec![MNodeT::default(); 1],
+                _leafs: vec![MLeafT::default(); 2],
+            });
+            w._plane.normal = Vec3T::new(1.0, 0.0, 0.0);
+            w._texinfo.vecs = [[0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0]];
+            let plane = &mut *w._plane as *mut MPlaneT;
+            let texinfo = &mut *w._texinfo as *mut MTexInfoT;
+            for leaf in w._leafs.iter_mut() {
+                leaf.contents = -1;
+            }
+            let children = [
+                &mut w._leafs[0] as *mut MLeafT as *mut MNodeT,
+                &mut w._leafs[1] as *mut MLeafT as *mut MNodeT,
+            ];
+            let surf = &mut w.surfs[0];
+            surf.plane = plane;
+            surf.texinfo = texinfo;
+            surf.extents = [64, 64];
+            surf.styles = [255; MAXLIGHTMAPS];
+            let node = &mut w.nodes[0];
+            node.plane = plane;
+            node.numsurfaces = 1;
+            node.children = children;
+            w.model.nodes = w.nodes.as_mut_ptr();
+            w.model.surfaces = w.surfs.as_mut_ptr();
+            w.model.numsurfaces = 1;
+            return w;
+        }
+    }
+
+    fn dlight(origin: Vec3T, radius: f32) -> DlightT {
+        let mut dl = DlightT::default();
+        dl.origin = origin;
+        dl.radius = radius;
+        dl.die = 10.0;
+        dl.color = Vec3T::new(1.0, 1.0, 1.0);
+        return dl;
+    }
+
+    #[test]
+    fn marks_lights_past_64() {
+        let w = TestWorld::new();
+        let mut dlights = vec![DlightT::default(); MAX_DLIGHTS];
+        dlights[3] = dlight(Vec3T::new(500.0, 32.0, 32.0), 100.0);
+        dlights[40] = dlight(Vec3T::new(10.0, 32.0, 32.0), 100.0);
+        dlights[MAX_DLIGHTS - 1] = dlight(Vec3T::new(-10.0, 32.0, 32.0), 100.0);
+
+        let framecount = push_dlights(&w.model, &dlights, 1.0, 0);
+        assert_eq!(framecount, 1);
+        assert_eq!(w.surfs[0].dlightframe, 1);
+        assert!(!surface_lit_by(&w.surfs[0], 3));
+        assert!(surface_lit_by(&w.surfs[0], 40));
+        assert!(surface_lit_by(&w.surfs[0], MAX_DLIGHTS - 1));
+        assert!(!surface_lit_by(&w.surfs[0], MAX_DLIGHTS));
+    }
+
+    #[test]
+    fn stale_bits_are_cleared() {
+        let mut w = TestWorld::new();
+        w.surfs[0].dlightbits = [0xffff_ffff; MAX_DLIGHTS_BITS];
+        let mut dlights = vec![DlightT::default(); MAX_DLIGHTS];
+        dlights[100] = dlight(Vec3T::new(10.0, 32.0, 32.0), 100.0);
+
+        push_dlights(&w.model, &dlights, 1.0, 0);
+        for num in 0..MAX_DLIGHTS {
+            assert_eq!(surface_lit_by(&w.surfs[0], num), num == 100);
+        }
+
+        // dead lights mark nothing
+        let framecount = push_dlights(&w.model, &dlights, 20.0, 1);
+        assert_eq!(framecount, 2);
+        assert_eq!(w.surfs[0].dlightframe, 1);
+    }
+
+    #[test]
+    fn dlights_brighten_lightmap() {
+        let mut w = TestWorld::new();
+        let mut dlights = vec![DlightT::default(); MAX_DLIGHTS];
+        dlights[70] = dlight(Vec3T::new(10.0, 32.0, 32.0), 100.0);
+        let lightstylevalue = [256; 256];
+
+        let framecount = push_dlights(&w.model, &dlights, 1.0, 0);
+        let lit = build_lightmap(
+            &mut w.surfs[0],
+            true,
+            &lightstylevalue,
+            &dlights,
+            framecount,
+            false,
+        );
+        assert_eq!(lit.len(), 5 * 5 * 4);
+        assert!(w.surfs[0].cached_dlight == QBoolean::True);
+        // radius 100 less distance 10, 8.8 shifted by 7; falls off toward the corners
+        assert_eq!(
+            &lit[(2 * 5 + 2) * 4..(2 * 5 + 3) * 4],
+            &[180, 180, 180, 255]
+        );
+        assert_eq!(&lit[0..4], &[84, 84, 84, 255]);
+
+        // lightmaps built in a later frame drop the light
+        assert!(lightmap_modified(
+            &w.surfs[0],
+            &lightstylevalue,
+            framecount + 1
+        ));
+        let dark = build_lightmap(
+            &mut w.surfs[0],
+            true,
+            &lightstylevalue,
+            &dlights,
+            framecount + 1,
+            false,
+        );
+        assert!(dark.chunks(4).all(|t| t == [0, 0, 0, 255]));
+        assert!(!lightmap_modified(
+            &w.surfs[0],
+            &lightstylevalue,
+            framecount + 1
+        ));
+    }
+
+    #[test]
+    fn store_lightmap_shifts_and_bounds() {
+        let blocklights = [128 << 7, 300 << 7, 0, 128 << 8, 0, 1];
+        assert_eq!(
+            store_lightmap(&blocklights, false),
+            vec![128, 255, 0, 255, 255, 0, 0, 255]
+        );
+        assert_eq!(
+            store_lightmap(&blocklights, true),
+            vec![64, 150, 0, 255, 128, 0, 0, 255]
+        );
+    }
+
+    #[test]
+    fn animates_light_styles() {
+        let mut styles = vec![LightstyleT::default(); 64];
+        styles[1].length = 2;
+        styles[1].map[0] = b'a' as _;
+        styles[1].map[1] = b'z' as _;
+        styles[1].average = b'm' as _;
+        styles[1].peak = b'z' as _;
+        let mut value = [0; 256];
+        animate_light(&styles, 0.15, 0, 0, &mut value);
+        assert_eq!(value[0], 256);
+        assert_eq!(value[1], 25 * 22);
+        animate_light(&styles, 0.15, 1, 0, &mut value);
+        assert_eq!(value[1], 12 * 22);
+    }
+
+    #[test]
+    fn points_take_every_dlight() {
+        let dlights = vec![dlight(Vec3T::new(0.0, 0.0, 0.0), 100.0); MAX_DLIGHTS + 1];
+        let c = add_dlights_to_point(Vec3T::default(), Vec3T::new(50.0, 0.0, 0.0), &dlights, 1.0);
+        assert_eq!(c[0], 50.0 * (MAX_DLIGHTS + 1) as f32);
+    }
+}
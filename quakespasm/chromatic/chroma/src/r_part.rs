@@ -0,0 +1,709 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2002-2009 John Fitzgibbons and others
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// r_part.rs -- particle emitters and simulation
+
+/*
+particles are plain data: a renderer only needs org and color, a palette index.  every random
+number comes from the system's own generator, so the same seed and the same sequence of
+emitter calls and frames always gives the same particles.
+*/
+
+use cl_main::ClientEffects;
+use cl_parse::ParseError;
+use cl_tent::TempEntityHandler;
+use common::MsgReaderT;
+use gl_model::QModelT;
+use mathlib::{vector_normalize, vector_scale, vector_subtract, RandT};
+use r_alias::{NUMVERTEXNORMALS, VERTEX_NORMALS};
+use render::EntityT;
+use std::os::raw::{c_double, c_float, c_int, c_uint};
+use std::ptr::null_mut;
+use Vec3T;
+
+/// default max # of particles at one time
+pub const MAX_PARTICLES: usize = 2048;
+/// no fewer than this no matter what's on the command line
+pub const ABSOLUTE_MIN_PARTICLES: usize = 512;
+
+pub const RAMP1: [c_int; 8] = [0x6f, 0x6d, 0x6b, 0x69, 0x67, 0x65, 0x63, 0x61];
+pub const RAMP2: [c_int; 8] = [0x6f, 0x6e, 0x6d, 0x6c, 0x6b, 0x6a, 0x68, 0x66];
+pub const RAMP3: [c_int; 8] = [0x6d, 0x6b, 6, 5, 4, 3, 0, 0];
+
+/// distance of entity particles from the entity's origin
+const ENTITY_PARTICLE_DIST: c_float = 64.0;
+const BEAM_LENGTH: c_float = 16.0;
+
+/// Formerly: ptype_t
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(C)]
+pub enum PtypeT {
+    Static,
+    Grav,
+    SlowGrav,
+    Fire,
+    Explode,
+    Explode2,
+    Blob,
+    Blob2,
+}
+
+impl PtypeT {
+    pub const fn default() -> Self {
+        PtypeT::Static
+    }
+}
+
+impl Default for PtypeT {
+    fn default() -> Self {
+        Self::default()
+    }
+}
+
+/// Formerly: particle_t
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct ParticleT {
+    // driver-usable fields
+    pub org: Vec3T,
+    /// palette index
+    pub color: c_int,
+    // drivers never touch the following fields
+    pub vel: Vec3T,
+    pub ramp: c_float,
+    pub die: c_float,
+    pub r#type: PtypeT,
+}
+
+impl ParticleT {
+    pub const fn default() -> Self {
+        Self {
+            org: Vec3T::new(0.0, 0.0, 0.0),
+            color: 0,
+            vel: Vec3T::new(0.0, 0.0, 0.0),
+            ramp: 0.0,
+            die: 0.0,
+            r#type: PtypeT::default(),
+        }
+    }
+}
+
+impl Default for ParticleT {
+    fn default() -> Self {
+        Self::default()
+    }
+}
+
+/// svc_particle, see parse_particle_effect
+#[derive(Clone, Copy)]
+pub struct ParticleEffectT {
+    pub org: Vec3T,
+    pub dir: Vec3T,
+    pub color: c_int,
+    pub count: c_int,
+}
+
+/// Formerly: R_ParseParticleEffect
+/// Parse an effect out of the server message
+pub fn parse_particle_effect(
+    msg: &mut MsgReaderT,
+    protocolflags: c_uint,
+) -> Result<ParticleEffectT, ParseError> {
+    let mut org = Vec3T::new(0.0, 0.0, 0.0);
+    let mut dir = Vec3T::new(0.0, 0.0, 0.0);
+    for i in 0..3 {
+        org[i] = msg.read_coord(protocolflags);
+    }
+    for i in 0..3 {
+        dir[i] = msg.read_char() as c_float * (1.0 / 16.0);
+    }
+    let msgcount = msg.read_byte();
+    let color = msg.read_byte();
+
+    if msg.badread {
+        return Err(ParseError::BadRead);
+    }
+    return Ok(ParticleEffectT {
+        org,
+        dir,
+        color,
+        count: if msgcount == 255 { 1024 } else { msgcount },
+    });
+}
+
+/// the live particles and everything the emitters keep between calls
+pub struct ParticlesT {
+    particles: Vec<ParticleT>,
+    max: usize,
+    /// cl.time, the time new particles are spawned at
+    pub time: c_double,
+    rand: RandT,
+    avelocities: Vec<Vec3T>,
+    tracercount: c_int,
+}
+
+impl ParticlesT {
+    /// Formerly: R_InitParticles
+    /// room for max particles, which is -particles and clamped like it
+    pub fn new(max: usize, seed: u32) -> Self {
+        let max = max.max(ABSOLUTE_MIN_PARTICLES);
+        return Self {
+            particles: Vec::with_capacity(max),
+            max,
+            time: 0.0,
+            rand: RandT::new(seed),
+            avelocities: Vec::new(),
+            tracercount: 0,
+        };
+    }
+
+    /// Formerly: R_ClearParticles
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    /// restart the random sequence, for reproducing a run
+    pub fn seed(&mut self, seed: u32) {
+        self.rand.seed(seed);
+        self.avelocities.clear();
+        self.tracercount = 0;
+    }
+
+    /// the live particles, for drawing
+    pub fn particles(&self) -> &[ParticleT] {
+        return &self.particles;
+    }
+
+    pub fn max(&self) -> usize {
+        return self.max;
+    }
+
+    fn is_full(&self) -> bool {
+        return self.particles.len() >= self.max;
+    }
+
+    fn rand(&mut self) -> c_int {
+        return self.rand.rand();
+    }
+
+    fn die(&self, after: c_double) -> c_float {
+        return (self.time + after) as c_float;
+    }
+
+    /// die time of after plus (rand() & mask) * step seconds
+    fn die_rand(&mut self, after: c_double, mask: c_int, step: c_double) -> c_float {
+        let r = self.rand() & mask;
+        return self.die(after + r as c_double * step);
+    }
+
+    /// org offset by rand()%32-16 on each axis, with a random velocity in -256..256
+    fn scatter(&mut self, org: Vec3T, p: &mut ParticleT) {
+        for j in 0..3 {
+            p.org[j] = org[j] + ((self.rand() % 32) - 16) as c_float;
+            p.vel[j] = ((self.rand() % 512) - 256) as c_float;
+        }
+    }
+
+    /// Formerly: R_EntityParticles
+    /// the sphere of particles around an EF_BRIGHTFIELD entity
+    pub fn entity_particles(&mut self, ent: &EntityT) {
+        if self.avelocities.is_empty() {
+            for _ in 0..NUMVERTEXNORMALS {
+                let mut v = Vec3T::new(0.0, 0.0, 0.0);
+                for j in 0..3 {
+                    v[j] = (self.rand() & 255) as c_float * 0.01;
+                }
+                self.avelocities.push(v);
+            }
+        }
+
+        for (i, normal) in VERTEX_NORMALS.iter().enumerate() {
+            let angle = self.time * self.avelocities[i][0] as c_double;
+            let (sy, cy) = (angle.sin() as c_float, angle.cos() as c_float);
+            let angle = self.time * self.avelocities[i][1] as c_double;
+            let (sp, cp) = (angle.sin() as c_float, angle.cos() as c_float);
+            let forward = Vec3T::new(cp * cy, cp * sy, -sp);
+
+            if self.is_full() {
+                return;
+            }
+            let mut p = ParticleT {
+                die: self.die(0.01),
+                color: 0x6f,
+                r#type: PtypeT::Explode,
+                ..ParticleT::default()
+            };
+            for j in 0..3 {
+                p.org[j] =
+                    ent.origin[j] + normal[j] * ENTITY_PARTICLE_DIST + forward[j] * BEAM_LENGTH;
+            }
+            self.particles.push(p);
+        }
+    }
+
+    /// Formerly: R_ReadPointFile_f
+    /// static markers at each point, e.g. from a leak's .pts file; returns how many fit
+    pub fn add_points(&mut self, points: &[Vec3T]) -> usize {
+        for (c, &org) in points.iter().enumerate() {
+            if self.is_full() {
+                return c;
+            }
+            self.particles.push(ParticleT {
+                org,
+                die: 99999.0,
+                color: -(c as c_int + 1) & 15,
+                r#type: PtypeT::Static,
+                ..ParticleT::default()
+            });
+        }
+        return points.len();
+    }
+
+    /// Formerly: R_ParticleExplosion
+    pub fn particle_explosion(&mut self, org: Vec3T) {
+        for i in 0..1024 {
+            if self.is_full() {
+                return;
+            }
+            let mut p = ParticleT {
+                die: self.die(5.0),
+                color: RAMP1[0],
+                ramp: (self.rand() & 3) as c_float,
+                r#type: if i & 1 != 0 {
+                    PtypeT::Explode
+                } else {
+                    PtypeT::Explode2
+                },
+                ..ParticleT::default()
+            };
+            self.scatter(org, &mut p);
+            self.particles.push(p);
+        }
+    }
+
+    /// Formerly: R_ParticleExplosion2
+    /// explosion in color_length colors starting at color_start
+    pub fn particle_explosion2(&mut self, org: Vec3T, color_start: c_int, color_length: c_int) {
+        for color_mod in 0..512 {
+            if self.is_full() {
+                return;
+            }
+            let mut p = ParticleT {
+                die: self.die(0.3),
+                color: color_start + color_mod % color_length.max(1),
+                r#type: PtypeT::Blob,
+                ..ParticleT::default()
+            };
+            self.scatter(org, &mut p);
+            self.particles.push(p);
+        }
+    }
+
+    /// Formerly: R_BlobExplosion
+    pub fn blob_explosion(&mut self, org: Vec3T) {
+        for i in 0..1024 {
+            if self.is_full() {
+                return;
+            }
+            let mut p = ParticleT {
+                die: self.die_rand(1.0, 8, 0.05),
+                ..ParticleT::default()
+            };
+            if i & 1 != 0 {
+                p.r#type = PtypeT::Blob;
+                p.color = 66 + self.rand() % 6;
+            } else {
+                p.r#type = PtypeT::Blob2;
+                p.color = 150 + self.rand() % 6;
+            }
+            self.scatter(org, &mut p);
+            self.particles.push(p);
+        }
+    }
+
+    /// Formerly: R_RunParticleEffect
+    /// count particles of color drifting along dir; a count of 1024 is a rocket explosion
+    pub fn run_particle_effect(&mut self, org: Vec3T, dir: Vec3T, color: c_int, count: c_int) {
+        if count == 1024 {
+            self.particle_explosion(org);
+            return;
+        }
+        for _ in 0..count {
+            if self.is_full() {
+                return;
+            }
+            let r = self.rand() % 5;
+            let die = self.die(0.1 * r as c_double);
+            let mut p = ParticleT {
+                die,
+                color: (color & !7) + (self.rand() & 7),
+                r#type: PtypeT::SlowGrav,
+                ..ParticleT::default()
+            };
+            for j in 0..3 {
+                p.org[j] = org[j] + ((self.rand() & 15) - 8) as c_float;
+                p.vel[j] = dir[j] * 15.0;
+            }
+            self.particles.push(p);
+        }
+    }
+
+    /// svc_particle, see parse_particle_effect
+    pub fn particle_effect(&mut self, effect: &ParticleEffectT) {
+        self.run_particle_effect(effect.org, effect.dir, effect.color, effect.count);
+    }
+
+    /// Formerly: R_LavaSplash
+    pub fn lava_splash(&mut self, org: Vec3T) {
+        for i in -16..16 {
+            for j in -16..16 {
+                if self.is_full() {
+                    return;
+                }
+                let mut p = ParticleT {
+                    die: self.die_rand(2.0, 31, 0.02),
+                    color: 224 + (self.rand() & 7),
+                    r#type: PtypeT::SlowGrav,
+                    ..ParticleT::default()
+                };
+
+                let mut dir = Vec3T::new(
+                    (j * 8 + (self.rand() & 7)) as c_float,
+                    (i * 8 + (self.rand() & 7)) as c_float,
+                    256.0,
+                );
+
+                p.org[0] = org[0] + dir[0];
+                p.org[1] = org[1] + dir[1];
+                p.org[2] = org[2] + (self.rand() & 63) as c_float;
+
+                vector_normalize(&mut dir);
+                let vel = (50 + (self.rand() & 63)) as c_float;
+                p.vel = vector_scale(dir, vel);
+                self.particles.push(p);
+            }
+        }
+    }
+
+    /// Formerly: R_TeleportSplash
+    pub fn teleport_splash(&mut self, org: Vec3T) {
+        for i in (-16..16).step_by(4) {
+            for j in (-16..16).step_by(4) {
+                for k in (-24..32).step_by(4) {
+                    if self.is_full() {
+                        return;
+                    }
+                    let mut p = ParticleT {
+                        die: self.die_rand(0.2, 7, 0.02),
+                        color: 7 + (self.rand() & 7),
+                        r#type: PtypeT::SlowGrav,
+                        ..ParticleT::default()
+                    };
+
+                    let mut dir =
+                        Vec3T::new((j * 8) as c_float, (i * 8) as c_float, (k * 8) as c_float);
+
+                    p.org[0] = org[0] + (i + (self.rand() & 3)) as c_float;
+                    p.org[1] = org[1] + (j + (self.rand() & 3)) as c_float;
+                    p.org[2] = org[2] + (k + (self.rand() & 3)) as c_float;
+
+                    vector_normalize(&mut dir);
+                    let vel = (50 + (self.rand() & 63)) as c_float;
+                    p.vel = vector_scale(dir, vel);
+                    self.particles.push(p);
+                }
+            }
+        }
+    }
+
+    /// Formerly: R_RocketTrail
+    /// trail from start to end.  type is 0 rocket, 1 smoke, 2 blood, 3 and 5 tracers, 4 slight
+    /// blood and 6 voor trail, plus 128 for one particle every unit instead of every three
+    pub fn rocket_trail(&mut self, start: Vec3T, end: Vec3T, r#type: c_int) {
+        let mut start = start;
+        let mut vec = vector_subtract(end, start);
+        let mut len = vector_normalize(&mut vec);
+        let (dec, r#type) = if r#type < 128 {
+            (3.0, r#type)
+        } else {
+            (1.0, r#type - 128)
+        };
+
+        while len > 0.0 {
+            len -= dec;
+
+            if self.is_full() {
+                return;
+            }
+            let mut p = ParticleT {
+                die: self.die(2.0),
+                ..ParticleT::default()
+            };
+
+            match r#type {
+                // rocket trail, smoke smoke
+                0 | 1 => {
+                    let base = if r#type == 0 { 0 } else { 2 };
+                    let ramp = (self.rand() & 3) + base;
+                    p.ramp = ramp as c_float;
+                    p.color = RAMP3[ramp as usize];
+                    p.r#type = PtypeT::Fire;
+                    for j in 0..3 {
+                        p.org[j] = start[j] + ((self.rand() % 6) - 3) as c_float;
+                    }
+                }
+                // blood, slight blood
+                2 | 4 => {
+                    p.r#type = PtypeT::Grav;
+                    p.color = 67 + (self.rand() & 3);
+                    for j in 0..3 {
+                        p.org[j] = start[j] + ((self.rand() % 6) - 3) as c_float;
+                    }
+                    if r#type == 4 {
+                        len -= 3.0;
+                    }
+                }
+                // tracer
+                3 | 5 => {
+                    p.die = self.die(0.5);
+                    p.r#type = PtypeT::Static;
+                    let base = if r#type == 3 { 52 } else { 230 };
+                    p.color = base + ((self.tracercount & 4) << 1);
+
+                    self.tracercount += 1;
+
+                    p.org = start;
+                    if self.tracercount & 1 != 0 {
+                        p.vel[0] = 30.0 * vec[1];
+                        p.vel[1] = 30.0 * -vec[0];
+                    } else {
+                        p.vel[0] = 30.0 * -vec[1];
+                        p.vel[1] = 30.0 * vec[0];
+                    }
+                }
+                // voor trail
+                6 => {
+                    p.color = 9 * 16 + 8 + (self.rand() & 3);
+                    p.r#type = PtypeT::Static;
+                    p.die = self.die(0.3);
+                    for j in 0..3 {
+                        p.org[j] = start[j] + ((self.rand() & 15) - 8) as c_float;
+                    }
+                }
+                _ => {}
+            }
+            self.particles.push(p);
+
+            for j in 0..3 {
+                start[j] += vec[j];
+            }
+        }
+    }
+
+    /// Formerly: CL_RunParticles
+    /// advance every particle from oldtime to time and drop the dead.  gravity is sv_gravity
+    pub fn run(&mut self, time: c_double, oldtime: c_double, gravity: c_float) {
+        self.time = time;
+        let frametime = (time - oldtime) as c_float;
+        let time3 = frametime * 15.0;
+        let time2 = frametime * 10.0;
+        let time1 = frametime * 5.0;
+        let grav = frametime * gravity * 0.05;
+        let dvel = 4.0 * frametime;
+
+        self.particles.retain(|p| p.die as c_double >= time);
+
+        for p in self.particles.iter_mut() {
+            for i in 0..3 {
+                p.org[i] += p.vel[i] * frametime;
+            }
+
+            match p.r#type {
+                PtypeT::Static => {}
+                PtypeT::Fire => {
+                    p.ramp += time1;
+                    if p.ramp >= 6.0 {
+                        p.die = -1.0;
+                    } else {
+                        p.color = RAMP3[p.ramp as usize];
+                    }
+                    p.vel[2] += grav;
+                }
+                PtypeT::Explode => {
+                    p.ramp += time2;
+                    if p.ramp >= 8.0 {
+                        p.die = -1.0;
+                    } else {
+                        p.color = RAMP1[p.ramp as usize];
+                    }
+                    for i in 0..3 {
+                        p.vel[i] += p.vel[i] * dvel;
+                    }
+                    p.vel[2] -= grav;
+                }
+                PtypeT::Explode2 => {
+                    p.ramp += time3;
+                    if p.ramp >= 8.0 {
+                        p.die = -1.0;
+                    } else {
+                        p.color = RAMP2[p.ramp as usize];
+                    }
+                    for i in 0..3 {
+                        p.vel[i] -= p.vel[i] * frametime;
+                    }
+                    p.vel[2] -= grav;
+                }
+                PtypeT::Blob => {
+                    for i in 0..3 {
+                        p.vel[i] += p.vel[i] * dvel;
+                    }
+                    p.vel[2] -= grav;
+                }
+                PtypeT::Blob2 => {
+                    for i in 0..2 {
+                        p.vel[i] -= p.vel[i] * dvel;
+                    }
+                    p.vel[2] -= grav;
+                }
+                PtypeT::Grav | PtypeT::SlowGrav => {
+                    p.vel[2] -= grav;
+                }
+            }
+        }
+    }
+}
+
+impl ClientEffects for ParticlesT {
+    fn entity_particles(&mut self, ent: &EntityT) {
+        ParticlesT::entity_particles(self, ent);
+    }
+
+    fn rocket_trail(&mut self, start: Vec3T, end: Vec3T, r#type: c_int) {
+        ParticlesT::rocket_trail(self, start, end, r#type);
+    }
+}
+
+/// particles only; sounds are dropped and beams have no model, so a client with sound and
+/// models wraps the particle system in its own handler
+impl TempEntityHandler for ParticlesT {
+    fn run_particle_effect(&mut self, org: Vec3T, dir: Vec3T, color: c_int, count: c_int) {
+        ParticlesT::run_particle_effect(self, org, dir, color, count);
+    }
+
+    fn particle_explosion(&mut self, org: Vec3T) {
+        ParticlesT::particle_explosion(self, org);
+    }
+
+    fn particle_explosion2(&mut self, org: Vec3T, color_start: c_int, color_length: c_int) {
+        ParticlesT::particle_explosion2(self, org, color_start, color_length);
+    }
+
+    fn blob_explosion(&mut self, org: Vec3T) {
+        ParticlesT::blob_explosion(self, org);
+    }
+
+    fn lava_splash(&mut self, org: Vec3T) {
+        ParticlesT::lava_splash(self, org);
+    }
+
+    fn teleport_splash(&mut self, org: Vec3T) {
+        ParticlesT::teleport_splash(self, org);
+    }
+
+    fn start_sound(&mut self, _name: &str, _org: Vec3T) {}
+
+    fn beam_model(&mut self, _name: &str) -> *mut QModelT {
+        return null_mut();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(p: &ParticleT) -> ([c_float; 3], [c_float; 3], c_int, c_float, c_float, PtypeT) {
+        return (p.org.0, p.vel.0, p.color, p.ramp, p.die, p.r#type);
+    }
+
+    /// a few frames of everything, with new emitters between frames
+    fn simulate(seed: u32) -> Vec<ParticleT> {
+        let mut ps = ParticlesT::new(MAX_PARTICLES, seed);
+        let mut ent = EntityT::default();
+        ent.origin = Vec3T::new(64.0, 0.0, 0.0);
+        let org = Vec3T::new(0.0, 0.0, 0.0);
+        for frame in 0..10 {
+            let (oldtime, time) = (frame as c_double * 0.05, (frame + 1) as c_double * 0.05);
+            match frame {
+                0 => ps.particle_explosion(org),
+                2 => ps.blob_explosion(org),
+                4 => ps.teleport_splash(org),
+                6 => ps.entity_particles(&ent),
+                _ => ps.rocket_trail(org, Vec3T::new(0.0, 100.0, 0.0), frame % 7),
+            }
+            ps.run_particle_effect(org, Vec3T::new(0.0, 0.0, 1.0), 73, 20);
+            ps.run(time, oldtime, 800.0);
+        }
+        return ps.particles().to_vec();
+    }
+
+    #[test]
+    fn same_seed_same_particles() {
+        let (a, b) = (simulate(5), simulate(5));
+        assert!(!a.is_empty());
+        assert_eq!(a.len(), b.len());
+        assert!(a.iter().zip(&b).all(|(a, b)| fields(a) == fields(b)));
+
+        let c = simulate(6);
+        assert!(a.len() != c.len() || a.iter().zip(&c).any(|(a, c)| fields(a) != fields(c)));
+    }
+
+    #[test]
+    fn reseeding_restarts_the_sequence() {
+        let mut ps = ParticlesT::new(MAX_PARTICLES, 1);
+        ps.particle_explosion(Vec3T::default());
+        let first = fields(&ps.particles()[0]);
+        // seed 1 draws 41, 18467, 6334, 26500, 19169: ramp, then org and vel per axis
+        assert_eq!(first.3, (41 & 3) as c_float);
+        assert_eq!(
+            (first.0[0], first.1[0]),
+            ((18467 % 32 - 16) as c_float, (6334 % 512 - 256) as c_float)
+        );
+        assert_eq!(
+            (first.0[1], first.1[1]),
+            ((26500 % 32 - 16) as c_float, (19169 % 512 - 256) as c_float)
+        );
+
+        ps.clear();
+        ps.seed(1);
+        ps.particle_explosion(Vec3T::default());
+        assert!(fields(&ps.particles()[0]) == first);
+    }
+
+    #[test]
+    fn emitters_stop_at_max() {
+        let mut ps = ParticlesT::new(0, 1);
+        assert_eq!(ps.max(), ABSOLUTE_MIN_PARTICLES);
+        ps.particle_explosion(Vec3T::default());
+        ps.lava_splash(Vec3T::default());
+        assert_eq!(ps.particles().len(), ABSOLUTE_MIN_PARTICLES);
+    }
+}
@@ -0,0 +1,1254 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2002-2009 John Fitzgibbons and others
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// r_soft.rs -- software rasterizer, for rendering without a gl context (screenshots, visual diffs)
+
+use client::DlightT;
+use gl_model::{
+    AliasHdrT, MSpriteFrameT, MSpriteGroupT, MSpriteT, MSurfaceT, ModTypeT, QModelT, SurfaceFlags,
+    TextureT,
+};
+use gl_texmgr::{read_source, GlTextureT, ImageSource, PalettesT, SrcFormat};
+use mathlib::{angle_vectors, dot_product, vector_ma, vector_normalize, vector_subtract};
+use modelgen::TriVertexT;
+use r_alias::lerp_alias_entity;
+use r_light::{
+    add_dlights_to_point, build_lightmap, light_point, lightmap_size, push_dlights,
+    push_dlights_for_node,
+};
+use r_world::WorldVisT;
+use render::{EntityT, RefDefT};
+use spritegn::{SpriteFrameTypeT, SpriteViewPosition};
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::io::{self, Write};
+use std::os::raw::{c_double, c_float, c_int};
+use std::slice;
+use {Byte, Vec3T, VecT};
+
+/// closest distance from the eye anything is drawn at
+const NEAR_CLIP: VecT = 4.0;
+
+/// a vertex in view space (x right, y up, z forward) with up to four interpolated attributes
+#[derive(Clone, Copy)]
+struct ClipVertT {
+    pos: Vec3T,
+    attr: [c_float; 4],
+}
+
+/// a vertex after projection, ready for rasterization
+#[derive(Clone, Copy)]
+struct ScreenVertT {
+    x: c_float,
+    y: c_float,
+    /// 1/z, interpolates linearly in screen space
+    iz: c_float,
+    attr: [c_float; 4],
+}
+
+/// an 8-bit image and its size
+struct SkinT<'a> {
+    data: &'a [Byte],
+    width: usize,
+    height: usize,
+}
+
+impl<'a> SkinT<'a> {
+    /// palette index at wrapped texel coordinates
+    fn texel(&self, s: c_float, t: c_float) -> Byte {
+        let x = (s.floor() as isize).rem_euclid(self.width as isize) as usize;
+        let y = (t.floor() as isize).rem_euclid(self.height as isize) as usize;
+        return self.data[y * self.width + x];
+    }
+}
+
+/// a sprite frame read back from its source
+#[derive(Clone)]
+struct SpriteImageT {
+    data: Vec<Byte>,
+    width: usize,
+    height: usize,
+    format: SrcFormat,
+}
+
+/// A CPU renderer drawing the world, alias models and sprites into an RGBA buffer.
+pub struct SoftRenderer {
+    width: usize,
+    height: usize,
+    /// packed like the palettes, so bytes are r, g, b, a in memory order
+    color: Vec<u32>,
+    /// 1/z of the closest thing drawn so far, 0 is infinitely far
+    depth: Vec<c_float>,
+    palettes: PalettesT,
+    /// 8.8 fixed point brightness of each light style; 256 is normal
+    pub lightstylevalue: [c_int; 256],
+    /// dynamic lights, as in cl_dlights; surfaces are lit by the first MAX_DLIGHTS, models by all
+    pub dlights: Vec<DlightT>,
+    pub overbright: bool,
+    /// draw palette indexes 224-255 without lighting
+    pub fullbrights: bool,
+    /// r_lerpmodels, see setup_alias_frame
+    pub lerpmodels: c_int,
+    /// r_lerpmove, blend the movement of MOVETYPE_STEP entities
+    pub lerpmove: bool,
+    /// r, g, b, a the buffer is cleared to before each view
+    pub clearcolor: [Byte; 4],
+    /// pvs and frustum culling of the world, kept between frames
+    pub vis: WorldVisT,
+    /// sprite frames are only kept by texture manager as a source reference
+    sprite_images: HashMap<*const GlTextureT, Option<SpriteImageT>>,
+
+    // current view
+    time: c_double,
+    origin: Vec3T,
+    vpn: Vec3T,
+    vright: Vec3T,
+    vup: Vec3T,
+    xcenter: c_float,
+    ycenter: c_float,
+    xscale: c_float,
+    yscale: c_float,
+    rect: [usize; 4],
+}
+
+impl SoftRenderer {
+    /// an empty framebuffer; pal is the 768 byte gfx/palette.lmp
+    pub fn new(width: usize, height: usize, pal: &[Byte]) -> Self {
+        Self {
+            width,
+            height,
+            color: vec![0; width * height],
+            depth: vec![0.0; width * height],
+            palettes: PalettesT::new(pal),
+            lightstylevalue: [256; 256],
+            dlights: Vec::new(),
+            overbright: true,
+            fullbrights: true,
+            lerpmodels: 1,
+            lerpmove: true,
+            clearcolor: [0, 0, 0, 255],
+            vis: WorldVisT::new(),
+            sprite_images: HashMap::new(),
+            time: 0.0,
+            origin: Vec3T::default(),
+            vpn: Vec3T::default(),
+            vright: Vec3T::default(),
+            vup: Vec3T::default(),
+            xcenter: 0.0,
+            ycenter: 0.0,
+            xscale: 1.0,
+            yscale: 1.0,
+            rect: [0, 0, width, height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        return self.width;
+    }
+
+    pub fn height(&self) -> usize {
+        return self.height;
+    }
+
+    /// the framebuffer as rows of r, g, b, a bytes, top row first
+    pub fn pixels(&self) -> Vec<Byte> {
+        let mut pixels = Vec::with_capacity(self.color.len() * 4);
+        for c in &self.color {
+            pixels.extend_from_slice(&c.to_le_bytes());
+        }
+        return pixels;
+    }
+
+    /// r, g, b, a of one pixel
+    pub fn pixel(&self, x: usize, y: usize) -> [Byte; 4] {
+        return self.color[y * self.width + x].to_le_bytes();
+    }
+
+    /// Formerly: Image_WriteTGA
+    /// write the framebuffer as an uncompressed 32 bit tga
+    pub fn write_tga<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut header = [0 as Byte; 18];
+        header[2] = 2; // uncompressed type
+        header[12..14].copy_from_slice(&(self.width as u16).to_le_bytes());
+        header[14..16].copy_from_slice(&(self.height as u16).to_le_bytes());
+        header[16] = 32; // pixel size
+        header[17] = 0x28; // 8 attribute bits, top-left origin
+        out.write_all(&header)?;
+
+        let mut row = Vec::with_capacity(self.width * 4);
+        for y in 0..self.height {
+            row.clear();
+            for c in &self.color[y * self.width..(y + 1) * self.width] {
+                let [r, g, b, a] = c.to_le_bytes();
+                row.extend_from_slice(&[b, g, r, a]);
+            }
+            out.write_all(&row)?;
+        }
+        return Ok(());
+    }
+
+    /// forget cached sprite images, e.g. after a map change
+    pub fn flush_cache(&mut self) {
+        self.vis.new_map();
+        self.sprite_images.clear();
+    }
+
+    /// Formerly: R_RenderView
+    /// draw the world and the visible entities from refdef.  time is cl.time, for texture and
+    /// model animation; sprite images are read back through source.
+    pub fn render_view<S: ImageSource>(
+        &mut self,
+        refdef: &RefDefT,
+        world: &QModelT,
+        entities: &[*mut EntityT],
+        time: c_double,
+        source: &mut S,
+    ) {
+        let refdef = self.setup_view(refdef, time);
+
+        let clear = u32::from_le_bytes(self.clearcolor);
+        for y in self.rect[1]..self.rect[3] {
+            let row = y * self.width;
+            for i in row + self.rect[0]..row + self.rect[2] {
+                self.color[i] = clear;
+                self.depth[i] = 0.0;
+            }
+        }
+
+        let mut visedicts = entities.to_vec();
+        self.draw_world(world, &refdef, &mut visedicts);
+
+        for &e in &visedicts {
+            if e.is_null() {
+                continue;
+            }
+            let e = unsafe { &mut *e };
+            if e.model.is_null() {
+                continue;
+            }
+            let model = unsafe { &*e.model };
+            match model.r#type {
+                ModTypeT::Brush => self.draw_brush_model(world, e, model),
+                ModTypeT::Alias => self.draw_alias_model(world, e, model),
+                ModTypeT::Sprite => self.draw_sprite_model(e, model, source),
+            }
+        }
+    }
+
+    /// Formerly: R_SetupView
+    /// returns refdef with the fov filled in
+    fn setup_view(&mut self, refdef: &RefDefT, time: c_double) -> RefDefT {
+        let vrect = &refdef.vrect;
+        self.rect = if vrect.width > 0 && vrect.height > 0 {
+            let x = (vrect.x.max(0) as usize).min(self.width);
+            let y = (vrect.y.max(0) as usize).min(self.height);
+            [
+                x,
+                y,
+                (x + vrect.width as usize).min(self.width),
+                (y + vrect.height as usize).min(self.height),
+            ]
+        } else {
+            [0, 0, self.width, self.height]
+        };
+
+        let w = (self.rect[2] - self.rect[0]) as c_float;
+        let h = (self.rect[3] - self.rect[1]) as c_float;
+        let fov_x = if refdef.fov_x > 0.0 {
+            refdef.fov_x
+        } else {
+            90.0
+        };
+        let fov_y = if refdef.fov_y > 0.0 {
+            refdef.fov_y
+        } else {
+            // same as CalcFovy
+            (h / (w / (fov_x * PI / 360.0).tan())).atan() * 360.0 / PI
+        };
+
+        self.time = time;
+        self.origin = refdef.vieworg;
+        let (vpn, vright, vup) = angle_vectors(refdef.viewangles);
+        self.vpn = vpn;
+        self.vright = vright;
+        self.vup = vup;
+        self.xcenter = self.rect[0] as c_float + w * 0.5;
+        self.ycenter = self.rect[1] as c_float + h * 0.5;
+        self.xscale = w * 0.5 / (fov_x * PI / 360.0).tan();
+        self.yscale = h * 0.5 / (fov_y * PI / 360.0).tan();
+
+        let mut refdef = *refdef;
+        refdef.fov_x = fov_x;
+        refdef.fov_y = fov_y;
+        return refdef;
+    }
+
+    /// world space point to view space
+    fn transform(&self, p: Vec3T) -> Vec3T {
+        let d = vector_subtract(p, self.origin);
+        return Vec3T::new(
+            dot_product(d, self.vright),
+            dot_product(d, self.vup),
+            dot_product(d, self.vpn),
+        );
+    }
+
+    /*
+    =============================================================================
+
+    RASTERIZATION
+
+    =============================================================================
+    */
+
+    /// clip a convex polygon against the near plane, project it, and fill it.  shade gets the
+    /// interpolated attributes of each covered pixel and returns its color, or None to leave
+    /// the pixel untouched (alpha test)
+    fn draw_polygon<F: FnMut(&[c_float; 4]) -> Option<u32>>(
+        &mut self,
+        verts: &[ClipVertT],
+        mut shade: F,
+    ) {
+        if verts.len() < 3 {
+            return;
+        }
+
+        let mut clipped: Vec<ClipVertT> = Vec::with_capacity(verts.len() + 2);
+        for (i, &v) in verts.iter().enumerate() {
+            let next = verts[(i + 1) % verts.len()];
+            let (din, dnext) = (v.pos[2] - NEAR_CLIP, next.pos[2] - NEAR_CLIP);
+            if din >= 0.0 {
+                clipped.push(v);
+            }
+            if (din >= 0.0) != (dnext >= 0.0) {
+                let frac = din / (din - dnext);
+                let mut mid = v;
+                for j in 0..3 {
+                    mid.pos[j] = v.pos[j] + frac * (next.pos[j] - v.pos[j]);
+                }
+                for j in 0..4 {
+                    mid.attr[j] = v.attr[j] + frac * (next.attr[j] - v.attr[j]);
+                }
+                clipped.push(mid);
+            }
+        }
+        if clipped.len() < 3 {
+            return;
+        }
+
+        let screen: Vec<ScreenVertT> = clipped
+            .iter()
+            .map(|v| {
+                let iz = 1.0 / v.pos[2];
+                ScreenVertT {
+                    x: self.xcenter + v.pos[0] * iz * self.xscale,
+                    y: self.ycenter - v.pos[1] * iz * self.yscale,
+                    iz,
+                    attr: v.attr,
+                }
+            })
+            .collect();
+
+        for i in 2..screen.len() {
+            self.draw_triangle(&screen[0], &screen[i - 1], &screen[i], &mut shade);
+        }
+    }
+
+    /// fill pixels whose centers are inside the triangle, with perspective correct attributes
+    fn draw_triangle<F: FnMut(&[c_float; 4]) -> Option<u32>>(
+        &mut self,
+        a: &ScreenVertT,
+        b: &ScreenVertT,
+        c: &ScreenVertT,
+        shade: &mut F,
+    ) {
+        let area = (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x);
+        if area.abs() < 1e-6 {
+            return;
+        }
+
+        let minx = a.x.min(b.x).min(c.x).floor().max(self.rect[0] as c_float) as usize;
+        let maxx = (a.x.max(b.x).max(c.x).ceil().max(0.0) as usize).min(self.rect[2]);
+        let miny = a.y.min(b.y).min(c.y).floor().max(self.rect[1] as c_float) as usize;
+        let maxy = (a.y.max(b.y).max(c.y).ceil().max(0.0) as usize).min(self.rect[3]);
+
+        // attributes premultiplied by 1/z
+        let mut pa = [0.0; 4];
+        let mut pb = [0.0; 4];
+        let mut pc = [0.0; 4];
+        for j in 0..4 {
+            pa[j] = a.attr[j] * a.iz;
+            pb[j] = b.attr[j] * b.iz;
+            pc[j] = c.attr[j] * c.iz;
+        }
+
+        let mut attr = [0.0; 4];
+        for y in miny..maxy {
+            let py = y as c_float + 0.5;
+            for x in minx..maxx {
+                let px = x as c_float + 0.5;
+                let w0 = ((b.x - px) * (c.y - py) - (b.y - py) * (c.x - px)) / area;
+                let w1 = ((c.x - px) * (a.y - py) - (c.y - py) * (a.x - px)) / area;
+                let w2 = 1.0 - w0 - w1;
+                if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                    continue;
+                }
+
+                let iz = w0 * a.iz + w1 * b.iz + w2 * c.iz;
+                let i = y * self.width + x;
+                if iz <= self.depth[i] {
+                    continue;
+                }
+
+                for j in 0..4 {
+                    attr[j] = (w0 * pa[j] + w1 * pb[j] + w2 * pc[j]) / iz;
+                }
+                if let Some(color) = shade(&attr) {
+                    self.color[i] = color;
+                    self.depth[i] = iz;
+                }
+            }
+        }
+    }
+
+    /*
+    =============================================================================
+
+    BRUSH MODELS
+
+    =============================================================================
+    */
+
+    /// Formerly: R_DrawWorld
+    /// draw the texture chains left by the world pass; static entities it finds are added to
+    /// visedicts.  a world without a bsp tree is drawn whole
+    fn draw_world(&mut self, world: &QModelT, refdef: &RefDefT, visedicts: &mut Vec<*mut EntityT>) {
+        let surfaces = if world.nodes.is_null() {
+            self.vis.framecount += 1;
+            model_surfaces(world)
+        } else {
+            push_dlights(world, &self.dlights, self.time, self.vis.framecount);
+            self.vis.setup_view(world, refdef);
+            self.vis.mark_surfaces(world, visedicts);
+            self.vis.cull_surfaces();
+            self.vis
+                .texture_chains()
+                .iter()
+                .flat_map(|chain| chain.surfaces.iter().cloned())
+                .collect()
+        };
+
+        let axis = (
+            Vec3T::new(1.0, 0.0, 0.0),
+            Vec3T::new(0.0, -1.0, 0.0),
+            Vec3T::new(0.0, 0.0, 1.0),
+        );
+        self.draw_surfaces(world, world, &surfaces, 0, Vec3T::default(), axis);
+    }
+
+    /// Formerly: R_DrawBrushModel
+    fn draw_brush_model(&mut self, world: &QModelT, e: &EntityT, model: &QModelT) {
+        // calculate dynamic lighting for bmodel if it's not an instanced model
+        if model.firstmodelsurface != 0 && !model.nodes.is_null() {
+            let headnode = unsafe { &*model.nodes.add(model.hulls[0].firstclipnode as usize) };
+            push_dlights_for_node(
+                world,
+                headnode,
+                &self.dlights,
+                self.time,
+                self.vis.framecount,
+            );
+        }
+
+        // R_DrawBrushModel negates pitch before R_RotateForEntity negates it again
+        let axis = angle_vectors(e.angles);
+        self.draw_surfaces(
+            world,
+            model,
+            &model_surfaces(model),
+            e.frame,
+            e.origin,
+            axis,
+        );
+    }
+
+    /// draw surfaces of a brush model placed at origin with the given forward, right, up
+    fn draw_surfaces(
+        &mut self,
+        world: &QModelT,
+        model: &QModelT,
+        surfaces: &[*mut MSurfaceT],
+        frame: c_int,
+        origin: Vec3T,
+        (forward, right, up): (Vec3T, Vec3T, Vec3T),
+    ) {
+        // the eye in model space, for backface culling
+        let d = vector_subtract(self.origin, origin);
+        let modelorg = Vec3T::new(
+            dot_product(d, forward),
+            -dot_product(d, right),
+            dot_product(d, up),
+        );
+
+        let lightdata = !world.lightdata.is_null();
+        for &surf in surfaces {
+            let surf = unsafe { &mut *surf };
+            let flags = SurfaceFlags::from_bits_truncate(surf.flags as u32);
+
+            // cull backfaces
+            if !surf.plane.is_null() {
+                let plane = unsafe { &*surf.plane };
+                let dot = dot_product(modelorg, plane.normal) - plane.dist;
+                if (dot < 0.0) != flags.contains(SurfaceFlags::PlaneBack) {
+                    continue;
+                }
+            }
+
+            let verts: Vec<ClipVertT> = surface_vertexes(model, surf)
+                .into_iter()
+                .map(|p| {
+                    let tex = unsafe { &*surf.texinfo };
+                    let s = dot_product(
+                        p,
+                        Vec3T::new(tex.vecs[0][0], tex.vecs[0][1], tex.vecs[0][2]),
+                    ) + tex.vecs[0][3];
+                    let t = dot_product(
+                        p,
+                        Vec3T::new(tex.vecs[1][0], tex.vecs[1][1], tex.vecs[1][2]),
+                    ) + tex.vecs[1][3];
+                    let world = vector_ma(
+                        vector_ma(vector_ma(origin, p[0], forward), -p[1], right),
+                        p[2],
+                        up,
+                    );
+                    ClipVertT {
+                        pos: self.transform(world),
+                        attr: [s, t, 0.0, 0.0],
+                    }
+                })
+                .collect();
+
+            let texture = texture_animation(unsafe { (*surf.texinfo).texture }, frame, self.time);
+            let skin = match miptex_image(texture) {
+                Some(skin) => skin,
+                None => continue,
+            };
+
+            if flags.contains(SurfaceFlags::DrawSky) {
+                let color = average_color(&self.palettes.standard, &skin);
+                self.draw_polygon(&verts, |_| Some(color));
+            } else if flags.contains(SurfaceFlags::DrawTurb) {
+                // same warp as the gl renderer's turbsin table
+                let time = self.time as c_float;
+                let palette = self.palettes.standard;
+                self.draw_polygon(&verts, |attr| {
+                    let s = attr[0] + (attr[1] * 0.125 + time).sin() * 8.0;
+                    let t = attr[1] + (attr[0] * 0.125 + time).sin() * 8.0;
+                    return Some(palette[skin.texel(s, t) as usize] | 0xff000000);
+                });
+            } else {
+                let lightmap = build_lightmap(
+                    surf,
+                    lightdata,
+                    &self.lightstylevalue,
+                    &self.dlights,
+                    self.vis.framecount,
+                    self.overbright,
+                );
+                let (smax, tmax) = lightmap_size(surf);
+                let texturemins = (
+                    surf.texturemins[0] as c_float,
+                    surf.texturemins[1] as c_float,
+                );
+                let fence = flags.contains(SurfaceFlags::DrawFence);
+                let scale = if self.overbright { 2.0 } else { 1.0 };
+                let fullbrights = self.fullbrights;
+                let palette = self.palettes.standard;
+                self.draw_polygon(&verts, |attr| {
+                    let index = skin.texel(attr[0], attr[1]);
+                    if fence && index == 255 {
+                        return None;
+                    }
+                    if fullbrights && index >= 224 {
+                        return Some(palette[index as usize] | 0xff000000);
+                    }
+                    let ls = (attr[0] - texturemins.0) / 16.0;
+                    let lt = (attr[1] - texturemins.1) / 16.0;
+                    let light = sample_lightmap(&lightmap, smax, tmax, ls, lt);
+                    return Some(modulate(palette[index as usize], light, scale));
+                });
+            }
+        }
+    }
+
+    /*
+    =============================================================================
+
+    ALIAS MODELS
+
+    =============================================================================
+    */
+
+    /// Formerly: R_DrawAliasModel
+    fn draw_alias_model(&mut self, world: &QModelT, e: &mut EntityT, model: &QModelT) {
+        let hdr = model.cache.data as *const AliasHdrT;
+        if hdr.is_null() {
+            return;
+        }
+        let paliashdr = unsafe { &*hdr };
+
+        //
+        // pose and skin
+        //
+        let lerpdata = lerp_alias_entity(
+            e,
+            model,
+            paliashdr,
+            self.time,
+            self.lerpmodels,
+            self.lerpmove,
+        );
+
+        let skinnum = if e.skinnum >= 0 && e.skinnum < paliashdr.numskins {
+            e.skinnum
+        } else {
+            0
+        };
+        let (skinwidth, skinheight) = (paliashdr.skinwidth as usize, paliashdr.skinheight as usize);
+        let skin = SkinT {
+            data: unsafe {
+                slice::from_raw_parts(
+                    (hdr as *const Byte).add(paliashdr.texels[skinnum as usize] as usize),
+                    skinwidth * skinheight,
+                )
+            },
+            width: skinwidth,
+            height: skinheight,
+        };
+
+        //
+        // lighting
+        //
+        let lightcolor = light_point(world, &self.lightstylevalue, e.origin);
+        let mut lightcolor = add_dlights_to_point(lightcolor, e.origin, &self.dlights, self.time);
+        if self.overbright {
+            // clamp lighting so it doesn't overbright as much (96)
+            let add = 288.0 / (lightcolor[0] + lightcolor[1] + lightcolor[2]);
+            if add < 1.0 {
+                for i in 0..3 {
+                    lightcolor[i] *= add;
+                }
+            }
+        }
+        let scale = if self.overbright { 2.0 } else { 1.0 } / 200.0;
+        let light = [
+            lightcolor[0] * scale,
+            lightcolor[1] * scale,
+            lightcolor[2] * scale,
+        ];
+
+        //
+        // transform, R_RotateForEntity negates pitch for alias models
+        //
+        let angles = lerpdata.angles;
+        let (forward, right, up) = angle_vectors(Vec3T::new(-angles[0], angles[1], angles[2]));
+        let verts =
+            unsafe { (hdr as *const Byte).add(paliashdr.posedata as usize) as *const TriVertexT };
+        let mut verts1 =
+            unsafe { verts.add((lerpdata.pose1 as c_int * paliashdr.poseverts) as usize) };
+        let mut verts2 =
+            unsafe { verts.add((lerpdata.pose2 as c_int * paliashdr.poseverts) as usize) };
+        let blend = lerpdata.blend;
+        let project = |v1: &TriVertexT, v2: &TriVertexT| {
+            let mut p = Vec3T::default();
+            for i in 0..3 {
+                let v = v1.v[i] as VecT * (1.0 - blend) + v2.v[i] as VecT * blend;
+                p[i] = v * paliashdr.scale[i] + paliashdr.scale_origin[i];
+            }
+            let world = vector_ma(
+                vector_ma(vector_ma(lerpdata.origin, p[0], forward), -p[1], right),
+                p[2],
+                up,
+            );
+            return self.transform(world);
+        };
+
+        //
+        // walk the command list
+        //
+        let mut commands =
+            unsafe { (hdr as *const Byte).add(paliashdr.commands as usize) as *const c_int };
+        let mut triangles = Vec::new();
+        loop {
+            let mut count = unsafe { *commands };
+            commands = unsafe { commands.add(1) };
+            if count == 0 {
+                break; // done
+            }
+            let fan = count < 0;
+            count = count.abs();
+
+            let mut strip = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let (s, t) = unsafe {
+                    (
+                        *(commands as *const c_float),
+                        *(commands.add(1) as *const c_float),
+                    )
+                };
+                commands = unsafe { commands.add(2) };
+                strip.push(ClipVertT {
+                    pos: project(unsafe { &*verts1 }, unsafe { &*verts2 }),
+                    attr: [
+                        s * skinwidth as c_float,
+                        t * skinheight as c_float,
+                        0.0,
+                        0.0,
+                    ],
+                });
+                verts1 = unsafe { verts1.add(1) };
+                verts2 = unsafe { verts2.add(1) };
+            }
+
+            for i in 2..strip.len() {
+                if fan {
+                    triangles.push([strip[0], strip[i - 1], strip[i]]);
+                } else if i % 2 == 1 {
+                    triangles.push([strip[i - 1], strip[i - 2], strip[i]]);
+                } else {
+                    triangles.push([strip[i - 2], strip[i - 1], strip[i]]);
+                }
+            }
+        }
+
+        let fullbrights = self.fullbrights;
+        let palette = self.palettes.standard;
+        for tri in &triangles {
+            self.draw_polygon(tri, |attr| {
+                let index = skin.texel(attr[0], attr[1]);
+                if fullbrights && index >= 224 {
+                    return Some(palette[index as usize] | 0xff000000);
+                }
+                return Some(modulate_rgb(palette[index as usize], light));
+            });
+        }
+    }
+
+    /*
+    =============================================================================
+
+    SPRITE MODELS
+
+    =============================================================================
+    */
+
+    /// Formerly: R_DrawSpriteModel
+    fn draw_sprite_model<S: ImageSource>(&mut self, e: &EntityT, model: &QModelT, source: &mut S) {
+        let psprite = model.cache.data as *const MSpriteT;
+        if psprite.is_null() {
+            return;
+        }
+        let frame = get_sprite_frame(
+            unsafe { &*psprite },
+            e.frame,
+            self.time + e.syncbase as c_double,
+        );
+        if frame.is_null() {
+            return;
+        }
+        let frame = unsafe { &*frame };
+
+        let (s_up, s_right) = match sprite_type(unsafe { (*psprite).r#type }) {
+            Some(SpriteViewPosition::ParallelUpright) => (Vec3T::new(0.0, 0.0, 1.0), self.vright),
+            Some(SpriteViewPosition::FacingUpright) => {
+                let mut v_forward = vector_subtract(e.origin, self.origin);
+                v_forward[2] = 0.0;
+                vector_normalize(&mut v_forward);
+                (
+                    Vec3T::new(0.0, 0.0, 1.0),
+                    Vec3T::new(v_forward[1], -v_forward[0], 0.0),
+                )
+            }
+            Some(SpriteViewPosition::Parallel) => (self.vup, self.vright),
+            Some(SpriteViewPosition::Oriented) => {
+                let (_, v_right, v_up) = angle_vectors(e.angles);
+                (v_up, v_right)
+            }
+            Some(SpriteViewPosition::ParallelOriented) => {
+                let angle = e.angles[2] * PI / 180.0;
+                let (sr, cr) = angle.sin_cos();
+                let mut v_right = Vec3T::default();
+                let mut v_up = Vec3T::default();
+                for i in 0..3 {
+                    v_right[i] = self.vright[i] * cr + self.vup[i] * sr;
+                    v_up[i] = self.vright[i] * -sr + self.vup[i] * cr;
+                }
+                (v_up, v_right)
+            }
+            None => return,
+        };
+
+        let glt = frame.gltexture as *const GlTextureT;
+        if glt.is_null() {
+            return;
+        }
+        let image = self.sprite_images.entry(glt).or_insert_with(|| {
+            let glt = unsafe { &*glt };
+            // a frame in memory lives in the sprite model, as long as e's model does
+            let image = unsafe { read_source(glt, source) };
+            return image.map(|(data, w, h)| SpriteImageT {
+                data,
+                width: w as usize,
+                height: h as usize,
+                format: glt.source_format,
+            });
+        });
+        let SpriteImageT {
+            data,
+            width,
+            height,
+            format,
+        } = match *image {
+            Some(ref image) => image.clone(),
+            None => return,
+        };
+
+        let corner = |updown: c_float, leftright: c_float, s: c_float, t: c_float| ClipVertT {
+            pos: self.transform(vector_ma(
+                vector_ma(e.origin, updown, s_up),
+                leftright,
+                s_right,
+            )),
+            attr: [s * width as c_float, t * height as c_float, 0.0, 0.0],
+        };
+        let verts = [
+            corner(frame.down, frame.left, 0.0, 1.0),
+            corner(frame.up, frame.left, 0.0, 0.0),
+            corner(frame.up, frame.right, 1.0, 0.0),
+            corner(frame.down, frame.right, 1.0, 1.0),
+        ];
+
+        let palette = self.palettes.standard;
+        self.draw_polygon(&verts, |attr| {
+            let x = (attr[0] as usize).min(width - 1);
+            let y = (attr[1] as usize).min(height - 1);
+            return match format {
+                SrcFormat::Indexed => {
+                    let index = data[y * width + x];
+                    if index == 255 {
+                        None
+                    } else {
+                        Some(palette[index as usize])
+                    }
+                }
+                _ => {
+                    let p = &data[(y * width + x) * 4..(y * width + x) * 4 + 4];
+                    if p[3] < 128 {
+                        None
+                    } else {
+                        Some(u32::from_le_bytes([p[0], p[1], p[2], 255]))
+                    }
+                }
+            };
+        });
+    }
+}
+
+/// all surfaces of a brush model
+fn model_surfaces(model: &QModelT) -> Vec<*mut MSurfaceT> {
+    if model.surfaces.is_null() {
+        return Vec::new();
+    }
+    return (0..model.nummodelsurfaces.max(0))
+        .map(|i| unsafe { model.surfaces.add((model.firstmodelsurface + i) as usize) })
+        .collect();
+}
+
+/// the polygon of a surface, walking its edges
+fn surface_vertexes(model: &QModelT, surf: &MSurfaceT) -> Vec<Vec3T> {
+    let mut verts = Vec::with_capacity(surf.numedges as usize);
+    for i in 0..surf.numedges {
+        unsafe {
+            let lindex = *model.surfedges.add((surf.firstedge + i) as usize);
+            let vertex = if lindex > 0 {
+                (*model.edges.add(lindex as usize)).v[0]
+            } else {
+                (*model.edges.add(-lindex as usize)).v[1]
+            };
+            verts.push((*model.vertexes.add(vertex as usize)).position);
+        }
+    }
+    return verts;
+}
+
+/// Formerly: R_TextureAnimation
+/// returns the proper texture for a given time and base texture
+pub fn texture_animation(base: *mut TextureT, frame: c_int, time: c_double) -> *mut TextureT {
+    let mut base = base;
+    if base.is_null() {
+        return base;
+    }
+    unsafe {
+        if frame != 0 && !(*base).alternate_anims.is_null() {
+            base = (*base).alternate_anims;
+        }
+
+        if (*base).anim_total == 0 {
+            return base;
+        }
+
+        let relative = (time * 10.0) as c_int % (*base).anim_total;
+
+        let mut count = 0;
+        while (*base).anim_min > relative || (*base).anim_max <= relative {
+            base = (*base).anim_next;
+            if base.is_null() {
+                panic!("R_TextureAnimation: broken cycle");
+            }
+            count += 1;
+            if count > 100 {
+                panic!("R_TextureAnimation: infinite cycle");
+            }
+        }
+    }
+    return base;
+}
+
+/// the first mip level of a miptex, stored right after the texture_t
+fn miptex_image<'a>(tx: *mut TextureT) -> Option<SkinT<'a>> {
+    if tx.is_null() {
+        return None;
+    }
+    let tx = unsafe { &*tx };
+    let (width, height) = (tx.width as usize, tx.height as usize);
+    if width == 0 || height == 0 || tx.offsets[0] == 0 {
+        return None;
+    }
+    let data = unsafe {
+        slice::from_raw_parts(
+            (tx as *const TextureT as *const Byte).add(tx.offsets[0] as usize),
+            width * height,
+        )
+    };
+    return Some(SkinT {
+        data,
+        width,
+        height,
+    });
+}
+
+/// Formerly: R_GetSpriteFrame
+fn get_sprite_frame(psprite: &MSpriteT, frame: c_int, time: c_double) -> *mut MSpriteFrameT {
+    let frame = if frame >= 0 && frame < psprite.numframes {
+        frame
+    } else {
+        0
+    };
+    let desc = unsafe { &*psprite.frames.as_ptr().add(frame as usize) };
+
+    if desc.r#type == SpriteFrameTypeT::Single {
+        return desc.frameptr;
+    }
+
+    let pspritegroup = unsafe { &*(desc.frameptr as *const MSpriteGroupT) };
+    let numframes = pspritegroup.numframes as usize;
+    let pintervals = unsafe { slice::from_raw_parts(pspritegroup.intervals, numframes) };
+    let fullinterval = pintervals[numframes - 1] as c_double;
+
+    // when loading in Mod_LoadSpriteGroup, we guaranteed all interval values
+    // are positive, so we don't have to worry about division by 0
+    let targettime =
+        (time - ((time / fullinterval) as c_int) as c_double * fullinterval) as c_float;
+
+    let mut i = 0;
+    while i < numframes - 1 {
+        if pintervals[i] > targettime {
+            break;
+        }
+        i += 1;
+    }
+    return unsafe { *pspritegroup.frames.as_ptr().add(i) };
+}
+
+fn sprite_type(t: c_int) -> Option<SpriteViewPosition> {
+    return match t {
+        0 => Some(SpriteViewPosition::ParallelUpright),
+        1 => Some(SpriteViewPosition::FacingUpright),
+        2 => Some(SpriteViewPosition::Parallel),
+        3 => Some(SpriteViewPosition::Oriented),
+        4 => Some(SpriteViewPosition::ParallelOriented),
+        _ => None,
+    };
+}
+
+/// bilinear sample of an rgba lightmap at luxel coordinates, as 0-1 rgb
+fn sample_lightmap(
+    lightmap: &[Byte],
+    smax: usize,
+    tmax: usize,
+    s: c_float,
+    t: c_float,
+) -> [c_float; 3] {
+    let s = s.max(0.0).min((smax - 1) as c_float);
+    let t = t.max(0.0).min((tmax - 1) as c_float);
+    let (s0, t0) = (s as usize, t as usize);
+    let (s1, t1) = ((s0 + 1).min(smax - 1), (t0 + 1).min(tmax - 1));
+    let (fs, ft) = (s - s0 as c_float, t - t0 as c_float);
+
+    let mut light = [0.0; 3];
+    for (i, l) in light.iter_mut().enumerate() {
+        let at = |s: usize, t: usize| lightmap[(t * smax + s) * 4 + i] as c_float;
+        let top = at(s0, t0) + (at(s1, t0) - at(s0, t0)) * fs;
+        let bottom = at(s0, t1) + (at(s1, t1) - at(s0, t1)) * fs;
+        *l = (top + (bottom - top) * ft) / 255.0;
+    }
+    return light;
+}
+
+/// a palette color lit by a 0-1 lightmap value, scaled for overbright
+fn modulate(color: u32, light: [c_float; 3], scale: c_float) -> u32 {
+    return modulate_rgb(
+        color,
+        [light[0] * scale, light[1] * scale, light[2] * scale],
+    );
+}
+
+fn modulate_rgb(color: u32, light: [c_float; 3]) -> u32 {
+    let c = color.to_le_bytes();
+    let channel = |i: usize| (c[i] as c_float * light[i]).min(255.0) as Byte;
+    return u32::from_le_bytes([channel(0), channel(1), channel(2), 255]);
+}
+
+/// flat color for sky surfaces
+fn average_color(palette: &[u32; 256], skin: &SkinT) -> u32 {
+    let mut sum = [0u64; 3];
+    for &index in skin.data {
+        let c = palette[index as usize].to_le_bytes();
+        for i in 0..3 {
+            sum[i] += c[i] as u64;
+        }
+    }
+    let n = skin.data.len().max(1) as u64;
+    return u32::from_le_bytes([
+        (sum[0] / n) as Byte,
+        (sum[1] / n) as Byte,
+        (sum[2] / n) as Byte,
+        255,
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gl_model::{MEdgeT, MTexInfoT, MVertexT};
+    use std::mem::size_of;
+    use std::os::raw::c_uint;
+
+    const RED: Byte = 1;
+    const GREEN: Byte = 2;
+
+    /// a texture_t followed by its first mip level, as a miptex lump is loaded
+    #[repr(C)]
+    struct MipTexT {
+        tx: TextureT,
+        pixels: [Byte; 16],
+    }
+
+    /// quads drawn whole, with no bsp tree and no lightdata, so they are fullbright
+    struct QuadsT {
+        model: QModelT,
+        _textures: Vec<Box<MipTexT>>,
+        _texinfo: Vec<MTexInfoT>,
+        _surfs: Vec<MSurfaceT>,
+        _edges: Vec<MEdgeT>,
+        _surfedges: Vec<c_int>,
+        _vertexes: Vec<MVertexT>,
+    }
+
+    impl QuadsT {
+        /// a quad for each set of corners, in one palette color each; s and t follow y and x
+        fn new(quads: &[([Vec3T; 4], Byte)]) -> Box<Self> {
+            let mut w = Box::new(QuadsT {
+                model: QModelT::default(),
+                _textures: Vec::new(),
+                _texinfo: Vec::new(),
+                _surfs: vec![MSurfaceT::default(); quads.len()],
+                _edges: vec![MEdgeT::default()],
+                _surfedges: Vec::new(),
+                _vertexes: Vec::new(),
+            });
+            for &(_, color) in quads {
+                let mut miptex = Box::new(MipTexT {
+                    tx: TextureT::default(),
+                    pixels: [color; 16],
+                });
+                miptex.tx.width = 4;
+                miptex.tx.height = 4;
+                miptex.tx.offsets[0] = size_of::<TextureT>() as u32;
+                let mut texinfo = MTexInfoT::default();
+                texinfo.vecs = [[1.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0]];
+                texinfo.texture = &mut miptex.tx;
+                w._textures.push(miptex);
+                w._texinfo.push(texinfo);
+            }
+            for (i, &(corners, _)) in quads.iter().enumerate() {
+                let first = w._vertexes.len() as c_uint;
+                for &position in corners.iter() {
+                    w._vertexes.push(MVertexT { position });
+                }
+                let firstedge = w._surfedges.len() as c_int;
+                for j in 0..4 {
+                    w._surfedges.push(w._edges.len() as c_int);
+                    w._edges.push(MEdgeT {
+                        v: [first + j, first + (j + 1) % 4],
+                        cachededgeoffset: 0,
+                    });
+                }
+                let surf = &mut w._surfs[i];
+                surf.firstedge = firstedge;
+                surf.numedges = 4;
+                surf.extents = [64, 64];
+                surf.texinfo = &mut w._texinfo[i];
+            }
+            w.model.vertexes = w._vertexes.as_mut_ptr();
+            w.model.edges = w._edges.as_mut_ptr();
+            w.model.surfedges = w._surfedges.as_mut_ptr();
+            w.model.surfaces = w._surfs.as_mut_ptr();
+            w.model.nummodelsurfaces = quads.len() as c_int;
+            return w;
+        }
+    }
+
+    /// a wall across the x axis at distance x, reaching half out to each side
+    fn wall(x: VecT, half: VecT) -> [Vec3T; 4] {
+        return [
+            Vec3T::new(x, -half, -half),
+            Vec3T::new(x, half, -half),
+            Vec3T::new(x, half, half),
+            Vec3T::new(x, -half, half),
+        ];
+    }
+
+    struct NoSourceT;
+
+    impl ImageSource for NoSourceT {
+        fn read_lump(&mut self, _file: &str, _offset: usize, _size: usize) -> Option<Vec<Byte>> {
+            return None;
+        }
+        fn load_image(&mut self, _file: &str) -> Option<(Vec<Byte>, u32, u32)> {
+            return None;
+        }
+    }
+
+    /// 32x32, index 1 red and 2 green, looking down +x from the origin
+    fn render(quads: &QuadsT) -> SoftRenderer {
+        let mut pal = [0; 768];
+        pal[RED as usize * 3] = 255;
+        pal[GREEN as usize * 3 + 1] = 255;
+        let mut r = SoftRenderer::new(32, 32, &pal);
+        r.overbright = false;
+        r.clearcolor = [0, 0, 255, 255];
+        r.render_view(&RefDefT::default(), &quads.model, &[], 0.0, &mut NoSourceT);
+        return r;
+    }
+
+    const CLEAR: [Byte; 4] = [0, 0, 255, 255];
+
+    #[test]
+    fn fills_what_the_fov_covers() {
+        // at distance 64 with a 90 degree fov, a wall 64 wide covers the middle half
+        let r = render(&QuadsT::new(&[(wall(64.0, 32.0), RED)]));
+        assert_eq!(r.pixel(16, 16), [255, 0, 0, 255]);
+        assert_eq!(r.pixel(9, 9), [255, 0, 0, 255]);
+        assert_eq!(r.pixel(22, 22), [255, 0, 0, 255]);
+        assert_eq!(r.pixel(6, 16), CLEAR);
+        assert_eq!(r.pixel(16, 25), CLEAR);
+        let covered = r
+            .pixels()
+            .chunks(4)
+            .filter(|p| *p == [255, 0, 0, 255])
+            .count();
+        assert_eq!(covered, 16 * 16);
+    }
+
+    #[test]
+    fn nearer_surfaces_win_whatever_the_order() {
+        let near = (wall(32.0, 8.0), GREEN);
+        let far = (wall(64.0, 32.0), RED);
+        for quads in [[near, far], [far, near]].iter() {
+            let r = render(&QuadsT::new(quads));
+            assert_eq!(r.pixel(16, 16), [0, 255, 0, 255]);
+            assert_eq!(r.pixel(10, 10), [255, 0, 0, 255]);
+        }
+    }
+
+    #[test]
+    fn polygons_behind_the_eye_are_clipped() {
+        // a floor running from behind the eye to far in front of it
+        let floor = [
+            Vec3T::new(-64.0, -64.0, -16.0),
+            Vec3T::new(-64.0, 64.0, -16.0),
+            Vec3T::new(512.0, 64.0, -16.0),
+            Vec3T::new(512.0, -64.0, -16.0),
+        ];
+        let r = render(&QuadsT::new(&[(floor, GREEN)]));
+        assert_eq!(r.pixel(16, 31), [0, 255, 0, 255]);
+        assert_eq!(r.pixel(16, 17), [0, 255, 0, 255]);
+        assert_eq!(r.pixel(16, 8), CLEAR);
+
+        // entirely behind it draws nothing
+        let r = render(&QuadsT::new(&[(wall(-64.0, 32.0), RED)]));
+        assert!(r.pixels().chunks(4).all(|p| p == CLEAR));
+    }
+
+    #[test]
+    fn tga_is_bgra_top_down() {
+        let r = render(&QuadsT::new(&[(wall(64.0, 32.0), RED)]));
+        let mut tga = Vec::new();
+        r.write_tga(&mut tga).unwrap();
+        assert_eq!(tga.len(), 18 + 32 * 32 * 4);
+        assert_eq!(&tga[12..18], &[32, 0, 32, 0, 32, 0x28]);
+        assert_eq!(&tga[18..22], &[255, 0, 0, 255]);
+        let center = 18 + (16 * 32 + 16) * 4;
+        assert_eq!(&tga[center..center + 4], &[0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn lightmaps_interpolate_and_clamp() {
+        // 2x1 luxels, black then white
+        let lightmap = [0, 0, 0, 255, 255, 255, 255, 255];
+        assert_eq!(sample_lightmap(&lightmap, 2, 1, 0.0, 0.0), [0.0; 3]);
+        assert_eq!(sample_lightmap(&lightmap, 2, 1, 0.5, 0.0), [0.5; 3]);
+        assert_eq!(sample_lightmap(&lightmap, 2, 1, 9.0, -3.0), [1.0; 3]);
+
+        let grey = u32::from_le_bytes([100, 100, 100, 255]);
+        assert_eq!(modulate(grey, [0.5; 3], 2.0), grey);
+        assert_eq!(
+            modulate(grey, [1.0; 3], 2.0).to_le_bytes(),
+            [200, 200, 200, 255]
+        );
+        assert_eq!(
+            modulate(grey, [4.0; 3], 1.0).to_le_bytes(),
+            [255, 255, 255, 255]
+        );
+    }
+}
@@ -0,0 +1,647 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2002-2009 John Fitzgibbons and others
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// r_world.rs -- world visibility: pvs marking, frustum culling and texture chains
+
+use bspfile::Contents;
+use client::MAX_VISEDICTS;
+use gl_model::{
+    leaf_pvs, no_vis_pvs, point_in_leaf, pvs_bytes, MLeafT, MNodeT, MPlaneT, MSurfaceT, QModelT,
+    SurfaceFlags, TexChainT, TextureT,
+};
+use mathlib::{angle_vectors, dot_product, vector_add};
+use render::{EntityT, RefDefT};
+use std::os::raw::{c_float, c_int};
+use std::ptr::null_mut;
+use std::slice;
+use {Byte, QBoolean, Vec3T};
+
+/// plane types 3-5 are non-axial, and their normal's largest component is x, y or z
+pub const PLANE_ANYZ: Byte = 5;
+
+/// Formerly: SignbitsForPlane
+/// for fast box on planeside test
+pub fn signbits_for_plane(plane: &MPlaneT) -> Byte {
+    let mut bits = 0;
+    for j in 0..3 {
+        if plane.normal[j] < 0.0 {
+            bits |= 1 << j;
+        }
+    }
+    return bits;
+}
+
+/// Formerly: TurnVector
+/// turn forward towards side on the plane defined by forward and side; if angle = 90, the
+/// result will be equal to side
+fn turn_vector(forward: Vec3T, side: Vec3T, angle: c_float) -> Vec3T {
+    let (scale_side, scale_forward) = angle.to_radians().sin_cos();
+    return Vec3T::new(
+        scale_forward * forward[0] + scale_side * side[0],
+        scale_forward * forward[1] + scale_side * side[1],
+        scale_forward * forward[2] + scale_side * side[2],
+    );
+}
+
+/// Formerly: R_SetFrustum
+/// left, right, bottom and top planes of the view, facing inwards
+pub fn set_frustum(
+    origin: Vec3T,
+    vpn: Vec3T,
+    vright: Vec3T,
+    vup: Vec3T,
+    fovx: c_float,
+    fovy: c_float,
+) -> [MPlaneT; 4] {
+    let mut frustum = [MPlaneT::default(); 4];
+    frustum[0].normal = turn_vector(vpn, vright, fovx / 2.0 - 90.0); // left plane
+    frustum[1].normal = turn_vector(vpn, vright, 90.0 - fovx / 2.0); // right plane
+    frustum[2].normal = turn_vector(vpn, vup, 90.0 - fovy / 2.0); // bottom plane
+    frustum[3].normal = turn_vector(vpn, vup, fovy / 2.0 - 90.0); // top plane
+
+    for plane in frustum.iter_mut() {
+        plane.r#type = PLANE_ANYZ;
+        plane.dist = dot_product(origin, plane.normal);
+        plane.signbits = signbits_for_plane(plane);
+    }
+    return frustum;
+}
+
+/// Formerly: R_CullBox
+/// true if the box is completely outside the frustum
+pub fn cull_box(frustum: &[MPlaneT; 4], emins: Vec3T, emaxs: Vec3T) -> bool {
+    for p in frustum {
+        // the corner furthest along the normal, picked by signbits
+        let mut corner = emaxs;
+        for j in 0..3 {
+            if p.signbits & (1 << j) != 0 {
+                corner[j] = emins[j];
+            }
+        }
+        if dot_product(p.normal, corner) < p.dist {
+            return true;
+        }
+    }
+    return false;
+}
+
+/// Formerly: R_CullModelForEntity
+/// uses correct bounds based on rotation
+pub fn cull_model_for_entity(frustum: &[MPlaneT; 4], e: &EntityT) -> bool {
+    let model = unsafe { &*e.model };
+    let (mins, maxs) = if e.angles[0] != 0.0 || e.angles[2] != 0.0 {
+        // pitch or roll
+        (model.rmins, model.rmaxs)
+    } else if e.angles[1] != 0.0 {
+        // yaw
+        (model.ymins, model.ymaxs)
+    } else {
+        // no rotation
+        (model.mins, model.maxs)
+    };
+    return cull_box(
+        frustum,
+        vector_add(e.origin, mins),
+        vector_add(e.origin, maxs),
+    );
+}
+
+/// Formerly: R_BackFaceCull
+/// true if the surface is facing away from vieworg
+pub fn back_face_cull(surf: &MSurfaceT, vieworg: Vec3T) -> bool {
+    let plane = unsafe { &*surf.plane };
+    let dot = if plane.r#type < 3 {
+        vieworg[plane.r#type as usize] as f64 - plane.dist as f64
+    } else {
+        dot_product(vieworg, plane.normal) as f64 - plane.dist as f64
+    };
+
+    let planeback =
+        SurfaceFlags::from_bits_truncate(surf.flags as u32).contains(SurfaceFlags::PlaneBack);
+    return (dot < 0.0) != planeback;
+}
+
+/// Formerly: R_ChainSurface
+/// adds the given surface to its texture chain
+pub fn chain_surface(surf: &mut MSurfaceT, chain: TexChainT) {
+    unsafe {
+        let texture = (*surf.texinfo).texture;
+        surf.texturechain = (*texture).texturechains[chain as usize];
+        (*texture).texturechains[chain as usize] = surf;
+    }
+}
+
+/// Formerly: R_ClearTextureChains
+/// clears texture chains for all textures used by the given model
+pub fn clear_texture_chains(model: &QModelT, chain: TexChainT) {
+    for &texture in model_textures(model) {
+        if !texture.is_null() {
+            unsafe { (*texture).texturechains[chain as usize] = null_mut() };
+        }
+    }
+}
+
+fn model_textures(model: &QModelT) -> &[*mut TextureT] {
+    if model.textures.is_null() {
+        return &[];
+    }
+    return unsafe { slice::from_raw_parts(model.textures, model.numtextures as usize) };
+}
+
+/// The surfaces of one world texture, in the order the world stores them.
+pub struct TextureChainT {
+    pub texture: *mut TextureT,
+    pub surfaces: Vec<*mut MSurfaceT>,
+}
+
+/// The world pass, run once per frame before drawing: finds the view leaf, marks the surfaces
+/// in its pvs, chains them by texture, and culls the chains against the view.  The results
+/// are plain lists any renderer can draw, and the gl texture chains are kept in step.
+pub struct WorldVisT {
+    /// bumped when going to a new pvs
+    pub visframecount: c_int,
+    /// bumped every frame, used for dlight push checking and static entities
+    pub framecount: c_int,
+    /// set to regenerate the chains even if the view leaf didn't change
+    pub vis_changed: bool,
+    /// r_novis: everything is potentially visible
+    pub novis: bool,
+    /// r_oldskyleaf: draw surfaces in sky leafs
+    pub oldskyleaf: bool,
+    /// world polys that survived culling last frame
+    pub brushpolys: c_int,
+
+    origin: Vec3T,
+    frustum: [MPlaneT; 4],
+    viewleaf: *mut MLeafT,
+    oldviewleaf: *mut MLeafT,
+    /// pvs marked surfaces
+    marked: Vec<TextureChainT>,
+    /// marked surfaces inside the frustum and facing the view
+    visible: Vec<TextureChainT>,
+}
+
+impl WorldVisT {
+    pub fn new() -> Self {
+        Self {
+            visframecount: 0,
+            framecount: 0,
+            vis_changed: false,
+            novis: false,
+            oldskyleaf: false,
+            brushpolys: 0,
+            origin: Vec3T::default(),
+            frustum: [MPlaneT::default(); 4],
+            viewleaf: null_mut(),
+            oldviewleaf: null_mut(),
+            marked: Vec::new(),
+            visible: Vec::new(),
+        }
+    }
+
+    /// Formerly: R_NewMap
+    /// forget everything about the previous world
+    pub fn new_map(&mut self) {
+        self.viewleaf = null_mut();
+        self.oldviewleaf = null_mut();
+        self.marked.clear();
+        self.visible.clear();
+    }
+
+    pub fn viewleaf(&self) -> *mut MLeafT {
+        return self.viewleaf;
+    }
+
+    pub fn frustum(&self) -> &[MPlaneT; 4] {
+        return &self.frustum;
+    }
+
+    /// visible surfaces by texture, after cull_surfaces
+    pub fn texture_chains(&self) -> &[TextureChainT] {
+        return &self.visible;
+    }
+
+    /// Formerly: R_SetupView
+    /// set the view origin, frustum and view leaf for a new frame
+    pub fn setup_view(&mut self, world: &QModelT, refdef: &RefDefT) {
+        self.framecount += 1;
+
+        self.origin = refdef.vieworg;
+        let (vpn, vright, vup) = angle_vectors(refdef.viewangles);
+        self.frustum = set_frustum(self.origin, vpn, vright, vup, refdef.fov_x, refdef.fov_y);
+
+        // current viewleaf
+        self.viewleaf = point_in_leaf(self.origin, world);
+    }
+
+    /// Formerly: R_MarkSurfaces
+    /// mark surfaces based on pvs and rebuild texture chains; static entities in visible leafs
+    /// are added to visedicts
+    pub fn mark_surfaces(&mut self, world: &QModelT, visedicts: &mut Vec<*mut EntityT>) {
+        // no view yet, e.g. a frame drawn between new_map and setup_view
+        if self.viewleaf.is_null() {
+            return;
+        }
+        let viewleaf = unsafe { &*self.viewleaf };
+
+        // check this leaf for water portals
+        // TODO: loop through all water surfs and use distance to leaf cullbox
+        let nearwaterportal = leaf_marksurfaces(viewleaf).iter().any(|&surf| {
+            SurfaceFlags::from_bits_truncate(unsafe { (*surf).flags } as u32)
+                .contains(SurfaceFlags::DrawTurb)
+        });
+
+        // choose vis data
+        let vis = if self.novis
+            || viewleaf.contents == Contents::Solid as c_int
+            || viewleaf.contents == Contents::Sky as c_int
+        {
+            no_vis_pvs(world)
+        } else if nearwaterportal {
+            fat_pvs(self.origin, world)
+        } else {
+            leaf_pvs(viewleaf, world)
+        };
+
+        let leafs = unsafe { slice::from_raw_parts(world.leafs.add(1), world.numleafs as usize) };
+        let visible = |i: usize| vis[i >> 3] & (1 << (i & 7)) != 0;
+
+        // if surface chains don't need regenerating, just add static entities and return
+        if self.oldviewleaf == self.viewleaf && !self.vis_changed && !nearwaterportal {
+            for (i, leaf) in leafs.iter().enumerate() {
+                if visible(i) {
+                    self.store_efrags(leaf, visedicts);
+                }
+            }
+            return;
+        }
+
+        self.vis_changed = false;
+        self.visframecount += 1;
+        self.oldviewleaf = self.viewleaf;
+
+        // iterate through leaves, marking surfaces
+        for (i, leaf) in leafs.iter().enumerate() {
+            if visible(i) {
+                if self.oldskyleaf || leaf.contents != Contents::Sky as c_int {
+                    for &surf in leaf_marksurfaces(leaf) {
+                        unsafe { (*surf).visframe = self.visframecount };
+                    }
+                }
+
+                // add static models
+                self.store_efrags(leaf, visedicts);
+            }
+        }
+
+        // set all chains to null
+        clear_texture_chains(world, TexChainT::World);
+        let textures = model_textures(world);
+        let mut chains: Vec<Vec<*mut MSurfaceT>> = vec![Vec::new(); textures.len()];
+
+        // rebuild chains
+        // iterate through surfaces one node at a time, to work with tyrann's skip removal tool,
+        // which doesn't remove the surfaces from the bsp surfaces lump nor from each leaf's
+        // marksurfaces list
+        let nodes = unsafe { slice::from_raw_parts(world.nodes, world.numnodes as usize) };
+        for node in nodes {
+            for j in 0..node.numsurfaces {
+                let surf = unsafe { world.surfaces.add((node.firstsurface + j) as usize) };
+                if unsafe { (*surf).visframe } != self.visframecount {
+                    continue;
+                }
+                chain_surface(unsafe { &mut *surf }, TexChainT::World);
+
+                let texture = unsafe { (*(*surf).texinfo).texture };
+                if let Some(k) = textures.iter().position(|&t| t == texture) {
+                    chains[k].push(surf);
+                }
+            }
+        }
+
+        self.marked = textures
+            .iter()
+            .zip(chains)
+            .filter(|(_, surfaces)| !surfaces.is_empty())
+            .map(|(&texture, surfaces)| TextureChainT { texture, surfaces })
+            .collect();
+    }
+
+    /// Formerly: R_CullSurfaces
+    /// flag marked surfaces outside the frustum or facing away, and keep the rest
+    pub fn cull_surfaces(&mut self) {
+        self.brushpolys = 0;
+        self.visible.clear();
+
+        for chain in &self.marked {
+            let mut surfaces = Vec::new();
+            for &s in &chain.surfaces {
+                let surf = unsafe { &mut *s };
+                let mins = Vec3T::new(surf.mins[0], surf.mins[1], surf.mins[2]);
+                let maxs = Vec3T::new(surf.maxs[0], surf.maxs[1], surf.maxs[2]);
+                if cull_box(&self.frustum, mins, maxs) || back_face_cull(surf, self.origin) {
+                    surf.culled = QBoolean::True;
+                } else {
+                    surf.culled = QBoolean::False;
+                    self.brushpolys += 1; // count wpolys here
+                    let texture = unsafe { &mut *(*surf.texinfo).texture };
+                    if !texture.warpimage.is_null() {
+                        texture.update_warp = QBoolean::True;
+                    }
+                    surfaces.push(s);
+                }
+            }
+            if !surfaces.is_empty() {
+                self.visible.push(TextureChainT {
+                    texture: chain.texture,
+                    surfaces,
+                });
+            }
+        }
+    }
+
+    /// Formerly: R_StoreEfrags
+    fn store_efrags(&self, leaf: &MLeafT, visedicts: &mut Vec<*mut EntityT>) {
+        let mut pefrag = leaf.efrags;
+        while !pefrag.is_null() {
+            let pent = unsafe { (*pefrag).entity };
+            unsafe {
+                if (*pent).visframe != self.framecount && visedicts.len() < MAX_VISEDICTS {
+                    visedicts.push(pent);
+                    (*pent).visframe = self.framecount;
+                }
+                pefrag = (*pefrag).leafnext;
+            }
+        }
+    }
+}
+
+impl Default for WorldVisT {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn leaf_marksurfaces(leaf: &MLeafT) -> &[*mut MSurfaceT] {
+    if leaf.firstmarksurface.is_null() {
+        return &[];
+    }
+    return unsafe { slice::from_raw_parts(leaf.firstmarksurface, leaf.nummarksurfaces as usize) };
+}
+
+/// Formerly: SV_FatPVS
+/// the inclusive or of the pvs of all leafs within 8 units of org
+pub fn fat_pvs(org: Vec3T, world: &QModelT) -> Vec<Byte> {
+    let mut fatpvs = vec![0; pvs_bytes(world)];
+    add_to_fat_pvs(&mut fatpvs, org, world.nodes, world);
+    return fatpvs;
+}
+
+/// Formerly: SV_AddToFatPVS
+fn add_to_fat_pvs(fatpvs: &mut [Byte], org: Vec3T, node: *mut MNodeT, world: &QModelT) {
+    let mut node = node;
+    loop {
+        let n = unsafe { &*node };
+        // if this is a leaf, accumulate the pvs bits
+        if n.contents < 0 {
+            if n.contents != Contents::Solid as c_int {
+                let pvs = leaf_pvs(unsafe { &*(node as *const MLeafT) }, world);
+                for (f, p) in fatpvs.iter_mut().zip(pvs) {
+                    *f |= p;
+                }
+            }
+            return;
+        }
+
+        let plane = unsafe { &*n.plane };
+        let d = dot_product(org, plane.normal) - plane.dist;
+        if d > 8.0 {
+            node = n.children[0];
+        } else if d < -8.0 {
+            node = n.children[1];
+        } else {
+            // go down both
+            add_to_fat_pvs(fatpvs, org, n.children[0], world);
+            node = n.children[1];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gl_model::MTexInfoT;
+
+    /// one node splitting on x = 0: leaf 1 in front sees only itself, leaf 2 behind sees both;
+    /// each holds a 64x64 wall 64 units out from the split, facing it, with its own texture
+    struct TestWorld {
+        model: QModelT,
+        surfs: Vec<MSurfaceT>,
+        textures: Vec<TextureT>,
+        _texture_list: Vec<*mut TextureT>,
+        _planes: Vec<MPlaneT>,
+        _texinfo: Vec<MTexInfoT>,
+        _marksurfaces: Vec<*mut MSurfaceT>,
+        _vis: Vec<Byte>,
+        _nodes: Vec<MNodeT>,
+        _leafs: Vec<MLeafT>,
+    }
+
+    impl TestWorld {
+        fn new() -> Box<Self> {
+            let mut w = Box::new(TestWorld {
+                model: QModelT::default(),
+                surfs: vec![MSurfaceT::default(); 2],
+                textures: vec![TextureT::default(); 2],
+                _texture_list: Vec::new(),
+                _planes: vec![MPlaneT::default(); 3],
+                _texinfo: vec![MTexInfoT::default(); 2],
+                _marksurfaces: Vec::new(),
+                // compressed rows: leaf 1 sees leaf 1, leaf 2 sees 1 and 2
+                _vis: vec![0x01, 0x03],
+                _nodes: vec![MNodeT::default(); 1],
+                _leafs: vec![MLeafT::default(); 3],
+            });
+            for (i, plane) in w._planes.iter_mut().enumerate() {
+                plane.normal = Vec3T::new(1.0, 0.0, 0.0);
+                plane.dist = [0.0, 64.0, -64.0][i];
+            }
+            w._texture_list = w.textures.iter_mut().map(|t| t as *mut TextureT).collect();
+            for i in 0..2 {
+                w._texinfo[i].texture = w._texture_list[i];
+                let x = [64.0, -64.0][i];
+                let surf = &mut w.surfs[i];
+                surf.plane = &mut w._planes[i + 1];
+                surf.texinfo = &mut w._texinfo[i];
+                surf.mins = [x, -32.0, -32.0];
+                surf.maxs = [x, 32.0, 32.0];
+            }
+            // the far wall faces back towards the split
+            w.surfs[0].flags = SurfaceFlags::PlaneBack.bits() as c_int;
+            w._marksurfaces = w.surfs.iter_mut().map(|s| s as *mut MSurfaceT).collect();
+
+            w._leafs[0].contents = Contents::Solid as c_int;
+            for i in 1..3 {
+                let leaf = &mut w._leafs[i];
+                leaf.contents = Contents::Empty as c_int;
+                leaf.compressed_vis = &mut w._vis[i - 1];
+                leaf.firstmarksurface = &mut w._marksurfaces[i - 1];
+                leaf.nummarksurfaces = 1;
+            }
+            let children = [
+                &mut w._leafs[1] as *mut MLeafT as *mut MNodeT,
+                &mut w._leafs[2] as *mut MLeafT as *mut MNodeT,
+            ];
+            let node = &mut w._nodes[0];
+            node.plane = &mut w._planes[0];
+            node.numsurfaces = 2;
+            node.children = children;
+
+            w.model.nodes = w._nodes.as_mut_ptr();
+            w.model.numnodes = 1;
+            w.model.leafs = w._leafs.as_mut_ptr();
+            w.model.numleafs = 2;
+            w.model.surfaces = w.surfs.as_mut_ptr();
+            w.model.numsurfaces = 2;
+            w.model.textures = w._texture_list.as_mut_ptr();
+            w.model.numtextures = 2;
+            return w;
+        }
+
+        fn chained(&self, vis: &WorldVisT) -> Vec<*mut TextureT> {
+            return vis.texture_chains().iter().map(|c| c.texture).collect();
+        }
+
+        fn texture(&mut self, i: usize) -> *mut TextureT {
+            return &mut self.textures[i];
+        }
+    }
+
+    fn view(x: c_float, yaw: c_float) -> RefDefT {
+        let mut refdef = RefDefT::default();
+        refdef.vieworg = Vec3T::new(x, 0.0, 0.0);
+        refdef.viewangles = Vec3T::new(0.0, yaw, 0.0);
+        refdef.fov_x = 90.0;
+        refdef.fov_y = 90.0;
+        return refdef;
+    }
+
+    fn frame(vis: &mut WorldVisT, w: &QModelT, refdef: &RefDefT) {
+        vis.setup_view(w, refdef);
+        vis.mark_surfaces(w, &mut Vec::new());
+        vis.cull_surfaces();
+    }
+
+    #[test]
+    fn nothing_is_marked_before_the_view_is_set() {
+        let w = TestWorld::new();
+        let mut vis = WorldVisT::new();
+        let mut visedicts = Vec::new();
+        vis.mark_surfaces(&w.model, &mut visedicts);
+        vis.cull_surfaces();
+        assert!(visedicts.is_empty());
+        assert!(vis.texture_chains().is_empty());
+
+        // and again after a map change
+        frame(&mut vis, &w.model, &view(16.0, 0.0));
+        vis.new_map();
+        vis.mark_surfaces(&w.model, &mut visedicts);
+        vis.cull_surfaces();
+        assert!(vis.texture_chains().is_empty());
+    }
+
+    #[test]
+    fn marks_what_the_view_leaf_sees() {
+        let mut w = TestWorld::new();
+        let mut vis = WorldVisT::new();
+
+        // in front of the split only the far wall is in the pvs
+        frame(&mut vis, &w.model, &view(16.0, 0.0));
+        assert_eq!(vis.viewleaf(), &mut w._leafs[1] as *mut MLeafT);
+        assert_eq!(w.chained(&vis), vec![w.texture(0)]);
+        assert_eq!(w.surfs[0].visframe, vis.visframecount);
+        assert_ne!(w.surfs[1].visframe, vis.visframecount);
+        assert_eq!(
+            w.textures[0].texturechains[TexChainT::World as usize],
+            &mut w.surfs[0] as *mut MSurfaceT
+        );
+        assert!(w.textures[1].texturechains[TexChainT::World as usize].is_null());
+
+        // behind it both are in the pvs, but only one is in view
+        frame(&mut vis, &w.model, &view(-16.0, 0.0));
+        assert_eq!(w.surfs[1].visframe, vis.visframecount);
+        assert_eq!(w.chained(&vis), vec![w.texture(0)]);
+        assert!(w.surfs[1].culled == QBoolean::True);
+
+        // r_novis marks everything from anywhere
+        vis.novis = true;
+        vis.vis_changed = true;
+        frame(&mut vis, &w.model, &view(16.0, 0.0));
+        assert_eq!(w.surfs[1].visframe, vis.visframecount);
+    }
+
+    #[test]
+    fn culls_what_is_behind_the_view() {
+        let w = TestWorld::new();
+        let mut vis = WorldVisT::new();
+        frame(&mut vis, &w.model, &view(16.0, 0.0));
+        assert_eq!(vis.brushpolys, 1);
+        assert!(w.surfs[0].culled == QBoolean::False);
+
+        // same leaf, so the chains are kept, but the wall is now behind
+        frame(&mut vis, &w.model, &view(16.0, 180.0));
+        assert_eq!(vis.brushpolys, 0);
+        assert!(w.surfs[0].culled == QBoolean::True);
+        assert!(w.chained(&vis).is_empty());
+    }
+
+    #[test]
+    fn frustum_planes_face_inwards() {
+        let frustum = set_frustum(
+            Vec3T::default(),
+            Vec3T::new(1.0, 0.0, 0.0),
+            Vec3T::new(0.0, -1.0, 0.0),
+            Vec3T::new(0.0, 0.0, 1.0),
+            90.0,
+            90.0,
+        );
+        let cube = |x: c_float, y: c_float| {
+            cull_box(
+                &frustum,
+                Vec3T::new(x - 1.0, y - 1.0, -1.0),
+                Vec3T::new(x + 1.0, y + 1.0, 1.0),
+            )
+        };
+        assert!(!cube(10.0, 0.0));
+        assert!(!cube(10.0, 10.5));
+        assert!(cube(10.0, 14.0));
+        assert!(cube(10.0, -14.0));
+        assert!(cube(-10.0, 0.0));
+
+        for plane in frustum.iter() {
+            assert_eq!(plane.signbits, signbits_for_plane(plane));
+        }
+        let mut plane = MPlaneT::default();
+        plane.normal = Vec3T::new(-1.0, 0.5, -0.5);
+        assert_eq!(signbits_for_plane(&plane), 0b101);
+    }
+}
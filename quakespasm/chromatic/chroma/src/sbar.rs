@@ -0,0 +1,577 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2002-2009 John Fitzgibbons and others
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// sbar.rs -- the scoreboard behind the status bar: player slots, frag sorting and results
+
+use cl_bot::BotEventT;
+use cl_parse::ParseError;
+use client::ClientStateT;
+use protocol::GameType;
+use std::fmt::Write as FmtWrite;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::os::raw::{c_char, c_double, c_int};
+use std::path::Path;
+use {Byte, MAX_SCOREBOARD, MAX_SCOREBOARDNAME};
+
+/// the results file in host_parms->userdir, a line of json for every level played to the end
+pub const RESULTS_FILE: &str = "results.jsonl";
+
+/// a player slot, what cl.scores holds plus when the player arrived
+#[derive(Clone)]
+pub struct PlayerInfoT {
+    pub slot: c_int,
+    /// empty for an unused slot
+    pub name: Vec<Byte>,
+    /// cl.time when the name was set in an empty slot
+    pub entertime: c_double,
+    pub frags: c_int,
+    /// two 4 bit fields
+    pub colors: c_int,
+}
+
+impl PlayerInfoT {
+    fn new(slot: c_int) -> Self {
+        Self {
+            slot,
+            name: Vec::new(),
+            entertime: 0.0,
+            frags: 0,
+            colors: 0,
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        return !self.name.is_empty();
+    }
+
+    pub fn top_color(&self) -> c_int {
+        return (self.colors & 0xf0) >> 4;
+    }
+
+    pub fn bottom_color(&self) -> c_int {
+        return self.colors & 15;
+    }
+}
+
+/// a change to a player's frags
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FragEventT {
+    /// cl.time of the svc_updatefrags
+    pub time: c_double,
+    pub slot: c_int,
+    /// the new total
+    pub frags: c_int,
+    /// the change from the last total, negative for suicides and team kills
+    pub delta: c_int,
+}
+
+/// the scoreboard of a level, built from svc_updatename, svc_updatefrags and
+/// svc_updatecolors; a new svc_serverinfo starts a new one
+pub struct ScoreboardModelT {
+    players: Vec<PlayerInfoT>,
+    /// cl.gametype, GameType
+    pub gametype: c_int,
+    history: Vec<FragEventT>,
+}
+
+impl ScoreboardModelT {
+    /// no slots, until the first reset
+    pub const fn default() -> Self {
+        Self {
+            players: Vec::new(),
+            gametype: 0,
+            history: Vec::new(),
+        }
+    }
+
+    /// maxclients slots; values past MAX_SCOREBOARD are clamped
+    pub fn new(maxclients: c_int, gametype: c_int) -> Self {
+        let mut scoreboard = Self {
+            players: Vec::new(),
+            gametype,
+            history: Vec::new(),
+        };
+        scoreboard.reset(maxclients, gametype);
+        return scoreboard;
+    }
+
+    /// empty every slot for a new level
+    pub fn reset(&mut self, maxclients: c_int, gametype: c_int) {
+        let count = maxclients.max(0).min(MAX_SCOREBOARD as c_int);
+        self.players = (0..count).map(PlayerInfoT::new).collect();
+        self.gametype = gametype;
+        self.history.clear();
+    }
+
+    /// every slot, cl.maxclients long
+    pub fn players(&self) -> &[PlayerInfoT] {
+        return &self.players;
+    }
+
+    /// every frag change of the level, oldest first
+    pub fn history(&self) -> &[FragEventT] {
+        return &self.history;
+    }
+
+    fn player_mut(&mut self, slot: c_int) -> Result<&mut PlayerInfoT, ParseError> {
+        if slot < 0 || slot as usize >= self.players.len() {
+            return Err(ParseError::BadScoreboardSlot(slot));
+        }
+        return Ok(&mut self.players[slot as usize]);
+    }
+
+    /// svc_updatename; a name in an empty slot is a player entering at time, an empty name a
+    /// player leaving
+    pub fn update_name(
+        &mut self,
+        slot: c_int,
+        name: &[Byte],
+        time: c_double,
+    ) -> Result<(), ParseError> {
+        let player = self.player_mut(slot)?;
+        let name = &name[..name.len().min(MAX_SCOREBOARDNAME - 1)];
+        if name.is_empty() {
+            *player = PlayerInfoT::new(slot);
+        } else {
+            if !player.active() {
+                player.entertime = time;
+            }
+            player.name = name.to_vec();
+        }
+        return Ok(());
+    }
+
+    /// svc_updatefrags; changes go into the history
+    pub fn update_frags(
+        &mut self,
+        slot: c_int,
+        frags: c_int,
+        time: c_double,
+    ) -> Result<(), ParseError> {
+        let player = self.player_mut(slot)?;
+        let delta = frags - player.frags;
+        player.frags = frags;
+        if delta != 0 {
+            self.history.push(FragEventT {
+                time,
+                slot,
+                frags,
+                delta,
+            });
+        }
+        return Ok(());
+    }
+
+    /// svc_updatecolors
+    pub fn update_colors(&mut self, slot: c_int, colors: c_int) -> Result<(), ParseError> {
+        self.player_mut(slot)?.colors = colors;
+        return Ok(());
+    }
+
+    /// Formerly: Sbar_SortFrags
+    /// the slots in use, most frags first; ties keep slot order
+    pub fn sort_frags(&self) -> Vec<usize> {
+        let mut fragsort: Vec<usize> = (0..self.players.len())
+            .filter(|&i| self.players[i].active())
+            .collect();
+        fragsort.sort_by(|&a, &b| self.players[b].frags.cmp(&self.players[a].frags));
+        return fragsort;
+    }
+
+    /// the slots in use in the order they entered the game; for coop, where frags are kills
+    pub fn sort_entertime(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.players.len())
+            .filter(|&i| self.players[i].active())
+            .collect();
+        order.sort_by(|&a, &b| {
+            let (a, b) = (&self.players[a], &self.players[b]);
+            return a
+                .entertime
+                .partial_cmp(&b.entertime)
+                .unwrap_or(a.slot.cmp(&b.slot));
+        });
+        return order;
+    }
+
+    /// the players in use as the intermission screen lists them: by frags for deathmatch, by
+    /// entertime for coop
+    pub fn sorted(&self) -> Vec<&PlayerInfoT> {
+        let order = if self.gametype == GameType::DeathMatch as c_int {
+            self.sort_frags()
+        } else {
+            self.sort_entertime()
+        };
+        return order.into_iter().map(|i| &self.players[i]).collect();
+    }
+
+    /// keep up with a bot or demo; at intermission or finale, the results of the level as
+    /// json, see to_json
+    pub fn apply(
+        &mut self,
+        event: &BotEventT,
+        cl: &ClientStateT,
+    ) -> Result<Option<String>, ParseError> {
+        match *event {
+            BotEventT::ServerInfo => self.reset(cl.maxclients, cl.gametype),
+            BotEventT::UpdateName { slot, ref name } => self.update_name(slot, name, cl.time)?,
+            BotEventT::UpdateFrags { slot, frags } => self.update_frags(slot, frags, cl.time)?,
+            BotEventT::UpdateColors { slot, colors } => self.update_colors(slot, colors)?,
+            BotEventT::Intermission | BotEventT::Finale(_) => {
+                return Ok(Some(self.to_json(
+                    c_str_bytes(&cl.mapname),
+                    c_str_bytes(&cl.levelname),
+                    cl.time,
+                )));
+            }
+            _ => {}
+        }
+        return Ok(None);
+    }
+
+    /// the final results: map, level name, game type, completion time, the sorted players and
+    /// the frag history
+    pub fn to_json(&self, mapname: &[Byte], levelname: &[Byte], time: c_double) -> String {
+        let mut json = String::new();
+        json.push_str("{\"map\":");
+        push_json_string(&mut json, mapname);
+        json.push_str(",\"level\":");
+        push_json_string(&mut json, levelname);
+        let gametype = if self.gametype == GameType::DeathMatch as c_int {
+            "deathmatch"
+        } else {
+            "coop"
+        };
+        let _ = write!(json, ",\"gametype\":\"{}\",\"time\":", gametype);
+        push_json_number(&mut json, time);
+
+        json.push_str(",\"players\":[");
+        for (i, player) in self.sorted().into_iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            let _ = write!(json, "{{\"slot\":{},\"name\":", player.slot);
+            push_json_string(&mut json, &player.name);
+            let _ = write!(
+                json,
+                ",\"frags\":{},\"top\":{},\"bottom\":{},\"entertime\":",
+                player.frags,
+                player.top_color(),
+                player.bottom_color()
+            );
+            push_json_number(&mut json, player.entertime);
+            json.push('}');
+        }
+
+        json.push_str("],\"frags\":[");
+        for (i, frag) in self.history.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            json.push_str("{\"time\":");
+            push_json_number(&mut json, frag.time);
+            let _ = write!(
+                json,
+                ",\"slot\":{},\"frags\":{},\"delta\":{}}}",
+                frag.slot, frag.frags, frag.delta
+            );
+        }
+        json.push_str("]}");
+        return json;
+    }
+}
+
+impl Default for ScoreboardModelT {
+    fn default() -> Self {
+        Self::default()
+    }
+}
+
+/// add the json of a level to the results file at path, creating it if needed
+pub fn append_results(path: &Path, json: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    return writeln!(file, "{}", json);
+}
+
+/// a fixed size C string up to its terminator
+fn c_str_bytes(s: &[c_char]) -> &[Byte] {
+    let len = s.iter().position(|&c| c == 0).unwrap_or(s.len());
+    return unsafe { &*(&s[..len] as *const [c_char] as *const [Byte]) };
+}
+
+/// a quake string as a json string; every byte is taken as its latin-1 character, so the
+/// colored high half of the charset survives
+fn push_json_string(json: &mut String, s: &[Byte]) {
+    json.push('"');
+    for &c in s {
+        match c {
+            b'"' => json.push_str("\\\""),
+            b'\\' => json.push_str("\\\\"),
+            0x20..=0x7e => json.push(c as char),
+            _ => {
+                let _ = write!(json, "\\u{:04x}", c);
+            }
+        }
+    }
+    json.push('"');
+}
+
+/// a number as json, which has no nan or infinity; those are written as null
+fn push_json_number(json: &mut String, v: c_double) {
+    if v.is_finite() {
+        let _ = write!(json, "{}", v);
+    } else {
+        json.push_str("null");
+    }
+}
+
+#[allow(bad_style)]
+pub mod capi {
+    use super::{append_results, c_str_bytes, ScoreboardModelT, RESULTS_FILE};
+    use cl_main::capi::cl;
+    use std::ffi::{CStr, CString};
+    use std::os::raw::{c_char, c_int};
+    use std::path::PathBuf;
+    use std::ptr::{addr_of, addr_of_mut};
+    use QuakeParmsT;
+
+    extern "C" {
+        static host_parms: *mut QuakeParmsT;
+        fn Con_Printf(fmt: *const c_char, ...);
+    }
+
+    /// the level the client is in
+    static mut SCOREBOARD: ScoreboardModelT = ScoreboardModelT::default();
+    /// the results of the level were written
+    static mut RECORDED: bool = false;
+
+    unsafe fn scoreboard() -> &'static mut ScoreboardModelT {
+        return &mut *addr_of_mut!(SCOREBOARD);
+    }
+
+    /// CL_ParseServerInfo, once cl.maxclients and cl.gametype are set
+    ///
+    /// # Safety
+    ///
+    /// reads cl; the scoreboard is shared with the client; only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn CL_ScoreboardNewLevel() {
+        scoreboard().reset(cl.maxclients, cl.gametype);
+        RECORDED = false;
+    }
+
+    /// svc_updatename, with the name as it went into cl.scores
+    ///
+    /// # Safety
+    ///
+    /// name must be a string; the scoreboard is shared with the client; only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn CL_ScoreboardUpdateName(slot: c_int, name: *const c_char) {
+        let name = CStr::from_ptr(name).to_bytes();
+        let _ = scoreboard().update_name(slot, name, cl.time);
+    }
+
+    /// svc_updatefrags
+    ///
+    /// # Safety
+    ///
+    /// the scoreboard is shared with the client; only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn CL_ScoreboardUpdateFrags(slot: c_int, frags: c_int) {
+        let _ = scoreboard().update_frags(slot, frags, cl.time);
+    }
+
+    /// svc_updatecolors
+    ///
+    /// # Safety
+    ///
+    /// the scoreboard is shared with the client; only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn CL_ScoreboardUpdateColors(slot: c_int, colors: c_int) {
+        let _ = scoreboard().update_colors(slot, colors);
+    }
+
+    /// svc_intermission and svc_finale: add the level's results to the results file, once
+    ///
+    /// # Safety
+    ///
+    /// reads cl and host_parms; only call this from the main thread
+    #[no_mangle]
+    pub unsafe extern "C" fn CL_ScoreboardIntermission() {
+        if RECORDED {
+            return;
+        }
+        RECORDED = true;
+
+        let client = &*addr_of!(cl);
+        let json = scoreboard().to_json(
+            c_str_bytes(&client.mapname),
+            c_str_bytes(&client.levelname),
+            client.time,
+        );
+        let userdir = CStr::from_ptr((*host_parms).userdir)
+            .to_string_lossy()
+            .into_owned();
+        let path = PathBuf::from(userdir).join(RESULTS_FILE);
+        if let Err(e) = append_results(&path, &json) {
+            let msg = format!("couldn't save {}: {}\n", path.display(), e);
+            let msg = CString::new(msg).unwrap_or_default();
+            Con_Printf(b"%s\0".as_ptr() as *const c_char, msg.as_ptr());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    /// four players in deathmatch: slot 2 left, 0 and 3 tie
+    fn deathmatch() -> ScoreboardModelT {
+        let mut sb = ScoreboardModelT::new(4, GameType::DeathMatch as c_int);
+        for &(slot, name, time) in [(0, "ranger", 1.0), (1, "grunt", 0.5), (3, "\"q\"", 2.0)].iter()
+        {
+            sb.update_name(slot, name.as_bytes(), time).unwrap();
+        }
+        sb.update_frags(1, 5, 10.0).unwrap();
+        sb.update_frags(0, 2, 11.0).unwrap();
+        sb.update_frags(3, 2, 12.0).unwrap();
+        sb.update_frags(1, 4, 13.0).unwrap();
+        sb.update_colors(0, 0x4d).unwrap();
+        return sb;
+    }
+
+    #[test]
+    fn frags_sort_most_first_ties_by_slot() {
+        let sb = deathmatch();
+        assert_eq!(sb.sort_frags(), [1, 0, 3]);
+        assert_eq!(sb.sorted()[0].name, b"grunt");
+        assert_eq!(sb.history().len(), 4);
+        assert_eq!(sb.history()[3].delta, -1);
+        assert_eq!(
+            (sb.players()[0].top_color(), sb.players()[0].bottom_color()),
+            (4, 13)
+        );
+
+        // the same frags again isn't a change
+        let mut sb = sb;
+        sb.update_frags(1, 4, 14.0).unwrap();
+        assert_eq!(sb.history().len(), 4);
+        assert!(sb.update_frags(4, 1, 14.0).is_err());
+    }
+
+    #[test]
+    fn entertime_sorts_by_arrival() {
+        let mut sb = deathmatch();
+        assert_eq!(sb.sort_entertime(), [1, 0, 3]);
+
+        // renaming keeps the arrival time, leaving and coming back doesn't
+        sb.update_name(1, b"sarge", 20.0).unwrap();
+        assert_eq!(sb.sort_entertime(), [1, 0, 3]);
+        sb.update_name(1, b"", 21.0).unwrap();
+        sb.update_name(1, b"sarge", 22.0).unwrap();
+        assert_eq!(sb.sort_entertime(), [0, 3, 1]);
+        assert_eq!(sb.players()[1].frags, 0);
+
+        sb.gametype = GameType::CoOp as c_int;
+        assert_eq!(sb.sorted()[0].slot, 0);
+    }
+
+    #[test]
+    fn json_results() {
+        let mut sb = deathmatch();
+        sb.history.truncate(1);
+        let json = sb.to_json(b"dm4", b"The Bad Place\x8d", 300.5);
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"map":"dm4","level":"The Bad Place\u008d","gametype":"deathmatch","time":300.5,"#,
+                r#""players":["#,
+                r#"{"slot":1,"name":"grunt","frags":4,"top":0,"bottom":0,"entertime":0.5},"#,
+                r#"{"slot":0,"name":"ranger","frags":2,"top":4,"bottom":13,"entertime":1},"#,
+                r#"{"slot":3,"name":"\"q\"","frags":2,"top":0,"bottom":0,"entertime":2}],"#,
+                r#""frags":[{"time":10,"slot":1,"frags":5,"delta":5}]}"#
+            )
+        );
+    }
+
+    #[test]
+    fn json_has_no_nan_or_infinity() {
+        let mut sb = ScoreboardModelT::new(1, GameType::CoOp as c_int);
+        sb.update_name(0, b"a", c_double::NAN).unwrap();
+        sb.update_frags(0, 1, c_double::INFINITY).unwrap();
+        let json = sb.to_json(b"e1m1", b"", c_double::NEG_INFINITY);
+        assert!(json.contains(r#""time":null,"#));
+        assert!(json.contains(r#""entertime":null}"#));
+        assert!(json.contains(r#"{"time":null,"slot":0"#));
+        assert!(!json.contains("NaN") && !json.contains("inf"));
+    }
+
+    #[test]
+    fn apply_follows_the_bot() {
+        let mut cl = Box::new(ClientStateT::default());
+        cl.maxclients = 2;
+        cl.gametype = GameType::DeathMatch as c_int;
+        for (c, &b) in cl.mapname.iter_mut().zip(b"dm2") {
+            *c = b as c_char;
+        }
+
+        let mut sb = ScoreboardModelT::new(8, 0);
+        let events = [
+            BotEventT::ServerInfo,
+            BotEventT::UpdateName {
+                slot: 1,
+                name: b"bot".to_vec(),
+            },
+            BotEventT::UpdateFrags { slot: 1, frags: 3 },
+            BotEventT::UpdateColors {
+                slot: 1,
+                colors: 0x11,
+            },
+            BotEventT::KilledMonster,
+        ];
+        for event in events.iter() {
+            assert_eq!(sb.apply(event, &cl), Ok(None));
+        }
+        assert_eq!(sb.players().len(), 2);
+        assert_eq!(sb.sort_frags(), [1]);
+
+        cl.time = 60.0;
+        let json = sb.apply(&BotEventT::Intermission, &cl).unwrap().unwrap();
+        assert!(json.starts_with(r#"{"map":"dm2","level":"","gametype":"deathmatch","time":60,"#));
+
+        let bad = BotEventT::UpdateFrags { slot: 2, frags: 1 };
+        assert_eq!(sb.apply(&bad, &cl), Err(ParseError::BadScoreboardSlot(2)));
+    }
+
+    #[test]
+    fn results_are_appended() {
+        let path = env::temp_dir().join(format!("sbar_results_{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        append_results(&path, "{}").unwrap();
+        append_results(&path, "{\"a\":1}").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}\n{\"a\":1}\n");
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -14,6 +14,25 @@
  * OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
  */
 
+use libc::c_char;
+
+/*
+ * q_strlcpy for a string that is already a slice, without its NUL: copy
+ * what fits of src to dst.  Always NUL terminates (unless dst is empty).
+ * Returns true if truncation occurred.
+ */
+pub fn strlcpy_slice(dst: &mut [c_char], src: &[u8]) -> bool {
+    if dst.is_empty() {
+        return !src.is_empty();
+    }
+    let len = (dst.len() - 1).min(src.len());
+    for (d, s) in dst.iter_mut().zip(&src[..len]) {
+        *d = *s as c_char;
+    }
+    dst[len] = 0;
+    return len < src.len();
+}
+
 pub mod capi {
     use libc::{c_char, size_t};
     use std::slice;
@@ -0,0 +1,871 @@
+/*
+Copyright (C) 1996-2001 Id Software, Inc.
+Copyright (C) 2002-2009 John Fitzgibbons and others
+Copyright (C) 2010-2014 QuakeSpasm developers
+
+This program is free software; you can redistribute it and/or
+modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation; either version 2
+of the License, or (at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.
+
+See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program; if not, write to the Free Software
+Foundation, Inc., 59 Temple Place - Suite 330, Boston, MA  02111-1307, USA.
+
+*/
+
+// view.rs -- player eye positioning and screen color shifts
+
+/*
+The view is allowed to move slightly from it's true position for bobbing,
+but if it exceeds 8 pixels linear distance (spherical, not box), the list of
+entities sent from the server may not include everything in the pvs, especially
+when crossing a water boudnary.
+*/
+
+use bspfile::Contents;
+use cl_parse::ParseError;
+use client::{
+    CShiftT, ClientStateT, CSHIFT_BONUS, CSHIFT_CONTENTS, CSHIFT_DAMAGE, CSHIFT_POWERUP,
+    NUM_CSHIFTS,
+};
+use common::MsgReaderT;
+use mathlib::{angle_vectors, anglemod, dot_product, vector_normalize, vector_subtract};
+use render::{EntityT, RefDefT};
+use std::f64::consts::PI;
+use std::os::raw::{c_double, c_float, c_int, c_uint};
+use {Byte, Vec3T};
+use {IT_INVISIBILITY, IT_INVULNERABILITY, IT_QUAD, IT_SUIT};
+use {STAT_HEALTH, STAT_WEAPON, STAT_WEAPONFRAME};
+
+const PITCH: usize = 0;
+const YAW: usize = 1;
+const ROLL: usize = 2;
+
+pub const CSHIFT_EMPTY: CShiftT = CShiftT {
+    destcolor: [130, 80, 50],
+    percent: 0,
+};
+pub const CSHIFT_WATER: CShiftT = CShiftT {
+    destcolor: [130, 80, 50],
+    percent: 128,
+};
+pub const CSHIFT_SLIME: CShiftT = CShiftT {
+    destcolor: [0, 25, 5],
+    percent: 150,
+};
+pub const CSHIFT_LAVA: CShiftT = CShiftT {
+    destcolor: [255, 80, 0],
+    percent: 150,
+};
+
+/// the cvars the view depends on, with their stock defaults
+#[derive(Clone, Copy)]
+pub struct ViewSettingsT {
+    /// scr_ofsx, scr_ofsy, scr_ofsz; only honored in single player
+    pub ofs: Vec3T,
+    /// cl_rollspeed
+    pub rollspeed: c_float,
+    /// cl_rollangle
+    pub rollangle: c_float,
+    /// cl_bob
+    pub bob: c_float,
+    /// cl_bobcycle
+    pub bobcycle: c_float,
+    /// cl_bobup
+    pub bobup: c_float,
+    /// v_kicktime
+    pub kicktime: c_float,
+    /// v_kickroll
+    pub kickroll: c_float,
+    /// v_kickpitch
+    pub kickpitch: c_float,
+    /// v_gunkick: 0 none, 1 original quake kick, 2 lerped kick
+    pub gunkick: c_int,
+    /// v_ipitch_cycle, v_iyaw_cycle, v_iroll_cycle
+    pub idle_cycle: Vec3T,
+    /// v_ipitch_level, v_iyaw_level, v_iroll_level
+    pub idle_level: Vec3T,
+    /// v_idlescale
+    pub idlescale: c_float,
+    /// v_centermove
+    pub centermove: c_float,
+    /// v_centerspeed
+    pub centerspeed: c_float,
+    /// gl_cshiftpercent
+    pub cshiftpercent: c_float,
+    /// gl_cshiftpercent_contents, _damage, _bonus and _powerup, by CSHIFT_*
+    pub cshiftpercents: [c_float; NUM_CSHIFTS],
+    /// r_viewmodel_quake
+    pub viewmodel_quake: bool,
+    /// scr_viewsize
+    pub viewsize: c_float,
+    /// cl_forwardspeed
+    pub forwardspeed: c_float,
+    /// lookspring
+    pub lookspring: bool,
+    /// noclip_anglehack, set by the local server while noclipping
+    pub noclip_anglehack: bool,
+    /// cls.demoplayback
+    pub demoplayback: bool,
+}
+
+impl ViewSettingsT {
+    pub const fn default() -> Self {
+        Self {
+            ofs: Vec3T::new(0.0, 0.0, 0.0),
+            rollspeed: 200.0,
+            rollangle: 2.0,
+            bob: 0.02,
+            bobcycle: 0.6,
+            bobup: 0.5,
+            kicktime: 0.5,
+            kickroll: 0.6,
+            kickpitch: 0.6,
+            gunkick: 1,
+            idle_cycle: Vec3T::new(1.0, 2.0, 0.5),
+            idle_level: Vec3T::new(0.3, 0.3, 0.1),
+            idlescale: 0.0,
+            centermove: 0.15,
+            centerspeed: 500.0,
+            cshiftpercent: 100.0,
+            cshiftpercents: [100.0; NUM_CSHIFTS],
+            viewmodel_quake: false,
+            viewsize: 100.0,
+            forwardspeed: 200.0,
+            lookspring: false,
+            noclip_anglehack: false,
+            demoplayback: false,
+        }
+    }
+}
+
+impl Default for ViewSettingsT {
+    fn default() -> Self {
+        Self::default()
+    }
+}
+
+/// view state kept from frame to frame outside of cl
+#[derive(Clone, Copy)]
+pub struct ViewStateT {
+    /// the last view computed; the renderer fills in vrect and fov
+    pub refdef: RefDefT,
+    /// rgba 0.0 - 1.0
+    pub blend: [c_float; 4],
+    /// contents shift outside of liquids, set by the v_cshift command
+    pub cshift_empty: CShiftT,
+    pub dmg_time: c_float,
+    pub dmg_roll: c_float,
+    pub dmg_pitch: c_float,
+    /// copied from cl.punchangle.  0 is current, 1 is previous value.  never the same unless
+    /// map just loaded
+    pub punchangles: [Vec3T; 2],
+    /// v_gunkick 2 kick lerped towards punchangles[0]
+    punch: Vec3T,
+    /// view height being smoothed towards the player's after a step up
+    oldz: c_float,
+    oldyaw: c_float,
+    oldpitch: c_float,
+}
+
+impl ViewStateT {
+    pub const fn default() -> Self {
+        Self {
+            refdef: RefDefT::default(),
+            blend: [0.0; 4],
+            cshift_empty: CSHIFT_EMPTY,
+            dmg_time: 0.0,
+            dmg_roll: 0.0,
+            dmg_pitch: 0.0,
+            punchangles: [Vec3T::new(0.0, 0.0, 0.0); 2],
+            punch: Vec3T::new(0.0, 0.0, 0.0),
+            oldz: 0.0,
+            oldyaw: 0.0,
+            oldpitch: 0.0,
+        }
+    }
+
+    /// Formerly: part of CL_ParseClientdata
+    /// remember a new cl.punchangle for the lerped kick
+    pub fn set_punchangle(&mut self, punchangle: Vec3T) {
+        if self.punchangles[0].0 != punchangle.0 {
+            self.punchangles[1] = self.punchangles[0];
+            self.punchangles[0] = punchangle;
+        }
+    }
+}
+
+impl Default for ViewStateT {
+    fn default() -> Self {
+        Self::default()
+    }
+}
+
+/// Formerly: V_CalcRoll
+/// Used by view and sv_user
+pub fn calc_roll(angles: Vec3T, velocity: Vec3T, settings: &ViewSettingsT) -> c_float {
+    let (_, right, _) = angle_vectors(angles);
+    let side = dot_product(velocity, right);
+    let sign = if side < 0.0 { -1.0 } else { 1.0 };
+    let side = side.abs();
+
+    let value = settings.rollangle;
+    let side = if side < settings.rollspeed {
+        side * value / settings.rollspeed
+    } else {
+        value
+    };
+
+    return side * sign;
+}
+
+/// Formerly: V_CalcBob
+pub fn calc_bob(cl: &ClientStateT, settings: &ViewSettingsT) -> c_float {
+    if settings.bobcycle <= 0.0 {
+        return 0.0;
+    }
+    let bobcycle = settings.bobcycle as c_double;
+    let bobup = settings.bobup as c_double;
+
+    let mut cycle = cl.time - (cl.time / bobcycle) as c_int as c_double * bobcycle;
+    cycle /= bobcycle;
+    cycle = if cycle < bobup {
+        PI * cycle / bobup
+    } else {
+        PI + PI * (cycle - bobup) / (1.0 - bobup)
+    };
+
+    // bob is proportional to velocity in the xy plane
+    // (don't count Z, or jumping messes it up)
+    let v = cl.velocity;
+    let bob = (v[0] * v[0] + v[1] * v[1]).sqrt() * settings.bob;
+    let bob = bob * 0.3 + bob * 0.7 * cycle.sin() as c_float;
+    return bob.clamp(-7.0, 4.0);
+}
+
+/// Formerly: V_StartPitchDrift
+/// the centerview command
+pub fn start_pitch_drift(cl: &mut ClientStateT, settings: &ViewSettingsT) {
+    if cl.laststop == cl.time {
+        // something else is keeping it from drifting
+        return;
+    }
+    if cl.nodrift == true || cl.pitchvel == 0.0 {
+        cl.pitchvel = settings.centerspeed;
+        cl.nodrift = false.into();
+        cl.driftmove = 0.0;
+    }
+}
+
+/// Formerly: V_StopPitchDrift
+pub fn stop_pitch_drift(cl: &mut ClientStateT) {
+    cl.laststop = cl.time;
+    cl.nodrift = true.into();
+    cl.pitchvel = 0.0;
+}
+
+/// Formerly: V_DriftPitch
+/// Moves the client pitch angle towards cl.idealpitch sent by the server.
+///
+/// If the user is adjusting pitch manually, either with lookup/lookdown,
+/// mlook and mouse, or klook and keyboard, pitch drifting is constantly stopped.
+pub fn drift_pitch(cl: &mut ClientStateT, settings: &ViewSettingsT, frametime: c_double) {
+    let frametime = frametime as c_float;
+    if settings.noclip_anglehack || cl.onground == false || settings.demoplayback {
+        cl.driftmove = 0.0;
+        cl.pitchvel = 0.0;
+        return;
+    }
+
+    // don't count small mouse motion
+    if cl.nodrift == true {
+        if cl.cmd.forwardmove.abs() < settings.forwardspeed {
+            cl.driftmove = 0.0;
+        } else {
+            cl.driftmove += frametime;
+        }
+
+        if cl.driftmove > settings.centermove && settings.lookspring {
+            start_pitch_drift(cl, settings);
+        }
+        return;
+    }
+
+    let delta = cl.idealpitch - cl.viewangles[PITCH];
+    if delta == 0.0 {
+        cl.pitchvel = 0.0;
+        return;
+    }
+
+    let mut r#move = frametime * cl.pitchvel;
+    cl.pitchvel += frametime * settings.centerspeed;
+
+    if delta > 0.0 {
+        if r#move > delta {
+            cl.pitchvel = 0.0;
+            r#move = delta;
+        }
+        cl.viewangles[PITCH] += r#move;
+    } else {
+        if r#move > -delta {
+            cl.pitchvel = 0.0;
+            r#move = -delta;
+        }
+        cl.viewangles[PITCH] -= r#move;
+    }
+}
+
+/// Formerly: V_ParseDamage
+/// svc_damage: flash the screen and kick the view away from where the damage came from.  ent
+/// is the player, cl_entities[cl.viewentity]
+pub fn parse_damage(
+    msg: &mut MsgReaderT,
+    cl: &mut ClientStateT,
+    view: &mut ViewStateT,
+    settings: &ViewSettingsT,
+    ent: &EntityT,
+    protocolflags: c_uint,
+) -> Result<(), ParseError> {
+    let armor = msg.read_byte();
+    let blood = msg.read_byte();
+    let mut from = Vec3T::new(0.0, 0.0, 0.0);
+    for i in 0..3 {
+        from[i] = msg.read_coord(protocolflags);
+    }
+    if msg.badread {
+        return Err(ParseError::BadRead);
+    }
+
+    let count = (blood as c_float * 0.5 + armor as c_float * 0.5).max(10.0);
+
+    // but sbar face into pain frame
+    cl.faceanimtime = (cl.time + 0.2) as c_float;
+
+    let damage = &mut cl.cshifts[CSHIFT_DAMAGE as usize];
+    damage.percent = (damage.percent + (3.0 * count) as c_int).clamp(0, 150);
+    damage.destcolor = if armor > blood {
+        [200, 100, 100]
+    } else if armor != 0 {
+        [220, 50, 50]
+    } else {
+        [255, 0, 0]
+    };
+
+    // calculate view angle kicks
+    let mut from = vector_subtract(from, ent.origin);
+    vector_normalize(&mut from);
+
+    let (forward, right, _) = angle_vectors(ent.angles);
+
+    let side = dot_product(from, right);
+    view.dmg_roll = count * side * settings.kickroll;
+
+    let side = dot_product(from, forward);
+    view.dmg_pitch = count * side * settings.kickpitch;
+
+    view.dmg_time = settings.kicktime;
+    return Ok(());
+}
+
+/// Formerly: V_BonusFlash_f
+/// When you run over an item, the server sends this command
+pub fn bonus_flash(cl: &mut ClientStateT) {
+    cl.cshifts[CSHIFT_BONUS as usize] = CShiftT {
+        destcolor: [215, 186, 69],
+        percent: 50,
+    };
+}
+
+/// Formerly: V_SetContentsColor
+/// Underwater, lava, etc each has a color shift
+pub fn set_contents_color(cl: &mut ClientStateT, view: &ViewStateT, contents: c_int) {
+    cl.cshifts[CSHIFT_CONTENTS as usize] = match contents {
+        // no blend in sky
+        c if c == Contents::Empty as c_int
+            || c == Contents::Solid as c_int
+            || c == Contents::Sky as c_int =>
+        {
+            view.cshift_empty
+        }
+        c if c == Contents::Lava as c_int => CSHIFT_LAVA,
+        c if c == Contents::Slime as c_int => CSHIFT_SLIME,
+        _ => CSHIFT_WATER,
+    };
+}
+
+/// Formerly: V_CalcPowerupCshift
+pub fn calc_powerup_cshift(cl: &mut ClientStateT) {
+    let items = cl.items as u32;
+    let powerup = &mut cl.cshifts[CSHIFT_POWERUP as usize];
+    let (destcolor, percent) = if items & IT_QUAD != 0 {
+        ([0, 0, 255], 30)
+    } else if items & IT_SUIT != 0 {
+        ([0, 255, 0], 20)
+    } else if items & IT_INVISIBILITY != 0 {
+        ([100, 100, 100], 100)
+    } else if items & IT_INVULNERABILITY != 0 {
+        ([255, 255, 0], 30)
+    } else {
+        powerup.percent = 0;
+        return;
+    };
+    powerup.destcolor = destcolor;
+    powerup.percent = percent;
+}
+
+/// Formerly: V_CalcBlend
+/// the rgba blend of every color shift, each scaled by gl_cshiftpercent and its own
+/// gl_cshiftpercent_* cvar
+pub fn calc_blend(cl: &ClientStateT, settings: &ViewSettingsT) -> [c_float; 4] {
+    let mut r = 0.0;
+    let mut g = 0.0;
+    let mut b = 0.0;
+    let mut a = 0.0;
+
+    for (j, shift) in cl.cshifts.iter().enumerate() {
+        if settings.cshiftpercent == 0.0 {
+            continue;
+        }
+
+        // only apply leaf contents color shifts during intermission
+        if cl.intermission != 0 && j != CSHIFT_CONTENTS as usize {
+            continue;
+        }
+
+        let mut a2 = ((shift.percent as c_float * settings.cshiftpercent) / 100.0) / 255.0;
+        a2 *= settings.cshiftpercents[j] / 100.0;
+        if a2 == 0.0 {
+            continue;
+        }
+        a += a2 * (1.0 - a);
+        a2 /= a;
+        r = r * (1.0 - a2) + shift.destcolor[0] as c_float * a2;
+        g = g * (1.0 - a2) + shift.destcolor[1] as c_float * a2;
+        b = b * (1.0 - a2) + shift.destcolor[2] as c_float * a2;
+    }
+
+    return [r / 255.0, g / 255.0, b / 255.0, a.clamp(0.0, 1.0)];
+}
+
+/// Formerly: V_UpdateBlend
+/// recompute view.blend if any color shift changed and fade the damage and bonus flashes.
+/// true if the blend changed
+pub fn update_blend(
+    cl: &mut ClientStateT,
+    view: &mut ViewStateT,
+    settings: &ViewSettingsT,
+    frametime: c_double,
+) -> bool {
+    calc_powerup_cshift(cl);
+
+    let mut blend_changed = false;
+    for (cur, prev) in cl.cshifts.iter().zip(cl.prev_cshifts.iter_mut()) {
+        if cur.percent != prev.percent || cur.destcolor != prev.destcolor {
+            blend_changed = true;
+            *prev = *cur;
+        }
+    }
+
+    // drop the damage value
+    let damage = &mut cl.cshifts[CSHIFT_DAMAGE as usize];
+    damage.percent = ((damage.percent as c_double - frametime * 150.0) as c_int).max(0);
+
+    // drop the bonus value
+    let bonus = &mut cl.cshifts[CSHIFT_BONUS as usize];
+    bonus.percent = ((bonus.percent as c_double - frametime * 100.0) as c_int).max(0);
+
+    if blend_changed {
+        view.blend = calc_blend(cl, settings);
+    }
+    return blend_changed;
+}
+
+fn angledelta(a: c_float) -> c_float {
+    let a = anglemod(a);
+    if a > 180.0 {
+        return a - 360.0;
+    }
+    return a;
+}
+
+/// Formerly: CalcGunAngle
+fn calc_gun_angle(
+    cl: &mut ClientStateT,
+    view: &mut ViewStateT,
+    settings: &ViewSettingsT,
+    frametime: c_double,
+) {
+    let viewangles = view.refdef.viewangles;
+    let yaw = viewangles[YAW];
+    let pitch = -viewangles[PITCH];
+
+    let mut yaw = (angledelta(yaw - viewangles[YAW]) * 0.4).clamp(-10.0, 10.0);
+    let mut pitch = (angledelta(-pitch - viewangles[PITCH]) * 0.4).clamp(-10.0, 10.0);
+    let r#move = (frametime * 20.0) as c_float;
+    if yaw > view.oldyaw {
+        yaw = yaw.min(view.oldyaw + r#move);
+    } else {
+        yaw = yaw.max(view.oldyaw - r#move);
+    }
+    if pitch > view.oldpitch {
+        pitch = pitch.min(view.oldpitch + r#move);
+    } else {
+        pitch = pitch.max(view.oldpitch - r#move);
+    }
+
+    view.oldyaw = yaw;
+    view.oldpitch = pitch;
+
+    let gun = &mut cl.viewent.angles;
+    gun[YAW] = viewangles[YAW] + yaw;
+    gun[PITCH] = -(viewangles[PITCH] + pitch);
+
+    let idle = idle_offsets(cl.time, settings, settings.idlescale);
+    for i in 0..3 {
+        gun[i] -= idle[i];
+    }
+}
+
+/// Formerly: V_BoundOffsets
+/// absolutely bound refresh reletive to entity clipping hull so the view can never be inside a
+/// solid wall
+fn bound_offsets(vieworg: &mut Vec3T, origin: Vec3T) {
+    vieworg[0] = vieworg[0].max(origin[0] - 14.0).min(origin[0] + 14.0);
+    vieworg[1] = vieworg[1].max(origin[1] - 14.0).min(origin[1] + 14.0);
+    vieworg[2] = vieworg[2].max(origin[2] - 22.0).min(origin[2] + 30.0);
+}
+
+/// Formerly: part of V_AddIdle
+/// Idle swaying as pitch, yaw, roll offsets
+fn idle_offsets(time: c_double, settings: &ViewSettingsT, idlescale: c_float) -> Vec3T {
+    let mut idle = Vec3T::new(0.0, 0.0, 0.0);
+    for i in 0..3 {
+        idle[i] = idlescale
+            * (time * settings.idle_cycle[i] as c_double).sin() as c_float
+            * settings.idle_level[i];
+    }
+    return idle;
+}
+
+/// Formerly: V_CalcViewRoll
+/// Roll is induced by movement and damage
+fn calc_view_roll(
+    cl: &ClientStateT,
+    view: &mut ViewStateT,
+    settings: &ViewSettingsT,
+    ent: &EntityT,
+    frametime: c_double,
+) {
+    let side = calc_roll(ent.angles, cl.velocity, settings);
+    let angles = &mut view.refdef.viewangles;
+    angles[ROLL] += side;
+
+    if view.dmg_time > 0.0 {
+        angles[ROLL] += view.dmg_time / settings.kicktime * view.dmg_roll;
+        angles[PITCH] += view.dmg_time / settings.kicktime * view.dmg_pitch;
+        view.dmg_time -= frametime as c_float;
+    }
+
+    if cl.stats[STAT_HEALTH as usize] <= 0 {
+        // dead view angle
+        angles[ROLL] = 80.0;
+    }
+}
+
+/// Formerly: V_CalcIntermissionRefdef
+/// ent is the player model, cl_entities[cl.viewentity]
+pub fn calc_intermission_refdef(
+    cl: &mut ClientStateT,
+    view: &mut ViewStateT,
+    settings: &ViewSettingsT,
+    ent: &EntityT,
+) {
+    view.refdef.vieworg = ent.origin;
+    view.refdef.viewangles = ent.angles;
+    // the weapon model is only visible from inside body
+    cl.viewent.model = ::std::ptr::null_mut();
+
+    // allways idle in intermission
+    let idle = idle_offsets(cl.time, settings, 1.0);
+    for i in 0..3 {
+        view.refdef.viewangles[i] += idle[i];
+    }
+}
+
+/// Formerly: V_CalcRefdef
+/// place the eye and the gun model for a player standing at ent, cl_entities[cl.viewentity].
+/// colormap is vid.colormap.  chase_active is left to the caller
+pub fn calc_refdef(
+    cl: &mut ClientStateT,
+    view: &mut ViewStateT,
+    settings: &ViewSettingsT,
+    ent: &mut EntityT,
+    frametime: c_double,
+    colormap: *mut Byte,
+) {
+    drift_pitch(cl, settings, frametime);
+
+    // transform the view offset by the model's matrix to get the offset from
+    // model origin for the view
+    // the model should face the view dir
+    ent.angles[YAW] = cl.viewangles[YAW];
+    ent.angles[PITCH] = -cl.viewangles[PITCH];
+
+    let bob = calc_bob(cl, settings);
+
+    // refresh position
+    let refdef = &mut view.refdef;
+    refdef.vieworg = ent.origin;
+    refdef.vieworg[2] += cl.viewheight + bob;
+
+    // never let it sit exactly on a node line, because a water plane can
+    // dissapear when viewed with the eye exactly on it.
+    // the server protocol only specifies to 1/16 pixel, so add 1/32 in each axis
+    for i in 0..3 {
+        refdef.vieworg[i] += 1.0 / 32.0;
+    }
+
+    refdef.viewangles = cl.viewangles;
+    calc_view_roll(cl, view, settings, ent, frametime);
+    let idle = idle_offsets(cl.time, settings, settings.idlescale);
+    for i in 0..3 {
+        view.refdef.viewangles[i] += idle[i];
+    }
+
+    // offsets
+    let mut angles = ent.angles;
+    // because entity pitches are actually backward
+    angles[PITCH] = -angles[PITCH];
+    let (forward, right, up) = angle_vectors(angles);
+
+    if cl.maxclients <= 1 {
+        for i in 0..3 {
+            view.refdef.vieworg[i] +=
+                settings.ofs[0] * forward[i] + settings.ofs[1] * right[i] + settings.ofs[2] * up[i];
+        }
+    }
+
+    bound_offsets(&mut view.refdef.vieworg, ent.origin);
+
+    // set up gun position
+    cl.viewent.angles = cl.viewangles;
+
+    calc_gun_angle(cl, view, settings, frametime);
+
+    let gun = &mut cl.viewent;
+    gun.origin = ent.origin;
+    gun.origin[2] += cl.viewheight;
+
+    for i in 0..3 {
+        gun.origin[i] += forward[i] * bob * 0.4;
+    }
+    gun.origin[2] += bob;
+
+    // gun position fudging to keep the gun from getting covered by sbar
+    if settings.viewmodel_quake {
+        gun.origin[2] += match settings.viewsize as c_int {
+            110 | 90 => 1.0,
+            100 => 2.0,
+            80 => 0.5,
+            _ => 0.0,
+        };
+    }
+
+    let weapon = cl.stats[STAT_WEAPON as usize];
+    gun.model = if weapon >= 0 && (weapon as usize) < cl.model_precache.len() {
+        cl.model_precache[weapon as usize]
+    } else {
+        ::std::ptr::null_mut()
+    };
+    gun.frame = cl.stats[STAT_WEAPONFRAME as usize];
+    gun.colormap = colormap;
+
+    let viewangles = &mut view.refdef.viewangles;
+    if settings.gunkick == 1 {
+        // original quake kick
+        for i in 0..3 {
+            viewangles[i] += cl.punchangle[i];
+        }
+    }
+    if settings.gunkick == 2 {
+        // lerped kick
+        let target = view.punchangles[0];
+        let prev = view.punchangles[1];
+        for i in 0..3 {
+            if view.punch[i] != target[i] {
+                // speed determined by how far we need to lerp in 1/10th of a second
+                let delta = (target[i] - prev[i]) * frametime as c_float * 10.0;
+
+                if delta > 0.0 {
+                    view.punch[i] = (view.punch[i] + delta).min(target[i]);
+                } else if delta < 0.0 {
+                    view.punch[i] = (view.punch[i] + delta).max(target[i]);
+                }
+            }
+            viewangles[i] += view.punch[i];
+        }
+    }
+
+    // smooth out stair step ups
+    let z = ent.origin[2];
+    if !settings.noclip_anglehack && cl.onground == true && z - view.oldz > 0.0 {
+        let steptime = (cl.time - cl.oldtime).max(0.0) as c_float;
+
+        view.oldz = (view.oldz + steptime * 80.0).min(z).max(z - 12.0);
+        view.refdef.vieworg[2] += view.oldz - z;
+        gun.origin[2] += view.oldz - z;
+    } else {
+        view.oldz = z;
+    }
+}
+
+/// the view for one frame, plain data for any renderer
+#[derive(Clone, Copy)]
+pub struct ViewT {
+    pub refdef: RefDefT,
+    /// rgba 0.0 - 1.0 to draw over the whole view
+    pub blend: [c_float; 4],
+}
+
+/// Formerly: V_RenderView, without the drawing
+/// the view at cl.time for the player at ent, cl_entities[cl.viewentity], advancing the view
+/// effects by frametime.  while paused the last view is kept
+pub fn calc_view(
+    cl: &mut ClientStateT,
+    view: &mut ViewStateT,
+    settings: &ViewSettingsT,
+    ent: &mut EntityT,
+    frametime: c_double,
+    colormap: *mut Byte,
+) -> ViewT {
+    if cl.intermission != 0 {
+        calc_intermission_refdef(cl, view, settings, ent);
+    } else if cl.paused == false {
+        calc_refdef(cl, view, settings, ent, frametime, colormap);
+    }
+
+    update_blend(cl, view, settings, frametime);
+
+    return ViewT {
+        refdef: view.refdef,
+        blend: view.blend,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr::null_mut;
+
+    fn client() -> Box<ClientStateT> {
+        let mut cl = Box::new(ClientStateT::default());
+        cl.maxclients = 1;
+        cl.stats[STAT_HEALTH as usize] = 100;
+        cl.viewheight = 22.0;
+        cl.time = 0.15;
+        return cl;
+    }
+
+    /// svc_damage: armor 0, blood 40, from (1024 0 0)
+    const DAMAGE: [Byte; 8] = [0, 40, 0x00, 0x04, 0, 0, 0, 0];
+
+    #[test]
+    fn damage_flash_and_kick() {
+        let mut cl = client();
+        let mut view = ViewStateT::default();
+        let settings = ViewSettingsT::default();
+        let mut ent = EntityT::default();
+        let mut msg = MsgReaderT::new(&DAMAGE);
+        parse_damage(&mut msg, &mut cl, &mut view, &settings, &ent, 0).unwrap();
+        assert_eq!(cl.cshifts[CSHIFT_DAMAGE as usize].percent, 60);
+        assert!(view.dmg_pitch > 0.0);
+
+        let v = calc_view(&mut cl, &mut view, &settings, &mut ent, 0.0, null_mut());
+        assert!(v.blend[3] > 0.0 && v.blend[0] > 0.99);
+        assert_eq!(v.refdef.vieworg[2], 22.0 + 1.0 / 32.0);
+        assert!(v.refdef.viewangles[PITCH] > 0.0);
+    }
+
+    #[test]
+    fn damage_flashes_can_be_disabled() {
+        let mut cl = client();
+        let mut view = ViewStateT::default();
+        let mut settings = ViewSettingsT::default();
+        settings.cshiftpercents[CSHIFT_DAMAGE as usize] = 0.0;
+        let mut msg = MsgReaderT::new(&DAMAGE);
+        parse_damage(
+            &mut msg,
+            &mut cl,
+            &mut view,
+            &settings,
+            &EntityT::default(),
+            0,
+        )
+        .unwrap();
+        assert_eq!(calc_blend(&cl, &settings)[3], 0.0);
+    }
+
+    #[test]
+    fn bob_and_roll() {
+        let mut cl = client();
+        cl.velocity = Vec3T::new(320.0, 0.0, 0.0);
+        let mut settings = ViewSettingsT::default();
+        let bob = calc_bob(&cl, &settings);
+        assert!(bob != 0.0 && bob <= 4.0);
+        settings.bob = 0.0;
+        assert_eq!(calc_bob(&cl, &settings), 0.0);
+
+        // strafing right at full speed rolls all the way
+        let settings = ViewSettingsT::default();
+        let velocity = Vec3T::new(0.0, -400.0, 0.0);
+        assert_eq!(calc_roll(Vec3T::default(), velocity, &settings), 2.0);
+    }
+
+    #[test]
+    fn stairs_are_smoothed() {
+        let mut cl = client();
+        cl.onground = true.into();
+        let mut view = ViewStateT::default();
+        let settings = ViewSettingsT::default();
+        let mut ent = EntityT::default();
+        calc_view(&mut cl, &mut view, &settings, &mut ent, 0.0, null_mut());
+
+        // a 16 unit step is taken 12 units behind
+        cl.oldtime = cl.time;
+        cl.time += 0.05;
+        ent.origin[2] = 16.0;
+        let v = calc_view(&mut cl, &mut view, &settings, &mut ent, 0.05, null_mut());
+        assert!((v.refdef.vieworg[2] - (4.0 + 22.0 + 1.0 / 32.0)).abs() < 0.01);
+    }
+
+    #[test]
+    fn dead_view_rolls_over() {
+        let mut cl = client();
+        cl.stats[STAT_HEALTH as usize] = 0;
+        let mut view = ViewStateT::default();
+        let settings = ViewSettingsT::default();
+        let mut ent = EntityT::default();
+        let v = calc_view(&mut cl, &mut view, &settings, &mut ent, 0.0, null_mut());
+        assert_eq!(v.refdef.viewangles[ROLL], 80.0);
+    }
+
+    #[test]
+    fn bound_offsets_stay_near_the_entity() {
+        let origin = Vec3T::new(0.0, 0.0, 0.0);
+        let mut vieworg = Vec3T::new(100.0, -100.0, 5.0);
+        bound_offsets(&mut vieworg, origin);
+        assert_eq!((vieworg[0], vieworg[1], vieworg[2]), (14.0, -14.0, 5.0));
+
+        // a NaN bound, e.g. from a bad origin, must not panic
+        let mut vieworg = Vec3T::new(1.0, 2.0, 3.0);
+        bound_offsets(&mut vieworg, Vec3T::new(c_float::NAN, 0.0, 0.0));
+        assert_eq!(vieworg[1], 2.0);
+    }
+}